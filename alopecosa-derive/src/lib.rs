@@ -0,0 +1,540 @@
+/*!
+  Derive macros for alopecosa.
+
+  They are reexported from alopecosa with `derive` feature.
+*/
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{quote, ToTokens};
+use syn::{
+  meta::ParseNestedMeta, parse_macro_input, Data, DeriveInput, Error,
+  Expr, ExprLit, Fields, Ident, Lit, LitInt, LitStr, Meta, Type,
+};
+
+/**
+  Derives `alopecosa::entity::Entity` for struct with named fields.
+
+  Struct fields are mapped to tuple fields in declaration order,
+  every field type should implement `Clone + Into<Value>`,
+  struct itself should implement `serde::Deserialize`.
+
+  Example:
+  ```rust
+    # use alopecosa::{Entity, Value, entity::Entity as _};
+    # use serde::Deserialize;
+    #[derive(Entity, Deserialize)]
+    #[space = "users"]
+    #[index(primary = "id")]
+    struct User {
+      id: u64,
+      name: String,
+    }
+
+    let user = User { id: 1, name: "ann".into() };
+    assert_eq!(User::SPACE, "users");
+    assert_eq!(user.primary_key(), vec![ Value::from(1u64) ]);
+  ```
+*/
+#[proc_macro_derive(Entity, attributes(space, index))]
+pub fn derive_entity(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+
+  match entity(input) {
+    Ok(tokens) => tokens.into(),
+    Err(err) => err.to_compile_error().into(),
+  }
+}
+
+fn entity(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+  let name = &input.ident;
+  let fields = named_fields(&input, "Entity")?;
+  let idents: Vec<Ident> = fields.iter().map(|(ident, _)| ident.clone()).collect();
+  let attrs = SpaceAttrs::parse(&input, &idents)?;
+
+  let (space, primary) = (&attrs.name, &attrs.primary);
+  let field_names = idents.iter().map(|field| field.to_string());
+
+  Ok(quote! {
+    impl ::alopecosa::entity::Entity for #name {
+      const SPACE: &'static str = #space;
+      const FIELDS: &'static [&'static str] = &[ #( #field_names ),* ];
+
+      fn primary_key(&self) -> ::std::vec::Vec<::alopecosa::Value> {
+        ::std::vec![ #( ::std::convert::Into::into(::std::clone::Clone::clone(&self.#primary)) ),* ]
+      }
+
+      fn to_tuple(&self) -> ::std::vec::Vec<::alopecosa::Value> {
+        ::std::vec![ #( ::std::convert::Into::into(::std::clone::Clone::clone(&self.#idents)) ),* ]
+      }
+    }
+  })
+}
+
+/**
+  Derives typed accessor of space for struct with named fields.
+
+  Accessor is unit struct named after space in camel case (or `accessor` attribute),
+  it has `get` by primary key and `by_<fields>` select by every secondary index.
+  Secondary index is resolved by name, by default its name is its fields joined with "_",
+  other name is given by `name`, e.g. `index(secondary = "email", name = "email_idx")`.
+  Key arguments have types of struct fields and should implement `Into<Value>`,
+  struct itself should implement `serde::Deserialize`.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, Space};
+    # use serde::Deserialize;
+    #[derive(Space, Deserialize)]
+    #[space(
+      name = "users",
+      index(primary = "id"),
+      index(secondary = "email", name = "email_idx"),
+      index(secondary = "city,age"),
+    )]
+    struct User {
+      id: u64,
+      email: String,
+      city: String,
+      age: u32,
+    }
+
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let user: Option<User> = Users::get(conn, 1).await?;
+    // index "email_idx"
+    let users: Vec<User> = Users::by_email(conn, "a@b.c".into()).await?;
+    // index "city_age"
+    let users: Vec<User> = Users::by_city_age(conn, "Paris".into(), 30).await?;
+    # Ok(()) }
+  ```
+*/
+#[proc_macro_derive(Space, attributes(space, index))]
+pub fn derive_space(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+
+  match space(input) {
+    Ok(tokens) => tokens.into(),
+    Err(err) => err.to_compile_error().into(),
+  }
+}
+
+fn space(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+  let (name, vis) = (&input.ident, &input.vis);
+  let fields = named_fields(&input, "Space")?;
+  let idents: Vec<Ident> = fields.iter().map(|(ident, _)| ident.clone()).collect();
+  let attrs = SpaceAttrs::parse(&input, &idents)?;
+
+  let space = &attrs.name;
+  let accessor = match &attrs.accessor {
+    Some(accessor) => Ident::new(accessor, Span::call_site()),
+    None => Ident::new(&camel_case(space), Span::call_site()),
+  };
+
+  let field_type = |part: &Ident| fields.iter()
+    .find(|(ident, _)| ident == part)
+    .map(|(_, ty)| ty.clone())
+    .expect("parts are checked by SpaceAttrs");
+
+  let primary = &attrs.primary;
+  let primary_types: Vec<Type> = primary.iter().map(field_type).collect();
+
+  let secondary = attrs.secondary.iter().map(|SecondaryIndex { parts, name: index_name }| {
+    let method = Ident::new(
+      &format!("by_{}", parts.iter().map(Ident::to_string).collect::<Vec<_>>().join("_")),
+      Span::call_site(),
+    );
+    let types: Vec<Type> = parts.iter().map(field_type).collect();
+
+    quote! {
+      #vis async fn #method<C>(
+        client: &C, #( #parts: #types ),*
+      ) -> ::std::result::Result<::std::vec::Vec<#name>, ::alopecosa::Error>
+        where C: ::alopecosa::TarantoolClient
+      {
+        let space_id = client.space_id(#space).await?;
+        let index_id = client.index_id(space_id, #index_name).await?;
+        client.select(::alopecosa::Select {
+          space_id, index_id,
+          limit: u32::MAX, offset: 0,
+          iterator: ::alopecosa::Iterator::Eq,
+          keys: ::std::vec![ #( ::std::convert::Into::into(#parts) ),* ],
+        }).await
+      }
+    }
+  });
+
+  Ok(quote! {
+    /// Typed accessor of space, see `alopecosa::Space` derive.
+    #vis struct #accessor;
+
+    impl #accessor {
+      /// name of space
+      #vis const SPACE: &'static str = #space;
+
+      #vis async fn get<C>(
+        client: &C, #( #primary: #primary_types ),*
+      ) -> ::std::result::Result<::std::option::Option<#name>, ::alopecosa::Error>
+        where C: ::alopecosa::TarantoolClient
+      {
+        let space_id = client.space_id(#space).await?;
+        client.get(space_id, 0, ::std::vec![ #( ::std::convert::Into::into(#primary) ),* ]).await
+      }
+
+      #( #secondary )*
+    }
+  })
+}
+
+/**
+  Derives `alopecosa::IntoTuple` for struct with named fields.
+
+  Fields are mapped to tuple fields in declaration order,
+  `#[tuple(index = N)]` places field at zero based position N
+  and following fields after it, gaps are filled with nulls.
+  Fields marked `#[tuple(skip)]` are not packed.
+  Every packed field type should implement `Into<Value>`.
+
+  Example:
+  ```rust
+    # use alopecosa::{Connection, Error, FromTuple, Insert, IntoTuple, Select, ToTuple, Value};
+    #[derive(ToTuple, FromTuple)]
+    struct User {
+      id: u64,
+      #[tuple(index = 2)]
+      name: String,
+      #[tuple(skip)]
+      cached: bool,
+    }
+
+    let user = User { id: 1, name: "ann".into(), cached: true };
+    assert_eq!(user.into_tuple(), vec![ Value::from(1u64), Value::Null, "ann".into() ]);
+    # async fn f(conn: &Connection, user: User, select: Select) -> Result<(), Error> {
+
+    conn.insert::<Vec<User>>(Insert { space_id: 512, tuple: user.into_tuple() }).await?;
+    let users: Vec<User> = conn.select(select).await?;
+    # Ok(()) }
+  ```
+*/
+#[proc_macro_derive(ToTuple, attributes(tuple))]
+pub fn derive_to_tuple(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+
+  match to_tuple(input) {
+    Ok(tokens) => tokens.into(),
+    Err(err) => err.to_compile_error().into(),
+  }
+}
+
+fn to_tuple(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+  let name = &input.ident;
+  let fields = tuple_fields(&input, "ToTuple")?;
+
+  let len = fields.iter().filter_map(|field| field.position).max().map_or(0, |max| max + 1);
+  let (idents, positions): (Vec<&Ident>, Vec<usize>) = fields.iter()
+    .filter_map(|field| field.position.map(|position| (&field.ident, position)))
+    .unzip();
+
+  Ok(quote! {
+    impl ::alopecosa::IntoTuple for #name {
+      fn into_tuple(self) -> ::std::vec::Vec<::alopecosa::Value> {
+        let mut tuple = ::std::vec![ ::alopecosa::Value::Null; #len ];
+        #( tuple[#positions] = ::std::convert::Into::into(self.#idents); )*
+        tuple
+      }
+    }
+  })
+}
+
+/**
+  Derives `serde::Deserialize` of struct with named fields from tuple,
+  so struct may be selected without serde adapters.
+
+  Fields are taken from tuple positions like in `ToTuple` derive,
+  tuple fields which are not mapped are ignored,
+  skipped fields are set to `Default::default()`.
+  Every mapped field type should implement `serde::Deserialize`.
+*/
+#[proc_macro_derive(FromTuple, attributes(tuple))]
+pub fn derive_from_tuple(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+
+  match from_tuple(input) {
+    Ok(tokens) => tokens.into(),
+    Err(err) => err.to_compile_error().into(),
+  }
+}
+
+fn from_tuple(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+  let name = &input.ident;
+  let fields = tuple_fields(&input, "FromTuple")?;
+  let serde = quote! { ::alopecosa::__private::serde };
+
+  let len = fields.iter().filter_map(|field| field.position).max().map_or(0, |max| max + 1);
+  let reads = (0..len).map(|position| {
+    match fields.iter().find(|field| field.position == Some(position)) {
+      Some(TupleField { ident, ty, .. }) => quote! {
+        let #ident: #ty = match #serde::de::SeqAccess::next_element(&mut seq)? {
+          ::std::option::Option::Some(value) => value,
+          ::std::option::Option::None =>
+            return ::std::result::Result::Err(#serde::de::Error::invalid_length(#position, &self)),
+        };
+      },
+      None => quote! {
+        #serde::de::SeqAccess::next_element::<#serde::de::IgnoredAny>(&mut seq)?;
+      },
+    }
+  });
+
+  let values = fields.iter().map(|TupleField { ident, position, .. }| match position {
+    Some(_) => quote! { #ident },
+    None => quote! { #ident: ::std::default::Default::default() },
+  });
+  let expecting = format!("tuple of {}", name);
+
+  Ok(quote! {
+    impl<'de> #serde::Deserialize<'de> for #name {
+      fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where D: #serde::Deserializer<'de>
+      {
+        struct TupleVisitor;
+
+        impl<'de> #serde::de::Visitor<'de> for TupleVisitor {
+          type Value = #name;
+
+          fn expecting(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+            f.write_str(#expecting)
+          }
+
+          fn visit_seq<A>(self, mut seq: A) -> ::std::result::Result<#name, A::Error>
+            where A: #serde::de::SeqAccess<'de>
+          {
+            #( #reads )*
+            while #serde::de::SeqAccess::next_element::<#serde::de::IgnoredAny>(&mut seq)?.is_some() {}
+
+            ::std::result::Result::Ok(#name { #( #values ),* })
+          }
+        }
+
+        deserializer.deserialize_seq(TupleVisitor)
+      }
+    }
+  })
+}
+
+/// Field of struct with its tuple position, it is none for skipped field.
+struct TupleField {
+  ident: Ident,
+  ty: Type,
+  position: Option<usize>,
+}
+
+/// fields of struct with positions from `#[tuple(...)]` attributes
+fn tuple_fields(input: &DeriveInput, derive: &str) -> Result<Vec<TupleField>, Error> {
+  let named = match &input.data {
+    Data::Struct(data) => match &data.fields {
+      Fields::Named(fields) => &fields.named,
+      _ => return Err(Error::new_spanned(&input.ident, format!("{} requires struct with named fields", derive))),
+    },
+    _ => return Err(Error::new_spanned(&input.ident, format!("{} can be derived only for struct", derive))),
+  };
+
+  let mut fields: Vec<TupleField> = Vec::with_capacity(named.len());
+  let mut next = 0;
+
+  for field in named.iter() {
+    let ident = field.ident.clone().expect("fields are named");
+    let (mut skip, mut index) = (false, None);
+
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("tuple")) {
+      attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("skip") {
+          skip = true;
+          Ok(())
+        } else if meta.path.is_ident("index") {
+          index = Some(meta.value()?.parse::<LitInt>()?.base10_parse::<usize>()?);
+          Ok(())
+        } else {
+          Err(meta.error("expected skip or index = N"))
+        }
+      })?;
+    }
+
+    let position = match (skip, index) {
+      (true, _) => None,
+      (false, index) => {
+        let position = index.unwrap_or(next);
+        if fields.iter().any(|field| field.position == Some(position)) {
+          return Err(Error::new_spanned(&ident, format!("tuple position {} is taken", position)));
+        }
+        next = position + 1;
+        Some(position)
+      },
+    };
+
+    fields.push(TupleField { ident, ty: field.ty.clone(), position });
+  }
+
+  Ok(fields)
+}
+
+/// fields of struct with their types
+fn named_fields(input: &DeriveInput, derive: &str) -> Result<Vec<(Ident, Type)>, Error> {
+  let name = &input.ident;
+
+  match &input.data {
+    Data::Struct(data) => match &data.fields {
+      Fields::Named(fields) => Ok(fields.named.iter()
+        .filter_map(|field| field.ident.clone().map(|ident| (ident, field.ty.clone())))
+        .collect()),
+      _ => Err(Error::new_spanned(name, format!("{} requires struct with named fields", derive))),
+    },
+    _ => Err(Error::new_spanned(name, format!("{} can be derived only for struct", derive))),
+  }
+}
+
+/**
+  Space attributes, both forms are accepted:
+  `#[space = "users"] #[index(primary = "id")]` and
+  `#[space(name = "users", index(primary = "id"), index(secondary = "email"))]`.
+*/
+struct SpaceAttrs {
+  name: String,
+  accessor: Option<String>,
+  primary: Vec<Ident>,
+  secondary: Vec<SecondaryIndex>,
+}
+
+impl SpaceAttrs {
+  fn parse(input: &DeriveInput, fields: &[Ident]) -> Result<SpaceAttrs, Error> {
+    let mut attrs = SpaceAttrs {
+      name: String::new(), accessor: None,
+      primary: Vec::new(), secondary: Vec::new(),
+    };
+    let mut name: Option<String> = None;
+
+    for attr in input.attrs.iter() {
+      if attr.path().is_ident("space") {
+        if let Meta::List(_) = attr.meta {
+          attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+              name = Some(meta.value()?.parse::<LitStr>()?.value());
+              Ok(())
+            } else if meta.path.is_ident("accessor") {
+              attrs.accessor = Some(meta.value()?.parse::<LitStr>()?.value());
+              Ok(())
+            } else if meta.path.is_ident("index") {
+              let mut index = IndexAttr::default();
+              meta.parse_nested_meta(|meta| index.parse(meta, fields))?;
+              attrs.push(index, &meta.path)
+            } else {
+              Err(meta.error("expected name, accessor or index"))
+            }
+          })?;
+        } else {
+          name = Some(str_value(&attr.meta)?);
+        }
+      } else if attr.path().is_ident("index") {
+        let mut index = IndexAttr::default();
+        attr.parse_nested_meta(|meta| index.parse(meta, fields))?;
+        attrs.push(index, attr)?;
+      }
+    }
+
+    attrs.name = name.ok_or_else(|| Error::new(
+      Span::call_site(), "missing #[space = \"name\"] attribute",
+    ))?;
+
+    if attrs.primary.is_empty() {
+      return Err(Error::new(Span::call_site(), "missing #[index(primary = \"field\")] attribute"));
+    }
+
+    Ok(attrs)
+  }
+
+  fn push<T: ToTokens>(&mut self, index: IndexAttr, tokens: T) -> Result<(), Error> {
+    match index {
+      IndexAttr { parts, .. } if parts.is_empty() =>
+        Err(Error::new_spanned(tokens, "expected primary = \"field\" or secondary = \"field\"")),
+      IndexAttr { primary: true, name: Some(_), .. } =>
+        Err(Error::new_spanned(tokens, "primary index is not resolved by name")),
+      IndexAttr { primary: true, parts, .. } => {
+        self.primary = parts;
+        Ok(())
+      },
+      IndexAttr { primary: false, parts, name } => {
+        let name = name.unwrap_or_else(|| parts.iter().map(Ident::to_string).collect::<Vec<_>>().join("_"));
+        self.secondary.push(SecondaryIndex { parts, name });
+        Ok(())
+      },
+    }
+  }
+}
+
+/// Secondary index with fields of its key and its name in space.
+struct SecondaryIndex {
+  parts: Vec<Ident>,
+  name: String,
+}
+
+/// items of one `index(...)` attribute
+#[derive(Default)]
+struct IndexAttr {
+  primary: bool,
+  parts: Vec<Ident>,
+  name: Option<String>,
+}
+
+impl IndexAttr {
+  fn parse(&mut self, meta: ParseNestedMeta<'_>, fields: &[Ident]) -> Result<(), Error> {
+    if meta.path.is_ident("name") {
+      self.name = Some(meta.value()?.parse::<LitStr>()?.value());
+      return Ok(());
+    }
+
+    let primary = meta.path.is_ident("primary");
+    if !primary && !meta.path.is_ident("secondary") {
+      return Err(meta.error("expected primary = \"field\", secondary = \"field\" or name = \"index\""));
+    }
+    if !self.parts.is_empty() {
+      return Err(meta.error("index should have one primary or secondary key"));
+    }
+
+    let parts: LitStr = meta.value()?.parse()?;
+    for part in parts.value().split(',') {
+      let mut part = syn::parse_str::<Ident>(part.trim()).map_err(|_| Error::new_spanned(
+        &parts, format!("expected comma separated field names, found {:?}", part.trim()),
+      ))?;
+      part.set_span(parts.span());
+      if !fields.contains(&part) {
+        return Err(Error::new_spanned(&parts, format!("unknown field {}", part)));
+      }
+      self.parts.push(part);
+    }
+
+    self.primary = primary;
+    Ok(())
+  }
+}
+
+/// "user_roles" becomes "UserRoles"
+fn camel_case(name: &str) -> String {
+  name.split(|c: char| !c.is_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .map(|word| {
+      let mut chars = word.chars();
+      chars.next()
+        .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+        .unwrap_or_default()
+    })
+    .collect()
+}
+
+fn str_value(meta: &Meta) -> Result<String, Error> {
+  match meta {
+    Meta::NameValue(value) => match &value.value {
+      Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => Ok(s.value()),
+      value => Err(Error::new_spanned(value, "expected string literal")),
+    },
+    meta => Err(Error::new_spanned(meta, "expected #[name = \"value\"]")),
+  }
+}
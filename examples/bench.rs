@@ -0,0 +1,54 @@
+use std::{env, error::Error, time::Duration};
+
+use alopecosa::{
+  Call, Connector, Insert, IntoTuple, Iterator, Select,
+  bench::Bench,
+};
+
+const USAGE: &str = "\
+usage: bench [--addr host:port] [--rps n] [--secs n] [--space id]
+             [--select weight] [--insert weight] [--call weight] [--function name]";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+  let mut addr = "127.0.0.1:3301".to_string();
+  let (mut rps, mut secs, mut space_id) = (1000u32, 10u64, 512u64);
+  let (mut select, mut insert, mut call) = (1u32, 0u32, 0u32);
+  let mut function = "box.info".to_string();
+
+  let mut args = env::args().skip(1);
+  while let Some(arg) = args.next() {
+    let mut value = || args.next().ok_or(USAGE);
+    match arg.as_str() {
+      "--addr" => addr = value()?,
+      "--rps" => rps = value()?.parse()?,
+      "--secs" => secs = value()?.parse()?,
+      "--space" => space_id = value()?.parse()?,
+      "--select" => select = value()?.parse()?,
+      "--insert" => insert = value()?.parse()?,
+      "--call" => call = value()?.parse()?,
+      "--function" => function = value()?,
+      _ => return Err(USAGE.into()),
+    }
+  }
+
+  let conn = Connector::new(addr.parse()?).connect().await?;
+
+  let report = Bench::new()
+    .with_rps(rps)
+    .with_duration(Duration::from_secs(secs))
+    .with_select(select, Select {
+      space_id, index_id: 0,
+      limit: 1, offset: 0,
+      iterator: Iterator::Eq,
+      keys: ( 1u64, ).into_tuple(),
+    })
+    .with_insert(insert, move |i| Insert {
+      space_id, tuple: ( u64::MAX / 2 + i, "bench" ).into_tuple(),
+    })
+    .with_call(call, Call { function, args: Vec::new() })
+    .run(conn).await;
+
+  print!("{}", report);
+  Ok(())
+}
@@ -0,0 +1,327 @@
+/*!
+  This module contains load generator.
+
+  It is enabled by `bench` feature and sends weighted mix of requests at target rate
+  through usual connection, so capacity tests measure the same codec
+  and connection stack as production. Latencies are reported per operation.
+
+  Example:
+  ```rust,no_run
+    # use std::{sync::Arc, time::Duration};
+    # use alopecosa::{Call, Connection, Insert, IntoTuple, Iterator, Select, bench::Bench};
+    # async fn f(conn: Arc<Connection>) {
+    let report = Bench::new()
+      .with_rps(5000)
+      .with_duration(Duration::from_secs(30))
+      .with_select(8, Select { space_id: 512, index_id: 0, limit: 1, offset: 0,
+        iterator: Iterator::Eq, keys: ( 1u64, ).into_tuple() })
+      .with_insert(1, |i| Insert { space_id: 513, tuple: ( i, "bench" ).into_tuple() })
+      .with_call(1, Call { function: "stat".into(), args: Vec::new() })
+      .run(conn).await;
+
+    println!("{}", report);
+    # }
+  ```
+*/
+
+use std::{
+  collections::HashMap,
+  fmt,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use tokio::{sync::Semaphore, time::{self, MissedTickBehavior}};
+
+use crate::{
+  connection::Connection,
+  iproto::request::{self, Call, Insert, Request, Select},
+};
+
+type Generator = Arc<dyn Fn(u64) -> Request + Send + Sync>;
+
+/// rate limit, interval between requests is one nanosecond at it
+const MAX_RPS: u32 = 1_000_000_000;
+
+struct Operation {
+  name: String,
+  weight: u32,
+  generate: Generator,
+}
+
+/// Latency statistics of one operation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyStats {
+  pub count: u64,
+  pub errors: u64,
+  pub mean: Duration,
+  pub p50: Duration,
+  pub p90: Duration,
+  pub p99: Duration,
+  pub max: Duration,
+}
+
+impl LatencyStats {
+  fn from_samples(mut samples: Vec<Duration>, errors: u64) -> LatencyStats {
+    samples.sort_unstable();
+
+    let percentile = |p: usize| match samples.len() {
+      0 => Duration::default(),
+      n => samples[(n * p).div_ceil(100).saturating_sub(1).min(n - 1)],
+    };
+
+    let count = samples.len() as u64;
+    let total: u128 = samples.iter().map(Duration::as_nanos).sum();
+
+    LatencyStats {
+      count, errors,
+      mean: match count {
+        0 => Duration::default(),
+        count => Duration::from_nanos((total / count as u128) as u64),
+      },
+      p50: percentile(50),
+      p90: percentile(90),
+      p99: percentile(99),
+      max: samples.last().copied().unwrap_or_default(),
+    }
+  }
+}
+
+/// Result of bench run.
+#[derive(Debug, Clone, Default)]
+pub struct BenchReport {
+  pub elapsed: Duration,
+  /// requests which were not sent because of concurrency limit
+  pub dropped: u64,
+  pub operations: HashMap<String, LatencyStats>,
+}
+
+impl BenchReport {
+  /// achieved rate of completed requests
+  pub fn rps(&self) -> f64 {
+    let count: u64 = self.operations.values().map(|stats| stats.count).sum();
+    match self.elapsed.as_secs_f64() {
+      secs if secs > 0.0 => count as f64 / secs,
+      _ => 0.0,
+    }
+  }
+}
+
+impl fmt::Display for BenchReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "elapsed {:?}, {:.1} rps, {} dropped", self.elapsed, self.rps(), self.dropped)?;
+
+    let mut names: Vec<&String> = self.operations.keys().collect();
+    names.sort();
+
+    for name in names {
+      let stats = &self.operations[name];
+      writeln!(
+        f, "{}: count {} errors {} mean {:?} p50 {:?} p90 {:?} p99 {:?} max {:?}",
+        name, stats.count, stats.errors, stats.mean, stats.p50, stats.p90, stats.p99, stats.max,
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Default)]
+struct Samples {
+  latencies: Vec<Duration>,
+  errors: u64,
+}
+
+/// This is load generator, see module docs.
+pub struct Bench {
+  rps: u32,
+  duration: Duration,
+  concurrency: usize,
+  operations: Vec<Operation>,
+}
+
+impl fmt::Debug for Bench {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Bench")
+      .field("rps", &self.rps)
+      .field("duration", &self.duration)
+      .field("concurrency", &self.concurrency)
+      .field("operations", &self.operations.iter()
+        .map(|op| (op.name.as_str(), op.weight))
+        .collect::<Vec<_>>())
+      .finish()
+  }
+}
+
+impl Default for Bench {
+  fn default() -> Self {
+    Bench {
+      rps: 1000,
+      duration: Duration::from_secs(10),
+      concurrency: 1000,
+      operations: Vec::new(),
+    }
+  }
+}
+
+impl Bench {
+  pub fn new() -> Bench {
+    Bench::default()
+  }
+
+  /// target rate, it is clamped to 1..=1e9
+  pub fn with_rps(mut self, rps: u32) -> Self {
+    self.rps = rps.clamp(1, MAX_RPS);
+    self
+  }
+
+  pub fn with_duration(mut self, duration: Duration) -> Self {
+    self.duration = duration;
+    self
+  }
+
+  /// limits number of requests waiting for response, requests over limit are dropped
+  pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+    self.concurrency = concurrency.max(1);
+    self
+  }
+
+  /// adds operation, generator gets sequence number of request
+  pub fn with_operation<S, F>(mut self, name: S, weight: u32, generate: F) -> Self
+    where S: Into<String>,
+          F: Fn(u64) -> Request + Send + Sync + 'static,
+  {
+    if weight > 0 {
+      self.operations.push(Operation { name: name.into(), weight, generate: Arc::new(generate) });
+    }
+    self
+  }
+
+  pub fn with_select(self, weight: u32, body: Select) -> Self {
+    self.with_operation("select", weight, move |_| request::select(body.clone()))
+  }
+
+  pub fn with_insert<F>(self, weight: u32, body: F) -> Self
+    where F: Fn(u64) -> Insert + Send + Sync + 'static
+  {
+    self.with_operation("insert", weight, move |i| request::insert(body(i)))
+  }
+
+  pub fn with_call(self, weight: u32, body: Call) -> Self {
+    self.with_operation("call", weight, move |_| request::call(body.clone()))
+  }
+
+  /// picks operation by sequence number proportionally to weights
+  fn pick(&self, seq: u64) -> &Operation {
+    let total: u64 = self.operations.iter().map(|op| op.weight as u64).sum();
+    let mut slot = seq % total;
+
+    for op in self.operations.iter() {
+      if slot < op.weight as u64 {
+        return op;
+      }
+      slot -= op.weight as u64;
+    }
+
+    &self.operations[0]
+  }
+
+  /// sends requests at target rate for configured duration and waits for their responses
+  pub async fn run(self, conn: Arc<Connection>) -> BenchReport {
+    let mut report = BenchReport::default();
+    if self.operations.is_empty() {
+      return report;
+    }
+
+    let samples: Arc<Mutex<HashMap<String, Samples>>> = Arc::new(Mutex::new(HashMap::new()));
+    let semaphore = Arc::new(Semaphore::new(self.concurrency));
+
+    let mut ticker = time::interval(Duration::from_secs(1) / self.rps);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+
+    let start = Instant::now();
+    let mut seq = 0u64;
+
+    while start.elapsed() < self.duration {
+      ticker.tick().await;
+
+      let permit = match semaphore.clone().try_acquire_owned() {
+        Ok(permit) => permit,
+        Err(_) => { report.dropped += 1; continue; },
+      };
+
+      let op = self.pick(seq);
+      let (name, req) = (op.name.clone(), (op.generate)(seq));
+      seq += 1;
+
+      let (conn, samples) = (conn.clone(), samples.clone());
+      tokio::spawn(async move {
+        let sent = Instant::now();
+        let result = conn.perform(req).await;
+        let latency = sent.elapsed();
+
+        let mut samples = samples.lock().unwrap();
+        let samples = samples.entry(name).or_default();
+        match result {
+          Ok(_) => samples.latencies.push(latency),
+          Err(_) => samples.errors += 1,
+        }
+
+        drop(permit);
+      });
+    }
+
+    // waits for requests in flight
+    let _ = semaphore.acquire_many(self.concurrency as u32).await;
+    report.elapsed = start.elapsed();
+
+    let samples = std::mem::take(&mut *samples.lock().unwrap());
+    report.operations = samples.into_iter()
+      .map(|(name, samples)| (name, LatencyStats::from_samples(samples.latencies, samples.errors)))
+      .collect();
+
+    report
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{connection::transport::tests::fake_connection, iproto::request::Eval};
+
+  use super::*;
+
+  #[test]
+  fn test_latency_stats() {
+    let samples = (1..=100).map(Duration::from_millis).collect();
+    let stats = LatencyStats::from_samples(samples, 2);
+
+    assert_eq!(stats.count, 100);
+    assert_eq!(stats.errors, 2);
+    assert_eq!(stats.p50, Duration::from_millis(50));
+    assert_eq!(stats.p99, Duration::from_millis(99));
+    assert_eq!(stats.max, Duration::from_millis(100));
+    assert_eq!(stats.mean, Duration::from_micros(50_500));
+
+    let stats = LatencyStats::from_samples(vec![ Duration::from_secs(1); 5 ], 0);
+    assert_eq!(stats.mean, Duration::from_secs(1));
+
+    assert_eq!(Bench::new().with_rps(u32::MAX).rps, MAX_RPS);
+  }
+
+  #[tokio::test]
+  async fn test_bench() {
+    let conn = fake_connection().await;
+
+    let report = Bench::new()
+      .with_rps(400)
+      .with_duration(Duration::from_millis(100))
+      .with_operation("ping", 3, |_| request::ping())
+      .with_operation("eval", 1, |_| request::eval(Eval { expr: "return".into(), args: Vec::new() }))
+      .run(conn).await;
+
+    let (ping, eval) = (&report.operations["ping"], &report.operations["eval"]);
+    assert!(ping.count > 0 && ping.errors == 0);
+    assert!(eval.count == 0 && eval.errors > 0);
+    assert!(ping.count >= 2 * eval.errors);
+  }
+}
@@ -0,0 +1,587 @@
+/*!
+  This module contains change data capture over replication protocol.
+
+  ChangeStream subscribes to tarantool as anonymous replica
+  and turns replicated rows into typed events.
+
+  Example:
+  ```rust,no_run
+    # use uuid::Uuid;
+    # use alopecosa::{Connection, Connector, Error, cdc::{ChangeEvent, ChangeStream, Checkpoint}};
+    # fn load_checkpoint() -> Result<Option<Checkpoint>, Error> { Ok(None) }
+    # fn store_checkpoint(_: &Checkpoint) -> Result<(), Error> { Ok(()) }
+    # async fn publish(_: Option<&str>, _: (u64, String)) -> Result<(), Error> { Ok(()) }
+    # async fn f(connector: Connector, conn: &Connection, instance_uuid: Uuid) -> Result<(), Error> {
+    let checkpoint = load_checkpoint()?.unwrap_or_default();
+
+    let mut changes = ChangeStream::subscribe(&connector, instance_uuid, checkpoint).await?
+      .load_space_names(conn).await?;
+
+    while let Some(change) = changes.next().await? {
+      if let ChangeEvent::Insert { space, tuple } = &change.event {
+        let user: (u64, String) = tuple.decode()?;
+        publish(space.name.as_deref(), user).await?;
+      }
+      store_checkpoint(changes.checkpoint())?;
+    }
+    # Ok(()) }
+  ```
+*/
+
+use std::{
+  collections::HashMap,
+  fmt,
+  io::{self, Cursor},
+};
+
+use num_traits::FromPrimitive;
+use rmp::decode::{read_int, read_map_len};
+use rmpv::decode::read_value;
+use serde::{
+  Deserialize, Serialize,
+  de::{DeserializeOwned, Deserializer, IgnoredAny, SeqAccess, Visitor},
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::{
+  client::TarantoolClient,
+  connection::{connector::Connector, transport::BoxedTransport},
+  iproto::{
+    constants::{Code, ERROR_BITMASK, Field, Iterator, RequestType, VSPACE_ID},
+    request::{self, Select, Subscribe, Vclock},
+    response::{BodyDecoder, ErrorBody},
+    types::Error,
+  },
+};
+
+const SPACE_ID: u64 = 280;
+/// zero based number of name field of _space
+const SPACE_NAME_FIELD: u64 = 2;
+
+/// Replication position, it may be stored to resume stream later.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+  pub vclock: Vclock,
+}
+
+impl Checkpoint {
+  fn advance(&mut self, replica_id: u32, lsn: u64) {
+    let current = self.vclock.entry(replica_id).or_insert(0);
+    *current = lsn.max(*current);
+  }
+}
+
+/// Raw msgpack value of replicated row part.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuple(Vec<u8>);
+
+impl Tuple {
+  pub fn decode<T: DeserializeOwned>(&self) -> Result<T, Error> {
+    rmp_serde::from_slice(&self.0).map_err(Error::ParseError)
+  }
+
+  pub fn as_bytes(&self) -> &[u8] {
+    &self.0
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpaceRef {
+  pub id: u64,
+  /// it is None if space name is not known to stream
+  pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeEvent {
+  Insert { space: SpaceRef, tuple: Tuple },
+  Replace { space: SpaceRef, tuple: Tuple },
+  Update { space: SpaceRef, key: Tuple, ops: Tuple },
+  Upsert { space: SpaceRef, tuple: Tuple, ops: Tuple },
+  Delete { space: SpaceRef, key: Tuple },
+}
+
+/// Replicated change with its position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+  pub replica_id: u32,
+  pub lsn: u64,
+  pub timestamp: f64,
+  pub event: ChangeEvent,
+}
+
+/// This is stream of changes read from replication connection.
+pub struct ChangeStream {
+  transport: BoxedTransport,
+  checkpoint: Checkpoint,
+  spaces: HashMap<u64, String>,
+  /// larger frame is invalid data, see Connector::with_max_response_size
+  max_frame_size: usize,
+}
+
+impl fmt::Debug for ChangeStream {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ChangeStream")
+      .field("checkpoint", &self.checkpoint)
+      .field("spaces", &self.spaces)
+      .finish()
+  }
+}
+
+impl ChangeStream {
+  pub(crate) fn new(transport: BoxedTransport, checkpoint: Checkpoint, max_frame_size: usize) -> ChangeStream {
+    ChangeStream { transport, checkpoint, spaces: HashMap::new(), max_frame_size }
+  }
+
+  /**
+    opens separate connection and subscribes to changes after checkpoint
+
+    Note: instance_uuid should be unique and stable for every consumer
+  */
+  pub async fn subscribe(
+    connector: &Connector, instance_uuid: Uuid, checkpoint: Checkpoint,
+  ) -> Result<ChangeStream, Error> {
+    let (transport, _, _) = connector.new_connection().await?;
+    let mut stream = ChangeStream::new(transport, checkpoint, connector.max_response_size);
+
+    let req = request::subscribe(Subscribe {
+      instance_uuid, cluster_uuid: None,
+      vclock: stream.checkpoint.vclock.clone(),
+      anon: true, id_filter: Vec::new(),
+    });
+
+    let mut buf: Vec<u8> = Vec::new();
+    req.pack(&mut buf)?;
+    stream.transport.write_all(&buf).await?;
+    stream.transport.flush().await?;
+
+    Ok(stream)
+  }
+
+  /// sets known space names, they are also updated from replicated _space changes
+  pub fn with_space_names(mut self, spaces: HashMap<u64, String>) -> Self {
+    self.spaces = spaces;
+    self
+  }
+
+  /// loads space names from _vspace using regular client
+  pub async fn load_space_names<C>(mut self, client: &C) -> Result<Self, Error>
+    where C: TarantoolClient
+  {
+    let spaces: Vec<SpaceRow> = client.select(Select {
+      space_id: VSPACE_ID, index_id: 0,
+      limit: u32::MAX, offset: 0,
+      iterator: Iterator::All,
+      keys: Vec::new(),
+    }).await?;
+
+    self.spaces.extend(spaces.into_iter().map(|row| (row.id, row.name)));
+    Ok(self)
+  }
+
+  /// position after last returned change
+  pub fn checkpoint(&self) -> &Checkpoint {
+    &self.checkpoint
+  }
+
+  /// waits for next change, returns None when server closed connection
+  pub async fn next(&mut self) -> Result<Option<Change>, Error> {
+    loop {
+      let frame = match self.read_frame().await? {
+        Some(frame) => frame,
+        None => return Ok(None),
+      };
+
+      if let Some(change) = self.handle_frame(&frame).await? {
+        return Ok(Some(change));
+      }
+    }
+  }
+
+  async fn read_frame(&mut self) -> Result<Option<Vec<u8>>, Error> {
+    let marker = match self.transport.read_u8().await {
+      Ok(marker) => marker,
+      Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+      Err(err) => return Err(err.into()),
+    };
+
+    let size = match marker {
+      0x00..=0x7f => marker as u64,
+      0xcc => self.transport.read_u8().await? as u64,
+      0xcd => self.transport.read_u16().await? as u64,
+      0xce => self.transport.read_u32().await? as u64,
+      0xcf => self.transport.read_u64().await?,
+      _ => return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unexpected packet size marker {}", marker),
+      ).into()),
+    };
+
+    if size > self.max_frame_size as u64 {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("row of {} bytes exceeds max response size {}", size, self.max_frame_size),
+      ).into());
+    }
+
+    let mut frame = vec![0u8; size as usize];
+    self.transport.read_exact(&mut frame).await?;
+
+    Ok(Some(frame))
+  }
+
+  async fn handle_frame(&mut self, frame: &[u8]) -> Result<Option<Change>, Error> {
+    let mut cur = Cursor::new(frame);
+    let header = RowHeader::unpack(&mut cur)?;
+    let body = &frame[cur.position() as usize..];
+
+    if header.code & ERROR_BITMASK as u64 != 0 {
+      let code = Code::from_u64(header.code).unwrap_or(Code::ErrorUnknown);
+      return Err(Error::TarantoolError(code, ErrorBody::unpack(body)?));
+    }
+
+    // subscribe response and heartbeats
+    if header.code == Code::Ok as u64 {
+      self.send_ack().await?;
+      return Ok(None);
+    }
+
+    if header.lsn > 0 {
+      self.checkpoint.advance(header.replica_id, header.lsn);
+    }
+
+    let row_type = match RequestType::from_u64(header.code) {
+      Some(row_type) => row_type,
+      None => {
+        log::debug!("skipping row with unknown type {}", header.code);
+        return Ok(None);
+      },
+    };
+
+    let row = RowBody::unpack(body)?;
+    let space = SpaceRef {
+      id: row.space_id,
+      name: self.spaces.get(&row.space_id).cloned(),
+    };
+
+    let missing = || Error::UnexpectedValue(Field::Tuple);
+
+    let event = match row_type {
+      RequestType::Insert => ChangeEvent::Insert {
+        space, tuple: row.tuple.ok_or_else(missing)?,
+      },
+      RequestType::Replace => ChangeEvent::Replace {
+        space, tuple: row.tuple.ok_or_else(missing)?,
+      },
+      RequestType::Update => ChangeEvent::Update {
+        space,
+        key: row.key.ok_or(Error::UnexpectedValue(Field::Key))?,
+        ops: row.tuple.ok_or_else(missing)?,
+      },
+      RequestType::Upsert => ChangeEvent::Upsert {
+        space,
+        tuple: row.tuple.ok_or_else(missing)?,
+        ops: row.ops.ok_or(Error::UnexpectedValue(Field::Ops))?,
+      },
+      RequestType::Delete => ChangeEvent::Delete {
+        space, key: row.key.ok_or(Error::UnexpectedValue(Field::Key))?,
+      },
+      // nop, synchro and other service rows
+      _ => return Ok(None),
+    };
+
+    self.track_schema(&event, row.index_base)?;
+
+    Ok(Some(Change {
+      replica_id: header.replica_id,
+      lsn: header.lsn,
+      timestamp: header.timestamp,
+      event,
+    }))
+  }
+
+  /// keeps space names up to date with replicated ddl
+  fn track_schema(&mut self, event: &ChangeEvent, index_base: u64) -> Result<(), Error> {
+    match event {
+      ChangeEvent::Insert { space, tuple } |
+      ChangeEvent::Replace { space, tuple } if space.id == SPACE_ID => {
+        let row: SpaceRow = tuple.decode()?;
+        self.spaces.insert(row.id, row.name);
+      },
+      ChangeEvent::Delete { space, key } if space.id == SPACE_ID => {
+        let (id,): (u64,) = key.decode()?;
+        self.spaces.remove(&id);
+      },
+      // rename, name is dropped if it is changed by other operation than assignment
+      ChangeEvent::Update { space, key, ops } if space.id == SPACE_ID => {
+        let (id,): (u64,) = key.decode()?;
+        let ops = read_value(&mut ops.as_bytes())?;
+        let name_field = SPACE_NAME_FIELD + index_base;
+
+        let renamed = ops.as_array().into_iter().flatten()
+          .filter_map(rmpv::Value::as_array)
+          .rfind(|op| matches!(
+            op.get(1),
+            Some(field) if field.as_u64() == Some(name_field) || field.as_str() == Some("name"),
+          ));
+
+        match renamed.map(|op| (op[0].as_str(), op.get(2).and_then(rmpv::Value::as_str))) {
+          Some((Some("="), Some(name))) => { self.spaces.insert(id, name.into()); },
+          Some(_) => { self.spaces.remove(&id); },
+          None => {},
+        }
+      },
+      _ => {},
+    }
+
+    Ok(())
+  }
+
+  /// reports replicated position, so server won't treat subscriber as dead
+  async fn send_ack(&mut self) -> Result<(), Error> {
+    let mut packet: Vec<u8> = Vec::new();
+
+    rmp::encode::write_map_len(&mut packet, 2)?;
+    rmp::encode::write_uint(&mut packet, Field::RequestType as u64)?;
+    rmp::encode::write_uint(&mut packet, Code::Ok as u64)?;
+    rmp::encode::write_uint(&mut packet, Field::Sync as u64)?;
+    rmp::encode::write_uint(&mut packet, 0)?;
+
+    rmp::encode::write_map_len(&mut packet, 1)?;
+    rmp::encode::write_uint(&mut packet, Field::Vclock as u64)?;
+    rmp::encode::write_map_len(&mut packet, self.checkpoint.vclock.len() as u32)?;
+    for (&replica_id, &lsn) in self.checkpoint.vclock.iter() {
+      rmp::encode::write_uint(&mut packet, replica_id as u64)?;
+      rmp::encode::write_uint(&mut packet, lsn)?;
+    }
+
+    let mut buf: Vec<u8> = Vec::with_capacity(5 + packet.len());
+    rmp::encode::write_uint(&mut buf, packet.len() as u64)?;
+    buf.extend(packet);
+
+    self.transport.write_all(&buf).await?;
+    self.transport.flush().await?;
+
+    Ok(())
+  }
+}
+
+#[derive(Debug, Default)]
+struct RowHeader {
+  code: u64,
+  replica_id: u32,
+  lsn: u64,
+  timestamp: f64,
+}
+
+impl RowHeader {
+  fn unpack(cur: &mut Cursor<&[u8]>) -> Result<RowHeader, Error> {
+    let mut header = RowHeader::default();
+
+    for _ in 0..read_map_len(cur)? {
+      let raw_field: u64 = read_int(cur)?;
+
+      match Field::from_u64(raw_field) {
+        Some(Field::RequestType) => { header.code = read_int(cur)?; },
+        Some(Field::ReplicaID) => { header.replica_id = read_int(cur)?; },
+        Some(Field::LSN) => { header.lsn = read_int(cur)?; },
+        Some(Field::Timestamp) => {
+          header.timestamp = read_value(cur)?.as_f64().unwrap_or_default();
+        },
+        _ => { read_value(cur)?; },
+      }
+    }
+
+    Ok(header)
+  }
+}
+
+#[derive(Debug, Default)]
+struct RowBody {
+  space_id: u64,
+  index_base: u64,
+  key: Option<Tuple>,
+  tuple: Option<Tuple>,
+  ops: Option<Tuple>,
+}
+
+impl RowBody {
+  fn unpack(body: &[u8]) -> Result<RowBody, Error> {
+    let mut row = RowBody::default();
+
+    if body.is_empty() {
+      return Ok(row);
+    }
+
+    let mut cur = Cursor::new(body);
+
+    for _ in 0..read_map_len(&mut cur)? {
+      let raw_field: u64 = read_int(&mut cur)?;
+
+      let start = cur.position() as usize;
+      read_value(&mut cur)?;
+      let raw = &body[start..cur.position() as usize];
+
+      match Field::from_u64(raw_field) {
+        Some(Field::SpaceID) => {
+          row.space_id = read_int(&mut Cursor::new(raw))?;
+        },
+        Some(Field::IndexBase) => {
+          row.index_base = read_int(&mut Cursor::new(raw))?;
+        },
+        Some(Field::Key) => { row.key = Some(Tuple(raw.to_vec())); },
+        Some(Field::Tuple) => { row.tuple = Some(Tuple(raw.to_vec())); },
+        Some(Field::Ops) => { row.ops = Some(Tuple(raw.to_vec())); },
+        _ => {},
+      }
+    }
+
+    Ok(row)
+  }
+}
+
+/// id and name of _space/_vspace tuple, other fields are skipped.
+struct SpaceRow {
+  id: u64,
+  name: String,
+}
+
+impl<'de> Deserialize<'de> for SpaceRow {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>
+  {
+    struct SpaceRowVisitor;
+
+    impl<'de> Visitor<'de> for SpaceRowVisitor {
+      type Value = SpaceRow;
+
+      fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("space definition tuple")
+      }
+
+      fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where A: SeqAccess<'de>
+      {
+        let invalid = |len| serde::de::Error::invalid_length(len, &self);
+
+        let id: u64 = seq.next_element()?.ok_or_else(|| invalid(0))?;
+        let _owner: IgnoredAny = seq.next_element()?.ok_or_else(|| invalid(1))?;
+        let name: String = seq.next_element()?.ok_or_else(|| invalid(2))?;
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+
+        Ok(SpaceRow { id, name })
+      }
+    }
+
+    deserializer.deserialize_seq(SpaceRowVisitor)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use rmpv::Value;
+  use tokio::io::duplex;
+
+  use super::*;
+
+  fn frame(header: Vec<(u64, Value)>, body: Vec<(u64, Value)>) -> Vec<u8> {
+    let to_map = |fields: Vec<(u64, Value)>| Value::Map(
+      fields.into_iter().map(|(k, v)| (k.into(), v)).collect()
+    );
+
+    let mut packet: Vec<u8> = Vec::new();
+    rmpv::encode::write_value(&mut packet, &to_map(header)).unwrap();
+    if !body.is_empty() {
+      rmpv::encode::write_value(&mut packet, &to_map(body)).unwrap();
+    }
+
+    let mut buf: Vec<u8> = Vec::new();
+    rmp::encode::write_u32(&mut buf, packet.len() as u32).unwrap();
+    buf.extend(packet);
+    buf
+  }
+
+  fn row(code: RequestType, lsn: u64, body: Vec<(u64, Value)>) -> Vec<u8> {
+    frame(vec![
+      (0, (code as u64).into()), (2, 1.into()),
+      (3, lsn.into()), (4, Value::F64(1.5)),
+    ], body)
+  }
+
+  #[tokio::test]
+  async fn test_change_stream() {
+    let (client, mut server) = duplex(4096);
+
+    let mut data: Vec<u8> = Vec::new();
+    // heartbeat
+    data.extend(frame(vec![ (0, 0.into()), (1, 0.into()) ], vec![]));
+    data.extend(row(RequestType::Replace, 10, vec![
+      (0x10, SPACE_ID.into()),
+      (0x21, Value::Array(vec![ 512.into(), 1.into(), "users".into(), "memtx".into() ])),
+    ]));
+    data.extend(row(RequestType::Insert, 11, vec![
+      (0x10, 512.into()),
+      (0x21, Value::Array(vec![ 1.into(), "alice".into() ])),
+    ]));
+    data.extend(row(RequestType::Nop, 12, vec![]));
+    // space:rename() in lua
+    data.extend(row(RequestType::Update, 13, vec![
+      (0x10, SPACE_ID.into()), (0x15, 1.into()),
+      (0x20, Value::Array(vec![ 512.into() ])),
+      (0x21, Value::Array(vec![ Value::Array(vec![ "=".into(), 3.into(), "members".into() ]) ])),
+    ]));
+    data.extend(row(RequestType::Delete, 14, vec![
+      (0x10, 512.into()), (0x11, 0.into()),
+      (0x20, Value::Array(vec![ 1.into() ])),
+    ]));
+    server.write_all(&data).await.unwrap();
+
+    let mut stream = ChangeStream::new(Box::new(client), Checkpoint::default(), 1024);
+
+    let change = stream.next().await.unwrap().unwrap();
+    assert_eq!(change.lsn, 10);
+
+    let change = stream.next().await.unwrap().unwrap();
+    assert_eq!(change.timestamp, 1.5);
+    match change.event {
+      ChangeEvent::Insert { space, tuple } => {
+        assert_eq!(space, SpaceRef { id: 512, name: Some("users".into()) });
+        let user: (u64, String) = tuple.decode().unwrap();
+        assert_eq!(user, (1, "alice".into()));
+      },
+      event => panic!("unexpected event {:?}", event),
+    }
+
+    let change = stream.next().await.unwrap().unwrap();
+    assert!(matches!(change.event, ChangeEvent::Update { .. }));
+
+    let change = stream.next().await.unwrap().unwrap();
+    match change.event {
+      ChangeEvent::Delete { space, .. } => assert_eq!(space.name.as_deref(), Some("members")),
+      event => panic!("unexpected event {:?}", event),
+    }
+    assert_eq!(stream.checkpoint().vclock.get(&1), Some(&14));
+
+    // subscriber replied to heartbeat with its vclock
+    let mut ack = [0u8; 1];
+    server.read_exact(&mut ack).await.unwrap();
+
+    drop(server);
+    assert!(stream.next().await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn test_max_frame_size() {
+    let (client, mut server) = duplex(4096);
+    let mut stream = ChangeStream::new(Box::new(client), Checkpoint::default(), 1024);
+
+    // size prefix of huge frame without its data
+    let mut data: Vec<u8> = Vec::new();
+    rmp::encode::write_u64(&mut data, u64::MAX).unwrap();
+    server.write_all(&data).await.unwrap();
+
+    let err = stream.next().await.unwrap_err();
+    assert!(err.to_string().contains("exceeds max response size"), "{}", err);
+  }
+}
@@ -0,0 +1,408 @@
+/*!
+  This module contains client abstraction over connection-like types.
+*/
+
+use std::{fmt, marker::PhantomData, sync::Arc};
+
+use async_trait::async_trait;
+use serde::de::{Deserialize, DeserializeOwned, Deserializer, IgnoredAny, SeqAccess, Visitor};
+
+use crate::{
+  connection::Connection,
+  crud::Crud,
+  iproto::{
+    constants::{Code, Iterator, VINDEX_ID, VSPACE_ID},
+    request::{
+      Call, Delete, Eval, Execute, Insert, IntoKey, IntoTuple,
+      PagedSelect, Replace, Select, Update, Upsert, Value,
+    },
+    response::{Page, SQLBody, TarantoolError},
+    types::Error,
+  },
+  sequence::Sequence,
+  space::{SelectOptions, Space},
+  triggers::Triggers,
+};
+
+/**
+  This trait covers high-level operations of tarantool client.
+
+  It is implemented by Connection, so application code
+  may accept `impl TarantoolClient` and tests may provide fakes.
+
+  Example:
+  ```rust
+    # use alopecosa::{Error, TarantoolClient};
+    async fn user_name(client: &impl TarantoolClient, id: u64) -> Result<Option<String>, Error> {
+      let user: Option<(u64, String)> = client.get(512, 0, vec![ id.into() ]).await?;
+      Ok(user.map(|(_, name)| name))
+    }
+  ```
+*/
+#[async_trait]
+pub trait TarantoolClient: Send + Sync {
+  async fn select<T>(&self, body: Select) -> Result<T, Error>
+    where T: DeserializeOwned;
+
+  /// selects page of tuples with its position, see `Connection::select_page`
+  async fn select_page<T>(&self, body: PagedSelect) -> Result<Page<T>, Error>
+    where T: DeserializeOwned;
+
+  async fn insert<T>(&self, body: Insert) -> Result<T, Error>
+    where T: DeserializeOwned;
+
+  async fn replace<T>(&self, body: Replace) -> Result<T, Error>
+    where T: DeserializeOwned;
+
+  async fn update<T>(&self, body: Update) -> Result<T, Error>
+    where T: DeserializeOwned;
+
+  async fn delete<T>(&self, body: Delete) -> Result<T, Error>
+    where T: DeserializeOwned;
+
+  async fn upsert(&self, body: Upsert) -> Result<(), Error>;
+
+  async fn call<T>(&self, body: Call) -> Result<T, Error>
+    where T: DeserializeOwned;
+
+  async fn eval<T>(&self, body: Eval) -> Result<T, Error>
+    where T: DeserializeOwned;
+
+  async fn execute(&self, body: Execute) -> Result<SQLBody, Error>;
+
+  async fn execute_select<T>(&self, body: Execute) -> Result<T, Error>
+    where T: DeserializeOwned;
+
+  /// selects single tuple by exact key
+  async fn get<T>(&self, space_id: u64, index_id: u64, key: Vec<Value>) -> Result<Option<T>, Error>
+    where T: DeserializeOwned + Send
+  {
+    let tuples: Vec<T> = self.select(Select {
+      space_id, index_id,
+      limit: 1, offset: 0,
+      iterator: Iterator::Eq,
+      keys: key,
+    }).await?;
+
+    Ok(tuples.into_iter().next())
+  }
+
+  /// calls function with arguments and decodes its result as T
+  async fn call_typed<T, A>(&self, function: &str, args: A) -> Result<T, Error>
+    where T: DeserializeOwned, A: IntoTuple + Send
+  {
+    self.call(Call { function: function.into(), args: args.into_tuple() }).await
+  }
+
+  /// evaluates lua expression with arguments and decodes its result as T
+  async fn eval_typed<T, A>(&self, expr: &str, args: A) -> Result<T, Error>
+    where T: DeserializeOwned, A: IntoTuple + Send
+  {
+    self.eval(Eval { expr: expr.into(), args: args.into_tuple() }).await
+  }
+
+  /// selects tuples of space by key of index with options and decodes them as T
+  async fn select_typed<T, K>(
+    &self, space_id: u64, index_id: u64, key: K, opts: SelectOptions,
+  ) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned, K: IntoKey + Send
+  {
+    self.select(Select {
+      space_id, index_id,
+      limit: opts.limit, offset: opts.offset,
+      iterator: opts.iterator,
+      keys: key.into_key(),
+    }).await
+  }
+
+  /// inserts tuple and decodes inserted one, it is the only tuple of result
+  async fn insert_typed<T, V>(&self, space_id: u64, tuple: V) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned, V: IntoTuple + Send
+  {
+    self.insert(Insert { space_id, tuple: tuple.into_tuple() }).await
+  }
+
+  /// updates tuple by key of index, result is empty if tuple doesn't exist
+  async fn update_typed<T, K, O>(
+    &self, space_id: u64, index_id: u64, key: K, ops: O,
+  ) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned, K: IntoKey + Send,
+          O: IntoIterator + Send, O::Item: Into<Vec<Value>>,
+  {
+    self.update(Update {
+      space_id, index_id, index_base: 0,
+      key: key.into_key(),
+      tuple: ops.into_iter().map(Into::into).collect(),
+    }).await
+  }
+
+  /// deletes tuple by key of index, result is empty if tuple doesn't exist
+  async fn delete_typed<T, K>(&self, space_id: u64, index_id: u64, key: K) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned, K: IntoKey + Send
+  {
+    self.delete(Delete { space_id, index_id, key: key.into_key() }).await
+  }
+
+  /// inserts tuple or updates existing one by ops
+  async fn upsert_typed<V, O>(&self, space_id: u64, tuple: V, ops: O) -> Result<(), Error>
+    where V: IntoTuple + Send, O: IntoIterator + Send, O::Item: Into<Vec<Value>>
+  {
+    self.upsert(Upsert {
+      space_id, index_base: 0,
+      ops: ops.into_iter().map(Into::into).collect(),
+      tuple: tuple.into_tuple(),
+    }).await
+  }
+
+  /// handle of space with given name, see space module
+  fn space(&self, name: &str) -> Space<'_, Self>
+    where Self: Sized
+  {
+    Space::new(self, name)
+  }
+
+  /// handle of space of crud module, see crud module
+  fn crud(&self, space: &str) -> Crud<'_, Self>
+    where Self: Sized
+  {
+    Crud::new(self, space)
+  }
+
+  /// handle of sequence with given name, see sequence module
+  fn sequence(&self, name: &str) -> Sequence<'_, Self>
+    where Self: Sized
+  {
+    Sequence::new(self, name)
+  }
+
+  /// handle of triggers of space with given name, see triggers module
+  fn triggers(&self, space: &str) -> Triggers<'_, Self>
+    where Self: Sized
+  {
+    Triggers::new(self, space)
+  }
+
+  /// resolves space id by name using _vspace system space
+  async fn space_id(&self, name: &str) -> Result<u64, Error> {
+    const VSPACE_NAME_INDEX: u64 = 2;
+
+    let spaces: Vec<TupleField<u64, 0>> = self.select(Select {
+      space_id: VSPACE_ID, index_id: VSPACE_NAME_INDEX,
+      limit: 1, offset: 0,
+      iterator: Iterator::Eq,
+      keys: vec![ name.into() ],
+    }).await?;
+
+    spaces.into_iter().next()
+      .map(|head| head.0)
+      .ok_or_else(|| Error::TarantoolError(
+        Code::ErrorNoSuchSpace,
+        TarantoolError::new(format!("Space '{}' does not exist", name)),
+      ))
+  }
+
+  /// resolves index id by its name using _vindex system space
+  async fn index_id(&self, space_id: u64, name: &str) -> Result<u64, Error> {
+    const VINDEX_NAME_INDEX: u64 = 2;
+
+    let indexes: Vec<TupleField<u64, 1>> = self.select(Select {
+      space_id: VINDEX_ID, index_id: VINDEX_NAME_INDEX,
+      limit: 1, offset: 0,
+      iterator: Iterator::Eq,
+      keys: vec![ space_id.into(), name.into() ],
+    }).await?;
+
+    indexes.into_iter().next()
+      .map(|field| field.0)
+      .ok_or_else(|| Error::TarantoolError(
+        Code::ErrorNoSuchIndexName,
+        TarantoolError::new(format!("No index '{}' is defined in space {}", name, space_id)),
+      ))
+  }
+}
+
+/// Deserializes only N-th field of tuple, the rest is skipped.
+pub(crate) struct TupleField<T, const N: usize>(pub(crate) T);
+
+impl<'de, T, const N: usize> Deserialize<'de> for TupleField<T, N>
+  where T: Deserialize<'de>
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>
+  {
+    struct FieldVisitor<T, const N: usize>(PhantomData<T>);
+
+    impl<'de, T, const N: usize> Visitor<'de> for FieldVisitor<T, N>
+      where T: Deserialize<'de>
+    {
+      type Value = TupleField<T, N>;
+
+      fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tuple with at least {} fields", N + 1)
+      }
+
+      fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where A: SeqAccess<'de>
+      {
+        for i in 0..N {
+          seq.next_element::<IgnoredAny>()?
+            .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+        }
+        let field = seq.next_element()?
+          .ok_or_else(|| serde::de::Error::invalid_length(N, &self))?;
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(TupleField(field))
+      }
+    }
+
+    deserializer.deserialize_seq(FieldVisitor(PhantomData))
+  }
+}
+
+#[async_trait]
+impl TarantoolClient for Connection {
+  async fn select<T>(&self, body: Select) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    Connection::select(self, body).await
+  }
+
+  async fn select_page<T>(&self, body: PagedSelect) -> Result<Page<T>, Error>
+    where T: DeserializeOwned
+  {
+    Connection::select_page(self, body).await
+  }
+
+  async fn insert<T>(&self, body: Insert) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    Connection::insert(self, body).await
+  }
+
+  async fn replace<T>(&self, body: Replace) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    Connection::replace(self, body).await
+  }
+
+  async fn update<T>(&self, body: Update) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    Connection::update(self, body).await
+  }
+
+  async fn delete<T>(&self, body: Delete) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    Connection::delete(self, body).await
+  }
+
+  async fn upsert(&self, body: Upsert) -> Result<(), Error> {
+    Connection::upsert(self, body).await
+  }
+
+  async fn call<T>(&self, body: Call) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    Connection::call(self, body).await
+  }
+
+  async fn eval<T>(&self, body: Eval) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    Connection::eval(self, body).await
+  }
+
+  async fn execute(&self, body: Execute) -> Result<SQLBody, Error> {
+    Connection::execute(self, body).await
+  }
+
+  async fn execute_select<T>(&self, body: Execute) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    Connection::execute_select(self, body).await
+  }
+
+  async fn space_id(&self, name: &str) -> Result<u64, Error> {
+    self.resolve_space(name).await
+  }
+
+  async fn index_id(&self, space_id: u64, name: &str) -> Result<u64, Error> {
+    self.resolve_index(space_id, name).await
+  }
+}
+
+#[async_trait]
+impl<C> TarantoolClient for Arc<C>
+  where C: TarantoolClient
+{
+  async fn select<T>(&self, body: Select) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    C::select(self, body).await
+  }
+
+  async fn select_page<T>(&self, body: PagedSelect) -> Result<Page<T>, Error>
+    where T: DeserializeOwned
+  {
+    C::select_page(self, body).await
+  }
+
+  async fn insert<T>(&self, body: Insert) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    C::insert(self, body).await
+  }
+
+  async fn replace<T>(&self, body: Replace) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    C::replace(self, body).await
+  }
+
+  async fn update<T>(&self, body: Update) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    C::update(self, body).await
+  }
+
+  async fn delete<T>(&self, body: Delete) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    C::delete(self, body).await
+  }
+
+  async fn upsert(&self, body: Upsert) -> Result<(), Error> {
+    C::upsert(self, body).await
+  }
+
+  async fn call<T>(&self, body: Call) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    C::call(self, body).await
+  }
+
+  async fn eval<T>(&self, body: Eval) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    C::eval(self, body).await
+  }
+
+  async fn execute(&self, body: Execute) -> Result<SQLBody, Error> {
+    C::execute(self, body).await
+  }
+
+  async fn execute_select<T>(&self, body: Execute) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    C::execute_select(self, body).await
+  }
+
+  async fn space_id(&self, name: &str) -> Result<u64, Error> {
+    C::space_id(self, name).await
+  }
+
+  async fn index_id(&self, space_id: u64, name: &str) -> Result<u64, Error> {
+    C::index_id(self, space_id, name).await
+  }
+}
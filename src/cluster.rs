@@ -0,0 +1,270 @@
+/*!
+  This module contains scatter-gather calls across shards.
+
+  Cluster is a set of named shards, shard is any client,
+  e.g. ReplicaSet, so calls of shard go to its master.
+  Function is called on every shard concurrently and
+  results are gathered with errors of failed shards.
+
+  Cluster is TarantoolClient too, its requests go to the first shard,
+  e.g. the one which holds unsharded spaces.
+
+  Example:
+  ```rust,no_run
+    # use std::sync::Arc;
+    # use alopecosa::{Cluster, Connection, IntoTuple, ReplicaSet};
+    # async fn f(
+    #   rs1_master: Arc<Connection>, rs1_replica: Arc<Connection>,
+    #   rs2_master: Arc<Connection>, rs2_replica: Arc<Connection>, user_id: u64,
+    # ) {
+    let cluster = Cluster::new()
+      .with_shard("rs1", ReplicaSet::new(rs1_master).with_replica(rs1_replica))
+      .with_shard("rs2", ReplicaSet::new(rs2_master).with_replica(rs2_replica));
+
+    let counts = cluster.map_call::<(u64,)>("orders_count", ( user_id, ).into_tuple()).await;
+    for (shard, err) in counts.errors.iter() {
+      log::error!("shard {} failed: {}", shard, err);
+    }
+
+    let total = counts.merge(0, |total, _, (count,)| total + count);
+    # }
+  ```
+*/
+
+use std::io;
+
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use serde::de::DeserializeOwned;
+
+use crate::{
+  client::TarantoolClient,
+  iproto::{
+    request::{Call, Delete, Eval, Execute, Insert, PagedSelect, Replace, Select, Update, Upsert, Value},
+    response::{Page, SQLBody},
+    types::Error,
+  },
+};
+
+/// Results of call gathered from shards in order of shards.
+#[derive(Debug)]
+pub struct ShardResults<T> {
+  pub results: Vec<(String, T)>,
+  pub errors: Vec<(String, Error)>,
+}
+
+impl<T> ShardResults<T> {
+  /// true if every shard answered
+  pub fn is_ok(&self) -> bool {
+    self.errors.is_empty()
+  }
+
+  /// folds results of shards which answered
+  pub fn merge<R, F>(self, init: R, merge: F) -> R
+    where F: FnMut(R, &str, T) -> R
+  {
+    let mut merge = merge;
+    self.results.into_iter()
+      .fold(init, |acc, (shard, result)| merge(acc, &shard, result))
+  }
+
+  /// folds results only if every shard answered, otherwise errors are returned
+  pub fn try_merge<R, F>(self, init: R, merge: F) -> Result<R, Vec<(String, Error)>>
+    where F: FnMut(R, &str, T) -> R
+  {
+    match self.errors.is_empty() {
+      true => Ok(self.merge(init, merge)),
+      false => Err(self.errors),
+    }
+  }
+}
+
+/// This is set of named shards, see module docs.
+#[derive(Debug)]
+pub struct Cluster<C> {
+  shards: Vec<(String, C)>,
+}
+
+impl<C> Default for Cluster<C> {
+  fn default() -> Self {
+    Cluster { shards: Vec::new() }
+  }
+}
+
+impl<C> Cluster<C>
+  where C: TarantoolClient
+{
+  pub fn new() -> Cluster<C> {
+    Cluster::default()
+  }
+
+  pub fn with_shard(mut self, name: &str, client: C) -> Self {
+    self.shards.push((name.into(), client));
+    self
+  }
+
+  pub fn shard(&self, name: &str) -> Option<&C> {
+    self.shards.iter()
+      .find(|(shard, _)| shard == name)
+      .map(|(_, client)| client)
+  }
+
+  pub fn shards(&self) -> impl Iterator<Item = &str> {
+    self.shards.iter().map(|(name, _)| name.as_str())
+  }
+
+  /// shard which serves requests of TarantoolClient
+  fn first(&self) -> Result<&C, Error> {
+    self.shards.first()
+      .map(|(_, client)| client)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "cluster has no shards").into())
+  }
+
+  /// calls function on every shard concurrently
+  pub async fn map_call<T>(&self, function: &str, args: Vec<Value>) -> ShardResults<T>
+    where T: DeserializeOwned + Send
+  {
+    let calls = self.shards.iter()
+      .map(|(_, client)| client.call::<T>(Call { function: function.into(), args: args.clone() }));
+
+    let mut gathered = ShardResults { results: Vec::new(), errors: Vec::new() };
+    for ((shard, _), result) in self.shards.iter().zip(join_all(calls).await) {
+      match result {
+        Ok(result) => gathered.results.push((shard.clone(), result)),
+        Err(err) => gathered.errors.push((shard.clone(), err)),
+      }
+    }
+
+    gathered
+  }
+}
+
+#[async_trait]
+impl<C> TarantoolClient for Cluster<C>
+  where C: TarantoolClient
+{
+  async fn select<T>(&self, body: Select) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.first()?.select(body).await
+  }
+
+  async fn select_page<T>(&self, body: PagedSelect) -> Result<Page<T>, Error>
+    where T: DeserializeOwned
+  {
+    self.first()?.select_page(body).await
+  }
+
+  async fn insert<T>(&self, body: Insert) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.first()?.insert(body).await
+  }
+
+  async fn replace<T>(&self, body: Replace) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.first()?.replace(body).await
+  }
+
+  async fn update<T>(&self, body: Update) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.first()?.update(body).await
+  }
+
+  async fn delete<T>(&self, body: Delete) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.first()?.delete(body).await
+  }
+
+  async fn upsert(&self, body: Upsert) -> Result<(), Error> {
+    self.first()?.upsert(body).await
+  }
+
+  async fn call<T>(&self, body: Call) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.first()?.call(body).await
+  }
+
+  async fn eval<T>(&self, body: Eval) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.first()?.eval(body).await
+  }
+
+  async fn execute(&self, body: Execute) -> Result<SQLBody, Error> {
+    self.first()?.execute(body).await
+  }
+
+  async fn execute_select<T>(&self, body: Execute) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.first()?.execute_select(body).await
+  }
+
+  async fn space_id(&self, name: &str) -> Result<u64, Error> {
+    self.first()?.space_id(name).await
+  }
+
+  async fn index_id(&self, space_id: u64, name: &str) -> Result<u64, Error> {
+    self.first()?.index_id(space_id, name).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{IntoTuple, iproto::constants::Code, testing::FakeClient};
+
+  use super::*;
+
+  fn shard(count: u64) -> FakeClient {
+    FakeClient::new().with_function("orders_count", move |args| match args.first() {
+      Some(Value::UInt(1)) => Ok(vec![ count.into() ]),
+      _ => Err(Error::TarantoolError(
+        Code::ErrorProcLua,
+        crate::iproto::response::TarantoolError::new("unknown user"),
+      )),
+    })
+  }
+
+  #[tokio::test]
+  async fn test_map_call() {
+    let cluster = Cluster::new()
+      .with_shard("rs1", shard(2))
+      .with_shard("rs2", shard(3))
+      .with_shard("rs3", FakeClient::new());
+
+    assert_eq!(cluster.shards().collect::<Vec<_>>(), vec![ "rs1", "rs2", "rs3" ]);
+
+    let counts = cluster.map_call::<(u64,)>("orders_count", ( 1u64, ).into_tuple()).await;
+    assert!(!counts.is_ok());
+    assert_eq!(counts.errors.len(), 1);
+    assert_eq!(counts.errors[0].0, "rs3");
+    assert_eq!(counts.merge(0, |total, _, (count,)| total + count), 5);
+
+    let counts = Cluster::new()
+      .with_shard("rs1", shard(2))
+      .with_shard("rs2", shard(3))
+      .map_call::<(u64,)>("orders_count", ( 1u64, ).into_tuple()).await;
+    let per_shard = counts.try_merge(Vec::new(), |mut all, shard, (count,)| {
+      all.push((shard.to_string(), count));
+      all
+    }).unwrap();
+    assert_eq!(per_shard, vec![ ("rs1".to_string(), 2), ("rs2".to_string(), 3) ]);
+  }
+
+  #[tokio::test]
+  async fn test_first_shard() {
+    let cluster = Cluster::new()
+      .with_shard("rs1", shard(2))
+      .with_shard("rs2", shard(3));
+    let (count,): (u64,) = cluster.call_typed("orders_count", ( 1u64, )).await.unwrap();
+    assert_eq!(count, 2);
+
+    let empty: Cluster<FakeClient> = Cluster::new();
+    assert!(empty.call_typed::<(u64,), _>("orders_count", ( 1u64, )).await.is_err());
+  }
+}
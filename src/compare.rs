@@ -0,0 +1,400 @@
+/*!
+  This module contains comparison of space on two instances.
+
+  Both spaces are scanned page by page in primary key order
+  and merged, so memory is bounded by page size and number of reported differences.
+  It is meant for verification of migrations and replicas.
+
+  Keys are ordered like tarantool orders unsigned, integer, number, string
+  (with binary collation), varbinary and uuid fields,
+  spaces with other collations may be reported wrongly.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, compare::{CompareOptions, compare_spaces}};
+    # async fn f(old: &Connection, new: &Connection) -> Result<(), Error> {
+    let comparison = compare_spaces(old, new, 512, CompareOptions::new()).await?;
+    for diff in comparison.differences.iter() {
+      println!("{:?}", diff);
+    }
+    assert!(comparison.is_equal());
+    # Ok(()) }
+  ```
+*/
+
+use std::{cmp::Ordering, collections::VecDeque, convert::TryFrom};
+
+use async_trait::async_trait;
+use rmpv::Value as MsgValue;
+use uuid::Uuid;
+
+use crate::{
+  connection::Connection,
+  iproto::{
+    constants::{Field, Iterator, VINDEX_ID},
+    request::{Select, Value},
+    types::Error,
+  },
+};
+
+const VINDEX_PARTS_FIELD: usize = 5;
+const MP_UUID: i8 = 2;
+
+/// This configures compare_spaces.
+#[derive(Debug, Clone, Copy)]
+pub struct CompareOptions {
+  page_size: u32,
+  max_differences: usize,
+}
+
+impl Default for CompareOptions {
+  fn default() -> Self {
+    CompareOptions { page_size: 1000, max_differences: 1000 }
+  }
+}
+
+impl CompareOptions {
+  pub fn new() -> CompareOptions {
+    CompareOptions::default()
+  }
+
+  pub fn with_page_size(mut self, page_size: u32) -> Self {
+    self.page_size = page_size.max(1);
+    self
+  }
+
+  /// comparison stops after given number of differences
+  pub fn with_max_differences(mut self, max_differences: usize) -> Self {
+    self.max_differences = max_differences;
+    self
+  }
+}
+
+/// Difference of tuples with the same primary key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+  /// tuple exists only on left instance
+  Missing { key: Vec<MsgValue>, left: Vec<MsgValue> },
+  /// tuple exists only on right instance
+  Extra { key: Vec<MsgValue>, right: Vec<MsgValue> },
+  /// tuples exist on both instances but differ
+  Differs { key: Vec<MsgValue>, left: Vec<MsgValue>, right: Vec<MsgValue> },
+}
+
+/// Result of comparison.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Comparison {
+  pub left_count: u64,
+  pub right_count: u64,
+  pub matching: u64,
+  pub differences: Vec<Difference>,
+  /// comparison was stopped due to max_differences
+  pub truncated: bool,
+}
+
+impl Comparison {
+  /// truncated comparison is not equal as some tuples are not compared
+  pub fn is_equal(&self) -> bool {
+    self.differences.is_empty() && !self.truncated
+  }
+}
+
+fn value_rank(value: &MsgValue) -> u8 {
+  match value {
+    MsgValue::Nil => 0,
+    MsgValue::Boolean(_) => 1,
+    MsgValue::Integer(_) | MsgValue::F32(_) | MsgValue::F64(_) => 2,
+    MsgValue::String(_) => 3,
+    MsgValue::Binary(_) => 4,
+    MsgValue::Ext(_, _) => 5,
+    MsgValue::Array(_) => 6,
+    MsgValue::Map(_) => 7,
+  }
+}
+
+fn as_i128(value: &MsgValue) -> Option<i128> {
+  match value {
+    MsgValue::Integer(i) => i.as_u64().map(i128::from).or_else(|| i.as_i64().map(i128::from)),
+    _ => None,
+  }
+}
+
+/// orders values like tarantool scalar fields with binary collation
+fn cmp_values(a: &MsgValue, b: &MsgValue) -> Ordering {
+  match (a, b) {
+    (MsgValue::Boolean(a), MsgValue::Boolean(b)) => a.cmp(b),
+    (MsgValue::String(a), MsgValue::String(b)) => a.as_bytes().cmp(b.as_bytes()),
+    (MsgValue::Binary(a), MsgValue::Binary(b)) => a.cmp(b),
+    (MsgValue::Ext(ta, a), MsgValue::Ext(tb, b)) => (ta, a).cmp(&(tb, b)),
+    (MsgValue::Array(a), MsgValue::Array(b)) => cmp_keys(a, b),
+    _ if value_rank(a) == 2 && value_rank(b) == 2 => match (as_i128(a), as_i128(b)) {
+      (Some(a), Some(b)) => a.cmp(&b),
+      _ => a.as_f64().unwrap_or_default()
+        .partial_cmp(&b.as_f64().unwrap_or_default())
+        .unwrap_or(Ordering::Equal),
+    },
+    _ => value_rank(a).cmp(&value_rank(b)),
+  }
+}
+
+fn cmp_keys(a: &[MsgValue], b: &[MsgValue]) -> Ordering {
+  a.iter().zip(b.iter())
+    .map(|(a, b)| cmp_values(a, b))
+    .find(|ord| *ord != Ordering::Equal)
+    .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// converts key field to request value, only scalar key types are supported
+fn key_value(value: &MsgValue) -> Result<Value, Error> {
+  let unsupported = || Error::InvalidKey(format!("unsupported key field {}", value));
+
+  Ok(match value {
+    MsgValue::Nil => Value::Null,
+    MsgValue::Boolean(b) => Value::Bool(*b),
+    MsgValue::Integer(i) => match i.as_u64() {
+      Some(u) => Value::UInt(u),
+      None => Value::Int(i.as_i64().ok_or_else(unsupported)?),
+    },
+    MsgValue::F32(f) => Value::F32(*f),
+    MsgValue::F64(f) => Value::F64(*f),
+    MsgValue::String(s) => Value::Str(s.as_str().ok_or_else(unsupported)?.into()),
+    MsgValue::Binary(b) => Value::Bin(b.clone()),
+    MsgValue::Ext(MP_UUID, data) => Value::Uuid(
+      Uuid::from_slice(data).map_err(|_| unsupported())?,
+    ),
+    _ => return Err(unsupported()),
+  })
+}
+
+/// field numbers of index parts, both old [field, type] and new {field = n} formats are parsed
+pub(crate) fn parse_parts(parts: &MsgValue) -> Result<Vec<usize>, Error> {
+  let parts = parts.as_array().ok_or(Error::UnexpectedValue(Field::Data))?;
+
+  parts.iter()
+    .map(|part| {
+      let field = match part {
+        MsgValue::Array(part) => part.first(),
+        MsgValue::Map(part) => part.iter()
+          .find(|(key, _)| key.as_str() == Some("field"))
+          .map(|(_, field)| field),
+        _ => None,
+      };
+
+      field.and_then(MsgValue::as_u64)
+        .and_then(|field| usize::try_from(field).ok())
+        .ok_or(Error::UnexpectedValue(Field::Data))
+    })
+    .collect()
+}
+
+async fn primary_key_fields(conn: &Connection, space_id: u64) -> Result<Vec<usize>, Error> {
+  let indexes = conn.select_formatted(Select {
+    space_id: VINDEX_ID, index_id: 0,
+    limit: 1, offset: 0,
+    iterator: Iterator::Eq,
+    keys: vec![ space_id.into(), 0u64.into() ],
+  }).await?;
+
+  let index = indexes.into_iter().next()
+    .ok_or_else(|| Error::InvalidKey(format!("space {} has no primary index", space_id)))?;
+
+  match index.values().get(VINDEX_PARTS_FIELD) {
+    Some(parts) => parse_parts(parts),
+    None => Err(Error::UnexpectedValue(Field::Data)),
+  }
+}
+
+/// tuple with its primary key
+type Keyed = (Vec<MsgValue>, Vec<MsgValue>);
+
+/// source of tuples in primary key order
+#[async_trait]
+trait Tuples {
+  async fn next(&mut self) -> Result<Option<Keyed>, Error>;
+}
+
+/// pages through space in primary key order
+struct Scanner<'c> {
+  conn: &'c Connection,
+  space_id: u64,
+  key_fields: &'c [usize],
+  page_size: u32,
+  page: VecDeque<Vec<MsgValue>>,
+  last_key: Option<Vec<MsgValue>>,
+  done: bool,
+}
+
+impl<'c> Scanner<'c> {
+  fn key(&self, tuple: &[MsgValue]) -> Vec<MsgValue> {
+    self.key_fields.iter()
+      .map(|&field| tuple.get(field).cloned().unwrap_or(MsgValue::Nil))
+      .collect()
+  }
+}
+
+#[async_trait]
+impl<'c> Tuples for Scanner<'c> {
+  /// next tuple with its key, pages are loaded on demand
+  async fn next(&mut self) -> Result<Option<Keyed>, Error> {
+    if self.page.is_empty() && !self.done {
+      let (iterator, keys) = match &self.last_key {
+        Some(key) => (Iterator::Gt, key.iter().map(key_value).collect::<Result<_, _>>()?),
+        None => (Iterator::All, Vec::new()),
+      };
+
+      let tuples = self.conn.select_formatted(Select {
+        space_id: self.space_id, index_id: 0,
+        limit: self.page_size, offset: 0,
+        iterator, keys,
+      }).await?;
+
+      self.done = tuples.len() < self.page_size as usize;
+      self.page.extend(tuples.into_iter().map(|tuple| tuple.into_values()));
+      if let Some(tuple) = self.page.back() {
+        self.last_key = Some(self.key(tuple));
+      }
+    }
+
+    Ok(self.page.pop_front().map(|tuple| (self.key(&tuple), tuple)))
+  }
+}
+
+/// compares space on two connections, see module docs
+pub async fn compare_spaces(
+  left: &Connection, right: &Connection, space_id: u64, opts: CompareOptions,
+) -> Result<Comparison, Error> {
+  let key_fields = primary_key_fields(left, space_id).await?;
+
+  let scanner = |conn| Scanner {
+    conn, space_id,
+    key_fields: &key_fields,
+    page_size: opts.page_size,
+    page: VecDeque::new(),
+    last_key: None,
+    done: false,
+  };
+  merge(&mut scanner(left), &mut scanner(right), opts.max_differences).await
+}
+
+/// merges tuples of both sides by key, it stops after max_differences
+async fn merge(
+  lefts: &mut (dyn Tuples + Send), rights: &mut (dyn Tuples + Send), max_differences: usize,
+) -> Result<Comparison, Error> {
+  let mut comparison = Comparison::default();
+  let (mut l, mut r) = (lefts.next().await?, rights.next().await?);
+
+  loop {
+    if comparison.differences.len() >= max_differences && (l.is_some() || r.is_some()) {
+      comparison.truncated = true;
+      break;
+    }
+
+    let ord = match (&l, &r) {
+      (None, None) => break,
+      (Some(_), None) => Ordering::Less,
+      (None, Some(_)) => Ordering::Greater,
+      (Some((lkey, _)), Some((rkey, _))) => cmp_keys(lkey, rkey),
+    };
+
+    match ord {
+      Ordering::Less => {
+        let (key, left) = l.take().unwrap_or_default();
+        comparison.left_count += 1;
+        comparison.differences.push(Difference::Missing { key, left });
+        l = lefts.next().await?;
+      },
+      Ordering::Greater => {
+        let (key, right) = r.take().unwrap_or_default();
+        comparison.right_count += 1;
+        comparison.differences.push(Difference::Extra { key, right });
+        r = rights.next().await?;
+      },
+      Ordering::Equal => {
+        let ((key, left), (_, right)) = (l.take().unwrap_or_default(), r.take().unwrap_or_default());
+        comparison.left_count += 1;
+        comparison.right_count += 1;
+        match left == right {
+          true => comparison.matching += 1,
+          false => comparison.differences.push(Difference::Differs { key, left, right }),
+        }
+        l = lefts.next().await?;
+        r = rights.next().await?;
+      },
+    }
+  }
+
+  Ok(comparison)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_key_order() {
+    let key = |values: Vec<MsgValue>| values;
+
+    assert_eq!(cmp_keys(&key(vec![ 1.into() ]), &key(vec![ 2.into() ])), Ordering::Less);
+    assert_eq!(cmp_keys(&key(vec![ (-1).into() ]), &key(vec![ 0.into() ])), Ordering::Less);
+    assert_eq!(cmp_keys(&key(vec![ 2.into() ]), &key(vec![ 1.5.into() ])), Ordering::Greater);
+    assert_eq!(cmp_keys(&key(vec![ "b".into() ]), &key(vec![ "ab".into() ])), Ordering::Greater);
+    assert_eq!(cmp_keys(&key(vec![ 1.into(), "a".into() ]), &key(vec![ 1.into(), "a".into() ])), Ordering::Equal);
+    assert_eq!(cmp_keys(&key(vec![ 1.into() ]), &key(vec![ "1".into() ])), Ordering::Less);
+
+    let parts = MsgValue::Array(vec![
+      MsgValue::Map(vec![ ("field".into(), 2.into()), ("type".into(), "string".into()) ]),
+      MsgValue::Array(vec![ 0.into(), "unsigned".into() ]),
+    ]);
+    assert_eq!(parse_parts(&parts).unwrap(), vec![ 2, 0 ]);
+  }
+
+  struct Fixed(VecDeque<Keyed>);
+
+  #[async_trait]
+  impl Tuples for Fixed {
+    async fn next(&mut self) -> Result<Option<Keyed>, Error> {
+      Ok(self.0.pop_front())
+    }
+  }
+
+  fn fixed(tuples: &[(u64, &str)]) -> Fixed {
+    Fixed(tuples.iter()
+      .map(|&(id, name)| (vec![ id.into() ], vec![ id.into(), name.into() ]))
+      .collect())
+  }
+
+  #[tokio::test]
+  async fn test_merge() {
+    let left = [ (1, "a"), (2, "b"), (4, "d"), (5, "e") ];
+    let right = [ (2, "b"), (3, "c"), (4, "x"), (5, "e"), (6, "f") ];
+
+    let comparison = merge(&mut fixed(&left), &mut fixed(&right), 10).await.unwrap();
+    assert_eq!((comparison.left_count, comparison.right_count, comparison.matching), (4, 5, 2));
+    assert_eq!(comparison.differences, vec![
+      Difference::Missing { key: vec![ 1.into() ], left: vec![ 1.into(), "a".into() ] },
+      Difference::Extra { key: vec![ 3.into() ], right: vec![ 3.into(), "c".into() ] },
+      Difference::Differs {
+        key: vec![ 4.into() ],
+        left: vec![ 4.into(), "d".into() ],
+        right: vec![ 4.into(), "x".into() ],
+      },
+      Difference::Extra { key: vec![ 6.into() ], right: vec![ 6.into(), "f".into() ] },
+    ]);
+    assert!(!comparison.truncated && !comparison.is_equal());
+
+    let comparison = merge(&mut fixed(&left), &mut fixed(&left), 10).await.unwrap();
+    assert!(comparison.is_equal());
+    assert_eq!(comparison.matching, 4);
+
+    let comparison = merge(&mut fixed(&left), &mut fixed(&right), 2).await.unwrap();
+    assert_eq!(comparison.differences.len(), 2);
+    assert!(comparison.truncated && !comparison.is_equal());
+
+    let comparison = merge(&mut fixed(&left), &mut fixed(&right), 0).await.unwrap();
+    assert!(comparison.differences.is_empty());
+    assert!(comparison.truncated && !comparison.is_equal());
+
+    let comparison = merge(&mut fixed(&[]), &mut fixed(&[]), 0).await.unwrap();
+    assert!(!comparison.truncated && comparison.is_equal());
+  }
+}
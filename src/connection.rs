@@ -1,25 +1,65 @@
+pub mod access;
+pub mod backup;
+pub mod batch;
 pub mod connector;
+pub mod export;
+pub mod features;
+pub mod health;
+pub mod labels;
+pub mod import;
+pub mod loader;
+pub mod push;
+pub mod query_log;
+pub mod raw;
+pub mod rate_limiter;
+pub mod reconnect;
+pub mod retry;
+pub mod schema_cache;
+pub mod statements;
+pub mod stream;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod transport;
+pub mod url;
+#[cfg(feature = "uring")]
+pub mod uring;
+pub mod watcher;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 mod connection_server;
 
-use std::sync::{
-  Arc, atomic::{AtomicBool, AtomicU64, Ordering},
+use std::{
+  fmt,
+  future::Future,
+  net::SocketAddr,
+  pin::Pin,
+  sync::{Arc, RwLock, atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}},
+  task::{Context, Poll},
+  time::{Duration, Instant},
 };
 
-use dashmap::DashMap;
-use serde::de::DeserializeOwned;
-use tokio::sync::{mpsc, oneshot};
+use dashmap::{DashMap, mapref::entry::Entry};
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::{sync::{mpsc, oneshot, Notify, OwnedSemaphorePermit, Semaphore}, task::JoinHandle};
+
+use rate_limiter::RateLimiter;
+use statements::StatementCache;
 
 use crate::iproto::{
+  constants::{Code, Field, Iterator, RequestType, VSPACE_ID},
+  redaction::Redaction,
   request::{
-    self, Call, Delete, Eval, Execute, Insert, Prepare,
-    Replace, Request, Select, Update, Upsert,
+    self, Body, ByName, Call, Call16, Delete, Eval, Execute, Insert, Prepare,
+    PagedSelect, Replace, Request, Select, Target, Unprepare, Update, Upsert,
   },
   response::{
     ErrorBody, Response,
-    SQLBody, SQLBodyDecoder,
+    SQLBody, SQLBodyDecoder, SQLNamedBody, SqlResult, SqlResultBody,
+    FormatField, FormattedBody, FormattedTuple, Page, PageBody, RawTupleBody, TarantoolError,
     TupleBody, TupleBodySelect
   },
-  types::Error,
+  serialize,
+  types::{Error, ErrorContext},
 };
 
 macro_rules! request_method {
@@ -30,43 +70,60 @@ macro_rules! request_method {
     {
       let req = request::$func(body);
 
-      let resp: Response = self.perform(req).await?;
+      let (resp, context) = self.perform_in_context(req).await?;
       //print!("resp request_method: {:#?}",resp );
       resp.unpack_body::<TupleBody<T>>()
+        .map_err(|err| context.wrap(err))
     }
   };
 }
 
-macro_rules! request_sql_method {
+macro_rules! request_by_name_method {
   ($func:ident, $body:ident) => {
     #[allow(dead_code)]
-    pub async fn $func(&self, body: $body) -> Result<SQLBody, Error> {
-      let req = request::$func(body);
+    pub async fn $func<T>(&self, space: &str, index: Option<&str>, body: $body) -> Result<T, Error>
+      where T: DeserializeOwned
+    {
+      let resp: Response = self.perform_by_name(
+        RequestType::$body, body, space, index,
+      ).await?;
+
+      resp.unpack_body::<TupleBody<T>>()
+        .map_err(|err| self.error_context(
+          RequestType::$body, (Some(space.into()), index.map(Into::into)), resp.header.sync,
+        ).wrap(err))
+    }
+  };
+}
 
-      let resp: Response = self.perform(req).await?;
+macro_rules! request_sql_method {
+  ($func:ident, $body:ident, $perform:ident) => {
+    #[allow(dead_code)]
+    pub async fn $func(&self, body: $body) -> Result<SQLBody, Error> {
+      let resp: Response = self.$perform(body).await?;
 
       resp.unpack_body::<SQLBodyDecoder>()
+        .map_err(|err| self.error_context(RequestType::$body, (None, None), resp.header.sync).wrap(err))
     }
   };
 }
 
 macro_rules! request_sqlselect_method {
-  ($func:ident, $body:ident) => {
+  ($func:ident, $body:ident, $perform:ident) => {
     #[allow(dead_code)]
     pub async fn $func<T>(&self, body: $body) -> Result<T, Error>
       where T: DeserializeOwned
     {
-      let req = request::$func(body);
-
-      let resp: Response = self.perform(req).await?;
+      let resp: Response = self.$perform(body).await?;
       //print!("resp: {:#?}",resp.body );
       resp.unpack_body_from_execute_select::<TupleBodySelect<T>>()
+        .map_err(|err| self.error_context(RequestType::$body, (None, None), resp.header.sync).wrap(err))
     }
   };
 }
 
 
-pub(crate) type RespChans = Arc<DashMap<u64, oneshot::Sender<Response>>>;
+pub(crate) type RespChans = Arc<DashMap<u64, RespChan>>;
 
 /**
   This is user part of connection,
@@ -75,7 +132,10 @@ pub(crate) type RespChans = Arc<DashMap<u64, oneshot::Sender<Response>>>;
   You usualy will work with Arc<Connection>, so it is async-safe.
 
   Example:
-  ```rust
+  ```rust,no_run
+    # use std::{sync::Arc, time::Duration};
+    # use alopecosa::{Call, Connection, Connector, Eval, IntoTuple, Iterator, Select};
+    # async fn f() {
     let conn: Arc<Connection> = Connector::new("127.0.0.1:3301".parse().unwrap())
       .connect().await.unwrap();
 
@@ -98,15 +158,50 @@ pub(crate) type RespChans = Arc<DashMap<u64, oneshot::Sender<Response>>>;
         args: ( 123, ).into_tuple(),
       })
     ).await.unwrap().unwrap();
+    # }
   ```
 */
 #[derive(Debug)]
 pub struct Connection {
   pub(crate) version: String,
+  /// features are negotiated again on every reconnect
+  pub(crate) features: Arc<RwLock<features::ProtocolFeatures>>,
   pub(crate) sync: AtomicU64,
-  pub(crate) req_chan_sender: mpsc::Sender<Request>,
+  /// id of the next stream, zero means no stream
+  pub(crate) stream_id: AtomicU64,
+  /// it is incremented when connection is lost, streams don't survive reconnection
+  pub(crate) generation: Arc<AtomicU64>,
+  pub(crate) req_chan_sender: mpsc::Sender<Outgoing>,
   pub(crate) resp_chans: RespChans,
   pub(crate) closed: Arc<AtomicBool>,
+  /// wakes writer to shut connection down, see Connection::close
+  pub(crate) shutdown: Arc<Notify>,
+  pub(crate) close_timeout: Duration,
+  /// task serving socket, it is aborted on drop
+  pub(crate) server: JoinHandle<()>,
+  pub(crate) statements: StatementCache,
+  pub(crate) schema: Arc<schema_cache::SchemaCache>,
+  pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+  pub(crate) retry: Option<retry::RetryPolicy>,
+  pub(crate) max_tuple_size: Option<usize>,
+  /// calls are sent as Call16, see Connector::with_legacy_call
+  pub(crate) legacy_call: bool,
+  /// count of requests waiting for responses
+  pub(crate) in_flight: Arc<AtomicUsize>,
+  /// notified once the last request in flight is done, see Connection::close
+  pub(crate) idle: Arc<Notify>,
+  pub(crate) in_flight_limit: Option<Arc<Semaphore>>,
+  pub(crate) addr: SocketAddr,
+  /// address with labels for logs, see Connector::peer
+  pub(crate) peer: String,
+  /// name of authenticated user
+  pub(crate) user: String,
+  pub(crate) query_log: Option<Redaction>,
+  pub(crate) labels: labels::Labels,
+  pub(crate) watchers: Arc<watcher::Watchers>,
+  pub(crate) pushes: push::Pushes,
+  #[cfg(feature = "otel")]
+  pub(crate) trace_propagation: telemetry::TracePropagation,
 }
 
 #[allow(dead_code)]
@@ -122,17 +217,105 @@ impl Connection {
   }
 
   /**
-    schedules connection for close
+    closes connection, new requests are rejected with Error::ConnectionClosed at once.
 
-    Note: this method is called on drop
+    Returned future waits for responses of requests in flight up to close timeout
+    of connector, then shuts socket down and fails the rest of requests
+    with Error::ConnectionClosed. If it is not awaited, socket is closed on drop.
   */
-  pub fn close(&self) {
-    self.closed.store(true, Ordering::SeqCst)
+  pub fn close(&self) -> Closing<'_> {
+    self.closed.store(true, Ordering::SeqCst);
+    Closing(Box::pin(self.shutdown()))
+  }
+
+  pub fn is_closed(&self) -> bool {
+    self.closed.load(Ordering::SeqCst)
+  }
+
+  /// count of requests waiting for responses, see Connector::with_max_in_flight
+  pub fn in_flight(&self) -> usize {
+    self.in_flight.load(Ordering::SeqCst)
   }
 
+  /// errors of request are wrapped with its context, see Error::root
   pub async fn perform(&self, req: Request) -> Result<Response, Error> {
-    let resp: Response = self.make_request(req).await;
+    let (resp, _) = self.perform_in_context(req).await?;
+
+    Ok(resp)
+  }
+
+  /**
+    sends request as is and returns its response without decoding,
+    error responses are returned as responses and request is never retried.
+
+    Sync is assigned by connection unless it is set with RequestBuilder::with_sync,
+    so custom Body types may be sent and their responses unpacked with
+    Response::unpack_body and custom BodyDecoder.
+  */
+  pub async fn send_raw(&self, req: Request) -> Result<Response, Error> {
+    let context = self.error_context(req.header.request, req.target(), 0);
+
+    let resp = self.make_request(req).await
+      .map_err(|err| context.wrap(err))?;
+    self.schema.observe(resp.header.schema);
+
+    Ok(resp)
+  }
+
+  /**
+    performs request and returns context for errors of response decoding,
+    request is retried if retry policy of connection allows it
+  */
+  async fn perform_in_context(&self, req: Request) -> Result<(Response, ErrorContext), Error> {
+    let policy = match &self.retry {
+      Some(policy) if policy.is_retryable(&req) => policy,
+      _ => return self.perform_once(req).await,
+    };
+
+    let context = self.error_context(req.header.request, req.target(), 0);
+
+    // span covers every attempt, so trace is injected before body is packed for replay
+    #[cfg(feature = "otel")]
+    let mut req = req;
+    #[cfg(feature = "otel")]
+    let trace = telemetry::start(&mut req, self.addr, &self.labels, self.trace_propagation);
+    #[cfg(feature = "otel")]
+    { req.header.trace = Some(trace.clone()); }
+
+    let replay = req.replay(self.query_log)
+      .map_err(|err| context.wrap(err))?;
+    let result = policy.run(|| self.perform_once(replay.request())).await;
+
+    #[cfg(feature = "otel")]
+    telemetry::finish_retried(&trace, result.as_ref().err());
+
+    result
+  }
+
+  async fn perform_once(&self, req: Request) -> Result<(Response, ErrorContext), Error> {
+    let mut context = self.error_context(req.header.request, req.target(), 0);
+
+    let resp: Response = match self.make_request(req).await {
+      Ok(resp) => resp,
+      Err(err) => return Err(context.wrap(err)),
+    };
+    context.sync = resp.header.sync;
+    self.schema.observe(resp.header.schema);
+
+    match Self::check_response(resp) {
+      Ok(resp) => Ok((resp, context)),
+      Err(err) => Err(context.wrap(err)),
+    }
+  }
+
+  fn error_context(
+    &self, request: RequestType, (space, index): (Option<String>, Option<String>), sync: u64,
+  ) -> ErrorContext {
+    ErrorContext { request, space, index, sync, addr: self.addr }
+  }
 
+  /// converts error response into Error
+  pub(crate) fn check_response(resp: Response) -> Result<Response, Error> {
     match resp.header.code.is_err() {
       false => Ok(resp),
       true => match resp.unpack_body::<ErrorBody>() {
@@ -142,73 +325,619 @@ impl Connection {
     }
   }
 
+  /**
+    creates batch of requests, they are written at once
+    and their responses are awaited together
+  */
+  pub fn batch(&self) -> batch::Batch<'_> {
+    batch::Batch::new(self)
+  }
+
   request_method!(select, Select);
-  request_method!(call, Call);
+  request_method!(call16, Call16);
   request_method!(insert, Insert);
   request_method!(replace, Replace);
   request_method!(update, Update);
   request_method!(delete, Delete);
   request_method!(eval, Eval);
 
+  request_by_name_method!(select_by_name, Select);
+  request_by_name_method!(insert_by_name, Insert);
+  request_by_name_method!(replace_by_name, Replace);
+  request_by_name_method!(update_by_name, Update);
+  request_by_name_method!(delete_by_name, Delete);
+
+
+  request_sql_method!(prepare, Prepare, perform_prepare);
+  request_sql_method!(execute, Execute, perform_execute);
+  request_sqlselect_method!(execute_select, Execute, perform_execute);
+
+  /// executes sql statement, its rows are accessed by column names (see SqlResult)
+  pub async fn execute_result(&self, body: Execute) -> Result<SqlResult, Error> {
+    let resp: Response = self.perform_execute(body).await?;
+
+    resp.unpack_body::<SqlResultBody>()
+      .map_err(|err| self.error_context(RequestType::Execute, (None, None), resp.header.sync).wrap(err))
+  }
+
+  /**
+    executes sql statement and maps every returned row onto T
+    by column names (see SQLNamedBody)
+  */
+  pub async fn execute_select_named<T>(&self, body: Execute) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned
+  {
+    let resp: Response = self.perform_execute(body).await?;
+
+    resp.unpack_body::<SQLNamedBody<T>>()
+      .map_err(|err| self.error_context(RequestType::Execute, (None, None), resp.header.sync).wrap(err))
+  }
+
+  /**
+    selects tuples with their formats,
+    so fields may be accessed by names (see FormattedBody)
+  */
+  pub async fn select_formatted(&self, body: Select) -> Result<Vec<FormattedTuple>, Error> {
+    let (resp, context) = self.perform_in_context(request::select(body)).await?;
+
+    resp.unpack_body::<FormattedBody>()
+      .map_err(|err| context.wrap(err))
+  }
+
+  /// selects tuples as request values, it is handy for dynamic schemas (see RawTupleBody)
+  pub async fn select_values(&self, body: Select) -> Result<Vec<Vec<request::Value>>, Error> {
+    let (resp, context) = self.perform_in_context(request::select(body)).await?;
+
+    resp.unpack_body::<RawTupleBody>()
+      .map_err(|err| context.wrap(err))
+  }
 
-  request_sql_method!(prepare, Prepare);
-  request_sql_method!(execute, Execute);
-  request_sqlselect_method!(execute_select, Execute);
+  /**
+    selects page of tuples with position of its last tuple,
+    position is passed as `after_position` to select the next page (tarantool 2.11+)
+  */
+  pub async fn select_page<T>(&self, mut body: PagedSelect) -> Result<Page<T>, Error>
+    where T: DeserializeOwned
+  {
+    body.fetch_position = true;
+    let (resp, context) = self.perform_in_context(request::paged_select(body)).await?;
+
+    resp.unpack_body::<PageBody<T>>()
+      .map_err(|err| context.wrap(err))
+  }
 
   pub async fn upsert(&self, body: Upsert) -> Result<(), Error> {
     let req = request::upsert(body);
 
-    let resp: Response = self.make_request(req).await;
+    self.perform(req).await?;
 
-    match resp.header.code.is_err() {
-      false => Ok(()),
-      true => match resp.unpack_body::<ErrorBody>() {
-        Ok(err) => Err(Error::TarantoolError(resp.header.code, err)),
-        Err(err) => Err(err),
+    Ok(())
+  }
+
+  /// inserts struct serialized as tuple, see iproto::serialize
+  pub async fn insert_struct<T, R>(&self, space_id: u64, tuple: &T) -> Result<R, Error>
+    where T: Serialize + ?Sized, R: DeserializeOwned
+  {
+    self.insert(Insert { space_id, tuple: serialize::to_tuple(tuple)? }).await
+  }
+
+  /// replaces struct serialized as tuple, see iproto::serialize
+  pub async fn replace_struct<T, R>(&self, space_id: u64, tuple: &T) -> Result<R, Error>
+    where T: Serialize + ?Sized, R: DeserializeOwned
+  {
+    self.replace(Replace { space_id, tuple: serialize::to_tuple(tuple)? }).await
+  }
+
+  /// calls function, it is sent as Call16 if connector has legacy call option
+  pub async fn call<T>(&self, body: Call) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    match self.legacy_call {
+      true => self.call16(body.into()).await,
+      false => {
+        let (resp, context) = self.perform_in_context(request::call(body)).await?;
+        resp.unpack_body::<TupleBody<T>>()
+          .map_err(|err| context.wrap(err))
       },
     }
   }
 
+  /// calls function with arguments serialized as tuple, e.g. struct or tuple
+  pub async fn call_struct<A, R>(&self, function: &str, args: &A) -> Result<R, Error>
+    where A: Serialize + ?Sized, R: DeserializeOwned
+  {
+    self.call(Call { function: function.into(), args: serialize::to_tuple(args)? }).await
+  }
+
   pub async fn ping(&self) -> Result<(), Error> {
     let req = request::ping();
 
-    let resp: Response = self.make_request(req).await;
+    self.perform(req).await?;
 
-    match resp.header.code.is_err() {
-      false => Ok(()),
-      true => match resp.unpack_body::<ErrorBody>() {
-        Ok(err) => Err(Error::TarantoolError(resp.header.code, err)),
-        Err(err) => Err(err),
-      },
+    Ok(())
+  }
+
+  async fn perform_prepare(&self, body: Prepare) -> Result<Response, Error> {
+    let sql = match &body {
+      Prepare::SQL(sql) => Some(sql.clone()),
+      Prepare::StatementID(_) => None,
+    };
+
+    let resp: Response = self.perform(request::prepare(body)).await?;
+
+    if let Some(sql) = sql {
+      let stmt_id = resp.unpack_body::<SQLBodyDecoder>()?
+        .get(&Field::StmtID)
+        .and_then(|id| id.as_i64());
+
+      if let Some(stmt_id) = stmt_id {
+        self.unprepare_evicted(self.statements.insert(stmt_id, sql)).await;
+      }
+    }
+
+    Ok(resp)
+  }
+
+  async fn unprepare_evicted(&self, evicted: Vec<i64>) {
+    for stmt_id in evicted {
+      if let Err(err) = self.perform(request::unprepare(Unprepare { stmt_id })).await {
+        log::debug!("failed to unprepare evicted statement {}: {}", stmt_id, err);
+      }
+    }
+  }
+
+  /**
+    frees statement prepared on server, statement prepared through connection is forgotten,
+    statement evicted from cache is already freed
+  */
+  pub async fn unprepare(&self, stmt_id: i64) -> Result<(), Error> {
+    if let Some(actual_id) = self.statements.remove(stmt_id) {
+      self.perform(request::unprepare(Unprepare { stmt_id: actual_id })).await?;
+    }
+
+    Ok(())
+  }
+
+  /**
+    executes statement prepared through this connection,
+    if server reports that statement is expired
+    it will be prepared again from stored sql text and retried once,
+    statement evicted from cache is prepared again before execution
+  */
+  async fn perform_execute(&self, body: Execute) -> Result<Response, Error> {
+    let stmt_id = match body.expr {
+      Prepare::StatementID(id) => id,
+      Prepare::SQL(_) => return self.perform(request::execute(body)).await,
+    };
+
+    let start = Instant::now();
+    let result = self.perform_statement(stmt_id, body).await;
+    self.statements.record(stmt_id, start.elapsed(), result.is_err());
+
+    result
+  }
+
+  async fn perform_statement(&self, stmt_id: i64, mut body: Execute) -> Result<Response, Error> {
+    let actual_id = match self.statements.resolve(stmt_id) {
+      Some(actual_id) => actual_id,
+      None => self.prepare_again(stmt_id).await?,
+    };
+    body.expr = Prepare::StatementID(actual_id);
+
+    let err = match self.perform(request::execute(body.clone())).await {
+      Ok(resp) => return Ok(resp),
+      Err(err) => err,
+    };
+
+    if !StatementCache::is_expired(&err) || self.statements.sql(stmt_id).is_none() {
+      return Err(err);
+    }
+
+    log::debug!("statement {} is expired, preparing it again", stmt_id);
+
+    body.expr = Prepare::StatementID(self.prepare_again(stmt_id).await?);
+    self.perform(request::execute(body)).await
+  }
+
+  /// prepares statement again from stored sql text, returns its new id on server
+  async fn prepare_again(&self, stmt_id: i64) -> Result<i64, Error> {
+    let sql = self.statements.sql(stmt_id)
+      .ok_or_else(|| Error::TarantoolError(
+        Code::ErrorWrongQueryID,
+        TarantoolError::new(format!("Prepared statement with id {} does not exist", stmt_id)),
+      ))?;
+
+    let new_id = self.perform(request::prepare(Prepare::SQL(sql))).await?
+      .unpack_body::<SQLBodyDecoder>()?
+      .get(&Field::StmtID)
+      .and_then(|id| id.as_i64())
+      .ok_or(Error::UnexpectedValue(Field::StmtID))?;
+
+    self.unprepare_evicted(self.statements.update(stmt_id, new_id)).await;
+    Ok(new_id)
+  }
+
+  /// fields of space format taken from _vspace system space
+  pub async fn space_format(&self, space_id: u64) -> Result<Vec<FormatField>, Error> {
+    const VSPACE_FORMAT_FIELD: usize = 6;
+
+    let spaces = self.select_formatted(Select {
+      space_id: VSPACE_ID, index_id: 0,
+      limit: 1, offset: 0,
+      iterator: Iterator::Eq,
+      keys: vec![ space_id.into() ],
+    }).await?;
+
+    let space = spaces.into_iter().next()
+      .ok_or_else(|| Error::TarantoolError(
+        Code::ErrorNoSuchSpace,
+        TarantoolError::new(format!("Space '{}' does not exist", space_id)),
+      ))?;
+
+    match space.values().get(VSPACE_FORMAT_FIELD) {
+      Some(rmpv::Value::Array(fields)) => fields.iter()
+        .map(FormatField::from_value)
+        .collect(),
+      _ => Err(Error::UnexpectedValue(Field::TupleFormats)),
     }
   }
 
+  /// hit, miss and eviction counts of statement cache and execution stats of statements
+  pub fn statement_stats(&self) -> statements::StatementCacheStats {
+    self.statements.stats()
+  }
+
+  pub fn labels(&self) -> &labels::Labels {
+    &self.labels
+  }
+
+  /// protocol version and features negotiated on connect or last reconnect
+  pub fn protocol_features(&self) -> features::ProtocolFeatures {
+    *self.features.read().unwrap()
+  }
+
+  /// true if server accepts space and index names in requests (tarantool 3.0+)
+  pub fn supports_names(&self) -> bool {
+    self.protocol_features().supports(features::Feature::SpaceAndIndexNames)
+  }
+
+  /**
+    performs request addressed by space and index names,
+    on older servers names are resolved to ids first
+  */
+  async fn perform_by_name<B>(
+    &self, request_type: RequestType, mut body: B, space: &str, index: Option<&str>,
+  ) -> Result<Response, Error>
+    where B: Body + Target + 'static
+  {
+    if self.supports_names() {
+      let body = ByName { space: space.into(), index: index.map(Into::into), body };
+      return self.perform(Request::new(request_type, body)).await;
+    }
+
+    let space_id = self.resolve_space(space).await?;
+    let index_id = match index {
+      Some(index) => self.resolve_index(space_id, index).await?,
+      None => 0,
+    };
+
+    body.set_target(space_id, index_id);
+    self.perform(Request::new(request_type, body)).await
+  }
+
+  /**
+    waits for requests in flight up to close timeout, then lets writer shut socket down.
+    Server is finished once it drops request channel.
+  */
+  async fn shutdown(&self) {
+    /// time given to writer to shut socket down after close timeout
+    const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(100);
+
+    let deadline = tokio::time::Instant::now() + self.close_timeout;
+    let drained = async {
+      loop {
+        // registered before check, so last request can't slip between them
+        let idle = self.idle.notified();
+        if self.in_flight() == 0 {
+          return;
+        }
+        idle.await;
+      }
+    };
+    let _ = tokio::time::timeout_at(deadline, drained).await;
+
+    self.shutdown.notify_one();
+    let finished = self.req_chan_sender.closed();
+    let _ = tokio::time::timeout_at(deadline + SHUTDOWN_TIMEOUT, finished).await;
+
+    self.server.abort();
+    self.resp_chans.clear();
+  }
+
   fn new_sync(&self) -> u64 {
     self.sync.fetch_add(1, Ordering::SeqCst)
   }
 
-  async fn make_request(&self, mut req: Request) -> Response {
+  async fn make_request(&self, req: Request) -> Result<Response, Error> {
+    self.send_request(req).await?.wait().await
+  }
+
+  /// registers and queues request, its response is awaited by returned pending
+  async fn send_request(&self, mut req: Request) -> Result<Pending, Error> {
+    self.admit(&mut req)?;
+    let pending = self.register(&mut req).await?;
+
+    let _ = self.req_chan_sender.send(Outgoing::Request(req)).await;
+
+    Ok(pending)
+  }
+
+  /**
+    queues request without waiting for its response,
+    response is dropped by reader since its sync isn't registered
+  */
+  pub(crate) fn send_detached(&self, mut req: Request) {
+    let request = req.header.request;
+    let sent = self.admit(&mut req)
+      .and_then(|_| self.req_chan_sender.try_send(Outgoing::Request(req))
+        .map_err(|_| Error::ConnectionClosed));
+    if let Err(err) = sent {
+      log::debug!("[{}] failed to send {:?}: {}", self.peer, request, err);
+    }
+  }
+
+  /**
+    assigns sync and checks request against max tuple size,
+    request which exceeds it is rejected here, so it takes neither rate limit nor in flight slot.
+    Max request size is checked by writer against packed request, see ConnectionServer::pack_request
+  */
+  fn admit(&self, req: &mut Request) -> Result<(), Error> {
     if self.closed.load(Ordering::SeqCst) {
-      panic!("request to closed connection");
+      return Err(Error::ConnectionClosed);
+    }
+
+    self.check_tuple(req)?;
+
+    if !req.header.fixed_sync {
+      req.header.sync = self.new_sync();
+    }
+    Ok(())
+  }
+
+  /// checks tuple of request against max tuple size
+  fn check_tuple(&self, req: &Request) -> Result<(), Error> {
+    let (limit, tuple) = match (self.max_tuple_size, req.tuple()) {
+      (Some(limit), Some(tuple)) => (limit, tuple),
+      _ => return Ok(()),
+    };
+
+    let mut buf: Vec<u8> = Vec::new();
+    rmp::encode::write_array_len(&mut buf, tuple.len() as u32)?;
+    let mut size = buf.len();
+
+    let mut largest = (0, 0);
+    for (i, field) in tuple.iter().enumerate() {
+      buf.clear();
+      field.pack(&mut buf)?;
+      size += buf.len();
+      if buf.len() > largest.1 {
+        largest = (i + 1, buf.len());
+      }
+    }
+
+    match size > limit {
+      true => Err(Error::TupleTooLarge { size, limit, field: largest.0, field_size: largest.1 }),
+      false => Ok(()),
+    }
+  }
+
+  /**
+    registers requests and sends them to be written at once,
+    writer splits them into writes which don't exceed max request size
+  */
+  pub(crate) async fn send_batch(&self, requests: Vec<Request>) -> Vec<Result<Pending, Error>> {
+    let mut results = Vec::with_capacity(requests.len());
+    let mut batch = Vec::new();
+
+    for mut req in requests {
+      match self.admit(&mut req) {
+        Ok(()) => (),
+        Err(err) => {
+          results.push(Err(err));
+          continue;
+        },
+      };
+
+      // registered requests are written before waiting for free slot, otherwise they never free it
+      let saturated = matches!(&self.in_flight_limit, Some(limit) if limit.available_permits() == 0);
+      if saturated && !batch.is_empty() {
+        let _ = self.req_chan_sender.send(Outgoing::Batch(std::mem::take(&mut batch))).await;
+      }
+
+      let pending = match self.register(&mut req).await {
+        Ok(pending) => pending,
+        Err(err) => {
+          results.push(Err(err));
+          continue;
+        },
+      };
+
+      batch.push(req);
+      results.push(Ok(pending));
+    }
+
+    if !batch.is_empty() {
+      let _ = self.req_chan_sender.send(Outgoing::Batch(batch)).await;
+    }
+
+    results
+  }
+
+  /**
+    applies rate limit, in flight limit, tracing and request log to request
+    and registers its response channel
+  */
+  async fn register(&self, req: &mut Request) -> Result<Pending, Error> {
+    let permit = match &self.in_flight_limit {
+      Some(limit) => Some(limit.clone().acquire_owned().await
+        .map_err(|_| Error::ConnectionClosed)?),
+      None => None,
+    };
+    let slot = InFlight::new(self.in_flight.clone(), self.idle.clone(), permit);
+
+    // bytes are taken by writer once request is packed
+    if let Some(limiter) = &self.rate_limiter {
+      limiter.acquire(1, 0).await;
+    }
+
+    // span of retried request is started by perform_in_context, its body is injected already
+    #[cfg(feature = "otel")]
+    let trace = match req.header.trace {
+      Some(_) => None,
+      None => Some(telemetry::start(req, self.addr, &self.labels, self.trace_propagation)),
+    };
+
+    let (sender, receiver) = oneshot::channel::<Result<Response, Error>>();
+    let resp_chan = RespChan { sender, _slot: slot };
+
+    // sync of request in flight is never reused, fixed one is rejected then
+    loop {
+      match self.resp_chans.entry(req.header.sync) {
+        Entry::Vacant(entry) => {
+          entry.insert(resp_chan);
+          break;
+        },
+        Entry::Occupied(_) if req.header.fixed_sync =>
+          return Err(Error::SyncInUse(req.header.sync)),
+        Entry::Occupied(_) => {
+          req.header.sync = self.new_sync();
+        },
+      }
     }
 
-    let (sender, receiver) = oneshot::channel::<Response>();
-    req.header.sync = self.new_sync();
+    if let Some(redaction) = self.query_log {
+      query_log::log(req, &self.peer, redaction);
+    }
+
+    Ok(Pending {
+      receiver,
+      closed: self.closed.clone(),
+      #[cfg(feature = "otel")]
+      trace,
+    })
+  }
+}
+
+/// This is future of graceful close, see Connection::close.
+#[must_use = "connection is closed gracefully only if Closing is awaited"]
+pub struct Closing<'a>(Pin<Box<dyn Future<Output = ()> + Send + 'a>>);
+
+impl fmt::Debug for Closing<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("Closing")
+  }
+}
+
+impl Future for Closing<'_> {
+  type Output = ();
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+    self.0.as_mut().poll(cx)
+  }
+}
+
+/// Requests passed to connection writer.
+#[derive(Debug)]
+pub(crate) enum Outgoing {
+  /// request is packed by writer straight into its write buffer
+  Request(Request),
+  /// requests which are written at once
+  Batch(Vec<Request>),
+}
+
+/// Registered request waiting for its response.
+pub(crate) struct Pending {
+  receiver: oneshot::Receiver<Result<Response, Error>>,
+  closed: Arc<AtomicBool>,
+  /// span of request which is ended with its response
+  #[cfg(feature = "otel")]
+  trace: Option<opentelemetry::Context>,
+}
+
+impl Pending {
+  /// waits for response of request sent by send_batch, rejected request yields its error
+  pub(crate) async fn result(pending: Result<Pending, Error>) -> Result<Response, Error> {
+    Connection::check_response(pending?.wait().await?)
+  }
 
-    if self.resp_chans.insert(req.header.sync, sender).is_some() {
-      log::error!("sync seems to be overflowed with {}", req.header.sync);
+  /**
+    response channel is dropped when connection is lost or closed,
+    request which can't be written gets its error through it
+  */
+  pub(crate) async fn wait(self) -> Result<Response, Error> {
+    let closed = self.closed;
+    let resp = self.receiver.await
+      .map_err(|_| match closed.load(Ordering::SeqCst) {
+        true => Error::ConnectionClosed,
+        false => Error::ConnectionReset,
+      })??;
+
+    #[cfg(feature = "otel")]
+    if let Some(trace) = &self.trace {
+      telemetry::finish(trace, &resp);
     }
 
+    Ok(resp)
+  }
+}
+
+/// Response channel of registered request, its in flight slot is freed with it.
+#[derive(Debug)]
+pub(crate) struct RespChan {
+  sender: oneshot::Sender<Result<Response, Error>>,
+  _slot: InFlight,
+}
+
+impl RespChan {
+  /// receiver is dropped when request is canceled
+  pub(crate) fn is_closed(&self) -> bool {
+    self.sender.is_closed()
+  }
+
+  /// delivers response or error of request, it is false if request is canceled
+  pub(crate) fn send(self, resp: Result<Response, Error>) -> bool {
+    self.sender.send(resp).is_ok()
+  }
+}
 
+/// Slot of request in flight, it is freed on drop.
+#[derive(Debug)]
+struct InFlight {
+  count: Arc<AtomicUsize>,
+  idle: Arc<Notify>,
+  _permit: Option<OwnedSemaphorePermit>,
+}
 
-    let _ = self.req_chan_sender.send(req).await;
+impl InFlight {
+  fn new(count: Arc<AtomicUsize>, idle: Arc<Notify>, permit: Option<OwnedSemaphorePermit>) -> InFlight {
+    count.fetch_add(1, Ordering::SeqCst);
+    InFlight { count, idle, _permit: permit }
+  }
+}
 
-    receiver.await.unwrap()
+impl Drop for InFlight {
+  fn drop(&mut self) {
+    if self.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+      self.idle.notify_waiters();
+    }
   }
 }
 
 impl Drop for Connection {
-  fn drop(&mut self) { self.close(); }
+  fn drop(&mut self) {
+    self.closed.store(true, Ordering::SeqCst);
+    self.server.abort();
+  }
 }
 
 
@@ -221,13 +950,283 @@ mod tests {
     constants::{Field, Iterator},
     request::{
       Call, Delete, Eval, Execute, ExecuteSelect,
-      Insert, Prepare, Replace, Select,
+      Insert, Prepare, Replace, RequestBuilder, Select,
       Update, Upsert,
     }
   }};
 
   use super::*;
 
+  #[tokio::test]
+  async fn test_error_context() {
+    let conn = crate::connection::transport::tests::fake_connection().await;
+
+    let err = conn.eval::<()>(Eval {
+      expr: "return 1".into(),
+      args: Vec::new(),
+    }).await.unwrap_err();
+
+    let context = err.context().expect("error without context");
+    assert_eq!(context.request, RequestType::Eval);
+    assert_eq!(context.space, None);
+    assert_eq!(context.addr, conn.addr);
+    assert!(err.to_string().contains("request=Eval"));
+    assert!(matches!(err.root(), Error::TarantoolError(Code::ErrorIllegalParams, _)));
+  }
+
+  #[tokio::test]
+  async fn test_send_raw() {
+    let conn = crate::connection::transport::tests::fake_connection().await;
+    let eval = || Eval { expr: "return 1".into(), args: Vec::new() };
+
+    let resp = conn.send_raw(
+      RequestBuilder::new(RequestType::Eval, eval()).with_sync(1 << 40).build(),
+    ).await.unwrap();
+    assert_eq!(resp.header.sync, 1 << 40);
+    assert_eq!(resp.header.code, Code::ErrorIllegalParams);
+    assert_eq!(conn.in_flight(), 0);
+
+    let err = conn.perform(request::eval(eval())).await.unwrap_err();
+    assert!(matches!(err.root(), Error::TarantoolError(Code::ErrorIllegalParams, _)));
+
+    conn.close().await;
+    let err = conn.send_raw(request::ping()).await.unwrap_err();
+    assert!(matches!(err.root(), Error::ConnectionClosed));
+    assert_eq!(err.context().unwrap().request, RequestType::Ping);
+  }
+
+  /// body which fails after part of it is packed
+  #[derive(Debug)]
+  struct Unpackable;
+
+  impl Body for Unpackable {
+    fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+      buf.push(0x81);
+      Err(Error::EncodeError("unpackable".into()))
+    }
+  }
+
+  #[tokio::test]
+  async fn test_pack_error() {
+    let conn = crate::connection::transport::tests::fake_connection().await;
+
+    // error reaches caller and requests written along with failed one are not corrupted
+    let (failed, ping) = tokio::time::timeout(Duration::from_secs(1), async {
+      tokio::join!(conn.perform(Request::new(RequestType::Call, Unpackable)), conn.ping())
+    }).await.unwrap();
+    assert!(matches!(failed.unwrap_err().root(), Error::EncodeError(_)));
+    assert!(ping.is_ok());
+    assert_eq!(conn.in_flight(), 0);
+  }
+
+  #[tokio::test]
+  async fn test_max_request_size() {
+    let conn = crate::connection::transport::tests::fake_connector(1)
+      .with_max_request_size(64)
+      .connect().await.unwrap();
+
+    // only waiter of oversized request fails, requests written along with it are not corrupted
+    let (err, ping) = tokio::time::timeout(Duration::from_secs(1), async {
+      tokio::join!(conn.eval::<()>(Eval { expr: "x".repeat(100), args: Vec::new() }), conn.ping())
+    }).await.unwrap();
+    assert!(matches!(err.unwrap_err().root(), Error::RequestTooLarge { limit: 64, .. }));
+    assert!(ping.is_ok());
+    assert_eq!(conn.in_flight(), 0);
+  }
+
+  #[tokio::test]
+  async fn test_sync_in_use() {
+    let conn = crate::connection::transport::tests::fake_connection().await;
+    let hang = || RequestBuilder::new(RequestType::Call, Call { function: "hang".into(), args: Vec::new() });
+
+    let in_flight = conn.send_raw(hang().with_sync(7).build());
+    tokio::pin!(in_flight);
+    assert!(poll_once(&mut in_flight).await.is_none());
+
+    // waiter of request in flight is not replaced
+    let err = conn.send_raw(hang().with_sync(7).build()).await.unwrap_err();
+    assert!(matches!(err.root(), Error::SyncInUse(7)));
+    assert_eq!(conn.in_flight(), 1);
+
+    // generated sync skips fixed one
+    conn.sync.store(7, Ordering::SeqCst);
+    let resp = conn.send_raw(request::ping()).await.unwrap();
+    assert_eq!(resp.header.sync, 8);
+  }
+
+  #[tokio::test]
+  async fn test_pack_error_in_writer() {
+    let conn = crate::connection::transport::tests::fake_connection().await;
+
+    // request is packed by writer, its waiter gets the error and connection keeps working
+    let err = conn.send_raw(request::ping().with_header_field(Field::Sync as u64, 5u64))
+      .await.unwrap_err();
+    assert!(matches!(err.root(), Error::UnexpectedField(field) if *field == Field::Sync as u64));
+    assert_eq!(conn.in_flight(), 0);
+    assert!(conn.ping().await.is_ok());
+  }
+
+  /// polls future once, it is none if future is pending
+  async fn poll_once<F: Future + Unpin>(fut: &mut F) -> Option<F::Output> {
+    std::future::poll_fn(|cx| Poll::Ready(match Pin::new(&mut *fut).poll(cx) {
+      Poll::Ready(output) => Some(output),
+      Poll::Pending => None,
+    })).await
+  }
+
+  #[tokio::test]
+  async fn test_legacy_call() {
+    let conn = crate::connection::transport::tests::fake_connector(1)
+      .with_legacy_call(true)
+      .connect().await.unwrap();
+    let call = || Call { function: "echo".into(), args: ( 1u64, "a" ).into_tuple() };
+
+    let (first, second): ((u64,), (String,)) = conn.call(call()).await.unwrap();
+    assert_eq!((first.0, second.0.as_str()), (1, "a"));
+
+    let results: Vec<Vec<Value>> = conn.call16(call().into()).await.unwrap();
+    assert_eq!(results, vec![ vec![ Value::UInt(1) ], vec![ Value::from("a") ] ]);
+  }
+
+  #[tokio::test]
+  async fn test_max_tuple_size() {
+    let conn = crate::connection::transport::tests::fake_connector(1)
+      .with_max_tuple_size(64)
+      .connect().await.unwrap();
+
+    let insert = |name: String| conn.perform(request::insert(Insert {
+      space_id: 512, tuple: ( 1u64, name, true ).into_tuple(),
+    }));
+
+    assert!(insert("small".into()).await.is_ok());
+
+    let err = insert("x".repeat(100)).await.unwrap_err();
+    assert!(matches!(
+      err.root(),
+      Error::TupleTooLarge { size: 105, limit: 64, field: 2, field_size: 102 }
+    ));
+    assert_eq!(err.context().unwrap().request, RequestType::Insert);
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_close() {
+    let conn = crate::connection::transport::tests::fake_connection().await;
+    let call = |function: &str| conn.perform(request::call(Call {
+      function: function.into(), args: Vec::new(),
+    }));
+
+    // request in flight is answered before socket is shut down
+    let (resp, _) = tokio::join!(call("sleep"), async {
+      tokio::time::sleep(Duration::from_millis(10)).await;
+      conn.close().await;
+    });
+    assert!(resp.is_ok());
+    assert!(conn.is_closed());
+    assert!(matches!(call("sleep").await.unwrap_err().root(), Error::ConnectionClosed));
+    assert!(conn.server.is_finished());
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_max_in_flight() {
+    let conn = crate::connection::transport::tests::fake_connector(1)
+      .with_max_in_flight(1)
+      .connect().await.unwrap();
+    let call = || conn.perform(request::call(Call {
+      function: "sleep".into(), args: Vec::new(),
+    }));
+
+    // the second request waits for slot of the first one
+    let (first, second, in_flight) = tokio::join!(call(), call(), async {
+      tokio::time::sleep(Duration::from_millis(10)).await;
+      conn.in_flight()
+    });
+    assert!(first.is_ok() && second.is_ok());
+    assert_eq!(in_flight, 1);
+    assert_eq!(conn.in_flight(), 0);
+
+    let results = conn.send_batch(vec![ request::ping(), request::ping(), request::ping() ]).await;
+    for pending in results {
+      Pending::result(pending).await.unwrap();
+    }
+    assert_eq!(conn.in_flight(), 0);
+  }
+
+  /// stream counting flushes, that is writes of connection
+  struct Flushes(tokio::io::DuplexStream, Arc<AtomicUsize>);
+
+  impl tokio::io::AsyncRead for Flushes {
+    fn poll_read(
+      mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+      Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+  }
+
+  impl tokio::io::AsyncWrite for Flushes {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+      Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+      self.1.fetch_add(1, Ordering::SeqCst);
+      Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+      Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+  }
+
+  #[derive(Debug)]
+  struct FlushesTransport(Arc<AtomicUsize>);
+
+  #[async_trait::async_trait]
+  impl transport::TransportConnector for FlushesTransport {
+    async fn connect(&self, _addr: SocketAddr) -> std::io::Result<transport::BoxedTransport> {
+      let stream = transport::tests::fake_stream();
+      Ok(Box::new(Flushes(stream, self.0.clone())))
+    }
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_write_batch() {
+    let flushes = Arc::new(AtomicUsize::new(0));
+    let conn = Connector::new("127.0.0.1:3301".parse().unwrap())
+      .with_transport(FlushesTransport(flushes.clone()))
+      .with_write_batch_delay(Duration::from_millis(20))
+      .connect().await.unwrap();
+    let connected = flushes.load(Ordering::SeqCst);
+
+    let pings: Vec<_> = (0..20)
+      .map(|_| {
+        let conn = conn.clone();
+        tokio::spawn(async move { conn.ping().await })
+      })
+      .collect();
+    for ping in pings {
+      ping.await.unwrap().unwrap();
+    }
+
+    // pings queued within delay are written at once
+    assert!(flushes.load(Ordering::SeqCst) - connected < 5);
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_close_timeout() {
+    let conn = crate::connection::transport::tests::fake_connector(1)
+      .with_close_timeout(Duration::from_millis(50))
+      .connect().await.unwrap();
+
+    let (resp, _) = tokio::join!(
+      conn.perform(request::call(Call { function: "hang".into(), args: Vec::new() })),
+      async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        conn.close().await;
+      },
+    );
+    assert!(matches!(resp.unwrap_err().root(), Error::ConnectionClosed));
+  }
+
   #[tokio::test]
   async fn test_tnt_queries() {
     let addr = "127.0.0.1:3301".parse().unwrap();
@@ -262,12 +1261,19 @@ mod tests {
     assert_eq!(res, (2, 3, 4));
 
     let (res,): ((u32, u32, u32),) = conn.update(Update {
-      space_id: 512, index_id: 0,
+      space_id: 512, index_id: 0, index_base: 0,
       key: [ 5u64 ].into_tuple(),
       tuple: vec![ ( "=", 2u32, 10u64 ).into_tuple() ],
     }).await.expect("bad query");
     assert_eq!(res, (5, 6, 10));
 
+    let (res,): ((u32, u32, u32),) = conn.update(Update {
+      space_id: 512, index_id: 0, index_base: 1,
+      key: [ 5u64 ].into_tuple(),
+      tuple: vec![ ( "+", 3u32, 0u64 ).into_tuple() ],
+    }).await.expect("bad query");
+    assert_eq!(res, (5, 6, 10));
+
     conn.upsert(Upsert {
       space_id: 512, index_base: 0,
       tuple: [ 5u64, 5, 5 ].into_tuple(),
@@ -346,6 +1352,23 @@ mod tests {
     assert_eq!(res, (1, 2));
   }
 
+  #[tokio::test]
+  async fn test_tnt_backup() {
+    let addr = "127.0.0.1:3301".parse().unwrap();
+
+    let conn: Arc<Connection> = Connector::new(addr)
+      .with_connect_timeout(Duration::from_secs(1))
+      .connect().await.unwrap();
+
+    let backup = conn.backup().await.expect("backup start");
+    assert!(!backup.files().is_empty());
+    assert!(backup.size() > 0);
+
+    conn.backup().await.expect_err("backup is already started");
+
+    backup.finish().await.expect("backup stop");
+  }
+
   #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
   async fn test_tnt_30k_async() {
     let addr = "127.0.0.1:3301".parse().unwrap();
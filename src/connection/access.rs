@@ -0,0 +1,255 @@
+use rmpv::Value as MsgValue;
+
+use crate::iproto::{
+  constants::{Code, Field, Iterator, VSPACE_ID},
+  request::Select,
+  response::{FormattedTuple, TarantoolError},
+  types::Error,
+};
+
+use super::Connection;
+
+const VFUNC_ID: u64 = 297;
+const VUSER_ID: u64 = 305;
+const VUSER_NAME_INDEX: u64 = 2;
+const VPRIV_ID: u64 = 313;
+/// index of _vspace and _vfunc by owner
+const OWNER_INDEX: u64 = 1;
+
+pub const PRIV_READ: u32 = 1;
+pub const PRIV_WRITE: u32 = 2;
+pub const PRIV_EXECUTE: u32 = 4;
+pub const PRIV_SESSION: u32 = 8;
+pub const PRIV_USAGE: u32 = 16;
+pub const PRIV_CREATE: u32 = 32;
+pub const PRIV_DROP: u32 = 64;
+pub const PRIV_ALTER: u32 = 128;
+
+/// This is user or role from _vuser system space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+  pub id: u64,
+  pub owner: u64,
+  pub name: String,
+  /// "user" or "role"
+  pub user_type: String,
+}
+
+/// This is grant from _vpriv system space, privileges are bitmask of PRIV_* constants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Privilege {
+  pub grantor: u64,
+  pub grantee: u64,
+  /// e.g. "universe", "space", "function", "sequence", "role"
+  pub object_type: String,
+  /// none means every object of type
+  pub object_id: Option<u64>,
+  pub privileges: u32,
+}
+
+impl Privilege {
+  /// true if grant is on given object, universe grant covers every object
+  #[allow(clippy::unnecessary_map_or)] // Option::is_none_or needs rust 1.82
+  pub fn covers(&self, object_type: &str, object_id: u64) -> bool {
+    match self.object_type.as_str() {
+      "universe" => true,
+      ty if ty == object_type => self.object_id.map_or(true, |id| id == object_id),
+      _ => false,
+    }
+  }
+}
+
+/**
+  This is privileges of user including ones granted through roles,
+  owner of space or function has every privilege on it.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error};
+    # async fn f(conn: &Connection, space_id: u64) -> Result<(), Error> {
+    let privileges = conn.privileges().await?;
+    if !privileges.can_write_space(space_id) {
+      panic!("user {} can't write space {}", conn.current_user().await?.name, space_id);
+    }
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Privileges {
+  pub grants: Vec<Privilege>,
+  /// spaces and functions owned by user as (object type, id)
+  pub owned: Vec<(String, u64)>,
+}
+
+impl Privileges {
+  /// true if object is owned by user or all of privilege bits are granted on it
+  pub fn allows(&self, object_type: &str, object_id: u64, privileges: u32) -> bool {
+    if self.owned.iter().any(|(ty, id)| ty == object_type && *id == object_id) {
+      return true;
+    }
+
+    let granted = self.grants.iter()
+      .filter(|grant| grant.covers(object_type, object_id))
+      .fold(0, |granted, grant| granted | grant.privileges);
+
+    granted & privileges == privileges
+  }
+
+  pub fn can_read_space(&self, space_id: u64) -> bool {
+    self.allows("space", space_id, PRIV_READ)
+  }
+
+  pub fn can_write_space(&self, space_id: u64) -> bool {
+    self.allows("space", space_id, PRIV_WRITE)
+  }
+
+  pub fn can_execute(&self, function_id: u64) -> bool {
+    self.allows("function", function_id, PRIV_EXECUTE)
+  }
+}
+
+impl Connection {
+  /// user which connection is authenticated as, it is guest without credentials
+  pub async fn current_user(&self) -> Result<User, Error> {
+    let users = self.select_formatted(Select {
+      space_id: VUSER_ID, index_id: VUSER_NAME_INDEX,
+      limit: 1, offset: 0,
+      iterator: Iterator::Eq,
+      keys: vec![ self.user.as_str().into() ],
+    }).await?;
+
+    match users.first() {
+      Some(user) => parse_user(user),
+      None => Err(Error::TarantoolError(
+        Code::ErrorNoSuchUser,
+        TarantoolError::new(format!("User '{}' is not found", self.user)),
+      )),
+    }
+  }
+
+  /**
+    privileges of current user, roles granted to user are expanded,
+    only grants visible to user in _vpriv are taken into account.
+    Spaces and functions owned by user are taken from _vspace and _vfunc.
+  */
+  pub async fn privileges(&self) -> Result<Privileges, Error> {
+    let mut privileges = Privileges::default();
+    let user = self.current_user().await?.id;
+    let mut grantees = vec![ user ];
+    let mut seen = grantees.clone();
+
+    for (object_type, space_id) in [ ("space", VSPACE_ID), ("function", VFUNC_ID) ] {
+      let objects = self.select_formatted(Select {
+        space_id, index_id: OWNER_INDEX,
+        limit: u32::MAX, offset: 0,
+        iterator: Iterator::Eq,
+        keys: vec![ user.into() ],
+      }).await?;
+
+      for object in objects.iter() {
+        privileges.owned.push((object_type.into(), field_u64(object, 0)?));
+      }
+    }
+
+    while let Some(grantee) = grantees.pop() {
+      let grants = self.select_formatted(Select {
+        space_id: VPRIV_ID, index_id: 0,
+        limit: u32::MAX, offset: 0,
+        iterator: Iterator::Eq,
+        keys: vec![ grantee.into() ],
+      }).await?;
+
+      for grant in grants.iter() {
+        let grant = parse_privilege(grant)?;
+
+        if let ("role", Some(role)) = (grant.object_type.as_str(), grant.object_id) {
+          if !seen.contains(&role) {
+            seen.push(role);
+            grantees.push(role);
+          }
+        }
+
+        privileges.grants.push(grant);
+      }
+    }
+
+    Ok(privileges)
+  }
+}
+
+fn field(tuple: &FormattedTuple, pos: usize) -> Result<&MsgValue, Error> {
+  tuple.values().get(pos).ok_or(Error::UnexpectedValue(Field::Data))
+}
+
+fn field_u64(tuple: &FormattedTuple, pos: usize) -> Result<u64, Error> {
+  field(tuple, pos)?.as_u64().ok_or(Error::UnexpectedValue(Field::Data))
+}
+
+fn field_str(tuple: &FormattedTuple, pos: usize) -> Result<String, Error> {
+  field(tuple, pos)?.as_str()
+    .map(String::from)
+    .ok_or(Error::UnexpectedValue(Field::Data))
+}
+
+fn parse_user(tuple: &FormattedTuple) -> Result<User, Error> {
+  Ok(User {
+    id: field_u64(tuple, 0)?,
+    owner: field_u64(tuple, 1)?,
+    name: field_str(tuple, 2)?,
+    user_type: field_str(tuple, 3)?,
+  })
+}
+
+/// object id of entity-wide grant is empty string
+fn parse_privilege(tuple: &FormattedTuple) -> Result<Privilege, Error> {
+  let object_id = match field(tuple, 3)? {
+    MsgValue::String(_) => None,
+    id => Some(id.as_u64().ok_or(Error::UnexpectedValue(Field::Data))?),
+  };
+
+  Ok(Privilege {
+    grantor: field_u64(tuple, 0)?,
+    grantee: field_u64(tuple, 1)?,
+    object_type: field_str(tuple, 2)?,
+    object_id,
+    privileges: field_u64(tuple, 4)? as u32,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_privileges() {
+    let grant = |values: Vec<MsgValue>| parse_privilege(&FormattedTuple::new(None, values)).unwrap();
+
+    let privileges = Privileges {
+      grants: vec![
+        grant(vec![ 1.into(), 32.into(), "space".into(), 512.into(), (PRIV_READ | PRIV_WRITE).into() ]),
+        grant(vec![ 1.into(), 32.into(), "space".into(), 513.into(), PRIV_READ.into() ]),
+        grant(vec![ 1.into(), 32.into(), "function".into(), "".into(), PRIV_EXECUTE.into() ]),
+        grant(vec![ 1.into(), 32.into(), "role".into(), 2.into(), PRIV_EXECUTE.into() ]),
+      ],
+      owned: vec![ ("space".into(), 600) ],
+    };
+
+    assert_eq!(privileges.grants[2].object_id, None);
+    assert_eq!(privileges.grants[3].object_id, Some(2));
+
+    assert!(privileges.can_write_space(512));
+    assert!(privileges.can_read_space(513));
+    assert!(!privileges.can_write_space(513));
+    assert!(!privileges.can_read_space(514));
+    assert!(privileges.can_execute(42));
+    assert!(!privileges.allows("space", 512, PRIV_READ | PRIV_ALTER));
+    assert!(privileges.allows("space", 600, PRIV_READ | PRIV_WRITE | PRIV_ALTER | PRIV_DROP));
+    assert!(!privileges.allows("sequence", 600, PRIV_READ));
+
+    let admin = Privileges {
+      grants: vec![ grant(vec![ 1.into(), 1.into(), "universe".into(), 0.into(), u32::MAX.into() ]) ],
+      owned: Vec::new(),
+    };
+    assert!(admin.can_write_space(513));
+  }
+}
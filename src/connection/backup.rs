@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, de::IgnoredAny};
+
+use crate::iproto::{request::{Eval, Value}, types::Error};
+
+use super::Connection;
+
+const BACKUP_START: &str = r#"
+  local fio = require('fio')
+  local files = {}
+  for _, path in ipairs(box.backup.start(...)) do
+    local stat = fio.stat(path)
+    table.insert(files, { path, stat and stat.size or 0 })
+  end
+  return files
+"#;
+
+const BACKUP_STOP: &str = "box.backup.stop()";
+
+/// File which should be copied by backup agent.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BackupFile {
+  pub path: String,
+  pub size: u64,
+}
+
+/**
+  This guard keeps backup started on server,
+  files are not removed by garbage collector until it is finished.
+
+  If guard is dropped without finish, backup is stopped in background.
+
+  Example:
+  ```rust,no_run
+    # use std::sync::Arc;
+    # use alopecosa::{Connection, Error};
+    # async fn copy(_: &str, _: &str) -> Result<(), Error> { Ok(()) }
+    # async fn f(conn: Arc<Connection>, dest: String) -> Result<(), Error> {
+    let backup = conn.backup().await?;
+    for file in backup.files() {
+      copy(&file.path, &dest).await?;
+    }
+    backup.finish().await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug)]
+pub struct BackupGuard {
+  conn: Option<Arc<Connection>>,
+  files: Vec<BackupFile>,
+}
+
+impl BackupGuard {
+  pub fn files(&self) -> &[BackupFile] {
+    &self.files
+  }
+
+  /// total size of backup files in bytes
+  pub fn size(&self) -> u64 {
+    self.files.iter().map(|file| file.size).sum()
+  }
+
+  /// stops backup on server
+  pub async fn finish(mut self) -> Result<(), Error> {
+    match self.conn.take() {
+      Some(conn) => stop(&conn).await,
+      None => Ok(()),
+    }
+  }
+}
+
+impl Drop for BackupGuard {
+  fn drop(&mut self) {
+    let conn = match self.conn.take() {
+      Some(conn) => conn,
+      None => return,
+    };
+
+    log::warn!("backup guard is dropped without finish, stopping backup");
+
+    match tokio::runtime::Handle::try_current() {
+      Ok(handle) => {
+        handle.spawn(async move {
+          if let Err(err) = stop(&conn).await {
+            log::error!("error while stopping backup: {}", err);
+          }
+        });
+      },
+      Err(_) => log::error!("can't stop backup outside of tokio runtime"),
+    }
+  }
+}
+
+async fn stop(conn: &Connection) -> Result<(), Error> {
+  let _: Vec<IgnoredAny> = conn.eval(Eval {
+    expr: BACKUP_STOP.into(),
+    args: Vec::new(),
+  }).await?;
+  Ok(())
+}
+
+impl Connection {
+  /// starts backup of last checkpoint, see box.backup.start()
+  pub async fn backup(self: &Arc<Self>) -> Result<BackupGuard, Error> {
+    self.backup_from(0).await
+  }
+
+  /// starts backup of checkpoint which is n checkpoints before the last one
+  pub async fn backup_from(self: &Arc<Self>, n: u64) -> Result<BackupGuard, Error> {
+    let (files,): (Vec<BackupFile>,) = self.eval(Eval {
+      expr: BACKUP_START.into(),
+      args: vec![ Value::UInt(n) ],
+    }).await?;
+
+    Ok(BackupGuard { conn: Some(self.clone()), files })
+  }
+}
@@ -0,0 +1,117 @@
+use crate::iproto::{request::Request, response::Response, types::Error};
+
+use super::{Connection, Pending};
+
+/**
+  This is batch of requests which are written to connection at once.
+
+  Requests are not transactional, every one of them may fail independently.
+  Results are returned in order of requests.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, IntoTuple, Response, request::{self, Call, Insert, Replace}};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let results = conn.batch()
+      .push(request::insert(Insert { space_id: 512, tuple: ( 1u64, ).into_tuple() }))
+      .push(request::replace(Replace { space_id: 513, tuple: ( 2u64, ).into_tuple() }))
+      .push(request::call(Call { function: "notify".into(), args: ().into_tuple() }))
+      .send().await;
+
+    for result in results {
+      let resp: Response = result?;
+    }
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug)]
+pub struct Batch<'c> {
+  conn: &'c Connection,
+  requests: Vec<Request>,
+}
+
+impl<'c> Batch<'c> {
+  pub(crate) fn new(conn: &'c Connection) -> Batch<'c> {
+    Batch { conn, requests: Vec::new() }
+  }
+
+  pub fn push(mut self, req: Request) -> Self {
+    self.requests.push(req);
+    self
+  }
+
+  pub fn len(&self) -> usize {
+    self.requests.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.requests.is_empty()
+  }
+
+  /// sends requests and waits for all of responses
+  pub async fn send(self) -> Vec<Result<Response, Error>> {
+    if self.requests.is_empty() {
+      return Vec::new();
+    }
+
+    let pending = self.conn.send_batch(self.requests).await;
+
+    let mut results = Vec::with_capacity(pending.len());
+    for pending in pending {
+      results.push(Pending::result(pending).await);
+    }
+
+    results
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    connection::transport::tests::{fake_connection, fake_connector},
+    iproto::{constants::Code, request::{self, Eval}},
+  };
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_batch() {
+    let conn = fake_connection().await;
+
+    let results = conn.batch()
+      .push(request::ping())
+      .push(request::eval(Eval { expr: "return 1".into(), args: Vec::new() }))
+      .push(request::ping())
+      .send().await;
+
+    assert_eq!(results.len(), 3);
+    assert!(results[0].is_ok());
+    assert!(matches!(
+      &results[1],
+      Err(Error::TarantoolError(Code::ErrorIllegalParams, err)) if err.message == "eval is forbidden"
+    ));
+    assert!(results[2].is_ok());
+
+    assert!(conn.batch().send().await.is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_batch_max_request_size() {
+    let conn = fake_connector(1).with_max_request_size(64).connect().await.unwrap();
+    let eval = |expr: String| request::eval(Eval { expr, args: Vec::new() });
+
+    let results = conn.batch()
+      .push(request::ping())
+      .push(eval("x".repeat(100)))
+      .push(request::ping())
+      .push(request::ping())
+      .send().await;
+
+    assert!(results[0].is_ok());
+    assert!(matches!(&results[1], Err(Error::RequestTooLarge { limit: 64, .. })));
+    assert!(results[2].is_ok() && results[3].is_ok());
+
+    let err = conn.eval::<()>(Eval { expr: "x".repeat(100), args: Vec::new() }).await.unwrap_err();
+    assert!(matches!(err.root(), Error::RequestTooLarge { .. }));
+  }
+}
@@ -1,46 +1,125 @@
-use std::{io::Cursor, net::SocketAddr, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+use std::{io::Cursor, sync::{Arc, RwLock, atomic::{AtomicBool, AtomicU64, Ordering}}};
 
-use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpStream, tcp::{OwnedReadHalf, OwnedWriteHalf}}, sync::mpsc};
+use bytes::BytesMut;
+use tokio::{io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf}, sync::{mpsc, Notify}};
 
-use crate::iproto::{request::Request, response::Response};
+use crate::iproto::{request::Request, response::Response, types::Error};
 
-use super::{RespChans, connector::Connector};
+use super::{
+  Outgoing, RespChans,
+  connector::Connector,
+  features::ProtocolFeatures,
+  transport::BoxedTransport,
+  push::{self, Pushes},
+  schema_cache::SchemaCache,
+  watcher::Watchers,
+};
 
 
 
 pub(crate) struct ConnectionServer {
   pub(crate) connector: Connector,
 
-  pub(crate) req_chan_reader: mpsc::Receiver<Request>,
+  pub(crate) req_chan_reader: mpsc::Receiver<Outgoing>,
 
   pub(crate) resp_chans: RespChans,
 
   pub(crate) closed: Arc<AtomicBool>,
+
+  /// writer shuts socket down once it is notified, see Connection::close
+  pub(crate) shutdown: Arc<Notify>,
+
+  pub(crate) watchers: Arc<Watchers>,
+
+  pub(crate) pushes: Pushes,
+
+  /// names of spaces and indexes are reloaded after reconnection
+  pub(crate) schema: Arc<SchemaCache>,
+
+  /// protocol features are negotiated by id request of every reconnection
+  pub(crate) features: Arc<RwLock<ProtocolFeatures>>,
+
+  /// incremented when connection is lost, see Frame::generation
+  pub(crate) generation: Arc<AtomicU64>,
+
+  /// watch requests are written along with requests of connection
+  pub(crate) watch_requests: mpsc::UnboundedReceiver<Request>,
 }
 
 impl ConnectionServer {
-  pub(crate) async fn serve_loop(mut self, stream: TcpStream) {
+  pub(crate) async fn serve_loop(mut self, stream: BoxedTransport) {
     let mut stream = Some(stream);
+    // failed attempts of reconnection in a row
+    let mut attempt = 0;
 
     while !self.closed.load(Ordering::SeqCst) {
       self.cleanup_after_reconnection();
 
+      let stream = match stream.take() {
+        Some(stream) => stream,
+        None => match self.reconnect(attempt + 1).await {
+          Some(Ok(stream)) => {
+            attempt = 0;
+            stream
+          },
+          Some(Err(err)) => {
+            attempt += 1;
+            log::error!("[{}] reconnection failed: {}", self.connector.peer(), err);
+            continue;
+          },
+          None => {
+            log::error!(
+              "[{}] closing connection after {} failed reconnections",
+              self.connector.peer(), attempt,
+            );
+            self.closed.store(true, Ordering::SeqCst);
+            break;
+          },
+        },
+      };
+
       if let Err(err) = self.serve(stream).await {
-        if let Some(interval) = self.connector.reconnect_interval {
-          log::error!(
-            "[{}] reconnecting in {:?} on error while serving connection: {}",
-            &self.connector.addr, interval, err,
-          );
-          tokio::time::sleep(interval).await;
-        } else {
-          log::error!(
-            "[{}] reconnecting on error while serving connection: {}",
-            &self.connector.addr, err,
-          );
-        }
+        log::error!("[{}] error while serving connection: {}", self.connector.peer(), err);
       }
-      stream = None;
+      self.generation.fetch_add(1, Ordering::SeqCst);
+      self.reset_in_flight();
+    }
+
+    self.reset_in_flight();
+  }
+
+  /**
+    reconnects after delay of reconnect policy, it is none if attempts are exhausted.
+    Protocol features are replaced by negotiated ones,
+    schema cache is invalidated and watched keys are watched again.
+  */
+  async fn reconnect(
+    &self, attempt: u32,
+  ) -> Option<Result<BoxedTransport, std::io::Error>> {
+    let delay = self.connector.reconnect.delay(attempt)?;
+    log::info!("[{}] reconnecting in {:?}", self.connector.peer(), delay);
+    tokio::time::sleep(delay).await;
+
+    let result = self.connector.new_connection().await
+      .map(|(stream, _, features)| {
+        *self.features.write().unwrap() = features;
+        stream
+      });
+    if result.is_ok() {
+      self.schema.invalidate();
+      self.watchers.rewatch();
     }
+    Some(result)
+  }
+
+  /**
+    fails requests which are sent or queued when connection is lost,
+    their waiters get Error::ConnectionReset
+  */
+  fn reset_in_flight(&mut self) {
+    while self.req_chan_reader.try_recv().is_ok() {}
+    self.resp_chans.clear();
+    self.pushes.clear();
   }
 
   fn cleanup_after_reconnection(&self) {
@@ -53,18 +132,12 @@ impl ConnectionServer {
       .for_each(|sync| { self.resp_chans.remove(sync); });
   }
 
-  async fn serve(&mut self, stream: Option<TcpStream>) -> Result<(), std::io::Error> {
-    let stream = match stream {
-      Some(s) => s,
-      None => self.connector.new_connection()
-          .await.map(|(s, _)| s)?,
-    };
-
-    let (read_stream, write_stream) = stream.into_split();
+  async fn serve(&mut self, stream: BoxedTransport) -> Result<(), std::io::Error> {
+    let (read_stream, write_stream) = tokio::io::split(stream);
 
     let reader_fut = Self::reader(
-      self.connector.addr, read_stream,
-      self.resp_chans.clone(), self.closed.clone());
+      self.connector.peer(), read_stream, self.connector.max_response_size,
+      self.resp_chans.clone(), self.watchers.clone(), self.pushes.clone());
     let writer_fut = self.writer(write_stream);
 
     tokio::select! {
@@ -73,133 +146,322 @@ impl ConnectionServer {
     }
   }
 
-  async fn writer(&mut self, mut write: OwnedWriteHalf) -> Result<(), std::io::Error> {
-    log::debug!("[{}] writer start", &self.connector.addr);
+  async fn writer(&mut self, mut write: WriteHalf<BoxedTransport>) -> Result<(), std::io::Error> {
+    log::debug!("[{}] writer start", self.connector.peer());
 
     #[allow(unused_variables)]
-    let on_exit = OnExit(self.connector.addr, "writer");
+    let on_exit = OnExit(self.connector.peer(), "writer");
 
     let mut write_buf: Vec<u8> = Vec::new();
+    // requests which didn't fit into previous write
+    let mut carried: Vec<u8> = Vec::new();
 
-    while !self.closed.load(Ordering::SeqCst) {
+    // requests in flight are served after close until shutdown
+    loop {
       write_buf.clear();
 
-      let req: Request = match self.req_chan_reader.recv().await {
-        Some(req) => req,
-        None => {
-          log::debug!(
-            "[{}] request channel closed, seems Connection dropped",
-            &self.connector.addr,
-          );
-          return Ok(())
+      if !carried.is_empty() {
+        write_buf.append(&mut carried);
+        self.coalesce(&mut write_buf, &mut carried).await;
+        self.write(&mut write, &write_buf).await?;
+        continue;
+      }
+
+      let outgoing: Outgoing = tokio::select! {
+        outgoing = self.req_chan_reader.recv() => match outgoing {
+          Some(outgoing) => outgoing,
+          None => {
+            log::debug!(
+              "[{}] request channel closed, seems Connection dropped",
+              self.connector.peer(),
+            );
+            return Ok(())
+          },
+        },
+        Some(req) = self.watch_requests.recv() => Outgoing::Request(req),
+        _ = self.shutdown.notified() => {
+          log::debug!("[{}] shutting connection down", self.connector.peer());
+          return write.shutdown().await;
         },
       };
 
-      if let Some(true) = self.resp_chans.get(&req.header.sync)
-        .map(|resp_chan| resp_chan.is_closed()) {
-        // won't send canceled requests
-        self.resp_chans.remove(&req.header.sync);
-        continue;
+      self.pack_outgoing(&outgoing, &mut write_buf);
+      self.coalesce(&mut write_buf, &mut carried).await;
+      self.write(&mut write, &write_buf).await?;
+    }
+  }
+
+  /**
+    appends queued requests to write buffer up to write batch size,
+    request which doesn't fit is packed into carried buffer
+  */
+  async fn coalesce(&mut self, write_buf: &mut Vec<u8>, carried: &mut Vec<u8>) {
+    let limit = self.write_limit();
+    let delay = self.connector.write_batch_delay;
+    let deadline = tokio::time::Instant::now() + delay;
+
+    while write_buf.len() < limit {
+      let outgoing = match self.req_chan_reader.try_recv() {
+        Ok(outgoing) => outgoing,
+        Err(mpsc::error::TryRecvError::Empty) if !delay.is_zero() => {
+          match tokio::time::timeout_at(deadline, self.req_chan_reader.recv()).await {
+            Ok(Some(outgoing)) => outgoing,
+            _ => return,
+          }
+        },
+        Err(_) => return,
+      };
+
+      self.pack_outgoing(&outgoing, carried);
+      if !write_buf.is_empty() && write_buf.len() + carried.len() > limit {
+        return;
       }
+      write_buf.append(carried);
+    }
+  }
 
-      if let Err(err) = req.pack(&mut write_buf) {
-        log::error!(
-          "[{}] error while packing request err: {}, req: {:?}",
-          &self.connector.addr, err, req,
-        );
-        continue;
+  /// writes are limited by write batch size and max request size
+  fn write_limit(&self) -> usize {
+    match self.connector.max_request_size {
+      Some(max_request_size) => max_request_size.min(self.connector.write_batch_size),
+      None => self.connector.write_batch_size,
+    }
+  }
+
+  /**
+    writes requests of buffer in chunks which don't exceed write limit, larger request
+    is written alone. Bytes of rate limiter are taken for the whole buffer first
+  */
+  async fn write(&self, write: &mut WriteHalf<BoxedTransport>, write_buf: &[u8]) -> Result<(), std::io::Error> {
+    if write_buf.is_empty() {
+      return Ok(());
+    }
+
+    if let Some(limiter) = &self.connector.rate_limiter {
+      limiter.acquire(0, write_buf.len()).await;
+    }
+
+    let limit = self.write_limit();
+    let written = async {
+      let mut rest = write_buf;
+      while !rest.is_empty() {
+        let len = chunk_len(rest, limit)?;
+        write.write_all(&rest[..len]).await?;
+        write.flush().await?;
+        rest = &rest[len..];
       }
+      Ok(())
+    };
 
-      match self.connector.send_request_timeout {
-        Some(timeout) => {
-          tokio::time::timeout(
-            timeout, write.write_all(&write_buf),
-          ).await??;
-        },
-        None => { write.write_all(&write_buf).await?; }
+    match self.connector.send_request_timeout {
+      Some(timeout) => tokio::time::timeout(timeout, written).await?,
+      None => written.await,
+    }
+  }
+
+  fn pack_outgoing(&self, outgoing: &Outgoing, write_buf: &mut Vec<u8>) {
+    match outgoing {
+      Outgoing::Request(req) => self.pack_request(req, write_buf),
+      Outgoing::Batch(requests) => requests.iter()
+        .for_each(|req| self.pack_request(req, write_buf)),
+    }
+  }
+
+  /**
+    packs request straight into write buffer with its size prefix filled in after it,
+    waiter of request which can't be packed or exceeds max request size
+    gets the error and nothing is written
+  */
+  fn pack_request(&self, req: &Request, write_buf: &mut Vec<u8>) {
+    let sync = req.header.sync;
+
+    if matches!(req.header.generation, Some(generation) if generation != self.generation.load(Ordering::SeqCst)) {
+      // stream of request is lost with connection it is opened on
+      if let Some((_, resp_chan)) = self.resp_chans.remove(&sync) {
+        resp_chan.send(Err(Error::ConnectionReset));
       }
+      return;
+    }
+
+    if let Some(true) = self.resp_chans.get(&sync)
+      .map(|resp_chan| resp_chan.is_closed()) {
+      // won't send canceled requests
+      self.resp_chans.remove(&sync);
+      return;
     }
 
-    Ok(())
+    let start = write_buf.len();
+    let packed = req.pack(write_buf).and_then(|_| match self.connector.max_request_size {
+      Some(limit) if write_buf.len() - start > limit =>
+        Err(Error::RequestTooLarge { size: write_buf.len() - start, limit }),
+      _ => Ok(()),
+    });
+
+    if let Err(err) = packed {
+      write_buf.truncate(start);
+      match self.resp_chans.remove(&sync) {
+        Some((_, resp_chan)) => { resp_chan.send(Err(err)); },
+        None => log::error!(
+          "[{}] error while packing {:?}: {}", self.connector.peer(), req.header.request, err,
+        ),
+      }
+    }
   }
 
   async fn reader(
-    addr: SocketAddr,
-    mut read: OwnedReadHalf,
+    peer: String,
+    mut read: ReadHalf<BoxedTransport>,
+    max_response_size: usize,
     resp_chans: RespChans,
-    closed: Arc<AtomicBool>,
+    watchers: Arc<Watchers>,
+    pushes: Pushes,
   ) -> Result<(), std::io::Error> {
-    log::debug!("[{}] reader start", addr);
+    log::debug!("[{}] reader start", peer);
 
     #[allow(unused_variables)]
-    let on_exit = OnExit(addr, "reader");
-
-    const REQUEST_LEN_LEN: usize = 9;
-    let mut buf = [0; REQUEST_LEN_LEN];
-    let mut req_buf: Vec<u8> = Vec::new();
+    let on_exit = OnExit(peer.clone(), "reader");
 
-    while !closed.load(Ordering::SeqCst) {
-      req_buf.clear();
+    let mut input = BytesMut::with_capacity(READ_BUF_SIZE);
 
-      read.read_exact(&mut buf).await?;
-
-      let mut cur = Cursor::new(&buf);
+    loop {
+      let frame = loop {
+        if let Some(len) = frame_len(&input, max_response_size)? {
+          if input.len() >= len {
+            break input.split_to(len).freeze();
+          }
+          input.reserve(len - input.len());
+        }
+        if input.capacity() == input.len() {
+          input.reserve(READ_BUF_SIZE);
+        }
 
-      let size = match rmp::decode::read_int::<u64, _>(&mut cur) {
-        Ok(size) => size,
-        Err(err) => {
-          return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!("error while parsing request size: {:?}", err),
-          ));
-        },
+        if read.read_buf(&mut input).await? == 0 {
+          return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
       };
 
-      let required_buf_size = size as usize + cur.position() as usize;
-
-      req_buf.extend_from_slice(&buf);
-      req_buf.resize(required_buf_size, 0);
-
-      read.read_exact(&mut req_buf[REQUEST_LEN_LEN..]).await?;
-
-      let mut req_cur = Cursor::new(&req_buf);
-
-      let resp = match Response::parse(&mut req_cur) {
+      let resp = match Response::from_frame(frame.clone()) {
         Ok(resp) => resp,
         Err(err) => {
           log::error!(
             "[{}] error while parsing response header: {}, resp: {:?}",
-            addr, err, &req_buf,
+            peer, err, &frame[..],
           );
           continue;
         },
       };
 
+      if resp.header.is_event() {
+        if let Err(err) = watchers.dispatch(&resp) {
+          log::error!("[{}] error while dispatching event: {}", peer, err);
+        }
+        continue;
+      }
+
+      if resp.header.is_chunk() {
+        if let Err(err) = push::dispatch(&pushes, &resp) {
+          log::error!("[{}] error while dispatching push: {}", peer, err);
+        }
+        continue;
+      }
+
+      // stream of pushes ends with response of its request
+      if !pushes.is_empty() {
+        pushes.remove(&resp.header.sync);
+      }
+
       if let Some((_, resp_chan)) = resp_chans.remove(&resp.header.sync) {
         if resp_chan.is_closed() {
           log::debug!(
             "[{}] can't find resp channel for {}",
-            addr, resp.header.sync,
+            peer, resp.header.sync,
           );
           continue;
         }
-        if let Err(resp) = resp_chan.send(resp) {
-          log::debug!(
-            "[{}] resp channel closed for {}",
-            addr, resp.header.sync,
-          );
+        let sync = resp.header.sync;
+        if !resp_chan.send(Ok(resp)) {
+          log::debug!("[{}] resp channel closed for {}", peer, sync);
         }
       }
     }
+  }
+}
 
-    Ok(())
+/// size of chunks read from socket, responses are sliced out of them
+const READ_BUF_SIZE: usize = 64 * 1024;
+
+/// msgpack uint is at most 9 bytes
+const MAX_SIZE_LEN: usize = 9;
+
+/// length of frame with its size prefix, it is none until prefix is received
+fn frame_len(input: &[u8], limit: usize) -> Result<Option<usize>, std::io::Error> {
+  let mut cur = Cursor::new(input);
+  match rmp::decode::read_int::<u64, _>(&mut cur) {
+    Ok(size) if size > limit as u64 => Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      format!("response of {} bytes exceeds max response size {}", size, limit),
+    )),
+    Ok(size) => Ok(Some(cur.position() as usize + size as usize)),
+    Err(_) if input.len() < MAX_SIZE_LEN => Ok(None),
+    Err(err) => Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      format!("error while parsing request size: {:?}", err),
+    )),
   }
 }
 
-struct OnExit(SocketAddr, &'static str);
+/// length of leading frames of buffer which fit into limit, it is at least one frame
+fn chunk_len(buf: &[u8], limit: usize) -> Result<usize, std::io::Error> {
+  let mut len = 0;
+  while len < buf.len() {
+    let frame = frame_len(&buf[len..], usize::MAX)?
+      .ok_or(std::io::ErrorKind::InvalidData)?;
+    if len > 0 && len + frame > limit {
+      break;
+    }
+    len += frame;
+  }
+  Ok(len.min(buf.len()))
+}
+
+impl Drop for ConnectionServer {
+  /// server is gone when connection is closed or its task is aborted
+  fn drop(&mut self) {
+    self.watchers.close();
+  }
+}
+
+struct OnExit(String, &'static str);
 
 impl Drop for OnExit {
   fn drop(&mut self) {
     log::debug!("[{}] {} closed", self.0, self.1);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_frame_len() {
+    assert_eq!(frame_len(&[ 0x05, 0x80 ], 1024).unwrap(), Some(6));
+    assert_eq!(frame_len(&[ 0xce, 0x00 ], 1024).unwrap(), None);
+
+    // 4GiB frame is refused before it is buffered
+    let err = frame_len(&[ 0xce, 0xff, 0xff, 0xff, 0xff ], 1024).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("exceeds max response size 1024"), "{}", err);
+  }
+
+  #[test]
+  fn test_chunk_len() {
+    let frames = [ 0x01, 0x80, 0x02, 0x80, 0x80, 0x01, 0x80 ];
+
+    assert_eq!(chunk_len(&frames, 5).unwrap(), 5);
+    assert_eq!(chunk_len(&frames[5..], 5).unwrap(), 2);
+    // frame larger than limit is written alone
+    assert_eq!(chunk_len(&frames[2..], 1).unwrap(), 3);
+    assert_eq!(chunk_len(&frames, 64).unwrap(), frames.len());
+  }
+}
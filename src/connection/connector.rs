@@ -1,7 +1,8 @@
 use std::{
-  str,
+  fmt, str,
+  future::Future,
   net::SocketAddr,
-  sync::{Arc, atomic::AtomicBool},
+  sync::{Arc, RwLock, atomic::{AtomicBool, AtomicU64, AtomicUsize}},
   time::Duration,
 };
 
@@ -10,41 +11,141 @@ use dashmap::DashMap;
 use sha1::{Digest, Sha1};
 use tokio::{
   io::{AsyncReadExt, AsyncWriteExt},
-  net::{TcpSocket, TcpStream},
-  sync::mpsc,
+  sync::{mpsc, Notify, Semaphore},
 };
 
 use crate::iproto::{
-  request::{self, Auth},
+  constants::Field,
+  redaction::Redaction,
+  request::{self, Auth, AuthMethod},
   response::Response,
 };
 
-use super::{Connection, connection_server::ConnectionServer};
+use super::{
+  Connection,
+  connection_server::ConnectionServer,
+  features::ProtocolFeatures,
+  labels::Labels,
+  rate_limiter::RateLimiter,
+  reconnect::ReconnectPolicy,
+  retry::RetryPolicy,
+  schema_cache::SchemaCache,
+  statements::StatementCache,
+  transport::{BoxedTransport, TcpTransport, TransportConnector},
+  push::Pushes,
+  watcher::Watchers,
+};
 
+/// default limit of write coalescing, see Connector::with_write_batch_size
+const DEFAULT_WRITE_BATCH_SIZE: usize = 64 * 1024;
+
+/// default limit of response frame, see Connector::with_max_response_size
+const DEFAULT_MAX_RESPONSE_SIZE: usize = 256 * 1024 * 1024;
+
+/// Phase of connection establishment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectPhase {
+  /// opening transport
+  Connect,
+  /// reading greeting
+  Greeting,
+  /// id request round trip negotiating protocol features
+  Identify,
+  /// auth round trip
+  Auth,
+  /// preloading schema names
+  Schema,
+}
+
+/**
+  This error is returned inside of io::Error with TimedOut kind
+  when phase of connection establishment times out.
+
+  Example:
+  ```rust,no_run
+    # use std::sync::Arc;
+    # use alopecosa::{Connection, Connector, PhaseTimeout};
+    # async fn serve(_: Arc<Connection>) {}
+    # async fn f(connector: Connector) {
+    match connector.connect().await {
+      Err(err) => match err.get_ref().and_then(|err| err.downcast_ref::<PhaseTimeout>()) {
+        Some(timeout) => log::error!("tarantool {:?} is too slow", timeout.phase),
+        None => log::error!("can't connect: {}", err),
+      },
+      Ok(conn) => serve(conn).await,
+    }
+    # }
+  ```
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhaseTimeout {
+  pub phase: ConnectPhase,
+  pub timeout: Duration,
+}
+
+impl fmt::Display for PhaseTimeout {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?} phase timed out after {:?}", self.phase, self.timeout)
+  }
+}
+
+impl std::error::Error for PhaseTimeout {}
 
 /**
   This is the only struct that allows you to connect to tarantool.
   It also allows you to tune connection.
 
   Example:
-  ```rust
+  ```rust,no_run
+    # use std::{sync::Arc, time::Duration};
+    # use alopecosa::{Connection, Connector};
+    # async fn f() {
     let addr = "127.0.0.1:3301".parse().unwrap();
 
     let conn: Arc<Connection> = Connector::new(addr)
       .with_auth("guest".into(), "guest".into())
       .with_connect_timeout(Duration::from_secs(1))
+      .with_auth_timeout(Duration::from_millis(300))
       .with_reconnect_interval(Duration::from_secs(1))
       .with_send_request_timeout(Duration::from_secs(10))
       .connect().await.unwrap();
+    # }
   ```
 */
 #[derive(Debug, Clone)]
 pub struct Connector {
   pub(crate) addr: SocketAddr,
-  pub(crate) reconnect_interval: Option<tokio::time::Duration>,
+  pub(crate) reconnect: ReconnectPolicy,
+  pub(crate) retry: Option<RetryPolicy>,
   pub(crate) connect_timeout: Option<tokio::time::Duration>,
+  pub(crate) tcp_connect_timeout: Option<Duration>,
+  pub(crate) greeting_timeout: Option<Duration>,
+  pub(crate) identify_timeout: Option<Duration>,
+  pub(crate) auth_timeout: Option<Duration>,
+  pub(crate) schema_timeout: Option<Duration>,
   pub(crate) send_request_timeout: Option<tokio::time::Duration>,
+  /// time Connection::close waits for requests in flight
+  pub(crate) close_timeout: Duration,
   pub(crate) credentials: Option<(String, String)>,
+  /// it is detected on connect if it is not set
+  pub(crate) auth_method: Option<AuthMethod>,
+  pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+  pub(crate) max_request_size: Option<usize>,
+  pub(crate) max_tuple_size: Option<usize>,
+  pub(crate) max_in_flight: Option<usize>,
+  /// larger response frames are not buffered, connection is reset instead
+  pub(crate) max_response_size: usize,
+  /// queued requests are coalesced into one write up to this size
+  pub(crate) write_batch_size: usize,
+  pub(crate) write_batch_delay: Duration,
+  pub(crate) statement_cache_size: Option<usize>,
+  pub(crate) preload_schema: bool,
+  pub(crate) legacy_call: bool,
+  pub(crate) transport: Arc<dyn TransportConnector>,
+  pub(crate) query_log: Option<Redaction>,
+  pub(crate) labels: Labels,
+  #[cfg(feature = "otel")]
+  pub(crate) trace_propagation: super::telemetry::TracePropagation,
 }
 
 #[allow(dead_code)]
@@ -52,9 +153,32 @@ impl Connector {
   pub fn new(addr: SocketAddr) -> Connector {
     Connector {
       addr, credentials: None,
+      auth_method: None,
       connect_timeout: None,
-      reconnect_interval: None,
+      tcp_connect_timeout: None,
+      greeting_timeout: None,
+      identify_timeout: None,
+      auth_timeout: None,
+      schema_timeout: None,
+      reconnect: ReconnectPolicy::default(),
+      retry: None,
       send_request_timeout: None,
+      close_timeout: Duration::from_secs(5),
+      rate_limiter: None,
+      max_request_size: None,
+      max_tuple_size: None,
+      max_in_flight: None,
+      max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+      write_batch_size: DEFAULT_WRITE_BATCH_SIZE,
+      write_batch_delay: Duration::ZERO,
+      statement_cache_size: None,
+      preload_schema: true,
+      legacy_call: false,
+      transport: Arc::new(TcpTransport),
+      query_log: None,
+      labels: Labels::default(),
+      #[cfg(feature = "otel")]
+      trace_propagation: Default::default(),
     }
   }
 
@@ -63,13 +187,64 @@ impl Connector {
     self
   }
 
+  /// timeout of opening transport, it is bounded by connect timeout too
+  pub fn with_tcp_connect_timeout(mut self, timeout: Duration) -> Self {
+    self.tcp_connect_timeout = Some(timeout);
+    self
+  }
+
+  /// timeout of reading greeting after transport is opened
+  pub fn with_greeting_timeout(mut self, timeout: Duration) -> Self {
+    self.greeting_timeout = Some(timeout);
+    self
+  }
+
+  /// timeout of id request round trip (tarantool 2.10+)
+  pub fn with_identify_timeout(mut self, timeout: Duration) -> Self {
+    self.identify_timeout = Some(timeout);
+    self
+  }
+
+  /// timeout of auth request round trip
+  pub fn with_auth_timeout(mut self, timeout: Duration) -> Self {
+    self.auth_timeout = Some(timeout);
+    self
+  }
+
+  /// timeout of schema preload, it is not bounded by connect timeout
+  pub fn with_schema_timeout(mut self, timeout: Duration) -> Self {
+    self.schema_timeout = Some(timeout);
+    self
+  }
+
   pub fn with_auth(mut self, user: String, password: String) -> Self {
     self.credentials = Some((user, password));
     self
   }
 
-  pub fn with_reconnect_interval(mut self, interval: Duration) -> Self {
-    self.reconnect_interval = Some(interval);
+  /**
+    sets auth method instead of detecting it on connect,
+    method is asked from tarantool 2.10+ and chap-sha1 is used for older ones
+  */
+  pub fn with_auth_method(mut self, method: AuthMethod) -> Self {
+    self.auth_method = Some(method);
+    self
+  }
+
+  /// reconnects with the same delay, see ReconnectPolicy::constant
+  pub fn with_reconnect_interval(self, interval: Duration) -> Self {
+    self.with_reconnect_policy(ReconnectPolicy::constant(interval))
+  }
+
+  /// by default connection reconnects forever with exponential backoff
+  pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+    self.reconnect = policy;
+    self
+  }
+
+  /// retries requests which are safe to retry, see RetryPolicy
+  pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+    self.retry = Some(policy);
     self
   }
 
@@ -79,9 +254,144 @@ impl Connector {
     self
   }
 
+  /// time Connection::close waits for responses of requests in flight, it is 5s by default
+  pub fn with_close_timeout(mut self, timeout: Duration) -> Self {
+    self.close_timeout = timeout;
+    self
+  }
+
+  /**
+    sends calls as Call16 which is understood by tarantool 1.6 and 1.7,
+    results of Connection::call are sequences of tuples then, see Call16
+  */
+  pub fn with_legacy_call(mut self, legacy: bool) -> Self {
+    self.legacy_call = legacy;
+    self
+  }
+
+  /**
+    rejects requests which are larger than size in bytes before they are written,
+    request is measured as it is packed by writer and only its waiter gets
+    Error::RequestTooLarge. Batches are split into writes which don't exceed it
+  */
+  pub fn with_max_request_size(mut self, size: usize) -> Self {
+    self.max_request_size = Some(size);
+    self
+  }
+
+  /**
+    rejects inserts, replaces and upserts of tuples larger than size in bytes
+    before they are written, it should match memtx_max_tuple_size of server.
+
+    Size of tuple is its msgpack size, overhead of tuple in server memory is not counted.
+  */
+  pub fn with_max_tuple_size(mut self, size: usize) -> Self {
+    self.max_tuple_size = Some(size);
+    self
+  }
+
+  /**
+    limits size of response frame announced by server before it is read,
+    larger frame is treated as invalid data, it is 256MiB by default
+  */
+  pub fn with_max_response_size(mut self, size: usize) -> Self {
+    self.max_response_size = size;
+    self
+  }
+
+  /**
+    requests queued while previous write is in progress are coalesced into one write
+    of at most size bytes, it is 64KiB by default. Larger request is written alone,
+    writes don't exceed max request size either.
+  */
+  pub fn with_write_batch_size(mut self, size: usize) -> Self {
+    self.write_batch_size = size;
+    self
+  }
+
+  /**
+    writer waits for more requests up to delay before write,
+    it trades latency for fewer syscalls under high load. There is no delay by default.
+  */
+  pub fn with_write_batch_delay(mut self, delay: Duration) -> Self {
+    self.write_batch_delay = delay;
+    self
+  }
+
+  /**
+    limits count of requests waiting for responses,
+    further requests wait for free slot before they are written
+  */
+  pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+    self.max_in_flight = Some(max_in_flight.max(1));
+    self
+  }
+
+  /**
+    limits count of statements prepared through connection,
+    the least recently used ones are unprepared on server when it is exceeded,
+    their sql text is kept and they are prepared again on the next execution
+  */
+  pub fn with_statement_cache_size(mut self, size: usize) -> Self {
+    self.statement_cache_size = Some(size);
+    self
+  }
+
+  /**
+    loads names of spaces and indexes on connect, it is on by default,
+    otherwise or if preload fails they are loaded on first request by name
+  */
+  pub fn with_preloaded_schema(mut self, preload: bool) -> Self {
+    self.preload_schema = preload;
+    self
+  }
+
+  /// throttle requests of connection, limiter may be shared between connections
+  pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+    self.rate_limiter = Some(limiter);
+    self
+  }
+
+  /// use custom transport instead of tcp
+  pub fn with_transport<T>(mut self, transport: T) -> Self
+    where T: TransportConnector + 'static
+  {
+    self.transport = Arc::new(transport);
+    self
+  }
+
+  /**
+    logs every request to "alopecosa::query" target at info level,
+    values, sql and lua literals are logged according to redaction
+  */
+  pub fn with_query_log(mut self, redaction: Redaction) -> Self {
+    self.query_log = Some(redaction);
+    self
+  }
+
+  /// attaches label to connection, see Labels
+  pub fn with_label<K, V>(mut self, key: K, value: V) -> Self
+    where K: Into<String>, V: Into<String>
+  {
+    self.labels = self.labels.with(key, value);
+    self
+  }
+
+  pub fn with_labels(mut self, labels: Labels) -> Self {
+    self.labels = labels;
+    self
+  }
+
+  /// pass trace context of request spans to tarantool
+  #[cfg(feature = "otel")]
+  pub fn with_trace_propagation(mut self, propagation: super::telemetry::TracePropagation) -> Self {
+    self.trace_propagation = propagation;
+    self
+  }
+
   /// perform connection to tarantool
   pub async fn connect(self) -> Result<Arc<Connection>, tokio::io::Error> {
-    let (stream, version) = self.new_connection().await?;
+    let (stream, version, features) = self.new_connection().await?;
 
     let (sender, reader) = mpsc::channel(1000);
 
@@ -89,65 +399,132 @@ impl Connector {
 
     let closed = Arc::new(AtomicBool::new(false));
 
-    let conn = Arc::new(Connection {
-        version, sync: 1.into(),
-        req_chan_sender: sender,
-        closed: closed.clone(),
-        resp_chans: resp_chans.clone(),
-    });
+    let (watchers, watch_requests) = Watchers::new();
+    let pushes = Pushes::default();
+
+    let schema = Arc::new(SchemaCache::default());
+    let shutdown = Arc::new(Notify::new());
+    let features = Arc::new(RwLock::new(features));
+    let generation = Arc::new(AtomicU64::new(0));
 
     let conn_server = ConnectionServer {
-      connector: self, req_chan_reader: reader,
-      resp_chans, closed,
+      connector: self.clone(), req_chan_reader: reader,
+      resp_chans: resp_chans.clone(), closed: closed.clone(), shutdown: shutdown.clone(),
+      watchers: watchers.clone(), watch_requests, pushes: pushes.clone(), schema: schema.clone(),
+      features: features.clone(), generation: generation.clone(),
     };
+    let server = tokio::spawn(conn_server.serve_loop(stream));
 
-    tokio::spawn(conn_server.serve_loop(stream));
+    let conn = Arc::new(Connection {
+        version, features, sync: 1.into(), stream_id: 1.into(), generation,
+        req_chan_sender: sender,
+        closed, shutdown, server,
+        close_timeout: self.close_timeout,
+        resp_chans,
+        statements: StatementCache::new(self.statement_cache_size),
+        schema,
+        rate_limiter: self.rate_limiter.clone(),
+        retry: self.retry.clone(),
+        max_tuple_size: self.max_tuple_size,
+        legacy_call: self.legacy_call,
+        in_flight: Arc::new(AtomicUsize::new(0)),
+        idle: Arc::new(Notify::new()),
+        in_flight_limit: self.max_in_flight.map(|limit| Arc::new(Semaphore::new(limit))),
+        addr: self.addr,
+        peer: self.peer(),
+        watchers, pushes,
+        user: self.credentials.as_ref()
+          .map_or_else(|| "guest".into(), |(user, _)| user.clone()),
+        query_log: self.query_log,
+        labels: self.labels.clone(),
+        #[cfg(feature = "otel")]
+        trace_propagation: self.trace_propagation,
+    });
+
+    if self.preload_schema {
+      if let Err(err) = self.phase(ConnectPhase::Schema, conn.reload_schema()).await {
+        log::warn!("failed to preload schema of {}: {}", conn.peer, err);
+      }
+    }
 
     Ok(conn)
   }
 
-  pub(crate) async fn new_connection(&self) -> Result<(TcpStream, String), std::io::Error> {
-    let sock = match self.addr.is_ipv4() {
-      true => TcpSocket::new_v4(),
-      false => TcpSocket::new_v6(),
-    }?;
+  /// address with labels for logs
+  pub(crate) fn peer(&self) -> String {
+    match self.labels.is_empty() {
+      true => self.addr.to_string(),
+      false => format!("{} {}", self.addr, self.labels),
+    }
+  }
 
-    let (conn, ver): (TcpStream, String) = match self.connect_timeout {
-      None => self.connect_and_greet(sock).await?,
+  pub(crate) async fn new_connection(
+    &self,
+  ) -> Result<(BoxedTransport, String, ProtocolFeatures), std::io::Error> {
+    match self.connect_timeout {
+      None => self.connect_and_greet().await,
       Some(timeout) =>
-        match tokio::time::timeout(timeout, self.connect_and_greet(sock)).await {
-          Ok(Ok(res)) => res,
-          Ok(err) => return err,
-          Err(elapsed) => return Err(elapsed.into()),
+        match tokio::time::timeout(timeout, self.connect_and_greet()).await {
+          Ok(res) => res,
+          Err(elapsed) => Err(elapsed.into()),
         },
-    };
+    }
+  }
 
-    Ok((conn, ver))
+  async fn connect_and_greet(
+    &self,
+  ) -> Result<(BoxedTransport, String, ProtocolFeatures), std::io::Error> {
+    let mut conn = self.phase(ConnectPhase::Connect, self.transport.connect(self.addr)).await?;
+    let (version, features) = self.handle_greating_and_auth(&mut conn).await?;
+    Ok((conn, version, features))
   }
 
-  async fn connect_and_greet(&self, sock: TcpSocket) -> Result<(TcpStream, String), std::io::Error> {
-    let mut conn = sock.connect(self.addr).await?;
-    let version = self.handle_greating_and_auth(&mut conn).await?;
-    Ok((conn, version))
+  /// runs phase of connection establishment with its timeout
+  async fn phase<F, T, E>(&self, phase: ConnectPhase, fut: F) -> Result<T, E>
+    where F: Future<Output = Result<T, E>>, E: From<std::io::Error>
+  {
+    let timeout = match phase {
+      ConnectPhase::Connect => self.tcp_connect_timeout,
+      ConnectPhase::Greeting => self.greeting_timeout,
+      ConnectPhase::Identify => self.identify_timeout,
+      ConnectPhase::Auth => self.auth_timeout,
+      ConnectPhase::Schema => self.schema_timeout,
+    };
+
+    match timeout {
+      None => fut.await,
+      Some(timeout) => match tokio::time::timeout(timeout, fut).await {
+        Ok(res) => res,
+        Err(_) => Err(std::io::Error::new(
+          std::io::ErrorKind::TimedOut,
+          PhaseTimeout { phase, timeout },
+        ).into()),
+      },
+    }
   }
 
   async fn handle_greating_and_auth(
-    &self, conn: &mut TcpStream,
-  ) -> Result<String, std::io::Error> {
+    &self, conn: &mut BoxedTransport,
+  ) -> Result<(String, ProtocolFeatures), std::io::Error> {
 
     let mut greeting_buf = [0u8; 128];
 
-    conn.read_exact(&mut greeting_buf).await?;
+    self.phase(ConnectPhase::Greeting, conn.read_exact(&mut greeting_buf)).await?;
 
-    let (version, salt) = self.parse_greeting(&greeting_buf)
+    let (version, salt) = Self::parse_greeting(&greeting_buf)
       .ok_or_else(|| std::io::Error::new(
         std::io::ErrorKind::InvalidData,
         "bad greeting",
       ))?;
 
+    let (features, auth_type) = match Self::version_at_least(version, (2, 10)) {
+      true => self.phase(ConnectPhase::Identify, Self::identify(conn, self.max_response_size)).await?,
+      false => (ProtocolFeatures::default(), None),
+    };
+
     let (user, password) = match &self.credentials {
       Some(creds) => creds,
-      None => return Ok(version.into()),
+      None => return Ok((version.into(), features)),
     };
 
     let salt = match decode(salt) {
@@ -158,39 +535,137 @@ impl Connector {
       )),
     };
 
+    let method = self.auth_method.or(auth_type).unwrap_or_default();
+
+    if method == AuthMethod::PapSha256 && !self.transport.is_encrypted() {
+      return Err(std::io::Error::new(
+        std::io::ErrorKind::PermissionDenied,
+        "auth method pap-sha256 sends password as is, it requires encrypted transport",
+      ));
+    }
+
+    let scramble = match method {
+      AuthMethod::ChapSha1 => Self::auth_scramble(&salt, password),
+      AuthMethod::PapSha256 => password.as_bytes().to_vec(),
+    };
+
     let mut buf: Vec<u8> = Vec::new();
-    request::auth(Auth {
-      user: user.clone(),
-      scramble: self.auth_scramble(&salt, password),
-    }).pack(&mut buf)
+    request::auth(Auth::new(user.as_str(), scramble).with_method(method))
+      .pack(&mut buf)
       .map_err(|_| std::io::Error::new(
         std::io::ErrorKind::Other,
         "auth pack error",
       ))?;
 
+    let req = self.phase(ConnectPhase::Auth, async {
+      conn.write_all(&buf).await?;
+      Self::read_response(conn, self.max_response_size).await
+    }).await?;
+
+    if req.header.sync != 0 || req.header.code.is_err() {
+      return Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("auth error: {:?}", req.header.code),
+      ));
+    }
+
+    Ok((version.into(), features))
+  }
+
+  /**
+    negotiates protocol features with tarantool 2.10+ by id request,
+    server also tells auth method (tarantool 2.11+)
+  */
+  async fn identify(
+    conn: &mut BoxedTransport, max_response_size: usize,
+  ) -> Result<(ProtocolFeatures, Option<AuthMethod>), std::io::Error> {
+    let client = ProtocolFeatures::client();
+
+    let mut buf: Vec<u8> = Vec::new();
+    request::id(client.id()).pack(&mut buf)
+      .map_err(|_| std::io::Error::other("id pack error"))?;
     conn.write_all(&buf).await?;
 
-    buf.resize(128, 0);
+    let resp = Self::read_response(conn, max_response_size).await?;
+    if resp.header.code.is_err() {
+      return Ok((ProtocolFeatures::default(), None));
+    }
+
+    let body = match &resp.body {
+      Some(body) => rmpv::decode::read_value(&mut &body[..])
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?,
+      None => return Ok((ProtocolFeatures::default(), None)),
+    };
 
-    let readed = conn.read(&mut buf).await?;
+    let field = |field: Field| body.as_map()
+      .and_then(|fields| fields.iter().find(|(key, _)| key.as_u64() == Some(field as u64)))
+      .map(|(_, value)| value);
+
+    let server = ProtocolFeatures::new(
+      field(Field::Version).and_then(rmpv::Value::as_u64).unwrap_or(0),
+      field(Field::Features).and_then(rmpv::Value::as_array)
+        .map(|features| features.iter().filter_map(rmpv::Value::as_u64).collect())
+        .unwrap_or_else(Vec::new),
+    );
+
+    let method = match field(Field::AuthType).and_then(rmpv::Value::as_str) {
+      None => None,
+      Some(name) => Some(AuthMethod::from_name(name)
+        .ok_or_else(|| std::io::Error::new(
+          std::io::ErrorKind::InvalidData,
+          format!("auth method {} is not supported", name),
+        ))?),
+    };
 
-    let req = Response::parse(&buf[0..readed])
-      .map_err(|_| std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "auth unpack resp error",
-      ))?;
+    Ok((client.negotiate(&server), method))
+  }
 
-    if req.header.sync != 0 || req.header.code.is_err() {
+
+  /// reads one response while connection is established
+  async fn read_response(conn: &mut BoxedTransport, limit: usize) -> Result<Response, std::io::Error> {
+    let bad_response = || std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      "bad response",
+    );
+
+    let mut frame = vec![ conn.read_u8().await? ];
+    let size_len = match rmp::Marker::from_u8(frame[0]) {
+      rmp::Marker::FixPos(_) => 0,
+      rmp::Marker::U8 => 1,
+      rmp::Marker::U16 => 2,
+      rmp::Marker::U32 => 4,
+      rmp::Marker::U64 => 8,
+      _ => return Err(bad_response()),
+    };
+    frame.resize(1 + size_len, 0);
+    conn.read_exact(&mut frame[1..]).await?;
+
+    let size: u64 = rmp::decode::read_int(&mut frame.as_slice())
+      .map_err(|_| bad_response())?;
+    if size > limit as u64 {
       return Err(std::io::Error::new(
-        std::io::ErrorKind::InvalidInput,
-        format!("auth error: {:?}", req.header.code),
+        std::io::ErrorKind::InvalidData,
+        format!("response of {} bytes exceeds max response size {}", size, limit),
       ));
     }
 
-    Ok(version.into())
+    let start = frame.len();
+    frame.resize(start + size as usize, 0);
+    conn.read_exact(&mut frame[start..]).await?;
+
+    Response::parse(frame.as_slice()).map_err(|_| bad_response())
+  }
+
+  /// compares major and minor parts of tarantool version
+  pub(crate) fn version_at_least(version: &str, (major, minor): (u32, u32)) -> bool {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit())
+      .map(|part| part.parse::<u32>().unwrap_or(0));
+
+    (parts.next().unwrap_or(0), parts.next().unwrap_or(0)) >= (major, minor)
   }
 
-  fn parse_greeting<'g>(&self, greeting: &'g [u8; 128]) -> Option<(&'g str, &'g [u8])> {
+  /// version and salt of greeting
+  pub(crate) fn parse_greeting(greeting: &[u8; 128]) -> Option<(&str, &[u8])> {
     const START: &str = "Tarantool ";
     const PROTO: &str = " (Binary) ";
     const SEP: &str = "\n";
@@ -213,7 +688,7 @@ impl Connector {
     Some((version, salt))
   }
 
-  fn auth_scramble(&self, salt: &[u8], password: &str) -> Vec<u8> {
+  pub(crate) fn auth_scramble(salt: &[u8], password: &str) -> Vec<u8> {
     let mut hasher = Sha1::default();
     Digest::update(&mut hasher, password);
     let hash1 = hasher.finalize();
@@ -232,3 +707,195 @@ impl Connector {
       .map(|(&a, &b)| { a ^ b }).collect()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+
+  use rmpv::Value;
+  use tokio::io::{DuplexStream, duplex};
+
+  use crate::{
+    connection::{
+      features::{Feature, PROTOCOL_VERSION},
+      transport::tests::{DuplexTransport, fake_stream},
+    },
+    iproto::constants::RequestType,
+  };
+
+  use super::*;
+
+  /// type and body of request, it is none if client is gone
+  async fn read_request(stream: &mut DuplexStream) -> Option<(u64, Value)> {
+    let size = match stream.read_u8().await.ok()? {
+      marker @ 0..=0x7f => marker as usize,
+      0xcc => stream.read_u8().await.unwrap() as usize,
+      0xce => stream.read_u32().await.unwrap() as usize,
+      _ => panic!("unexpected size of request"),
+    };
+    let mut frame = vec![ 0u8; size ];
+    stream.read_exact(&mut frame).await.unwrap();
+
+    let mut cur = frame.as_slice();
+    let header = rmpv::decode::read_value(&mut cur).unwrap();
+    let body = rmpv::decode::read_value(&mut cur).unwrap();
+    Some((header.as_map().unwrap()[0].1.as_u64().unwrap(), body))
+  }
+
+  async fn write_response(stream: &mut DuplexStream, body: Value) {
+    let mut resp: Vec<u8> = Vec::new();
+    rmpv::encode::write_value(&mut resp, &Value::Map(vec![
+      (0.into(), 0.into()), (1.into(), 0.into()),
+    ])).unwrap();
+    rmpv::encode::write_value(&mut resp, &body).unwrap();
+
+    let mut frame: Vec<u8> = Vec::new();
+    rmp::encode::write_u32(&mut frame, resp.len() as u32).unwrap();
+    frame.extend(resp);
+    stream.write_all(&frame).await.unwrap();
+  }
+
+  /**
+    announces auth type in id response and returns method of auth request,
+    stream is returned too, so connection isn't broken
+  */
+  async fn fake_auth_server(
+    mut stream: DuplexStream, auth_type: &'static str,
+  ) -> (Option<String>, DuplexStream) {
+    let mut greeting = [b' '; 128];
+    greeting[..30].copy_from_slice(b"Tarantool 2.11.0 (Binary) uuid");
+    greeting[63] = b'\n';
+    greeting[64..108].copy_from_slice(&[b'A'; 44]);
+    stream.write_all(&greeting).await.unwrap();
+
+    let (request, _) = read_request(&mut stream).await.unwrap();
+    assert_eq!(request, RequestType::Id as u64);
+    write_response(&mut stream, Value::Map(vec![
+      (0x54.into(), 6.into()),
+      (0x55.into(), Value::Array(vec![ 0.into(), 1.into(), 2.into(), 3.into() ])),
+      (0x5b.into(), auth_type.into()),
+    ])).await;
+
+    let (request, body) = match read_request(&mut stream).await {
+      Some(request) => request,
+      None => return (None, stream),
+    };
+    assert_eq!(request, RequestType::Auth as u64);
+    write_response(&mut stream, Value::Map(Vec::new())).await;
+
+    let method = body.as_map().unwrap().iter()
+      .find(|(key, _)| key.as_u64() == Some(Field::Tuple as u64))
+      .and_then(|(_, tuple)| tuple.as_array()?[0].as_str().map(String::from));
+    (method, stream)
+  }
+
+  fn auth_connector(client: DuplexStream) -> Connector {
+    Connector::new("127.0.0.1:3301".parse().unwrap())
+      .with_transport(DuplexTransport(Mutex::new(vec![ client ])))
+      .with_auth("user".into(), "secret".into())
+      .with_preloaded_schema(false)
+  }
+
+  #[tokio::test]
+  async fn test_auth_method_detection() {
+    let (client, server) = duplex(4096);
+    let server = tokio::spawn(fake_auth_server(server, "chap-sha1"));
+    let conn = auth_connector(client).connect().await.unwrap();
+    assert_eq!(conn.tarantool_version(), "2.11.0");
+    let features = conn.protocol_features();
+    assert_eq!(features.version, PROTOCOL_VERSION);
+    assert!(features.supports(Feature::Transactions));
+    assert!(features.supports(Feature::ErrorExtension));
+    assert!(!conn.supports_names());
+    let (method, _stream) = server.await.unwrap();
+    assert_eq!(method.as_deref(), Some("chap-sha1"));
+
+    // pap-sha256 is refused over plain transport
+    let (client, server) = duplex(4096);
+    let server = tokio::spawn(fake_auth_server(server, "pap-sha256"));
+    let err = auth_connector(client).connect().await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    assert!(err.to_string().contains("encrypted transport"));
+    assert_eq!(server.await.unwrap().0, None);
+
+    assert!(Connector::version_at_least("2.11.0-entrypoint-113-g803baaffe", (2, 10)));
+    assert!(!Connector::version_at_least("1.10.15", (2, 10)));
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_features_on_reconnect() {
+    let (client, server) = duplex(4096);
+    let server = tokio::spawn(fake_auth_server(server, "chap-sha1"));
+    // fake server of reconnection announces no features
+    let conn = Connector::new("127.0.0.1:3301".parse().unwrap())
+      .with_transport(DuplexTransport(Mutex::new(vec![ fake_stream(), client ])))
+      .with_auth("user".into(), "secret".into())
+      .with_reconnect_policy(ReconnectPolicy::constant(Duration::from_millis(1)))
+      .with_preloaded_schema(false)
+      .connect().await.unwrap();
+    assert!(conn.protocol_features().supports(Feature::Transactions));
+
+    drop(server.await.unwrap().1);
+    while conn.ping().await.is_err() {}
+    assert!(!conn.protocol_features().supports(Feature::Transactions));
+  }
+
+  #[tokio::test]
+  async fn test_phase_timeout() {
+    // server never sends greeting
+    let (client, _server) = duplex(4096);
+
+    let err = Connector::new("127.0.0.1:3301".parse().unwrap())
+      .with_transport(DuplexTransport(Mutex::new(vec![ client ])))
+      .with_greeting_timeout(Duration::from_millis(10))
+      .connect().await.unwrap_err();
+
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    let timeout = err.get_ref()
+      .and_then(|err| err.downcast_ref::<PhaseTimeout>())
+      .unwrap();
+    assert_eq!(timeout.phase, ConnectPhase::Greeting);
+  }
+
+  #[tokio::test]
+  async fn test_identify_phase_timeout() {
+    // server sends greeting and never answers id request
+    let (client, mut server) = duplex(4096);
+    let mut greeting = [b' '; 128];
+    greeting[..30].copy_from_slice(b"Tarantool 2.11.0 (Binary) uuid");
+    greeting[63] = b'\n';
+    greeting[64..108].copy_from_slice(&[b'A'; 44]);
+    server.write_all(&greeting).await.unwrap();
+
+    let err = Connector::new("127.0.0.1:3301".parse().unwrap())
+      .with_transport(DuplexTransport(Mutex::new(vec![ client ])))
+      .with_identify_timeout(Duration::from_millis(10))
+      .with_auth_timeout(Duration::from_secs(10))
+      .connect().await.unwrap_err();
+
+    let timeout = err.get_ref()
+      .and_then(|err| err.downcast_ref::<PhaseTimeout>())
+      .unwrap();
+    assert_eq!(timeout.phase, ConnectPhase::Identify);
+  }
+
+  #[tokio::test]
+  async fn test_max_response_size() {
+    // server answers id request by frame of 4GiB
+    let (client, mut server) = duplex(4096);
+    let mut greeting = [b' '; 128];
+    greeting[..30].copy_from_slice(b"Tarantool 2.11.0 (Binary) uuid");
+    greeting[63] = b'\n';
+    greeting[64..108].copy_from_slice(&[b'A'; 44]);
+    server.write_all(&greeting).await.unwrap();
+    server.write_all(&[ 0xce, 0xff, 0xff, 0xff, 0xff ]).await.unwrap();
+
+    let err = Connector::new("127.0.0.1:3301".parse().unwrap())
+      .with_transport(DuplexTransport(Mutex::new(vec![ client ])))
+      .with_max_response_size(1024)
+      .connect().await.unwrap_err();
+
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("exceeds max response size 1024"), "{}", err);
+  }
+}
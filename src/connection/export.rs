@@ -0,0 +1,267 @@
+use rmpv::Value;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::iproto::{
+  constants::Field,
+  request::{self, Execute, PagedSelect, Select},
+  response::{ColumnMeta, SQLBodyDecoder},
+  types::Error,
+};
+
+use super::Connection;
+
+/// number of tuples selected by one page of export
+const EXPORT_PAGE_SIZE: u32 = 1000;
+
+/// This is text format of exported and imported rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+  /// one json object per line, keys are column names
+  JsonLines,
+  /// comma separated values with header line
+  Csv,
+}
+
+/// converts msgpack value to json, binary and extension values are base64 encoded
+pub(crate) fn to_json(value: &Value) -> serde_json::Value {
+  use serde_json::Value as Json;
+
+  match value {
+    Value::Nil => Json::Null,
+    Value::Boolean(b) => Json::Bool(*b),
+    Value::Integer(i) => match i.as_u64() {
+      Some(u) => u.into(),
+      None => i.as_i64().map_or(Json::Null, Into::into),
+    },
+    Value::F32(f) => serde_json::Number::from_f64(*f as f64).map_or(Json::Null, Json::Number),
+    Value::F64(f) => serde_json::Number::from_f64(*f).map_or(Json::Null, Json::Number),
+    Value::String(s) => Json::String(String::from_utf8_lossy(s.as_bytes()).into_owned()),
+    Value::Binary(b) => Json::String(base64::encode(b)),
+    Value::Ext(_, data) => Json::String(base64::encode(data)),
+    Value::Array(items) => Json::Array(items.iter().map(to_json).collect()),
+    Value::Map(entries) => Json::Object(entries.iter()
+      .map(|(key, value)| {
+        let key = match key.as_str() {
+          Some(key) => key.to_string(),
+          None => to_json(key).to_string(),
+        };
+        (key, to_json(value))
+      })
+      .collect()),
+  }
+}
+
+fn csv_field(value: &Value) -> String {
+  let text = match value {
+    Value::Nil => return String::new(),
+    Value::String(s) => String::from_utf8_lossy(s.as_bytes()).into_owned(),
+    value => to_json(value).to_string(),
+  };
+
+  if text.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", text.replace('"', "\"\""))
+  } else {
+    text
+  }
+}
+
+/// formats one row of export, columns beyond known names are named by their positions
+fn format_row(format: ExportFormat, columns: &[String], row: &[Value]) -> String {
+  match format {
+    ExportFormat::JsonLines => {
+      let object: serde_json::Map<String, serde_json::Value> = row.iter().enumerate()
+        .map(|(i, value)| {
+          let name = columns.get(i).cloned().unwrap_or_else(|| (i + 1).to_string());
+          (name, to_json(value))
+        })
+        .collect();
+
+      let mut line = serde_json::Value::Object(object).to_string();
+      line.push('\n');
+      line
+    },
+    ExportFormat::Csv => {
+      let mut line = row.iter().map(csv_field).collect::<Vec<_>>().join(",");
+      line.push('\n');
+      line
+    },
+  }
+}
+
+/// writes rows one by one as they are received
+struct RowWriter<'a, W> {
+  writer: &'a mut W,
+  format: ExportFormat,
+  columns: Vec<String>,
+  count: u64,
+}
+
+impl<'a, W> RowWriter<'a, W>
+  where W: AsyncWrite + Unpin
+{
+  /// writes header of csv
+  async fn start(writer: &'a mut W, format: ExportFormat, columns: Vec<String>) -> Result<RowWriter<'a, W>, Error> {
+    if format == ExportFormat::Csv {
+      let header: Vec<Value> = columns.iter().map(|name| name.as_str().into()).collect();
+      writer.write_all(format_row(format, &columns, &header).as_bytes()).await?;
+    }
+
+    Ok(RowWriter { writer, format, columns, count: 0 })
+  }
+
+  async fn write(&mut self, row: Value) -> Result<(), Error> {
+    let row = match row {
+      Value::Array(row) => row,
+      _ => return Err(Error::UnexpectedValue(Field::Data)),
+    };
+
+    self.writer.write_all(format_row(self.format, &self.columns, &row).as_bytes()).await?;
+    self.count += 1;
+    Ok(())
+  }
+
+  /// flushes writer and returns number of written rows
+  async fn finish(self) -> Result<u64, Error> {
+    self.writer.flush().await?;
+    Ok(self.count)
+  }
+}
+
+/// takes rows out of body decoded by SQLBodyDecoder
+fn take_rows(body: &mut std::collections::HashMap<Field, Value>) -> Result<Vec<Value>, Error> {
+  match body.remove(&Field::Data) {
+    Some(Value::Array(rows)) => Ok(rows),
+    _ => Err(Error::UnexpectedValue(Field::Data)),
+  }
+}
+
+impl Connection {
+  /**
+    writes selected tuples to writer, column names are taken from space format.
+    Tuples are selected by pages after position of the previous page
+    and every page is written before the next one is selected,
+    so it requires tarantool 2.11+.
+
+    Returns number of written rows.
+
+    Example:
+    ```rust,no_run
+      # use alopecosa::{Connection, Error, ExportFormat, Select};
+      # async fn f(conn: &Connection, select: Select) -> Result<(), Error> {
+      let mut file = tokio::fs::File::create("users.jsonl").await?;
+      conn.export_select(select, ExportFormat::JsonLines, &mut file).await?;
+      # Ok(()) }
+    ```
+  */
+  pub async fn export_select<W>(
+    &self, body: Select, format: ExportFormat, writer: &mut W,
+  ) -> Result<u64, Error>
+    where W: AsyncWrite + Unpin
+  {
+    let columns: Vec<String> = self.space_format(body.space_id).await?
+      .into_iter()
+      .map(|field| field.name)
+      .collect();
+
+    let mut rows = RowWriter::start(writer, format, columns).await?;
+    let mut remaining = body.limit;
+    let mut page = PagedSelect { select: body, fetch_position: true, ..Default::default() };
+
+    while remaining > 0 {
+      page.select.limit = remaining.min(EXPORT_PAGE_SIZE);
+      let (resp, context) = self.perform_in_context(request::paged_select(page.clone())).await?;
+      let mut resp = resp.unpack_body::<SQLBodyDecoder>()
+        .map_err(|err| context.wrap(err))?;
+
+      let tuples = take_rows(&mut resp)?;
+      let full = tuples.len() as u32 == page.select.limit;
+      remaining -= tuples.len() as u32;
+      for tuple in tuples {
+        rows.write(tuple).await?;
+      }
+
+      page.select.offset = 0;
+      page.after_position = match resp.remove(&Field::Position) {
+        Some(Value::String(pos)) => Some(pos.into_bytes()),
+        Some(Value::Binary(pos)) => Some(pos),
+        Some(_) => return Err(Error::UnexpectedValue(Field::Position)),
+        None => None,
+      };
+
+      if !full || page.after_position.is_none() {
+        break;
+      }
+    }
+
+    rows.finish().await
+  }
+
+  /// writes result of sql query to writer, column names are taken from metadata
+  pub async fn export_sql<W>(
+    &self, body: Execute, format: ExportFormat, writer: &mut W,
+  ) -> Result<u64, Error>
+    where W: AsyncWrite + Unpin
+  {
+    let mut resp = self.perform_execute(body).await?
+      .unpack_body::<SQLBodyDecoder>()?;
+
+    let columns: Vec<String> = match resp.get(&Field::Metadata) {
+      Some(Value::Array(meta)) => meta.iter()
+        .map(|column| ColumnMeta::from_value(column).map(|column| column.name))
+        .collect::<Result<_, _>>()?,
+      _ => return Err(Error::UnexpectedValue(Field::Metadata)),
+    };
+
+    let mut rows = RowWriter::start(writer, format, columns).await?;
+    for row in take_rows(&mut resp)? {
+      rows.write(row).await?;
+    }
+
+    rows.finish().await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  async fn write_rows(writer: &mut Vec<u8>, format: ExportFormat, columns: &[String], rows: Vec<Value>) -> u64 {
+    let mut writer = RowWriter::start(writer, format, columns.to_vec()).await.unwrap();
+    for row in rows {
+      writer.write(row).await.unwrap();
+    }
+    writer.finish().await.unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_export_format() {
+    let columns = vec![ "id".to_string(), "name".to_string() ];
+    let rows = vec![
+      Value::Array(vec![ Value::from(1), Value::from("plain") ]),
+      Value::Array(vec![ Value::from(2), Value::from("with, \"quotes\"") ]),
+      Value::Array(vec![ Value::from(3), Value::Nil, Value::Array(vec![ Value::from(true) ]) ]),
+    ];
+
+    let mut csv: Vec<u8> = Vec::new();
+    let count = write_rows(&mut csv, ExportFormat::Csv, &columns, rows.clone()).await;
+    assert_eq!(count, 3);
+    assert_eq!(
+      String::from_utf8(csv).unwrap(),
+      "id,name\n1,plain\n2,\"with, \"\"quotes\"\"\"\n3,,[true]\n",
+    );
+
+    let mut jsonl: Vec<u8> = Vec::new();
+    write_rows(&mut jsonl, ExportFormat::JsonLines, &columns, rows).await;
+
+    let mut writer = RowWriter::start(&mut jsonl, ExportFormat::JsonLines, columns).await.unwrap();
+    assert!(writer.write(Value::from(1)).await.is_err());
+    assert_eq!(
+      String::from_utf8(jsonl).unwrap(),
+      concat!(
+        "{\"id\":1,\"name\":\"plain\"}\n",
+        "{\"id\":2,\"name\":\"with, \\\"quotes\\\"\"}\n",
+        "{\"3\":[true],\"id\":3,\"name\":null}\n",
+      ),
+    );
+  }
+}
@@ -0,0 +1,117 @@
+use crate::iproto::request::Id;
+
+/// version of protocol announced by connector
+pub const PROTOCOL_VERSION: u64 = 3;
+
+/// Feature of iproto protocol, features are negotiated by id request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+  Streams               = 0,
+  Transactions          = 1,
+  ErrorExtension        = 2,
+  Watchers              = 3,
+  Pagination            = 4,
+  SpaceAndIndexNames    = 5,
+  WatchOnce             = 6,
+  /// tuples of dml responses are sent with their formats
+  DmlTupleExtension     = 7,
+  /// tuples returned by call and eval are sent with their formats
+  CallRetTupleExtension = 8,
+}
+
+/// features which are implemented by connection, watch once request is not
+const CLIENT_FEATURES: &[Feature] = &[
+  Feature::Streams,
+  Feature::Transactions,
+  Feature::ErrorExtension,
+  Feature::Watchers,
+  Feature::Pagination,
+  Feature::SpaceAndIndexNames,
+  Feature::DmlTupleExtension,
+  Feature::CallRetTupleExtension,
+];
+
+/**
+  This is protocol version and features supported by both sides.
+
+  Servers older than 2.10 don't know id request,
+  so they are considered to support none of features.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Begin, Connection, Error, Feature};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let features = conn.protocol_features();
+    if features.supports(Feature::Transactions) {
+      let tx = conn.transaction(Begin::default()).await?;
+      // ...
+    # tx.commit().await?;
+    }
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtocolFeatures {
+  pub version: u64,
+  features: u64,
+}
+
+impl ProtocolFeatures {
+  /// features of connector, they are sent in id request
+  pub(crate) fn client() -> ProtocolFeatures {
+    ProtocolFeatures::new(PROTOCOL_VERSION, CLIENT_FEATURES.iter().map(|&f| f as u64))
+  }
+
+  /// unknown features are ignored
+  pub fn new<I>(version: u64, features: I) -> ProtocolFeatures
+    where I: IntoIterator<Item = u64>
+  {
+    let features = features.into_iter()
+      .filter(|&feature| feature < 64)
+      .fold(0, |bitmap, feature| bitmap | 1 << feature);
+
+    ProtocolFeatures { version, features }
+  }
+
+  /// lower version and features known to both sides
+  pub(crate) fn negotiate(&self, server: &ProtocolFeatures) -> ProtocolFeatures {
+    ProtocolFeatures {
+      version: self.version.min(server.version),
+      features: self.features & server.features,
+    }
+  }
+
+  pub fn supports(&self, feature: Feature) -> bool {
+    self.features & 1 << feature as u64 != 0
+  }
+
+  pub(crate) fn id(&self) -> Id {
+    Id {
+      version: self.version,
+      features: (0..64).filter(|feature| self.features & 1 << feature != 0).collect(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_negotiate() {
+    let server = ProtocolFeatures::new(6, vec![ 0, 1, 2, 3, 4, 5, 6, 70 ]);
+    let features = ProtocolFeatures::client().negotiate(&server);
+
+    assert_eq!(features.version, PROTOCOL_VERSION);
+    assert!(features.supports(Feature::Streams));
+    assert!(features.supports(Feature::Transactions));
+    assert!(features.supports(Feature::ErrorExtension));
+    assert!(features.supports(Feature::Pagination));
+    assert!(!features.supports(Feature::WatchOnce));
+    assert!(!features.supports(Feature::DmlTupleExtension));
+    assert_eq!(ProtocolFeatures::client().id().features, vec![ 0, 1, 2, 3, 4, 5, 7, 8 ]);
+
+    let old = ProtocolFeatures::client().negotiate(&ProtocolFeatures::default());
+    assert!(!old.supports(Feature::Streams));
+  }
+}
@@ -0,0 +1,176 @@
+use std::time::{Duration, Instant};
+
+use crate::iproto::request::Eval;
+
+use super::Connection;
+
+/// status of instance, read only flag and maximal upstream lag, -1 means stopped replication
+const HEALTH_EXPR: &str = r#"
+  local info = box.info
+  local lag = 0
+  for _, replica in pairs(info.replication) do
+    local upstream = replica.upstream
+    if upstream ~= nil then
+      if upstream.status ~= 'follow' then
+        lag = -1
+        break
+      end
+      lag = math.max(lag, upstream.lag or 0)
+    end
+  end
+  return info.status, info.ro, lag
+"#;
+
+/// Verdict of health check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Readiness {
+  Ready,
+  /// instance serves requests, but some checks failed
+  Degraded,
+  NotReady,
+}
+
+/// This sets limits of health check.
+#[derive(Debug, Clone, Copy)]
+pub struct HealthThresholds {
+  pub max_rtt: Duration,
+  pub max_replication_lag: Duration,
+}
+
+impl Default for HealthThresholds {
+  fn default() -> Self {
+    HealthThresholds {
+      max_rtt: Duration::from_millis(100),
+      max_replication_lag: Duration::from_secs(5),
+    }
+  }
+}
+
+/**
+  This is result of health check.
+
+  Values which could not be fetched are none, reasons are listed in problems.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+  pub readiness: Readiness,
+  pub rtt: Option<Duration>,
+  /// box.info.status
+  pub status: Option<String>,
+  pub read_only: Option<bool>,
+  /// maximal lag of upstreams, none if replication is stopped or unknown
+  pub replication_lag: Option<Duration>,
+  pub problems: Vec<String>,
+}
+
+impl Health {
+  /// degraded instance still may serve traffic
+  pub fn is_ready(&self) -> bool {
+    self.readiness != Readiness::NotReady
+  }
+
+  fn problem(&mut self, readiness: Readiness, problem: String) {
+    if readiness == Readiness::NotReady || self.readiness == Readiness::Ready {
+      self.readiness = readiness;
+    }
+    self.problems.push(problem);
+  }
+}
+
+impl Connection {
+  /// same as health_check_with with default thresholds
+  pub async fn health_check(&self) -> Health {
+    self.health_check_with(HealthThresholds::default()).await
+  }
+
+  /**
+    checks ping round trip, box.info.status and replication lag.
+
+    Instance is not ready if ping fails or it is not running,
+    it is degraded if it is slow, lags behind or its status can't be fetched.
+
+    Example:
+    ```rust,no_run
+      # use alopecosa::Connection;
+      # async fn f(conn: &Connection) {
+      let health = conn.health_check().await;
+      if !health.is_ready() {
+        log::warn!("tarantool is not ready: {:?}", health.problems);
+      }
+      # }
+    ```
+  */
+  pub async fn health_check_with(&self, thresholds: HealthThresholds) -> Health {
+    let mut health = Health {
+      readiness: Readiness::Ready,
+      rtt: None, status: None,
+      read_only: None, replication_lag: None,
+      problems: Vec::new(),
+    };
+
+    let start = Instant::now();
+    if let Err(err) = self.ping().await {
+      health.problem(Readiness::NotReady, format!("ping failed: {}", err));
+      return health;
+    }
+
+    let rtt = start.elapsed();
+    health.rtt = Some(rtt);
+    if rtt > thresholds.max_rtt {
+      health.problem(Readiness::Degraded, format!("ping took {:?}", rtt));
+    }
+
+    let (status, read_only, lag): (String, bool, f64) = match self.eval(Eval {
+      expr: HEALTH_EXPR.into(),
+      args: Vec::new(),
+    }).await {
+      Ok(info) => info,
+      Err(err) => {
+        health.problem(Readiness::Degraded, format!("can't get box.info: {}", err));
+        return health;
+      },
+    };
+
+    if status != "running" {
+      health.problem(Readiness::NotReady, format!("instance is {}", status));
+    }
+    health.status = Some(status);
+    health.read_only = Some(read_only);
+
+    if lag < 0.0 || !lag.is_finite() {
+      health.problem(Readiness::Degraded, "replication is stopped".into());
+    } else {
+      let lag = Duration::from_secs_f64(lag);
+      health.replication_lag = Some(lag);
+      if lag > thresholds.max_replication_lag {
+        health.problem(Readiness::Degraded, format!("replication lag is {:?}", lag));
+      }
+    }
+
+    health
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::connection::transport::tests::fake_connection;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_health_check() {
+    let conn = fake_connection().await;
+
+    // fake server forbids eval, so only ping succeeds
+    let health = conn.health_check_with(HealthThresholds {
+      max_rtt: Duration::from_secs(1),
+      ..HealthThresholds::default()
+    }).await;
+
+    assert_eq!(health.readiness, Readiness::Degraded);
+    assert!(health.is_ready());
+    assert!(health.rtt.is_some());
+    assert_eq!(health.status, None);
+    assert_eq!(health.problems.len(), 1);
+  }
+}
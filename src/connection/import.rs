@@ -0,0 +1,438 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDateTime};
+use futures_util::stream;
+use rust_decimal::Decimal;
+use serde_json::Value as Json;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, Lines};
+use uuid::Uuid;
+
+use crate::iproto::{
+  request::{IntoTuple, Value},
+  response::FormatField,
+  types::Error,
+};
+
+use super::{
+  Connection,
+  export::ExportFormat,
+  loader::{LoadOptions, LoadReport},
+};
+
+/// This configures import_from.
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+  format: ExportFormat,
+  dry_run: bool,
+  load: LoadOptions,
+}
+
+impl ImportOptions {
+  pub fn new(format: ExportFormat) -> ImportOptions {
+    ImportOptions { format, dry_run: false, load: LoadOptions::default() }
+  }
+
+  /// only validates rows, nothing is written
+  pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+    self.dry_run = dry_run;
+    self
+  }
+
+  /// sets how valid rows are written, see LoadOptions
+  pub fn with_load_options(mut self, load: LoadOptions) -> Self {
+    self.load = load;
+    self
+  }
+}
+
+/// Row which doesn't match space format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+  /// number of line where row starts, starting from one
+  pub line: u64,
+  pub message: String,
+}
+
+/// Result of import.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+  /// rows which match space format
+  pub valid_rows: u64,
+  pub invalid: Vec<RowError>,
+  /// result of writing valid rows, it is none for dry run
+  pub load: Option<LoadReport>,
+}
+
+impl ImportReport {
+  #[allow(clippy::unnecessary_map_or)] // Option::is_none_or needs rust 1.82
+  pub fn is_ok(&self) -> bool {
+    self.invalid.is_empty() && self.load.as_ref().map_or(true, LoadReport::is_ok)
+  }
+}
+
+/// splits csv record, returns none if quoted field continues on next line
+fn parse_csv(record: &str) -> Option<Vec<String>> {
+  let mut fields = Vec::new();
+  let mut field = String::new();
+  let mut quoted = false;
+  let mut chars = record.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    match (quoted, c) {
+      (true, '"') if chars.peek() == Some(&'"') => { chars.next(); field.push('"'); },
+      (true, '"') => quoted = false,
+      (false, '"') if field.is_empty() => quoted = true,
+      (false, ',') => fields.push(std::mem::take(&mut field)),
+      (_, c) => field.push(c),
+    }
+  }
+
+  if quoted {
+    return None;
+  }
+
+  fields.push(field);
+  Some(fields)
+}
+
+fn from_json(value: Json) -> Result<Value, String> {
+  match value {
+    Json::Null => Ok(Value::Null),
+    Json::Bool(b) => Ok(Value::Bool(b)),
+    Json::Number(n) => n.as_u64().map(Value::UInt)
+      .or_else(|| n.as_i64().map(Value::Int))
+      .or_else(|| n.as_f64().map(Value::F64))
+      .ok_or_else(|| format!("unsupported number {}", n)),
+    Json::String(s) => Ok(Value::Str(s)),
+    Json::Array(items) => Ok(Value::Array(
+      items.into_iter().map(from_json).collect::<Result<_, _>>()?,
+    )),
+    Json::Object(_) => Err("maps are not supported".into()),
+  }
+}
+
+fn parse<T: FromStr>(text: &str, field_type: &str) -> Result<T, String> {
+  text.trim().parse::<T>().map_err(|_| format!("{:?} is not {}", text, field_type))
+}
+
+/**
+  converts value to type of field, strings are parsed
+  to numbers, booleans, uuids, decimals and datetimes,
+  empty csv fields are nulls
+*/
+fn coerce(field: &FormatField, value: Json) -> Result<Value, String> {
+  let field_type = field.field_type.as_str();
+
+  if value.is_null() {
+    return match field.is_nullable {
+      true => Ok(Value::Null),
+      false => Err(format!("field {} is not nullable", field.name)),
+    };
+  }
+
+  let mismatch = |value: &Json| format!("{} is not {}", value, field_type);
+
+  let value = match (field_type, value) {
+    ("unsigned", Json::Number(n)) => n.as_u64().map(Value::UInt).ok_or_else(|| mismatch(&n.into()))?,
+    ("unsigned", Json::String(s)) => Value::UInt(parse(&s, field_type)?),
+
+    ("integer", Json::Number(n)) => n.as_u64().map(Value::UInt)
+      .or_else(|| n.as_i64().map(Value::Int))
+      .ok_or_else(|| mismatch(&n.into()))?,
+    ("integer", Json::String(s)) => Value::Int(parse(&s, field_type)?),
+
+    ("number", Json::String(s)) => match s.trim().parse::<i64>() {
+      Ok(i) => Value::Int(i),
+      Err(_) => Value::F64(parse(&s, field_type)?),
+    },
+    ("double", Json::Number(n)) => Value::F64(n.as_f64().ok_or_else(|| mismatch(&n.into()))?),
+    ("double", Json::String(s)) => Value::F64(parse(&s, field_type)?),
+
+    ("string", Json::String(s)) => Value::Str(s),
+    ("string", value @ Json::Number(_)) | ("string", value @ Json::Bool(_)) => Value::Str(value.to_string()),
+
+    ("boolean", Json::Bool(b)) => Value::Bool(b),
+    ("boolean", Json::String(s)) => Value::Bool(parse(&s, field_type)?),
+
+    ("varbinary", Json::String(s)) => Value::Bin(
+      base64::decode(s.trim()).map_err(|_| format!("{:?} is not base64", s))?,
+    ),
+    ("uuid", Json::String(s)) => Value::Uuid(parse::<Uuid>(&s, field_type)?),
+    ("decimal", Json::String(s)) => Value::Decimal(parse::<Decimal>(&s, field_type)?),
+    ("decimal", Json::Number(n)) => Value::Decimal(parse::<Decimal>(&n.to_string(), field_type)?),
+    // datetime with offset keeps it, naive one is UTC
+    ("datetime", Json::String(s)) => match DateTime::parse_from_rfc3339(s.trim()) {
+      Ok(time) => Value::DateTimeTz(time),
+      Err(_) => Value::DateTime(
+        NaiveDateTime::parse_from_str(s.trim(), "%Y-%m-%dT%H:%M:%S%.f")
+          .or_else(|_| NaiveDateTime::parse_from_str(s.trim(), "%Y-%m-%d %H:%M:%S%.f"))
+          .map_err(|_| format!("{:?} is not datetime", s))?,
+      ),
+    },
+    ("array", Json::String(s)) => match serde_json::from_str::<Json>(&s) {
+      Ok(array @ Json::Array(_)) => from_json(array)?,
+      _ => return Err(format!("{:?} is not array", s)),
+    },
+    ("array", array @ Json::Array(_)) => from_json(array)?,
+
+    ("unsigned", value) | ("integer", value) | ("double", value)
+    | ("string", value) | ("boolean", value) | ("varbinary", value)
+    | ("uuid", value) | ("decimal", value) | ("datetime", value)
+    | ("array", value) => return Err(mismatch(&value)),
+
+    // number, scalar, any and unknown types
+    (_, value) => from_json(value)?,
+  };
+
+  Ok(value)
+}
+
+/**
+  maps named or positional columns to tuple of space format,
+  names which are not in format are positions starting from one
+  as export names columns beyond format
+*/
+fn to_tuple(format: &[FormatField], columns: Vec<(Option<String>, Json)>) -> Result<Vec<Value>, String> {
+  let mut values: Vec<Option<Json>> = vec![ None; format.len() ];
+
+  for (i, (name, value)) in columns.into_iter().enumerate() {
+    let position = match name {
+      Some(name) => format.iter().position(|field| field.name == name)
+        .or_else(|| name.parse::<usize>().ok().filter(|&n| n > 0).map(|n| n - 1))
+        .ok_or_else(|| format!("unknown field {}", name))?,
+      None => i,
+    };
+
+    if position >= values.len() {
+      values.resize(position + 1, None);
+    }
+    values[position] = Some(value);
+  }
+
+  values.into_iter().enumerate()
+    .map(|(i, value)| {
+      let value = value.unwrap_or(Json::Null);
+      match format.get(i) {
+        Some(field) => coerce(field, value),
+        None => from_json(value),
+      }
+    })
+    .collect()
+}
+
+struct Row(Vec<Value>);
+
+impl IntoTuple for Row {
+  fn into_tuple(self) -> Vec<Value> { self.0 }
+}
+
+/// parses rows of input one by one, invalid rows are collected with their line numbers
+struct RowReader<'a, R> {
+  lines: Lines<R>,
+  format: ExportFormat,
+  space_format: &'a [FormatField],
+  header: Option<Vec<String>>,
+  line_no: u64,
+  valid_rows: u64,
+  invalid: Vec<RowError>,
+  /// error of reading input, it stops import
+  error: Option<Error>,
+}
+
+impl<'a, R> RowReader<'a, R>
+  where R: AsyncBufRead + Unpin
+{
+  fn new(reader: R, format: ExportFormat, space_format: &'a [FormatField]) -> RowReader<'a, R> {
+    RowReader {
+      lines: reader.lines(), format, space_format,
+      header: None, line_no: 0, valid_rows: 0,
+      invalid: Vec::new(), error: None,
+    }
+  }
+
+  /// next valid row, it is none at the end of input
+  async fn next_row(&mut self) -> Result<Option<Vec<Value>>, Error> {
+    while let Some(mut line) = self.lines.next_line().await? {
+      self.line_no += 1;
+      let start = self.line_no;
+
+      let columns: Result<Vec<(Option<String>, Json)>, String> = match self.format {
+        ExportFormat::JsonLines => {
+          if line.trim().is_empty() {
+            continue;
+          }
+
+          match serde_json::from_str::<Json>(&line) {
+            Ok(Json::Object(object)) => Ok(object.into_iter()
+              .map(|(name, value)| (Some(name), value))
+              .collect()),
+            Ok(Json::Array(items)) => Ok(items.into_iter()
+              .map(|value| (None, value))
+              .collect()),
+            Ok(_) => Err("expected object or array".into()),
+            Err(err) => Err(err.to_string()),
+          }
+        },
+        ExportFormat::Csv => {
+          let fields = loop {
+            if let Some(fields) = parse_csv(&line) {
+              break fields;
+            }
+
+            match self.lines.next_line().await? {
+              Some(next) => { self.line_no += 1; line.push('\n'); line.push_str(&next); },
+              None => break Vec::new(),
+            }
+          };
+
+          let names = match &self.header {
+            Some(names) => names,
+            None => { self.header = Some(fields); continue; },
+          };
+
+          if fields.is_empty() {
+            Err("unterminated quoted field".into())
+          } else if fields.len() != names.len() {
+            Err(format!("expected {} fields, got {}", names.len(), fields.len()))
+          } else {
+            Ok(names.iter().cloned().zip(fields)
+              .map(|(name, value)| match value.is_empty() {
+                true => (Some(name), Json::Null),
+                false => (Some(name), Json::String(value)),
+              })
+              .collect())
+          }
+        },
+      };
+
+      match columns.and_then(|columns| to_tuple(self.space_format, columns)) {
+        Ok(tuple) => {
+          self.valid_rows += 1;
+          return Ok(Some(tuple));
+        },
+        Err(message) => self.invalid.push(RowError { line: start, message }),
+      }
+    }
+
+    Ok(None)
+  }
+}
+
+impl Connection {
+  /**
+    reads rows from JSON Lines or CSV and writes them into space.
+
+    Columns are matched to space format by names
+    (keys of json objects or csv header), json arrays are matched by positions.
+    Values are converted to types of fields, rows which can't be converted
+    are reported and skipped. Input is read while rows are written,
+    so it isn't kept in memory.
+
+    Example:
+    ```rust,no_run
+      # use alopecosa::{Connection, Error, ExportFormat, ImportOptions};
+      # async fn f(conn: &Connection) -> Result<(), Error> {
+      let file = tokio::io::BufReader::new(tokio::fs::File::open("users.csv").await?);
+      let report = conn.import_from(512, file, ImportOptions::new(ExportFormat::Csv)
+        .with_dry_run(true)).await?;
+
+      for err in report.invalid {
+        println!("line {}: {}", err.line, err.message);
+      }
+      # Ok(()) }
+    ```
+  */
+  pub async fn import_from<R>(
+    &self, space_id: u64, reader: R, opts: ImportOptions,
+  ) -> Result<ImportReport, Error>
+    where R: AsyncBufRead + Unpin
+  {
+    let space_format = self.space_format(space_id).await?;
+    let mut reader = RowReader::new(reader, opts.format, &space_format);
+
+    let load = match opts.dry_run {
+      true => {
+        while reader.next_row().await?.is_some() {}
+        None
+      },
+      false => {
+        // valid rows are streamed to loader, error of input is kept in reader
+        let rows = stream::unfold(&mut reader, |reader| async move {
+          match reader.next_row().await {
+            Ok(Some(tuple)) => Some((Row(tuple), reader)),
+            Ok(None) => None,
+            Err(err) => { reader.error = Some(err); None },
+          }
+        });
+        Some(self.load_from(space_id, Box::pin(rows), opts.load).await)
+      },
+    };
+
+    if let Some(err) = reader.error {
+      return Err(err);
+    }
+
+    Ok(ImportReport { valid_rows: reader.valid_rows, invalid: reader.invalid, load })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn field(name: &str, field_type: &str, is_nullable: bool) -> FormatField {
+    FormatField { name: name.into(), field_type: field_type.into(), is_nullable }
+  }
+
+  async fn read_rows(
+    input: &str, format: ExportFormat, space_format: &[FormatField],
+  ) -> (Vec<Vec<Value>>, Vec<RowError>) {
+    let mut reader = RowReader::new(input.as_bytes(), format, space_format);
+    let mut rows = Vec::new();
+    while let Some(row) = reader.next_row().await.unwrap() {
+      rows.push(row);
+    }
+
+    assert_eq!(reader.valid_rows, rows.len() as u64);
+    (rows, reader.invalid)
+  }
+
+  #[tokio::test]
+  async fn test_read_rows() {
+    let format = vec![
+      field("id", "unsigned", false),
+      field("name", "string", false),
+      field("score", "double", true),
+    ];
+
+    let csv = "name,id,score\n\"multi\nline\",1,\nb,x,1.5\nc,3,2\n";
+    let (rows, invalid) = read_rows(csv, ExportFormat::Csv, &format).await;
+
+    assert_eq!(format!("{:?}", rows), format!("{:?}", vec![
+      vec![ Value::UInt(1), Value::Str("multi\nline".into()), Value::Null ],
+      vec![ Value::UInt(3), Value::Str("c".into()), Value::F64(2.0) ],
+    ]));
+    assert_eq!(invalid, vec![
+      RowError { line: 4, message: "\"x\" is not unsigned".into() },
+    ]);
+
+    let jsonl = "{\"id\": 1, \"name\": \"a\"}\n[2, \"b\", 0.5]\n{\"id\": 3}\n{\"id\": 4, \"age\": 1}\n";
+    let (rows, invalid) = read_rows(jsonl, ExportFormat::JsonLines, &format).await;
+
+    assert_eq!(rows.len(), 2);
+    assert_eq!(invalid, vec![
+      RowError { line: 3, message: "field name is not nullable".into() },
+      RowError { line: 4, message: "unknown field age".into() },
+    ]);
+
+    // keys are matched by names whatever their order, extra fields of export by positions
+    let jsonl = "{\"score\": 0.5, \"name\": \"a\", \"id\": 1}\n{\"id\": 2, \"name\": \"b\", \"5\": true, \"4\": \"x\"}\n";
+    let (rows, invalid) = read_rows(jsonl, ExportFormat::JsonLines, &format).await;
+
+    assert!(invalid.is_empty());
+    assert_eq!(format!("{:?}", rows), format!("{:?}", vec![
+      vec![ Value::UInt(1), Value::Str("a".into()), Value::F64(0.5) ],
+      vec![ Value::UInt(2), Value::Str("b".into()), Value::Null, Value::Str("x".into()), Value::Bool(true) ],
+    ]));
+  }
+}
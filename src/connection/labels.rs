@@ -0,0 +1,92 @@
+use std::fmt;
+
+/**
+  This is set of labels attached to connection at build time,
+  e.g. service, shard or role.
+
+  Labels are included in connection logs, request log and tracing spans,
+  so connections to different clusters may be told apart.
+
+  Example:
+  ```rust
+    # use alopecosa::{Connector, Labels};
+    let labels = Labels::new()
+      .with("shard", "3")
+      .with("role", "master");
+    assert_eq!(labels.get("role"), Some("master"));
+    assert_eq!(labels.to_string(), "shard=3 role=master");
+
+    let connector = Connector::new("127.0.0.1:3301".parse().unwrap())
+      .with_labels(labels)
+      .with_label("role", "replica");
+  ```
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Labels(Vec<(String, String)>);
+
+impl Labels {
+  pub fn new() -> Labels {
+    Labels::default()
+  }
+
+  /// sets label, value of existing label is replaced
+  pub fn with<K, V>(mut self, key: K, value: V) -> Self
+    where K: Into<String>, V: Into<String>
+  {
+    let (key, value) = (key.into(), value.into());
+    match self.0.iter_mut().find(|(k, _)| *k == key) {
+      Some((_, v)) => *v = value,
+      None => self.0.push((key, value)),
+    }
+    self
+  }
+
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self.0.iter()
+      .find(|(k, _)| k == key)
+      .map(|(_, v)| v.as_str())
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+    self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+/// labels are formatted as space separated key=value pairs
+impl fmt::Display for Labels {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, (key, value)) in self.0.iter().enumerate() {
+      if i > 0 {
+        f.write_str(" ")?;
+      }
+      write!(f, "{}={}", key, value)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_labels() {
+    let labels = Labels::new()
+      .with("service", "billing")
+      .with("shard", "1")
+      .with("shard", "2");
+
+    assert_eq!(labels.len(), 2);
+    assert_eq!(labels.get("shard"), Some("2"));
+    assert_eq!(labels.get("role"), None);
+    assert_eq!(labels.to_string(), "service=billing shard=2");
+  }
+}
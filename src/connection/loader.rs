@@ -0,0 +1,391 @@
+use std::{
+  collections::VecDeque,
+  fmt,
+  future::poll_fn,
+  pin::Pin,
+  sync::Arc,
+  task::{Context, Poll},
+};
+
+use futures_core::Stream;
+
+use crate::iproto::{
+  request::{self, Insert, IntoTuple, Replace, Request, Upsert, Value},
+  types::Error,
+};
+
+use super::{Connection, Pending};
+
+/// This is request used to write loaded rows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LoadMode {
+  /// rows with existing primary key are reported as errors
+  #[default]
+  Insert,
+  /// rows with existing primary key overwrite stored ones
+  Replace,
+  /**
+    rows with existing primary key are updated by operations of LoadOptions::with_upsert_ops,
+    without them stored rows are kept
+  */
+  Upsert,
+}
+
+/// Progress of load reported after every written batch.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LoadProgress {
+  /// rows which were written or failed
+  pub rows: u64,
+  pub failed_rows: u64,
+  pub batches: u64,
+}
+
+/// Rows of one batch which were not written.
+#[derive(Debug)]
+pub struct BatchError {
+  /// number of batch starting from zero
+  pub batch: u64,
+  /// position of first row of batch in stream
+  pub offset: u64,
+  /// positions of rows in batch and their errors
+  pub errors: Vec<(usize, Error)>,
+}
+
+/// Result of load.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+  pub progress: LoadProgress,
+  pub errors: Vec<BatchError>,
+  /// true if rows were left unwritten because of stop on error
+  pub stopped: bool,
+}
+
+impl LoadReport {
+  pub fn is_ok(&self) -> bool {
+    self.errors.is_empty() && !self.stopped
+  }
+}
+
+/// update operations of upserted row made from its tuple
+type UpsertOps = Arc<dyn Fn(&[Value]) -> Vec<Vec<Value>> + Send + Sync>;
+
+/**
+  This configures load_from and upsert_many.
+
+  Rows are grouped into batches, every batch is written at once,
+  and at most max_in_flight batches wait for responses,
+  so stream is not read faster than tarantool writes.
+*/
+#[derive(Clone)]
+pub struct LoadOptions {
+  mode: LoadMode,
+  batch_size: usize,
+  max_in_flight: usize,
+  stop_on_error: bool,
+  upsert_ops: Option<UpsertOps>,
+  progress: Option<Arc<dyn Fn(LoadProgress) + Send + Sync>>,
+}
+
+impl fmt::Debug for LoadOptions {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("LoadOptions")
+      .field("mode", &self.mode)
+      .field("batch_size", &self.batch_size)
+      .field("max_in_flight", &self.max_in_flight)
+      .field("stop_on_error", &self.stop_on_error)
+      .finish()
+  }
+}
+
+impl Default for LoadOptions {
+  fn default() -> Self {
+    LoadOptions {
+      mode: LoadMode::default(),
+      batch_size: 1000,
+      max_in_flight: 4,
+      stop_on_error: false,
+      upsert_ops: None,
+      progress: None,
+    }
+  }
+}
+
+impl LoadOptions {
+  pub fn new() -> LoadOptions {
+    LoadOptions::default()
+  }
+
+  pub fn with_mode(mut self, mode: LoadMode) -> Self {
+    self.mode = mode;
+    self
+  }
+
+  pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+    self.batch_size = batch_size.max(1);
+    self
+  }
+
+  pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+    self.max_in_flight = max_in_flight.max(1);
+    self
+  }
+
+  /**
+    stops reading rows once any row fails,
+    batches which are already sent are still awaited and reported
+  */
+  pub fn with_stop_on_error(mut self, stop_on_error: bool) -> Self {
+    self.stop_on_error = stop_on_error;
+    self
+  }
+
+  /// sets update operations of rows upserted in LoadMode::Upsert, they are made from tuple of row
+  pub fn with_upsert_ops<F>(mut self, ops: F) -> Self
+    where F: Fn(&[Value]) -> Vec<Vec<Value>> + Send + Sync + 'static
+  {
+    self.upsert_ops = Some(Arc::new(ops));
+    self
+  }
+
+  /// sets callback which is called after every batch
+  pub fn with_progress<F>(mut self, progress: F) -> Self
+    where F: Fn(LoadProgress) + Send + Sync + 'static
+  {
+    self.progress = Some(Arc::new(progress));
+    self
+  }
+}
+
+struct InFlight {
+  batch: u64,
+  offset: u64,
+  pending: Vec<Result<Pending, Error>>,
+}
+
+/// rows of iterator as stream
+struct IterRows<I>(I);
+
+impl<I: Iterator + Unpin> Stream for IterRows<I> {
+  type Item = I::Item;
+
+  fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<I::Item>> {
+    Poll::Ready(self.0.next())
+  }
+}
+
+impl Connection {
+  /**
+    writes rows from stream into space, see LoadOptions.
+
+    Failed rows don't stop load, they are returned in report.
+
+    Example:
+    ```rust,no_run
+      # use alopecosa::{Connection, LoadMode, LoadOptions};
+      # struct Record { id: u64, name: String }
+      # async fn f(conn: &Connection, records: Vec<Record>) {
+      let rows = futures_util::stream::iter(records.into_iter().map(|r| ( r.id, r.name )));
+      let report = conn.load_from(512, rows, LoadOptions::new()
+        .with_mode(LoadMode::Replace)
+        .with_progress(|progress| log::info!("loaded {} rows", progress.rows))).await;
+      # }
+    ```
+  */
+  pub async fn load_from<S>(&self, space_id: u64, rows: S, opts: LoadOptions) -> LoadReport
+    where S: Stream + Unpin,
+          S::Item: IntoTuple,
+  {
+    let (mode, upsert_ops) = (opts.mode, opts.upsert_ops.clone());
+    let to_request = move |row: S::Item| {
+      let tuple = row.into_tuple();
+      match mode {
+        LoadMode::Insert => request::insert(Insert { space_id, tuple }),
+        LoadMode::Replace => request::replace(Replace { space_id, tuple }),
+        LoadMode::Upsert => {
+          let ops = upsert_ops.as_ref().map(|ops| ops(&tuple)).unwrap_or_default();
+          request::upsert(Upsert { space_id, index_base: 0, ops, tuple })
+        },
+      }
+    };
+
+    self.load(rows, to_request, &opts).await
+  }
+
+  /**
+    upserts rows into space in pipelined batches, see LoadOptions, its mode is ignored.
+
+    Update operations of every row are made by ops function
+    before row is converted into tuple.
+
+    Example:
+    ```rust,no_run
+      # use alopecosa::{Connection, LoadOptions};
+      # async fn f(conn: &Connection, counters: Vec<(u64, u64)>) {
+      let report = conn.upsert_many(
+        512, counters,
+        |&(id, hits): &(u64, u64)| vec![ vec![ "+".into(), 1.into(), hits.into() ] ],
+        LoadOptions::new().with_batch_size(500).with_stop_on_error(true),
+      ).await;
+
+      for batch in report.errors {
+        log::error!("batch {} failed: {:?}", batch.batch, batch.errors);
+      }
+      # }
+    ```
+  */
+  pub async fn upsert_many<I, F>(&self, space_id: u64, rows: I, ops: F, opts: LoadOptions) -> LoadReport
+    where I: IntoIterator,
+          I::IntoIter: Unpin,
+          I::Item: IntoTuple,
+          F: Fn(&I::Item) -> Vec<Vec<Value>>,
+  {
+    let to_request = |row: I::Item| {
+      let ops = ops(&row);
+      request::upsert(Upsert { space_id, index_base: 0, ops, tuple: row.into_tuple() })
+    };
+
+    self.load(IterRows(rows.into_iter()), to_request, &opts).await
+  }
+
+  async fn load<S, F>(&self, mut rows: S, mut to_request: F, opts: &LoadOptions) -> LoadReport
+    where S: Stream + Unpin,
+          F: FnMut(S::Item) -> Request,
+  {
+    let mut report = LoadReport::default();
+    let mut in_flight: VecDeque<InFlight> = VecDeque::new();
+    let (mut batch, mut offset) = (0u64, 0u64);
+
+    loop {
+      let mut requests: Vec<Request> = Vec::with_capacity(opts.batch_size);
+      while requests.len() < opts.batch_size {
+        match poll_fn(|cx| Pin::new(&mut rows).poll_next(cx)).await {
+          Some(row) => requests.push(to_request(row)),
+          None => break,
+        }
+      }
+
+      if requests.is_empty() {
+        break;
+      }
+
+      if opts.stop_on_error && report.progress.failed_rows > 0 {
+        report.stopped = true;
+        break;
+      }
+
+      if in_flight.len() >= opts.max_in_flight {
+        if let Some(done) = in_flight.pop_front() {
+          self.finish_batch(done, &mut report, opts).await;
+        }
+      }
+
+      let size = requests.len() as u64;
+      let pending = self.send_batch(requests).await;
+
+      in_flight.push_back(InFlight { batch, offset, pending });
+      batch += 1;
+      offset += size;
+
+      // failure must be known before the next batch is sent
+      if opts.stop_on_error {
+        if let Some(done) = in_flight.pop_front() {
+          self.finish_batch(done, &mut report, opts).await;
+        }
+      }
+    }
+
+    while let Some(done) = in_flight.pop_front() {
+      self.finish_batch(done, &mut report, opts).await;
+    }
+
+    report
+  }
+
+  async fn finish_batch(&self, done: InFlight, report: &mut LoadReport, opts: &LoadOptions) {
+    let mut errors = Vec::new();
+    let rows = done.pending.len() as u64;
+
+    for (i, pending) in done.pending.into_iter().enumerate() {
+      if let Err(err) = Pending::result(pending).await {
+        errors.push((i, err));
+      }
+    }
+
+    report.progress.rows += rows;
+    report.progress.batches += 1;
+    report.progress.failed_rows += errors.len() as u64;
+
+    if !errors.is_empty() {
+      log::warn!("{} rows of batch {} are not loaded", errors.len(), done.batch);
+      report.errors.push(BatchError { batch: done.batch, offset: done.offset, errors });
+    }
+
+    if let Some(progress) = &opts.progress {
+      progress(report.progress);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{sync::Mutex, task::{Context, Poll}};
+
+  use crate::connection::transport::tests::fake_connection;
+
+  use super::*;
+
+  struct Rows(std::ops::Range<u64>);
+
+  impl Stream for Rows {
+    type Item = (u64,);
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<(u64,)>> {
+      Poll::Ready(self.0.next().map(|id| (id,)))
+    }
+  }
+
+  #[tokio::test]
+  async fn test_load_from() {
+    let conn = fake_connection().await;
+    let reported = Arc::new(Mutex::new(Vec::new()));
+
+    let opts = {
+      let reported = reported.clone();
+      LoadOptions::new()
+        .with_batch_size(3)
+        .with_max_in_flight(2)
+        .with_progress(move |progress| reported.lock().unwrap().push(progress.rows))
+    };
+
+    let report = conn.load_from(512, Rows(0..10), opts).await;
+
+    assert!(report.is_ok());
+    assert_eq!(report.progress, LoadProgress { rows: 10, failed_rows: 0, batches: 4 });
+    assert_eq!(*reported.lock().unwrap(), vec![ 3, 6, 9, 10 ]);
+
+    let opts = LoadOptions::new()
+      .with_mode(LoadMode::Upsert)
+      .with_upsert_ops(|tuple| vec![ vec![ "=".into(), 1.into(), tuple[0].clone() ] ]);
+    let report = conn.load_from(512, Rows(0..5), opts).await;
+    assert!(report.is_ok());
+    assert_eq!(report.progress.rows, 5);
+  }
+
+  #[tokio::test]
+  async fn test_upsert_many() {
+    let conn = fake_connection().await;
+    let ops = |&(_, hits): &(u64, u64)| vec![ vec![ "+".into(), 1.into(), hits.into() ] ];
+
+    let rows: Vec<(u64, u64)> = (0..10).map(|id| (id, 1)).collect();
+    let report = conn.upsert_many(
+      512, rows, ops, LoadOptions::new().with_batch_size(3).with_max_in_flight(2),
+    ).await;
+
+    assert!(report.is_ok());
+    assert_eq!(report.progress, LoadProgress { rows: 10, failed_rows: 0, batches: 4 });
+
+    let empty: Vec<(u64, u64)> = Vec::new();
+    let report = conn.upsert_many(512, empty, ops, LoadOptions::new()).await;
+    assert_eq!(report.progress.batches, 0);
+  }
+}
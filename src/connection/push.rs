@@ -0,0 +1,203 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  sync::Arc,
+  task::{Context, Poll},
+};
+
+use dashmap::DashMap;
+use futures_core::Stream;
+use rmpv::Value as MsgValue;
+use serde::de::DeserializeOwned;
+use tokio::sync::mpsc;
+
+use crate::iproto::{
+  constants::Field,
+  request::{self, Call, Eval, Request},
+  response::{Response, TupleBody},
+  types::Error,
+};
+
+use super::Connection;
+
+/// channels of pushed values by sync of request
+pub(crate) type Pushes = Arc<DashMap<u64, mpsc::UnboundedSender<MsgValue>>>;
+
+/// passes value pushed by box.session.push to stream of its request
+pub(crate) fn dispatch(pushes: &Pushes, chunk: &Response) -> Result<(), Error> {
+  let push = match pushes.get(&chunk.header.sync) {
+    Some(push) => push,
+    // request was performed without push stream
+    None => return Ok(()),
+  };
+
+  let body = match &chunk.body {
+    Some(body) => rmpv::decode::read_value(&mut &body[..])?,
+    None => return Err(Error::UnexpectedValue(Field::Data)),
+  };
+
+  let value = body.as_map()
+    .and_then(|fields| fields.iter().find(|(key, _)| key.as_u64() == Some(Field::Data as u64)))
+    .and_then(|(_, data)| data.as_array()?.first().cloned())
+    .ok_or(Error::UnexpectedValue(Field::Data))?;
+
+  let _ = push.send(value);
+  Ok(())
+}
+
+/**
+  This is stream of values pushed by box.session.push during call or eval.
+
+  Request is sent before stream is returned, so stream may be drained
+  before its result is awaited. Stream ends once the final response of request
+  is received. Values may be decoded with TypedEvents.
+
+  Example:
+  ```rust,no_run
+    # use futures_util::StreamExt;
+    # use alopecosa::{Call, Connection, Error, IntoTuple};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let (mut progress, result) = conn.call_with_push::<(u64,)>(Call {
+      function: "reindex".into(),
+      args: ().into_tuple(),
+    }).await?;
+
+    while let Some(done) = progress.next().await {
+      log::info!("reindexed {:?}", done);
+    }
+    let (total,) = result.await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug)]
+pub struct PushStream {
+  sync: u64,
+  receiver: mpsc::UnboundedReceiver<MsgValue>,
+  pushes: Pushes,
+}
+
+impl Stream for PushStream {
+  type Item = MsgValue;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<MsgValue>> {
+    self.receiver.poll_recv(cx)
+  }
+}
+
+impl Drop for PushStream {
+  fn drop(&mut self) {
+    self.pushes.remove(&self.sync);
+  }
+}
+
+impl Connection {
+  /// sends call and streams values it pushes, see PushStream
+  pub async fn call_with_push<T>(
+    &self, body: Call,
+  ) -> Result<(PushStream, impl Future<Output = Result<T, Error>>), Error>
+    where T: DeserializeOwned
+  {
+    self.perform_with_push(request::call(body)).await
+  }
+
+  /// sends eval and streams values it pushes, see PushStream
+  pub async fn eval_with_push<T>(
+    &self, body: Eval,
+  ) -> Result<(PushStream, impl Future<Output = Result<T, Error>>), Error>
+    where T: DeserializeOwned
+  {
+    self.perform_with_push(request::eval(body)).await
+  }
+
+  /// request is not retried, since its pushes can't be taken back
+  async fn perform_with_push<T>(
+    &self, mut req: Request,
+  ) -> Result<(PushStream, impl Future<Output = Result<T, Error>>), Error>
+    where T: DeserializeOwned
+  {
+    let mut context = self.error_context(req.header.request, req.target(), 0);
+
+    // sync is known in advance, so pushes are not missed
+    let sync = self.new_sync();
+    req.header.sync = sync;
+    req.header.fixed_sync = true;
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    self.pushes.insert(sync, sender);
+    // push channel is removed by reader along with response of request
+    let stream = PushStream { sync, receiver, pushes: self.pushes.clone() };
+
+    let pending = self.send_request(req).await
+      .map_err(|err| context.wrap(err))?;
+    let schema = self.schema.clone();
+
+    let result = async move {
+      let resp = pending.wait().await
+        .map_err(|err| context.wrap(err))?;
+      context.sync = resp.header.sync;
+      schema.observe(resp.header.schema);
+
+      Connection::check_response(resp)
+        .and_then(|resp| resp.unpack_body::<TupleBody<T>>())
+        .map_err(|err| context.wrap(err))
+    };
+
+    Ok((stream, result))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::future::poll_fn;
+
+  use crate::{
+    connection::transport::tests::fake_connection,
+    iproto::{constants::RequestType, response::Header},
+  };
+
+  use super::*;
+
+  fn chunk(sync: u64, value: MsgValue) -> Response {
+    let mut body: Vec<u8> = Vec::new();
+    rmpv::encode::write_value(&mut body, &MsgValue::Map(vec![
+      ((Field::Data as u64).into(), MsgValue::Array(vec![ value ])),
+    ])).unwrap();
+    #[cfg(not(feature = "vec-body"))]
+    let body = body.into();
+
+    Response {
+      header: Header { raw_code: RequestType::Chunk as u64, sync, ..Default::default() },
+      body: Some(body),
+    }
+  }
+
+  #[tokio::test]
+  async fn test_push() {
+    let conn = fake_connection().await;
+
+    let (mut pushes, result) = conn.eval_with_push::<()>(Eval {
+      expr: "box.session.push(1) box.session.push('two')".into(),
+      args: Vec::new(),
+    }).await.unwrap();
+    // request is only queued, so chunks come before its response
+    dispatch(&conn.pushes, &chunk(pushes.sync, 1.into())).unwrap();
+    dispatch(&conn.pushes, &chunk(pushes.sync, "two".into())).unwrap();
+    // chunks of other requests are skipped
+    dispatch(&conn.pushes, &chunk(pushes.sync + 100, 3.into())).unwrap();
+
+    // stream is drained before result is awaited
+    let mut values = Vec::new();
+    while let Some(value) = poll_fn(|cx| Pin::new(&mut pushes).poll_next(cx)).await {
+      values.push(value);
+    }
+    assert_eq!(values, vec![ 1.into(), "two".into() ]);
+
+    // eval is forbidden by fake server
+    let err = result.await.unwrap_err();
+    assert!(matches!(err.root(), Error::TarantoolError(..)));
+    assert_eq!(err.context().unwrap().request, RequestType::Eval);
+
+    drop(pushes);
+    assert!(conn.pushes.is_empty());
+  }
+}
@@ -0,0 +1,12 @@
+use crate::iproto::{redaction::Redaction, request::Request};
+
+/// log target of request log, it may be enabled separately from other logs
+pub const QUERY_LOG_TARGET: &str = "alopecosa::query";
+
+/// writes request to request log
+pub(crate) fn log(req: &Request, peer: &str, redaction: Redaction) {
+  log::info!(
+    target: QUERY_LOG_TARGET,
+    "[{}] {:?} sync={} {}", peer, req.header.request, req.header.sync, req.describe(redaction),
+  );
+}
@@ -0,0 +1,136 @@
+use std::{
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+/**
+  This is client-side token bucket limiter.
+
+  It may limit requests per second and/or request bytes per second,
+  bucket capacity equals to one second of rate. Requests are counted when they are sent
+  and bytes when packed requests are written.
+  Limiter may be shared between connections to throttle them together.
+
+  Example:
+  ```rust,no_run
+    # use std::{net::SocketAddr, sync::Arc};
+    # use alopecosa::{Connection, Connector, RateLimiter};
+    # async fn f(addr: SocketAddr) -> std::io::Result<()> {
+    let limiter = Arc::new(RateLimiter::new()
+      .with_requests_per_sec(1000)
+      .with_bytes_per_sec(1024 * 1024));
+
+    let conn: Arc<Connection> = Connector::new(addr)
+      .with_rate_limiter(limiter.clone())
+      .connect().await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+  requests: Option<Mutex<Bucket>>,
+  bytes: Option<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+  /// creates limiter without limits
+  pub fn new() -> RateLimiter {
+    RateLimiter::default()
+  }
+
+  pub fn with_requests_per_sec(mut self, rate: u64) -> Self {
+    self.requests = Some(Mutex::new(Bucket::new(rate)));
+    self
+  }
+
+  pub fn with_bytes_per_sec(mut self, rate: u64) -> Self {
+    self.bytes = Some(Mutex::new(Bucket::new(rate)));
+    self
+  }
+
+  /// waits until given count of requests and bytes may be sent
+  pub(crate) async fn acquire(&self, requests: usize, bytes: usize) {
+    let wait = self.reserve(requests, bytes, Instant::now());
+
+    if wait > Duration::from_secs(0) {
+      log::trace!("request is throttled for {:?}", wait);
+      tokio::time::sleep(wait).await;
+    }
+  }
+
+  /**
+    takes tokens from buckets and returns time to wait,
+    bucket goes into debt so concurrent requests are queued fairly.
+    Bucket is left alone if nothing is taken from it
+  */
+  fn reserve(&self, requests: usize, bytes: usize, now: Instant) -> Duration {
+    let requests = self.requests.as_ref()
+      .filter(|_| requests > 0)
+      .map(|bucket| bucket.lock().unwrap().take(requests as f64, now))
+      .unwrap_or_default();
+
+    let bytes = self.bytes.as_ref()
+      .filter(|_| bytes > 0)
+      .map(|bucket| bucket.lock().unwrap().take(bytes as f64, now))
+      .unwrap_or_default();
+
+    requests.max(bytes)
+  }
+}
+
+#[derive(Debug)]
+struct Bucket {
+  rate: f64,
+  tokens: f64,
+  updated: Instant,
+}
+
+impl Bucket {
+  fn new(rate: u64) -> Bucket {
+    let rate = rate.max(1) as f64;
+    Bucket { rate, tokens: rate, updated: Instant::now() }
+  }
+
+  fn take(&mut self, amount: f64, now: Instant) -> Duration {
+    let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+    self.updated = now.max(self.updated);
+
+    self.tokens -= amount;
+
+    match self.tokens < 0.0 {
+      true => Duration::from_secs_f64(-self.tokens / self.rate),
+      false => Duration::from_secs(0),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_rate_limiter() {
+    let limiter = RateLimiter::new()
+      .with_requests_per_sec(10)
+      .with_bytes_per_sec(100);
+    let now = Instant::now();
+
+    for _ in 0..5 {
+      assert_eq!(limiter.reserve(1, 10, now), Duration::from_secs(0));
+    }
+
+    // bytes bucket is exhausted first
+    assert!(limiter.reserve(1, 60, now) >= Duration::from_millis(99));
+
+    // requests don't wait for bytes which are taken by writer
+    assert_eq!(limiter.reserve(1, 0, now), Duration::from_secs(0));
+
+    // after one second buckets are refilled
+    let later = now + Duration::from_secs(1);
+    assert_eq!(limiter.reserve(1, 10, later), Duration::from_secs(0));
+
+    let unlimited = RateLimiter::new();
+    assert_eq!(unlimited.reserve(1, 1 << 30, now), Duration::from_secs(0));
+  }
+}
@@ -0,0 +1,359 @@
+use std::{collections::VecDeque, convert::TryInto, io::{self, Cursor}};
+
+use crate::iproto::{
+  request::{self, Auth, AuthMethod, Request},
+  response::{ErrorBody, Response},
+  types::Error,
+};
+
+use super::connector::Connector;
+
+const GREETING_LEN: usize = 128;
+/// size prefix of frame is msgpack integer of at most 9 bytes
+const MAX_SIZE_LEN: usize = 9;
+
+/// Event produced by RawConnection.
+#[derive(Debug)]
+pub enum RawEvent {
+  /// greeting and auth are done, version of tarantool is known
+  Ready,
+  Response(Response),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+  Greeting,
+  Auth,
+  Ready,
+}
+
+/**
+  This is protocol state machine without io.
+
+  Bytes read from socket are fed into connection and it produces events,
+  bytes which should be written to socket are taken from it.
+  So it may be driven by any executor or event loop, e.g. epoll or io_uring.
+
+  Requests sent before greeting and auth are done are queued.
+  Reconnection, timeouts and response routing are up to caller.
+
+  Example:
+  ```rust,no_run
+    # use std::io::{Read, Write};
+    # use alopecosa::{Error, RawConnection, RawEvent, request};
+    # fn f(socket: &mut std::net::TcpStream) -> Result<(), Error> {
+    # let mut buf = [ 0u8; 4096 ];
+    let mut raw = RawConnection::new().with_auth("user".into(), "password".into());
+    let sync = raw.send(request::ping())?;
+
+    loop {
+      let n = socket.read(&mut buf)?;
+      raw.feed(&buf[..n])?;
+
+      while let Some(event) = raw.poll_event() {
+        if let RawEvent::Response(resp) = event {
+          assert_eq!(resp.header.sync, sync);
+        }
+      }
+
+      let written = socket.write(raw.output())?;
+      raw.consume_output(written);
+    }
+    # }
+  ```
+*/
+#[derive(Debug)]
+pub struct RawConnection {
+  state: State,
+  credentials: Option<(String, String)>,
+  auth_method: AuthMethod,
+  version: Option<String>,
+  sync: u64,
+  input: Vec<u8>,
+  output: Vec<u8>,
+  queued: Vec<u8>,
+  events: VecDeque<RawEvent>,
+}
+
+impl Default for RawConnection {
+  fn default() -> Self {
+    RawConnection {
+      state: State::Greeting,
+      credentials: None,
+      auth_method: AuthMethod::default(),
+      version: None,
+      sync: 1,
+      input: Vec::new(),
+      output: Vec::new(),
+      queued: Vec::new(),
+      events: VecDeque::new(),
+    }
+  }
+}
+
+impl RawConnection {
+  pub fn new() -> RawConnection {
+    RawConnection::default()
+  }
+
+  pub fn with_auth(mut self, user: String, password: String) -> Self {
+    self.credentials = Some((user, password));
+    self
+  }
+
+  /**
+    sets auth method, it is chap-sha1 by default.
+
+    Greeting doesn't tell which method server expects,
+    pap-sha256 sends password as is so it should be used only over encrypted transport.
+  */
+  pub fn with_auth_method(mut self, method: AuthMethod) -> Self {
+    self.auth_method = method;
+    self
+  }
+
+  /// version of tarantool, it is known after greeting
+  pub fn version(&self) -> Option<&str> {
+    self.version.as_deref()
+  }
+
+  pub fn is_ready(&self) -> bool {
+    self.state == State::Ready
+  }
+
+  /**
+    packs request into output and returns its sync,
+    sync set by RequestBuilder is kept
+  */
+  pub fn send(&mut self, mut req: Request) -> Result<u64, Error> {
+    if !req.header.fixed_sync {
+      req.header.sync = self.sync;
+      self.sync += 1;
+    }
+
+    match self.state {
+      State::Ready => req.pack(&mut self.output)?,
+      _ => req.pack(&mut self.queued)?,
+    }
+
+    Ok(req.header.sync)
+  }
+
+  /// bytes which should be written to socket
+  pub fn output(&self) -> &[u8] {
+    &self.output
+  }
+
+  /// removes written bytes from output
+  pub fn consume_output(&mut self, written: usize) {
+    self.output.drain(..written.min(self.output.len()));
+  }
+
+  /// takes whole output, it is handy for io with owned buffers
+  pub fn take_output(&mut self) -> Vec<u8> {
+    std::mem::take(&mut self.output)
+  }
+
+  /**
+    processes bytes read from socket,
+    error means that connection is broken and should be closed
+  */
+  pub fn feed(&mut self, data: &[u8]) -> Result<(), Error> {
+    self.input.extend_from_slice(data);
+
+    loop {
+      let consumed = match self.state {
+        State::Greeting => self.greeting()?,
+        State::Auth | State::Ready => match self.frame()? {
+          Some((consumed, resp)) => {
+            self.response(resp)?;
+            consumed
+          },
+          None => 0,
+        },
+      };
+
+      if consumed == 0 {
+        return Ok(());
+      }
+      self.input.drain(..consumed);
+    }
+  }
+
+  pub fn poll_event(&mut self) -> Option<RawEvent> {
+    self.events.pop_front()
+  }
+
+  fn greeting(&mut self) -> Result<usize, Error> {
+    let greeting: &[u8; GREETING_LEN] = match self.input.get(..GREETING_LEN) {
+      Some(greeting) => greeting.try_into().expect("greeting has fixed length"),
+      None => return Ok(0),
+    };
+
+    let (version, salt) = Connector::parse_greeting(greeting)
+      .ok_or_else(|| invalid_data("bad greeting"))?;
+    self.version = Some(version.into());
+
+    let (user, password) = match &self.credentials {
+      Some(creds) => creds,
+      None => {
+        self.ready();
+        return Ok(GREETING_LEN);
+      },
+    };
+
+    let salt = base64::decode(salt).map_err(|_| invalid_data("bad salt"))?;
+    let scramble = match self.auth_method {
+      AuthMethod::ChapSha1 => Connector::auth_scramble(&salt, password),
+      AuthMethod::PapSha256 => password.as_bytes().to_vec(),
+    };
+    request::auth(Auth::new(user.as_str(), scramble).with_method(self.auth_method))
+      .pack(&mut self.output)?;
+
+    self.state = State::Auth;
+    Ok(GREETING_LEN)
+  }
+
+  /// parses frame if it is received completely
+  fn frame(&self) -> Result<Option<(usize, Response)>, Error> {
+    let mut cur = Cursor::new(self.input.as_slice());
+    let size: u64 = match rmp::decode::read_int(&mut cur) {
+      Ok(size) => size,
+      Err(_) if self.input.len() < MAX_SIZE_LEN => return Ok(None),
+      Err(err) => return Err(err.into()),
+    };
+
+    let end = cur.position() as usize + size as usize;
+    if self.input.len() < end {
+      return Ok(None);
+    }
+
+    Ok(Some((end, Response::parse(&self.input[..end])?)))
+  }
+
+  fn response(&mut self, resp: Response) -> Result<(), Error> {
+    if self.state == State::Ready {
+      self.events.push_back(RawEvent::Response(resp));
+      return Ok(());
+    }
+
+    // auth request has zero sync
+    if resp.header.code.is_err() {
+      return Err(Error::TarantoolError(resp.header.code, resp.unpack_body::<ErrorBody>()?));
+    }
+    if resp.header.sync != 0 {
+      return Err(invalid_data("unexpected response before auth"));
+    }
+
+    self.ready();
+    Ok(())
+  }
+
+  fn ready(&mut self) {
+    self.state = State::Ready;
+    self.output.append(&mut self.queued);
+    self.events.push_back(RawEvent::Ready);
+  }
+}
+
+fn invalid_data(message: &str) -> Error {
+  io::Error::new(io::ErrorKind::InvalidData, message).into()
+}
+
+#[cfg(test)]
+mod tests {
+  use rmpv::Value;
+
+  use crate::iproto::constants::Code;
+
+  use super::*;
+
+  fn greeting() -> Vec<u8> {
+    let mut greeting = vec![ b' '; GREETING_LEN ];
+    greeting[..30].copy_from_slice(b"Tarantool 2.10.0 (Binary) uuid");
+    greeting[63] = b'\n';
+    greeting[64..108].copy_from_slice(&[ b'A'; 44 ]);
+    greeting
+  }
+
+  fn response(code: u64, sync: u64) -> Vec<u8> {
+    let mut resp: Vec<u8> = Vec::new();
+    rmpv::encode::write_value(&mut resp, &Value::Map(vec![
+      (0.into(), code.into()), (1.into(), sync.into()),
+    ])).unwrap();
+    if code != 0 {
+      rmpv::encode::write_value(&mut resp, &Value::Map(vec![
+        (0x31.into(), "error".into()),
+      ])).unwrap();
+    }
+
+    let mut frame: Vec<u8> = Vec::new();
+    rmp::encode::write_u32(&mut frame, resp.len() as u32).unwrap();
+    frame.extend(resp);
+    frame
+  }
+
+  #[test]
+  fn test_raw_connection() {
+    let mut raw = RawConnection::new().with_auth("user".into(), "secret".into());
+
+    // requests are queued until auth is done
+    let sync = raw.send(request::ping()).unwrap();
+    assert!(raw.output().is_empty());
+
+    let greeting = greeting();
+    raw.feed(&greeting[..100]).unwrap();
+    assert_eq!(raw.version(), None);
+    raw.feed(&greeting[100..]).unwrap();
+    assert_eq!(raw.version(), Some("2.10.0"));
+    assert!(!raw.is_ready());
+
+    // only auth request is written
+    let auth_len = raw.output().len();
+    assert!(auth_len > 0);
+    raw.consume_output(auth_len);
+
+    raw.feed(&response(0, 0)).unwrap();
+    assert!(raw.is_ready());
+    assert!(matches!(raw.poll_event(), Some(RawEvent::Ready)));
+    assert!(!raw.output().is_empty());
+
+    // frame may be split in any place
+    let frame = response(0, sync);
+    raw.feed(&frame[..3]).unwrap();
+    assert!(raw.poll_event().is_none());
+    raw.feed(&frame[3..]).unwrap();
+    match raw.poll_event() {
+      Some(RawEvent::Response(resp)) => assert_eq!(resp.header.sync, sync),
+      event => panic!("unexpected event {:?}", event),
+    }
+  }
+
+  #[test]
+  fn test_raw_connection_auth_method() {
+    let mut raw = RawConnection::new()
+      .with_auth("user".into(), "secret".into())
+      .with_auth_method(AuthMethod::PapSha256);
+    raw.feed(&greeting()).unwrap();
+
+    let mut output = raw.output();
+    let size: u64 = rmp::decode::read_int(&mut output).unwrap();
+    assert_eq!(size as usize, output.len());
+    let _header = rmpv::decode::read_value(&mut output).unwrap();
+    let body = rmpv::decode::read_value(&mut output).unwrap();
+
+    let tuple = body.as_map().unwrap().iter()
+      .find(|(key, _)| key.as_u64() == Some(0x21))
+      .map(|(_, tuple)| tuple.clone());
+    assert_eq!(tuple, Some(Value::Array(vec![ "pap-sha256".into(), "secret".into() ])));
+  }
+
+  #[test]
+  fn test_raw_connection_auth_error() {
+    let mut raw = RawConnection::new().with_auth("user".into(), "wrong".into());
+    raw.feed(&greeting()).unwrap();
+
+    let err = raw.feed(&response(Code::ErrorAccessDenied as u64, 0)).unwrap_err();
+    assert!(matches!(err, Error::TarantoolError(Code::ErrorAccessDenied, _)));
+  }
+}
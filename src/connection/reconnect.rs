@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use super::retry::Backoff;
+
+/**
+  This is policy of reconnection after connection is lost.
+
+  Reconnection is attempted with exponential backoff,
+  attempts are counted in a row and are reset by successful connect.
+  Connection is closed once max attempts are failed.
+
+  Requests sent while connection is down wait for reconnection,
+  requests in flight fail with Error::ConnectionReset.
+
+  Example:
+  ```rust,no_run
+    # use std::{net::SocketAddr, time::Duration};
+    # use alopecosa::{Backoff, Connector, ReconnectPolicy};
+    # async fn f(addr: SocketAddr) -> std::io::Result<()> {
+    let conn = Connector::new(addr)
+      .with_reconnect_policy(ReconnectPolicy::new()
+        .with_backoff(Backoff::new(10)
+          .with_initial(Duration::from_millis(200))
+          .with_max(Duration::from_secs(10))))
+      .connect().await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+  pub backoff: Backoff,
+}
+
+impl Default for ReconnectPolicy {
+  /// reconnects forever with delays from 100ms to 30s
+  fn default() -> Self {
+    ReconnectPolicy {
+      backoff: Backoff::new(u32::MAX)
+        .with_initial(Duration::from_millis(100))
+        .with_max(Duration::from_secs(30)),
+    }
+  }
+}
+
+impl ReconnectPolicy {
+  pub fn new() -> ReconnectPolicy {
+    ReconnectPolicy::default()
+  }
+
+  /// reconnects forever with the same delay
+  pub fn constant(interval: Duration) -> ReconnectPolicy {
+    ReconnectPolicy::new().with_backoff(Backoff::new(u32::MAX)
+      .with_initial(interval)
+      .with_max(interval)
+      .with_jitter(0.0))
+  }
+
+  /// max attempts of backoff are failed attempts in a row
+  pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+    self.backoff = backoff;
+    self
+  }
+
+  /// delay before given attempt in a row, attempts start from one
+  pub(crate) fn delay(&self, attempt: u32) -> Option<Duration> {
+    match attempt <= self.backoff.max_attempts {
+      true => Some(self.backoff.delay(attempt)),
+      false => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    IntoTuple,
+    connection::transport::tests::fake_connector,
+    iproto::{request::Call, types::Error},
+  };
+
+  use super::*;
+
+  #[test]
+  fn test_reconnect_policy() {
+    let policy = ReconnectPolicy::constant(Duration::from_millis(300));
+    assert_eq!(policy.delay(1), Some(Duration::from_millis(300)));
+    assert_eq!(policy.delay(100), Some(Duration::from_millis(300)));
+
+    let policy = ReconnectPolicy::new().with_backoff(Backoff::new(2).with_jitter(0.0));
+    assert_eq!(policy.delay(1), Some(Duration::from_millis(50)));
+    assert_eq!(policy.delay(2), Some(Duration::from_millis(100)));
+    assert_eq!(policy.delay(3), None);
+  }
+
+  #[tokio::test]
+  async fn test_connection_reset() {
+    let conn = fake_connector(2)
+      .with_reconnect_policy(ReconnectPolicy::constant(Duration::from_millis(10)))
+      .connect().await.unwrap();
+
+    let err = conn.call::<()>(Call {
+      function: "reset".into(),
+      args: ().into_tuple(),
+    }).await.unwrap_err();
+    assert!(matches!(err.root(), Error::ConnectionReset));
+
+    // requests wait for reconnection
+    conn.ping().await.unwrap();
+  }
+}
@@ -0,0 +1,346 @@
+use std::{
+  collections::{HashMap, HashSet},
+  future::Future,
+  io,
+  time::Duration,
+};
+
+use rand::Rng;
+
+use crate::iproto::{
+  constants::{Code, RequestType},
+  request::Request,
+  types::Error,
+};
+
+/// Kind of error used to pick retry backoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+  /// client side attempt timeout and server side timeouts
+  Timeout,
+  /// ER_READONLY, request reached replica or master is being switched
+  ReadOnly,
+  /// ER_TUPLE_FOUND, duplicate key
+  TupleFound,
+  /// transaction conflicts, they usually succeed on retry
+  Conflict,
+  /// server is loading or has no memory
+  Unavailable,
+  /// connection was lost while request was in flight, it may be applied
+  ConnectionReset,
+  /// other errors returned by tarantool
+  Tarantool,
+  /// encoding, decoding and io errors
+  Client,
+}
+
+impl ErrorClass {
+  pub fn of(err: &Error) -> ErrorClass {
+    match err.root() {
+      Error::TarantoolError(code, _) => match code {
+        Code::ErrorTimeout | Code::ErrorSyncQuorumTimeout => ErrorClass::Timeout,
+        Code::ErrorReadonly => ErrorClass::ReadOnly,
+        Code::ErrorTupleFound => ErrorClass::TupleFound,
+        Code::ErrorTransactionConflict | Code::ErrorSyncRollback => ErrorClass::Conflict,
+        Code::ErrorLoading | Code::ErrorMemoryIssue => ErrorClass::Unavailable,
+        _ => ErrorClass::Tarantool,
+      },
+      Error::ConnectionReset => ErrorClass::ConnectionReset,
+      Error::ParseError(rmp_serde::decode::Error::InvalidDataRead(err))
+        if err.kind() == io::ErrorKind::TimedOut => ErrorClass::Timeout,
+      _ => ErrorClass::Client,
+    }
+  }
+}
+
+/**
+  This is exponential backoff of one error class.
+
+  Every delay is reduced by random part of it up to jitter,
+  so clients which failed together don't retry together.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+  /// attempts including the first one
+  pub max_attempts: u32,
+  pub initial: Duration,
+  pub max: Duration,
+  pub multiplier: f64,
+  /// part of delay from 0 to 1 which is randomized
+  pub jitter: f64,
+}
+
+impl Backoff {
+  pub fn new(max_attempts: u32) -> Backoff {
+    Backoff {
+      max_attempts,
+      initial: Duration::from_millis(50),
+      max: Duration::from_secs(5),
+      multiplier: 2.0,
+      jitter: 0.5,
+    }
+  }
+
+  pub fn with_initial(mut self, initial: Duration) -> Self {
+    self.initial = initial;
+    self
+  }
+
+  pub fn with_max(mut self, max: Duration) -> Self {
+    self.max = max;
+    self
+  }
+
+  pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+    self.multiplier = multiplier.max(1.0);
+    self
+  }
+
+  pub fn with_jitter(mut self, jitter: f64) -> Self {
+    self.jitter = jitter.clamp(0.0, 1.0);
+    self
+  }
+
+  /// delay after given failed attempt, attempts start from one
+  pub fn delay(&self, attempt: u32) -> Duration {
+    let exp = self.multiplier.powi(attempt.saturating_sub(1) as i32);
+    let delay = (self.initial.as_secs_f64() * exp).min(self.max.as_secs_f64());
+
+    let jitter = match self.jitter > 0.0 {
+      true => rand::thread_rng().gen_range(0.0..self.jitter),
+      false => 0.0,
+    };
+
+    Duration::from_secs_f64(delay * (1.0 - jitter))
+  }
+}
+
+/**
+  This is retry policy with backoff per error class.
+
+  Errors of classes without backoff are returned immediately.
+  Attempts are counted per class, so e.g. timeouts don't use up
+  attempts of read only errors.
+
+  Example:
+  ```rust,no_run
+    # use std::time::Duration;
+    # use alopecosa::{Backoff, Connection, Error, ErrorClass, RetryPolicy, Select};
+    # async fn f(conn: &Connection, select: Select) -> Result<(), Error> {
+    let policy = RetryPolicy::new()
+      .with_attempt_timeout(Duration::from_secs(1))
+      .with_class(ErrorClass::Timeout, Backoff::new(3))
+      .with_class(ErrorClass::ReadOnly, Backoff::new(10)
+        .with_initial(Duration::from_millis(200)))
+      .with_class(ErrorClass::Conflict, Backoff::new(5).with_jitter(1.0));
+
+    let user: Vec<(u64, String)> = policy.run(|| conn.select(select.clone())).await?;
+    # Ok(()) }
+  ```
+
+  Policy may be set on connection, then it is applied to requests
+  which are safe to retry: selects, pings, calls of read only functions
+  and requests marked idempotent by Request::with_idempotent.
+  Requests of streams are never retried by connection.
+
+  Example:
+  ```rust,no_run
+    # use std::net::SocketAddr;
+    # use alopecosa::{Connector, Error, Replace, RetryPolicy, request};
+    # async fn f(addr: SocketAddr, replace: Replace) -> Result<(), Error> {
+    let conn = Connector::new(addr)
+      .with_retry_policy(RetryPolicy::transient(3)
+        .with_read_only_function("get_user"))
+      .connect().await?;
+
+    conn.perform(request::replace(replace).with_idempotent()).await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Clone, Default)]
+pub struct RetryPolicy {
+  classes: HashMap<ErrorClass, Backoff>,
+  attempt_timeout: Option<Duration>,
+  read_only: HashSet<String>,
+}
+
+impl RetryPolicy {
+  /// creates policy without retries
+  pub fn new() -> RetryPolicy {
+    RetryPolicy::default()
+  }
+
+  /// retries timeouts, read only errors and connection resets with default backoff
+  pub fn transient(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy::new()
+      .with_class(ErrorClass::Timeout, Backoff::new(max_attempts))
+      .with_class(ErrorClass::ReadOnly, Backoff::new(max_attempts))
+      .with_class(ErrorClass::ConnectionReset, Backoff::new(max_attempts))
+  }
+
+  pub fn with_class(mut self, class: ErrorClass, backoff: Backoff) -> Self {
+    self.classes.insert(class, backoff);
+    self
+  }
+
+  /// bounds every attempt, elapsed attempt fails with Timeout class
+  pub fn with_attempt_timeout(mut self, timeout: Duration) -> Self {
+    self.attempt_timeout = Some(timeout);
+    self
+  }
+
+  /// calls of function are retried by connection like selects
+  pub fn with_read_only_function<S: Into<String>>(mut self, function: S) -> Self {
+    self.read_only.insert(function.into());
+    self
+  }
+
+  pub fn backoff(&self, class: ErrorClass) -> Option<&Backoff> {
+    self.classes.get(&class)
+  }
+
+  /// true if connection may retry request with this policy
+  pub fn is_retryable(&self, req: &Request) -> bool {
+    if req.header.stream_id.is_some() {
+      return false;
+    }
+
+    req.header.idempotent || match req.header.request {
+      RequestType::Select | RequestType::Ping => true,
+      RequestType::Call | RequestType::Call16 =>
+        matches!(req.function(), Some(function) if self.read_only.contains(function)),
+      _ => false,
+    }
+  }
+
+  /// runs operation until it succeeds or its error may not be retried anymore
+  pub async fn run<F, Fut, T>(&self, mut operation: F) -> Result<T, Error>
+    where F: FnMut() -> Fut,
+          Fut: Future<Output = Result<T, Error>>,
+  {
+    let mut attempts: HashMap<ErrorClass, u32> = HashMap::new();
+
+    loop {
+      let result = match self.attempt_timeout {
+        None => operation().await,
+        Some(timeout) => match tokio::time::timeout(timeout, operation()).await {
+          Ok(result) => result,
+          Err(elapsed) => Err(io::Error::from(elapsed).into()),
+        },
+      };
+
+      let err = match result {
+        Ok(value) => return Ok(value),
+        Err(err) => err,
+      };
+
+      let class = ErrorClass::of(&err);
+      let backoff = match self.classes.get(&class) {
+        Some(backoff) => backoff,
+        None => return Err(err),
+      };
+
+      let attempt = attempts.entry(class).or_insert(0);
+      *attempt += 1;
+      if *attempt >= backoff.max_attempts {
+        return Err(err);
+      }
+
+      let delay = backoff.delay(*attempt);
+      log::debug!("retrying {:?} error in {:?}: {}", class, delay, err);
+      tokio::time::sleep(delay).await;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::atomic::{AtomicU32, Ordering};
+
+  use crate::{
+    IntoTuple,
+    connection::{reconnect::ReconnectPolicy, transport::tests::fake_connector},
+    iproto::{
+      constants::Iterator,
+      request::{self, Call, Insert, RequestBuilder, Select},
+      response::TarantoolError,
+    },
+  };
+
+  use super::*;
+
+  fn tarantool_error(code: Code) -> Error {
+    Error::TarantoolError(code, TarantoolError::new("error"))
+  }
+
+  #[tokio::test]
+  async fn test_retry_policy() {
+    let policy = RetryPolicy::new()
+      .with_class(ErrorClass::ReadOnly, Backoff::new(3).with_initial(Duration::from_millis(1)))
+      .with_class(ErrorClass::Timeout, Backoff::new(2).with_initial(Duration::from_millis(1)));
+
+    // attempts are counted per class
+    let calls = AtomicU32::new(0);
+    let result = policy.run(|| async {
+      match calls.fetch_add(1, Ordering::SeqCst) {
+        0 | 1 => Err(tarantool_error(Code::ErrorReadonly)),
+        2 => Err(tarantool_error(Code::ErrorTimeout)),
+        _ => Ok(42),
+      }
+    }).await;
+    assert_eq!(result.unwrap(), 42);
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    // duplicates are not retried
+    let calls = AtomicU32::new(0);
+    let result: Result<(), _> = policy.run(|| async {
+      calls.fetch_add(1, Ordering::SeqCst);
+      Err(tarantool_error(Code::ErrorTupleFound))
+    }).await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let backoff = Backoff::new(5)
+      .with_initial(Duration::from_millis(100))
+      .with_max(Duration::from_millis(300))
+      .with_jitter(0.0);
+    assert_eq!(backoff.delay(1), Duration::from_millis(100));
+    assert_eq!(backoff.delay(2), Duration::from_millis(200));
+    assert_eq!(backoff.delay(4), Duration::from_millis(300));
+    assert!(Backoff::new(5).delay(1) <= Duration::from_millis(50));
+  }
+
+  #[test]
+  fn test_is_retryable() {
+    let policy = RetryPolicy::transient(3).with_read_only_function("get_user");
+    let call = |function: &str| request::call(Call { function: function.into(), args: Vec::new() });
+    let insert = || request::insert(Insert { space_id: 512, tuple: (1,).into_tuple() });
+
+    assert!(policy.is_retryable(&request::ping()));
+    assert!(policy.is_retryable(&call("get_user")));
+    assert!(!policy.is_retryable(&call("add_user")));
+    assert!(!policy.is_retryable(&insert()));
+    assert!(policy.is_retryable(&insert().with_idempotent()));
+    assert!(!policy.is_retryable(&RequestBuilder::from(request::select(Select {
+      space_id: 512, index_id: 0, limit: 1, offset: 0,
+      iterator: Iterator::Eq,
+      keys: Vec::new(),
+    })).with_stream_id(1).build()));
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_connection_retry() {
+    let conn = fake_connector(2)
+      .with_reconnect_policy(ReconnectPolicy::constant(Duration::from_millis(1)))
+      .with_retry_policy(RetryPolicy::transient(2).with_read_only_function("reset"))
+      .connect().await.unwrap();
+
+    let err = conn.call::<()>(Call { function: "reset".into(), args: Vec::new() })
+      .await.unwrap_err();
+    assert!(matches!(err.root(), Error::ConnectionReset));
+
+    // both fake servers are used up by attempts
+    assert!(tokio::time::timeout(Duration::from_millis(50), conn.ping()).await.is_err());
+    conn.close().await;
+  }
+}
@@ -0,0 +1,217 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, RwLock, atomic::{AtomicU64, Ordering}},
+  time::Duration,
+};
+
+use rmpv::Value as MsgValue;
+use tokio::time::Instant;
+
+use crate::iproto::{
+  constants::{Code, Field, Iterator, VINDEX_ID, VSPACE_ID},
+  request::{self, Select},
+  response::{FormattedBody, FormattedTuple, TarantoolError},
+  types::Error,
+};
+
+use super::Connection;
+
+/// names which are missing in fresh cache don't reload it more often
+const MISS_RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// spaces and indexes are selected again if schema is changed between them
+const LOAD_ATTEMPTS: usize = 3;
+
+/// Names of spaces and indexes of one schema version.
+#[derive(Debug)]
+struct Names {
+  version: u64,
+  loaded: Instant,
+  spaces: HashMap<String, u64>,
+  indexes: HashMap<(u64, String), u64>,
+}
+
+impl Names {
+  /// tuples are taken from _vspace and _vindex, names are third fields of both
+  fn from_tuples(
+    version: u64, spaces: &[FormattedTuple], indexes: &[FormattedTuple],
+  ) -> Result<Names, Error> {
+    let field = |tuple: &FormattedTuple, pos: usize| tuple.values().get(pos)
+      .cloned()
+      .ok_or(Error::UnexpectedValue(Field::Data));
+    let id = |tuple, pos| field(tuple, pos)?.as_u64().ok_or(Error::UnexpectedValue(Field::Data));
+    let name = |tuple, pos| match field(tuple, pos)? {
+      MsgValue::String(name) => name.into_str().ok_or(Error::UnexpectedValue(Field::Data)),
+      _ => Err(Error::UnexpectedValue(Field::Data)),
+    };
+
+    Ok(Names {
+      version,
+      loaded: Instant::now(),
+      spaces: spaces.iter()
+        .map(|space| Ok((name(space, 2)?, id(space, 0)?)))
+        .collect::<Result<_, Error>>()?,
+      indexes: indexes.iter()
+        .map(|index| Ok(((id(index, 0)?, name(index, 2)?), id(index, 1)?)))
+        .collect::<Result<_, Error>>()?,
+    })
+  }
+}
+
+/**
+  This is cache of space and index names of connection.
+
+  It is loaded on connect or first use and is reloaded when some response
+  reports schema version newer than version of cache.
+  Missing name reloads cache at most once per MISS_RELOAD_INTERVAL,
+  so requests with wrong names don't reload schema on every call.
+*/
+#[derive(Debug, Default)]
+pub(crate) struct SchemaCache {
+  /// the newest schema version reported by responses
+  seen: AtomicU64,
+  names: RwLock<Option<Arc<Names>>>,
+  loading: tokio::sync::Mutex<()>,
+}
+
+impl SchemaCache {
+  pub(crate) fn observe(&self, version: u64) {
+    self.seen.fetch_max(version, Ordering::Relaxed);
+  }
+
+  /// forgets names and versions, schema of the new session is loaded on next use
+  pub(crate) fn invalidate(&self) {
+    *self.names.write().unwrap() = None;
+    self.seen.store(0, Ordering::Relaxed);
+  }
+
+  fn fresh(&self) -> Option<Arc<Names>> {
+    let names = self.names.read().unwrap().clone()?;
+    match names.version >= self.seen.load(Ordering::Relaxed) {
+      true => Some(names),
+      false => None,
+    }
+  }
+
+  fn store(&self, names: Names) -> Arc<Names> {
+    let names = Arc::new(names);
+    *self.names.write().unwrap() = Some(names.clone());
+    names
+  }
+}
+
+impl Connection {
+  /// loads names of spaces and indexes regardless of schema version
+  pub async fn reload_schema(&self) -> Result<(), Error> {
+    let _loading = self.schema.loading.lock().await;
+    self.schema.store(self.load_names().await?);
+    Ok(())
+  }
+
+  /// resolves space id by name with schema cache
+  pub(crate) async fn resolve_space(&self, name: &str) -> Result<u64, Error> {
+    self.resolve(|names| names.spaces.get(name).copied()).await?
+      .ok_or_else(|| Error::TarantoolError(
+        Code::ErrorNoSuchSpace,
+        TarantoolError::new(format!("Space '{}' does not exist", name)),
+      ))
+  }
+
+  /// resolves index id by name with schema cache
+  pub(crate) async fn resolve_index(&self, space_id: u64, name: &str) -> Result<u64, Error> {
+    let key = (space_id, name.to_string());
+    self.resolve(|names| names.indexes.get(&key).copied()).await?
+      .ok_or_else(|| Error::TarantoolError(
+        Code::ErrorNoSuchIndexName,
+        TarantoolError::new(format!("No index '{}' is defined in space {}", name, space_id)),
+      ))
+  }
+
+  /**
+    missing name is looked up once more in reloaded names, it may be created recently,
+    unless names were loaded less than MISS_RELOAD_INTERVAL ago
+  */
+  async fn resolve<F>(&self, lookup: F) -> Result<Option<u64>, Error>
+    where F: Fn(&Names) -> Option<u64>
+  {
+    let cached = |names: Option<Arc<Names>>| names.and_then(|names| match lookup(&names) {
+      Some(id) => Some(Some(id)),
+      None if names.loaded.elapsed() < MISS_RELOAD_INTERVAL => Some(None),
+      None => None,
+    });
+
+    if let Some(id) = cached(self.schema.fresh()) {
+      return Ok(id);
+    }
+
+    let _loading = self.schema.loading.lock().await;
+    // names may be reloaded while lock was awaited
+    if let Some(id) = cached(self.schema.fresh()) {
+      return Ok(id);
+    }
+
+    let names = self.schema.store(self.load_names().await?);
+    Ok(lookup(&names))
+  }
+
+  /// spaces and indexes are taken from the same schema version
+  async fn load_names(&self) -> Result<Names, Error> {
+    let all = |space_id| request::select(Select {
+      space_id, index_id: 0,
+      limit: u32::MAX, offset: 0,
+      iterator: Iterator::All,
+      keys: Vec::new(),
+    });
+
+    for _ in 0..LOAD_ATTEMPTS {
+      let (spaces, spaces_context) = self.perform_in_context(all(VSPACE_ID)).await?;
+      let (indexes, indexes_context) = self.perform_in_context(all(VINDEX_ID)).await?;
+
+      let version = spaces.header.schema;
+      if version != indexes.header.schema {
+        log::debug!("schema is changed while it was loaded, loading it again");
+        continue;
+      }
+
+      let spaces = spaces.unpack_body::<FormattedBody>().map_err(|err| spaces_context.wrap(err))?;
+      let indexes = indexes.unpack_body::<FormattedBody>().map_err(|err| indexes_context.wrap(err))?;
+      return Names::from_tuples(version, &spaces, &indexes);
+    }
+
+    Err(Error::UnexpectedValue(Field::SchemaVersion))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_schema_cache() {
+    let tuple = |values: Vec<MsgValue>| FormattedTuple::new(None, values);
+    let names = Names::from_tuples(
+      5,
+      &[ tuple(vec![ 512.into(), 1.into(), "users".into(), "memtx".into() ]) ],
+      &[
+        tuple(vec![ 512.into(), 0.into(), "primary".into(), "tree".into() ]),
+        tuple(vec![ 512.into(), 1.into(), "email".into(), "hash".into() ]),
+      ],
+    ).unwrap();
+    assert_eq!(names.spaces.get("users"), Some(&512));
+    assert_eq!(names.indexes.get(&(512, "email".into())), Some(&1));
+
+    assert!(Names::from_tuples(5, &[ tuple(vec![ 512.into() ]) ], &[]).is_err());
+
+    let cache = SchemaCache::default();
+    assert!(cache.fresh().is_none());
+    cache.observe(5);
+    cache.store(names);
+    assert!(cache.fresh().is_some());
+
+    // newer schema version invalidates names
+    cache.observe(4);
+    assert!(cache.fresh().is_some());
+    cache.observe(6);
+    assert!(cache.fresh().is_none());
+  }
+}
@@ -0,0 +1,268 @@
+use std::{
+  sync::atomic::{AtomicU64, Ordering},
+  time::Duration,
+};
+
+use dashmap::DashMap;
+
+use crate::iproto::{constants::Code, types::Error};
+
+/// Execution statistics of prepared statement.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatementStats {
+  /// id known by user
+  pub id: i64,
+  pub sql: String,
+  pub executions: u64,
+  pub errors: u64,
+  pub total_latency: Duration,
+  pub max_latency: Duration,
+}
+
+impl StatementStats {
+  pub fn mean_latency(&self) -> Duration {
+    match self.executions {
+      0 => Duration::default(),
+      executions => Duration::from_nanos((self.total_latency.as_nanos() / executions as u128) as u64),
+    }
+  }
+}
+
+/**
+  Statistics of statement cache of connection.
+
+  Hit is execution of statement prepared through connection,
+  miss is execution of statement id unknown to connection,
+  eviction is statement unprepared over capacity of cache,
+  reprepare is statement expired on server or evicted and prepared again.
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatementCacheStats {
+  pub hits: u64,
+  pub misses: u64,
+  pub evictions: u64,
+  pub reprepares: u64,
+  /// statements ordered by total latency, the slowest first
+  pub statements: Vec<StatementStats>,
+}
+
+#[derive(Debug)]
+struct Statement {
+  sql: String,
+  /// id of statement on server, none if it was evicted
+  actual_id: Option<i64>,
+  /// tick of the latest use, the least recently used statement is unprepared first
+  used: u64,
+  stats: StatementStats,
+}
+
+/**
+  Remembers sql text of statements prepared through connection,
+  so expired statement ids can be transparently prepared again.
+
+  Key is statement id known by user, value is sql text
+  and id of statement currently alive on server.
+  Cache of limited capacity evicts the least recently used statements,
+  they should be unprepared on server by caller, but their sql text is kept
+  until statement is removed, so it is prepared again on the next execution.
+*/
+#[derive(Debug, Default)]
+pub(crate) struct StatementCache {
+  statements: DashMap<i64, Statement>,
+  capacity: Option<usize>,
+  tick: AtomicU64,
+  hits: AtomicU64,
+  misses: AtomicU64,
+  evictions: AtomicU64,
+  reprepares: AtomicU64,
+}
+
+impl StatementCache {
+  pub(crate) fn new(capacity: Option<usize>) -> StatementCache {
+    StatementCache { capacity, ..StatementCache::default() }
+  }
+
+  /// remembers statement, returns server ids of statements evicted over capacity
+  pub(crate) fn insert(&self, id: i64, sql: String) -> Vec<i64> {
+    let stats = StatementStats { id, sql: sql.clone(), ..StatementStats::default() };
+    let used = self.tick.fetch_add(1, Ordering::Relaxed);
+    self.statements.insert(id, Statement { sql, actual_id: Some(id), used, stats });
+    self.evict(id)
+  }
+
+  /// unprepares the least recently used statements over capacity except given one
+  fn evict(&self, keep: i64) -> Vec<i64> {
+    let capacity = match self.capacity {
+      Some(capacity) => capacity.max(1),
+      None => return Vec::new(),
+    };
+
+    let mut prepared: Vec<(u64, i64)> = self.statements.iter()
+      .filter(|stmt| stmt.actual_id.is_some() && *stmt.key() != keep)
+      .map(|stmt| (stmt.used, *stmt.key()))
+      .collect();
+    prepared.sort_unstable();
+
+    let excess = (prepared.len() + 1).saturating_sub(capacity);
+    let evicted: Vec<i64> = prepared.into_iter()
+      .take(excess)
+      .filter_map(|(_, id)| self.statements.get_mut(&id)?.actual_id.take())
+      .collect();
+
+    self.evictions.fetch_add(evicted.len() as u64, Ordering::Relaxed);
+    evicted
+  }
+
+  /// forgets statement, returns its id to unprepare on server, unknown id is returned as is
+  pub(crate) fn remove(&self, id: i64) -> Option<i64> {
+    match self.statements.remove(&id) {
+      Some((_, stmt)) => stmt.actual_id,
+      None => Some(id),
+    }
+  }
+
+  /**
+    returns id which should be sent to server instead of user one, it counts hit or miss,
+    none means that statement was evicted and it should be prepared again
+  */
+  pub(crate) fn resolve(&self, id: i64) -> Option<i64> {
+    match self.statements.get_mut(&id) {
+      Some(mut stmt) => {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        stmt.used = self.tick.fetch_add(1, Ordering::Relaxed);
+        stmt.actual_id
+      },
+      None => {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        Some(id)
+      },
+    }
+  }
+
+  pub(crate) fn sql(&self, id: i64) -> Option<String> {
+    self.statements.get(&id)
+      .map(|stmt| stmt.sql.clone())
+  }
+
+  /**
+    sets id of statement prepared again after it was expired or evicted,
+    returns server ids of statements evicted over capacity
+  */
+  pub(crate) fn update(&self, id: i64, actual_id: i64) -> Vec<i64> {
+    match self.statements.get_mut(&id) {
+      Some(mut stmt) => {
+        stmt.actual_id = Some(actual_id);
+        self.reprepares.fetch_add(1, Ordering::Relaxed);
+      },
+      None => return Vec::new(),
+    }
+    self.evict(id)
+  }
+
+  pub(crate) fn record(&self, id: i64, latency: Duration, failed: bool) {
+    if let Some(mut stmt) = self.statements.get_mut(&id) {
+      let stats = &mut stmt.stats;
+      stats.executions += 1;
+      stats.errors += failed as u64;
+      stats.total_latency += latency;
+      stats.max_latency = stats.max_latency.max(latency);
+    }
+  }
+
+  pub(crate) fn stats(&self) -> StatementCacheStats {
+    let mut statements: Vec<StatementStats> = self.statements.iter()
+      .map(|stmt| stmt.stats.clone())
+      .collect();
+    statements.sort_by_key(|stmt| std::cmp::Reverse(stmt.total_latency));
+
+    StatementCacheStats {
+      hits: self.hits.load(Ordering::Relaxed),
+      misses: self.misses.load(Ordering::Relaxed),
+      evictions: self.evictions.load(Ordering::Relaxed),
+      reprepares: self.reprepares.load(Ordering::Relaxed),
+      statements,
+    }
+  }
+
+  /**
+    checks that error means that server doesn't know statement anymore
+    (it was invalidated by schema change or session restart)
+  */
+  pub(crate) fn is_expired(err: &Error) -> bool {
+    match err.root() {
+      Error::TarantoolError(Code::ErrorWrongQueryID, _) => true,
+      Error::TarantoolError(Code::ErrorSQLExecute, err) =>
+        err.message.contains("expired"),
+      _ => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::iproto::response::TarantoolError;
+
+  use super::*;
+
+  #[test]
+  fn test_statement_cache() {
+    let cache = StatementCache::default();
+    cache.insert(10, "SELECT 1".into());
+
+    assert_eq!(cache.resolve(10), Some(10));
+    assert_eq!(cache.resolve(11), Some(11));
+
+    assert!(cache.update(10, 12).is_empty());
+    assert_eq!(cache.resolve(10), Some(12));
+    assert_eq!(cache.sql(10).as_deref(), Some("SELECT 1"));
+    assert_eq!(cache.sql(12), None);
+
+    let err = |code, message: &str| Error::TarantoolError(code, TarantoolError {
+      message: message.into(), stack: Vec::new(),
+    });
+
+    assert!(StatementCache::is_expired(&err(Code::ErrorWrongQueryID, "")));
+    assert!(StatementCache::is_expired(&err(Code::ErrorSQLExecute, "statement has expired")));
+    assert!(!StatementCache::is_expired(&err(Code::ErrorSQLExecute, "syntax error")));
+
+    cache.insert(20, "SELECT 2".into());
+    cache.record(10, Duration::from_millis(10), false);
+    cache.record(10, Duration::from_millis(30), true);
+    cache.record(20, Duration::from_millis(5), false);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.evictions, stats.reprepares), (2, 1, 0, 1));
+    assert_eq!(stats.statements.len(), 2);
+    assert_eq!(stats.statements[0].sql, "SELECT 1");
+    assert_eq!(stats.statements[0].executions, 2);
+    assert_eq!(stats.statements[0].errors, 1);
+    assert_eq!(stats.statements[0].max_latency, Duration::from_millis(30));
+    assert_eq!(stats.statements[0].mean_latency(), Duration::from_millis(20));
+  }
+
+  #[test]
+  fn test_statement_cache_capacity() {
+    let cache = StatementCache::new(Some(2));
+    assert!(cache.insert(1, "SELECT 1".into()).is_empty());
+    assert!(cache.insert(2, "SELECT 2".into()).is_empty());
+
+    // the first statement is used later than the second one
+    cache.resolve(1);
+    cache.update(2, 20);
+    assert_eq!(cache.insert(3, "SELECT 3".into()), vec![ 20 ]);
+    assert_eq!(cache.resolve(2), None);
+    assert_eq!(cache.sql(2).as_deref(), Some("SELECT 2"));
+
+    // evicted statement is prepared again and evicts the least recently used one
+    assert_eq!(cache.update(2, 21), vec![ 1 ]);
+    assert_eq!(cache.resolve(2), Some(21));
+    assert_eq!(cache.resolve(1), None);
+
+    assert_eq!(cache.remove(1), None);
+    assert_eq!(cache.remove(2), Some(21));
+    assert_eq!(cache.remove(2), Some(2));
+
+    let stats = cache.stats();
+    assert_eq!((stats.evictions, stats.reprepares), (2, 2));
+  }
+}
@@ -0,0 +1,339 @@
+use std::{ops::Deref, sync::atomic::{AtomicBool, Ordering}};
+
+use serde::de::DeserializeOwned;
+
+use crate::iproto::{
+  request::{self, Begin, Call, Delete, Eval, Insert, Replace, Request, Select, Update, Upsert},
+  response::{Response, TupleBody},
+  types::Error,
+};
+
+use super::Connection;
+
+macro_rules! stream_method {
+  ($func:ident, $body:ident) => {
+    pub async fn $func<T>(&self, body: $body) -> Result<T, Error>
+      where T: DeserializeOwned
+    {
+      let (resp, context) = self.conn.perform_in_context(self.tag(request::$func(body))).await?;
+
+      resp.unpack_body::<TupleBody<T>>()
+        .map_err(|err| context.wrap(err))
+    }
+  };
+}
+
+/**
+  This is stream of connection, it is supported by tarantool 2.10+.
+
+  Requests of one stream are processed by tarantool sequentially,
+  requests of different streams and of connection itself are not ordered.
+  Stream may run interactive transaction, it requires memtx mvcc or vinyl.
+
+  Stream belongs to connection it is opened on, so after reconnection
+  its requests fail with Error::ConnectionReset. Transaction which is begun
+  and neither committed nor rolled back is rolled back when stream is dropped.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Begin, Connection, Error, IntoTuple, Update, ops};
+    # async fn f(conn: &Connection, from: u64, to: u64, amount: u64) -> Result<(), Error> {
+    let stream = conn.stream();
+    stream.begin(Begin::default()).await?;
+
+    let _: Vec<(u64, u64)> = stream.update(Update {
+      space_id: 512, index_id: 0, index_base: 0,
+      key: ( from, ).into_tuple(),
+      tuple: ops().subtract(1, amount).build()?,
+    }).await?;
+    let _: Vec<(u64, u64)> = stream.update(Update {
+      space_id: 512, index_id: 0, index_base: 0,
+      key: ( to, ).into_tuple(),
+      tuple: ops().add(1, amount).build()?,
+    }).await?;
+
+    stream.commit().await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug)]
+pub struct Stream<'c> {
+  conn: &'c Connection,
+  id: u64,
+  generation: u64,
+  /// transaction is begun and not finished yet
+  in_transaction: AtomicBool,
+}
+
+impl<'c> Stream<'c> {
+  pub(crate) fn new(conn: &'c Connection) -> Stream<'c> {
+    Stream {
+      conn,
+      id: conn.stream_id.fetch_add(1, Ordering::Relaxed),
+      generation: conn.generation.load(Ordering::SeqCst),
+      in_transaction: AtomicBool::new(false),
+    }
+  }
+
+  pub fn id(&self) -> u64 {
+    self.id
+  }
+
+  /// performs request in stream, stream id set by caller is replaced
+  pub async fn perform(&self, req: Request) -> Result<Response, Error> {
+    self.conn.perform(self.tag(req)).await
+  }
+
+  pub async fn begin(&self, body: Begin) -> Result<(), Error> {
+    self.conn.perform_in_context(self.tag(request::begin(body))).await?;
+    self.in_transaction.store(true, Ordering::SeqCst);
+    Ok(())
+  }
+
+  pub async fn commit(&self) -> Result<(), Error> {
+    self.conn.perform_in_context(self.tag(request::commit())).await?;
+    self.in_transaction.store(false, Ordering::SeqCst);
+    Ok(())
+  }
+
+  pub async fn rollback(&self) -> Result<(), Error> {
+    self.conn.perform_in_context(self.tag(request::rollback())).await?;
+    self.in_transaction.store(false, Ordering::SeqCst);
+    Ok(())
+  }
+
+  stream_method!(select, Select);
+  stream_method!(call, Call);
+  stream_method!(insert, Insert);
+  stream_method!(replace, Replace);
+  stream_method!(update, Update);
+  stream_method!(delete, Delete);
+  stream_method!(eval, Eval);
+
+  pub async fn upsert(&self, body: Upsert) -> Result<(), Error> {
+    self.conn.perform_in_context(self.tag(request::upsert(body))).await?;
+    Ok(())
+  }
+
+  fn tag(&self, mut req: Request) -> Request {
+    req.header.stream_id = Some(self.id);
+    req.header.generation = Some(self.generation);
+    req
+  }
+}
+
+impl Drop for Stream<'_> {
+  fn drop(&mut self) {
+    if self.in_transaction.load(Ordering::SeqCst) && !self.conn.is_closed() {
+      self.conn.send_detached(self.tag(request::rollback()));
+    }
+  }
+}
+
+/**
+  This is interactive transaction, it runs in its own stream.
+
+  It is finished by commit or rollback. Transaction which is dropped
+  unfinished is rolled back, rollback is sent without waiting for its response.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Begin, Connection, Error, Insert, IntoTuple};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let tx = conn.transaction(Begin { timeout: Some(5.0), ..Default::default() }).await?;
+
+    let _: Vec<(u64, String)> = tx.insert(Insert {
+      space_id: 512, tuple: ( 1u64, "one" ).into_tuple(),
+    }).await?;
+
+    tx.commit().await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug)]
+pub struct Transaction<'c> {
+  stream: Stream<'c>,
+}
+
+impl<'c> Transaction<'c> {
+  pub async fn commit(self) -> Result<(), Error> {
+    self.stream.commit().await
+  }
+
+  pub async fn rollback(self) -> Result<(), Error> {
+    self.stream.rollback().await
+  }
+}
+
+impl<'c> Deref for Transaction<'c> {
+  type Target = Stream<'c>;
+
+  fn deref(&self) -> &Stream<'c> {
+    &self.stream
+  }
+}
+
+impl Connection {
+  /// creates stream with new id, stream itself doesn't send anything
+  pub fn stream(&self) -> Stream<'_> {
+    Stream::new(self)
+  }
+
+  /// begins transaction in new stream
+  pub async fn transaction(&self, body: Begin) -> Result<Transaction<'_>, Error> {
+    let stream = self.stream();
+    stream.begin(body).await?;
+
+    Ok(Transaction { stream })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::Cursor,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+  };
+
+  use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+
+  use crate::{
+    Connector,
+    connection::{
+      reconnect::ReconnectPolicy,
+      transport::{self, tests::{fake_connection, fake_connector, fake_stream}},
+    },
+    iproto::constants::{Field, RequestType},
+  };
+
+  use super::*;
+
+  /// stream to fake server which keeps bytes written by connection
+  struct Recorded(DuplexStream, Arc<Mutex<Vec<u8>>>);
+
+  impl AsyncRead for Recorded {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+      Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+  }
+
+  impl AsyncWrite for Recorded {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+      let written = Pin::new(&mut self.0).poll_write(cx, buf);
+      if let Poll::Ready(Ok(n)) = written {
+        self.1.lock().unwrap().extend_from_slice(&buf[..n]);
+      }
+      written
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+      Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+      Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+  }
+
+  #[derive(Debug)]
+  struct RecordedTransport(Arc<Mutex<Vec<u8>>>);
+
+  #[async_trait::async_trait]
+  impl transport::TransportConnector for RecordedTransport {
+    async fn connect(&self, _addr: SocketAddr) -> std::io::Result<transport::BoxedTransport> {
+      Ok(Box::new(Recorded(fake_stream(), self.0.clone())))
+    }
+  }
+
+  /// types and stream ids of written requests
+  fn requests(written: &[u8]) -> Vec<(u64, Option<u64>)> {
+    let mut cur = Cursor::new(written);
+    let mut requests = Vec::new();
+    while (cur.position() as usize) < written.len() {
+      let size: u64 = rmp::decode::read_int(&mut cur).unwrap();
+      let end = cur.position() + size;
+      let header = rmpv::decode::read_value(&mut cur).unwrap();
+      let field = |field: Field| header.as_map().unwrap().iter()
+        .find(|(key, _)| key.as_u64() == Some(field as u64))
+        .and_then(|(_, value)| value.as_u64());
+      requests.push((field(Field::RequestType).unwrap(), field(Field::StreamID)));
+      cur.set_position(end);
+    }
+    requests
+  }
+
+  #[tokio::test]
+  async fn test_stream() {
+    let conn = fake_connection().await;
+
+    let (first, second) = (conn.stream(), conn.stream());
+    assert_ne!(first.id(), second.id());
+    assert_eq!(first.tag(request::ping()).header.stream_id, Some(first.id()));
+
+    let tx = conn.transaction(Begin::default()).await.unwrap();
+    assert!(tx.id() > second.id());
+    tx.perform(request::ping()).await.unwrap();
+    tx.commit().await.unwrap();
+
+    // eval is forbidden by fake server, error keeps request context
+    let err = second.eval::<()>(Eval { expr: "return".into(), args: Vec::new() }).await.unwrap_err();
+    assert!(err.context().is_some());
+    second.rollback().await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_rollback_on_drop() {
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let conn = Connector::new("127.0.0.1:3301".parse().unwrap())
+      .with_transport(RecordedTransport(written.clone()))
+      .connect().await.unwrap();
+    let last = |n: usize| {
+      let requests = requests(&written.lock().unwrap());
+      requests[requests.len() - n..].to_vec()
+    };
+
+    // rollback is queued before ping, so it is written once ping is answered
+    let tx = conn.transaction(Begin::default()).await.unwrap();
+    let id = tx.id();
+    drop(tx);
+    conn.ping().await.unwrap();
+    assert_eq!(last(2), vec![
+      (RequestType::Rollback as u64, Some(id)), (RequestType::Ping as u64, None),
+    ]);
+
+    let tx = conn.transaction(Begin::default()).await.unwrap();
+    let id = tx.id();
+    tx.commit().await.unwrap();
+    conn.ping().await.unwrap();
+    assert_eq!(last(2), vec![
+      (RequestType::Commit as u64, Some(id)), (RequestType::Ping as u64, None),
+    ]);
+
+    // stream which didn't begin transaction sends nothing
+    drop(conn.stream());
+    conn.ping().await.unwrap();
+    assert_eq!(last(2), vec![ (RequestType::Ping as u64, None); 2 ]);
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_stream_after_reconnect() {
+    let conn = fake_connector(2)
+      .with_reconnect_policy(ReconnectPolicy::constant(Duration::from_millis(1)))
+      .connect().await.unwrap();
+    let stream = conn.stream();
+    stream.perform(request::ping()).await.unwrap();
+
+    let reset = stream.call::<()>(Call { function: "reset".into(), args: Vec::new() }).await;
+    assert!(matches!(reset.unwrap_err().root(), Error::ConnectionReset));
+    while conn.ping().await.is_err() {}
+
+    // stream id is unknown to new connection
+    let err = stream.perform(request::ping()).await.unwrap_err();
+    assert!(matches!(err.root(), Error::ConnectionReset));
+    conn.stream().perform(request::ping()).await.unwrap();
+  }
+}
@@ -0,0 +1,160 @@
+use std::net::SocketAddr;
+
+use opentelemetry::{
+  Context, KeyValue, global,
+  trace::{SpanKind, Status, TraceContextExt, Tracer},
+};
+
+use crate::iproto::{request::Request, response::Response, types::Error};
+
+use super::labels::Labels;
+
+/**
+  This sets how trace context is passed to tarantool,
+  so lua code may correlate its logs with client span.
+
+  Context is passed in w3c traceparent format.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TracePropagation {
+  /// context is not passed
+  #[default]
+  None,
+  /// traceparent is appended as last argument of call and eval
+  CallArgument,
+  /// sql text is prefixed with /*traceparent='...'*/ comment
+  SqlComment,
+}
+
+/// starts client span for request and injects trace context into it, labels are added as attributes
+pub(crate) fn start(
+  req: &mut Request, addr: SocketAddr, labels: &Labels, propagation: TracePropagation,
+) -> Context {
+  let operation = format!("{:?}", req.header.request).to_uppercase();
+
+  let tracer = global::tracer("alopecosa");
+  let mut attributes = vec![
+    KeyValue::new("db.system", "tarantool"),
+    KeyValue::new("db.operation", operation.clone()),
+    KeyValue::new("net.peer.name", addr.ip().to_string()),
+    KeyValue::new("net.peer.port", addr.port() as i64),
+  ];
+  attributes.extend(labels.iter()
+    .map(|(key, value)| KeyValue::new(key.to_string(), value.to_string())));
+
+  let span = tracer.span_builder(operation)
+    .with_kind(SpanKind::Client)
+    .with_attributes(attributes)
+    .start(&tracer);
+
+  let cx = Context::current_with_span(span);
+
+  if propagation != TracePropagation::None {
+    if let Some(traceparent) = traceparent(&cx) {
+      req.inject_trace(propagation, &traceparent);
+    }
+  }
+
+  cx
+}
+
+/// ends request span, error responses mark span as failed
+pub(crate) fn finish(cx: &Context, resp: &Response) {
+  let span = cx.span();
+
+  if resp.header.code.is_err() {
+    span.set_status(Status::error(format!("{:?}", resp.header.code)));
+  }
+
+  span.end();
+}
+
+/// ends span of retried request after its last attempt
+pub(crate) fn finish_retried(cx: &Context, error: Option<&Error>) {
+  let span = cx.span();
+
+  if let Some(err) = error {
+    span.set_status(Status::error(err.to_string()));
+  }
+
+  span.end();
+}
+
+fn traceparent(cx: &Context) -> Option<String> {
+  let span = cx.span();
+  let span_context = span.span_context();
+
+  if !span_context.is_valid() {
+    return None;
+  }
+
+  Some(format!(
+    "00-{}-{}-{:02x}",
+    span_context.trace_id(),
+    span_context.span_id(),
+    span_context.trace_flags().to_u8(),
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+
+  use crate::iproto::request::{self, Call, Execute, Prepare};
+
+  use super::*;
+
+  fn remote_context() -> Context {
+    Context::new().with_remote_span_context(SpanContext::new(
+      TraceId::from_bytes(1u128.to_be_bytes()), SpanId::from_bytes(2u64.to_be_bytes()),
+      TraceFlags::SAMPLED, true, TraceState::default(),
+    ))
+  }
+
+  #[test]
+  fn test_trace_propagation() {
+    let cx = remote_context();
+
+    let traceparent = traceparent(&cx).unwrap();
+    assert_eq!(traceparent, "00-00000000000000000000000000000001-0000000000000002-01");
+
+    assert_eq!(self::traceparent(&Context::new()), None);
+
+    let mut call = Call { function: "echo".into(), args: Vec::new() };
+    request::Body::inject_trace(&mut call, TracePropagation::CallArgument, &traceparent);
+    assert_eq!(call.args.len(), 1);
+    request::Body::inject_trace(&mut call, TracePropagation::SqlComment, &traceparent);
+    assert_eq!(call.args.len(), 1);
+
+    let mut execute = Execute {
+      expr: Prepare::SQL("SELECT 1".into()),
+      sql_bind: Vec::new(), options: Vec::new(),
+    };
+    request::Body::inject_trace(&mut execute, TracePropagation::SqlComment, &traceparent);
+    assert!(matches!(
+      &execute.expr,
+      Prepare::SQL(sql) if sql == &format!("/*traceparent='{}'*/ SELECT 1", traceparent)
+    ));
+  }
+
+  #[test]
+  fn test_replay_keeps_trace() {
+    let cx = remote_context();
+    let traceparent = traceparent(&cx).unwrap();
+
+    let mut req = request::call(Call { function: "echo".into(), args: Vec::new() });
+    req.inject_trace(TracePropagation::CallArgument, &traceparent);
+    req.header.trace = Some(cx);
+
+    // every attempt carries span of request and injected body
+    let replay = req.replay(None).unwrap();
+    for _ in 0..2 {
+      let attempt = replay.request();
+      assert!(attempt.header.trace.is_some());
+
+      let mut buf: Vec<u8> = Vec::new();
+      attempt.pack(&mut buf).unwrap();
+      assert!(buf.windows(traceparent.len()).any(|window| window == traceparent.as_bytes()));
+    }
+  }
+}
@@ -0,0 +1,334 @@
+use std::{fmt::Debug, io, net::SocketAddr};
+
+use async_trait::async_trait;
+use tokio::{
+  io::{AsyncRead, AsyncWrite},
+  net::TcpSocket,
+};
+
+/// Any bidirectional byte stream may be used as connection transport.
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T> Transport for T
+  where T: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+pub type BoxedTransport = Box<dyn Transport>;
+
+/**
+  This trait opens transport to tarantool,
+  it is called on connect and on every reconnect.
+
+  Example:
+  ```rust,no_run
+    # use std::{io, net::SocketAddr};
+    # use async_trait::async_trait;
+    # use tokio::net::TcpStream;
+    # use alopecosa::{BoxedTransport, Connector, TransportConnector};
+    # mod tokio_native_tls {
+    #   pub struct TlsConnector;
+    #   impl std::fmt::Debug for TlsConnector {
+    #     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { f.write_str("TlsConnector") }
+    #   }
+    #   impl TlsConnector {
+    #     pub async fn connect<S>(&self, _: &str, stream: S) -> Result<S, std::io::Error> { Ok(stream) }
+    #   }
+    # }
+    # async fn f(addr: SocketAddr, connector: tokio_native_tls::TlsConnector) -> io::Result<()> {
+    #[derive(Debug)]
+    struct TlsConnector(tokio_native_tls::TlsConnector);
+
+    #[async_trait]
+    impl TransportConnector for TlsConnector {
+      async fn connect(&self, addr: SocketAddr) -> io::Result<BoxedTransport> {
+        let tcp = TcpStream::connect(addr).await?;
+        let tls = self.0.connect("tarantool.local", tcp).await
+          .map_err(io::Error::other)?;
+        Ok(Box::new(tls))
+      }
+
+      fn is_encrypted(&self) -> bool { true }
+    }
+
+    let conn = Connector::new(addr)
+      .with_transport(TlsConnector(connector))
+      .connect().await?;
+    # Ok(()) }
+  ```
+*/
+#[async_trait]
+pub trait TransportConnector: Debug + Send + Sync {
+  async fn connect(&self, addr: SocketAddr) -> io::Result<BoxedTransport>;
+
+  /// true if transport encrypts traffic, only then pap-sha256 auth is allowed
+  fn is_encrypted(&self) -> bool {
+    false
+  }
+}
+
+/// This is default tcp transport.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpTransport;
+
+#[async_trait]
+impl TransportConnector for TcpTransport {
+  async fn connect(&self, addr: SocketAddr) -> io::Result<BoxedTransport> {
+    let sock = match addr.is_ipv4() {
+      true => TcpSocket::new_v4(),
+      false => TcpSocket::new_v6(),
+    }?;
+
+    Ok(Box::new(sock.connect(addr).await?))
+  }
+}
+
+/**
+  This is tcp transport to host name, address of connector is ignored.
+  Name is resolved on every connect and its addresses are tried in order.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connector, HostTransport};
+    # async fn f() -> std::io::Result<()> {
+    let conn = Connector::new(([ 0, 0, 0, 0 ], 3301).into())
+      .with_transport(HostTransport::new("tarantool.local", 3301))
+      .connect().await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Clone)]
+pub struct HostTransport {
+  host: String,
+  port: u16,
+}
+
+impl HostTransport {
+  pub fn new<S: Into<String>>(host: S, port: u16) -> HostTransport {
+    HostTransport { host: host.into(), port }
+  }
+
+  pub fn host(&self) -> &str {
+    &self.host
+  }
+}
+
+#[async_trait]
+impl TransportConnector for HostTransport {
+  async fn connect(&self, _addr: SocketAddr) -> io::Result<BoxedTransport> {
+    let mut last_err = None;
+    for addr in tokio::net::lookup_host((self.host.as_str(), self.port)).await? {
+      match TcpTransport.connect(addr).await {
+        Ok(conn) => return Ok(conn),
+        Err(err) => last_err = Some(err),
+      }
+    }
+
+    Err(last_err.unwrap_or_else(|| io::Error::new(
+      io::ErrorKind::NotFound, format!("{} has no addresses", self.host),
+    )))
+  }
+}
+
+/**
+  This is transport over unix socket, address of connector is ignored.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connector, connection::transport::UnixTransport};
+    # async fn f() -> std::io::Result<()> {
+    let conn = Connector::new(([ 127, 0, 0, 1 ], 0).into())
+      .with_transport(UnixTransport::new("/var/run/tarantool/app.sock"))
+      .connect().await?;
+    # Ok(()) }
+  ```
+*/
+#[cfg(unix)]
+#[derive(Debug, Clone)]
+pub struct UnixTransport {
+  path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixTransport {
+  pub fn new<P: Into<std::path::PathBuf>>(path: P) -> UnixTransport {
+    UnixTransport { path: path.into() }
+  }
+
+  pub fn path(&self) -> &std::path::Path {
+    &self.path
+  }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl TransportConnector for UnixTransport {
+  async fn connect(&self, _addr: SocketAddr) -> io::Result<BoxedTransport> {
+    Ok(Box::new(tokio::net::UnixStream::connect(&self.path).await?))
+  }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use std::{io::Cursor, sync::{Arc, Mutex}};
+
+  use rmpv::{Value, decode::read_value};
+  use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, duplex};
+
+  use crate::{
+    connection::{Connection, connector::Connector},
+    iproto::constants::{Code, RequestType},
+  };
+
+  use super::*;
+
+  /// hands out prepared in-memory streams
+  #[derive(Debug)]
+  pub(crate) struct DuplexTransport(pub(crate) Mutex<Vec<DuplexStream>>);
+
+  #[async_trait]
+  impl TransportConnector for DuplexTransport {
+    async fn connect(&self, _addr: SocketAddr) -> io::Result<BoxedTransport> {
+      self.0.lock().unwrap().pop()
+        .map(|stream| Box::new(stream) as BoxedTransport)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::ConnectionRefused, "no streams"))
+    }
+  }
+
+  /**
+    answers to eval with error, to legacy call with its arguments packed as tuples
+    and to other requests with empty successful response,
+    watch is answered with event which data is number of watch request up to three,
+    call of "reset" function closes connection, call of "sleep" is answered after 50ms
+    and call of "hang" is not answered
+  */
+  async fn fake_tarantool(mut stream: DuplexStream) {
+    let mut greeting = [b' '; 128];
+    greeting[..30].copy_from_slice(b"Tarantool 2.10.0 (Binary) uuid");
+    greeting[63] = b'\n';
+    greeting[64..108].copy_from_slice(&[b'A'; 44]);
+    stream.write_all(&greeting).await.unwrap();
+
+    let mut buf = vec![0u8; 4096];
+    let mut watches = 0u64;
+    loop {
+      let n = match stream.read(&mut buf).await {
+        Ok(0) | Err(_) => return,
+        Ok(n) => n,
+      };
+
+      let mut cur = Cursor::new(&buf[..n]);
+      while (cur.position() as usize) < n {
+        let size = read_value(&mut cur).unwrap().as_u64().unwrap();
+        let end = cur.position() + size;
+
+        let header = read_value(&mut cur).unwrap();
+        let field = |key| header.as_map().unwrap().iter()
+          .find(|(k, _)| k.as_u64() == Some(key))
+          .and_then(|(_, v)| v.as_u64())
+          .unwrap();
+        let (code, sync) = (field(0), field(1));
+        let body = match cur.position() < end {
+          true => read_value(&mut cur).unwrap(),
+          false => Value::Nil,
+        };
+        cur.set_position(end);
+
+        let function = body.as_map()
+          .and_then(|body| body.iter().find(|(k, _)| k.as_u64() == Some(0x22)))
+          .and_then(|(_, v)| v.as_str());
+        if code == RequestType::Call as u64 && function == Some("reset") {
+          return;
+        }
+        if code == RequestType::Call as u64 && function == Some("hang") {
+          continue;
+        }
+        if code == RequestType::Call as u64 && function == Some("sleep") {
+          tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+
+        let mut resp: Vec<u8> = Vec::new();
+        if code == RequestType::Unwatch as u64 || (code == RequestType::Watch as u64 && watches == 3) {
+          continue;
+        } else if code == RequestType::Watch as u64 {
+          watches += 1;
+          rmpv::encode::write_value(&mut resp, &Value::Map(vec![
+            (0.into(), (RequestType::Event as u64).into()), (1.into(), 0.into()),
+          ])).unwrap();
+          rmpv::encode::write_value(&mut resp, &Value::Map(vec![
+            (0x57.into(), body.as_map().unwrap()[0].1.clone()), (0x58.into(), watches.into()),
+          ])).unwrap();
+        } else if code == RequestType::Call16 as u64 {
+          let args = body.as_map().unwrap().iter()
+            .find(|(k, _)| k.as_u64() == Some(0x21))
+            .and_then(|(_, v)| v.as_array().cloned())
+            .unwrap();
+          rmpv::encode::write_value(&mut resp, &Value::Map(vec![
+            (0.into(), 0.into()), (1.into(), sync.into()), (5.into(), 1.into()),
+          ])).unwrap();
+          rmpv::encode::write_value(&mut resp, &Value::Map(vec![
+            (0x30.into(), Value::Array(args.into_iter().map(|arg| Value::Array(vec![ arg ])).collect())),
+          ])).unwrap();
+        } else if code == RequestType::Eval as u64 {
+          rmpv::encode::write_value(&mut resp, &Value::Map(vec![
+            (0.into(), (Code::ErrorIllegalParams as u64).into()),
+            (1.into(), sync.into()), (5.into(), 1.into()),
+          ])).unwrap();
+          rmpv::encode::write_value(&mut resp, &Value::Map(vec![
+            (0x31.into(), "eval is forbidden".into()),
+          ])).unwrap();
+        } else {
+          rmpv::encode::write_value(&mut resp, &Value::Map(vec![
+            (0.into(), 0.into()), (1.into(), sync.into()), (5.into(), 1.into()),
+          ])).unwrap();
+        }
+
+        let mut frame: Vec<u8> = Vec::new();
+        rmp::encode::write_u32(&mut frame, resp.len() as u32).unwrap();
+        frame.extend(resp);
+        stream.write_all(&frame).await.unwrap();
+      }
+    }
+  }
+
+  /// connection to in-memory fake server
+  pub(crate) async fn fake_connection() -> Arc<Connection> {
+    fake_connector(1).connect().await.unwrap()
+  }
+
+  /// in-memory stream to fake server
+  pub(crate) fn fake_stream() -> DuplexStream {
+    let (client, server) = duplex(4096);
+    tokio::spawn(fake_tarantool(server));
+    client
+  }
+
+  /// connector which may open given number of connections to fake servers
+  pub(crate) fn fake_connector(connections: usize) -> Connector {
+    let clients = (0..connections)
+      .map(|_| fake_stream())
+      .collect();
+
+    Connector::new("127.0.0.1:3301".parse().unwrap())
+      .with_transport(DuplexTransport(Mutex::new(clients)))
+  }
+
+  #[tokio::test]
+  async fn test_custom_transport() {
+    let conn = fake_connection().await;
+
+    assert_eq!(conn.tarantool_version(), "2.10.0");
+    conn.ping().await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_host_transport() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let transport = HostTransport::new("localhost", port);
+    let (conn, accepted) = tokio::join!(transport.connect(([ 0, 0, 0, 0 ], 0).into()), listener.accept());
+    conn.unwrap();
+    accepted.unwrap();
+
+    assert!(HostTransport::new("", port).connect(([ 0, 0, 0, 0 ], 0).into()).await.is_err());
+  }
+}
@@ -0,0 +1,253 @@
+use std::{
+  cell::{Cell, RefCell},
+  collections::HashMap,
+  io,
+  net::SocketAddr,
+  rc::Rc,
+};
+
+use tokio::sync::{Mutex, oneshot};
+use tokio_uring::net::TcpStream;
+
+use crate::iproto::{request::{self, Request}, response::Response, types::Error};
+
+use super::{Connection, raw::{RawConnection, RawEvent}};
+
+const READ_BUF_SIZE: usize = 64 * 1024;
+
+type Pending = Rc<RefCell<HashMap<u64, oneshot::Sender<Response>>>>;
+
+/**
+  This is connection over io_uring, it runs on tokio-uring runtime.
+
+  Runtime is single threaded, so connection is used through Rc.
+  Protocol is handled by RawConnection, reconnection is not supported,
+  connection fails all pending and following requests once socket is broken.
+
+  Example:
+  ```rust,no_run
+    # use std::net::SocketAddr;
+    # use alopecosa::{Error, Select, TupleBody, UringConnection, request};
+    # fn f(addr: SocketAddr, select: Select) -> Result<(), Error> {
+    tokio_uring::start(async {
+      let conn = UringConnection::connect(addr, Some(("user".into(), "password".into()))).await?;
+
+      let resp = conn.perform(request::select(select)).await?;
+      let tuples: Vec<(u64, String)> = resp.unpack_body::<TupleBody<_>>()?;
+    # Ok(())
+    })
+    # }
+  ```
+*/
+pub struct UringConnection {
+  stream: Rc<TcpStream>,
+  raw: Rc<RefCell<RawConnection>>,
+  pending: Pending,
+  closed: Rc<Cell<bool>>,
+  write_lock: Mutex<()>,
+  version: String,
+}
+
+impl UringConnection {
+  /// connects, performs greeting and auth and spawns reader on current runtime
+  pub async fn connect(
+    addr: SocketAddr, credentials: Option<(String, String)>,
+  ) -> Result<Rc<UringConnection>, io::Error> {
+    let stream = Rc::new(TcpStream::connect(addr).await?);
+
+    let mut raw = RawConnection::new();
+    if let Some((user, password)) = credentials {
+      raw = raw.with_auth(user, password);
+    }
+
+    let mut buf = vec![ 0u8; READ_BUF_SIZE ];
+    while !raw.is_ready() {
+      let (read, returned) = stream.read(buf).await;
+      buf = returned;
+
+      match read? {
+        0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+        n => raw.feed(&buf[..n]).map_err(into_io_error)?,
+      }
+
+      let output = raw.take_output();
+      if !output.is_empty() {
+        stream.write_all(output).await.0?;
+      }
+    }
+    while raw.poll_event().is_some() {}
+
+    let conn = Rc::new(UringConnection {
+      version: raw.version().unwrap_or_default().into(),
+      stream, raw: Rc::new(RefCell::new(raw)),
+      pending: Pending::default(),
+      closed: Rc::new(Cell::new(false)),
+      write_lock: Mutex::new(()),
+    });
+
+    tokio_uring::spawn(Self::reader(
+      conn.stream.clone(), conn.raw.clone(), conn.pending.clone(), conn.closed.clone(), buf,
+    ));
+
+    Ok(conn)
+  }
+
+  pub fn tarantool_version(&self) -> &str {
+    &self.version
+  }
+
+  pub fn is_closed(&self) -> bool {
+    self.closed.get()
+  }
+
+  pub async fn perform(&self, req: Request) -> Result<Response, Error> {
+    if self.closed.get() {
+      return Err(io::Error::from(io::ErrorKind::NotConnected).into());
+    }
+
+    let (sync, output) = {
+      let mut raw = self.raw.borrow_mut();
+      let sync = raw.send(req)?;
+      (sync, raw.take_output())
+    };
+
+    let (sender, receiver) = oneshot::channel();
+    self.pending.borrow_mut().insert(sync, sender);
+
+    {
+      let _lock = self.write_lock.lock().await;
+      if let (Err(err), _) = self.stream.write_all(output).await {
+        self.pending.borrow_mut().remove(&sync);
+        return Err(err.into());
+      }
+    }
+
+    let resp = receiver.await
+      .map_err(|_| io::Error::from(io::ErrorKind::ConnectionAborted))?;
+
+    Connection::check_response(resp)
+  }
+
+  pub async fn ping(&self) -> Result<(), Error> {
+    self.perform(request::ping()).await?;
+    Ok(())
+  }
+
+  async fn reader(
+    stream: Rc<TcpStream>, raw: Rc<RefCell<RawConnection>>,
+    pending: Pending, closed: Rc<Cell<bool>>, mut buf: Vec<u8>,
+  ) {
+    loop {
+      let (read, returned) = stream.read(buf).await;
+      buf = returned;
+
+      let fed = match read {
+        Ok(0) => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        Ok(n) => raw.borrow_mut().feed(&buf[..n]),
+        Err(err) => Err(err.into()),
+      };
+
+      if let Err(err) = fed {
+        log::error!("uring connection is broken: {}", err);
+        break;
+      }
+
+      while let Some(event) = raw.borrow_mut().poll_event() {
+        if let RawEvent::Response(resp) = event {
+          if let Some(sender) = pending.borrow_mut().remove(&resp.header.sync) {
+            let _ = sender.send(resp);
+          }
+        }
+      }
+    }
+
+    // waiters of dropped senders get ConnectionAborted
+    closed.set(true);
+    pending.borrow_mut().clear();
+  }
+}
+
+impl Drop for UringConnection {
+  fn drop(&mut self) {
+    let _ = self.stream.shutdown(std::net::Shutdown::Both);
+  }
+}
+
+fn into_io_error(err: Error) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::net::TcpListener;
+
+  use crate::{
+    connection::transport::tests::fake_stream,
+    iproto::{request::Call, response::TupleBody},
+  };
+
+  use super::*;
+
+  /// address of tcp proxy to fake server, it runs on its own thread with tokio runtime
+  fn fake_server() -> SocketAddr {
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+      let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+      runtime.block_on(async move {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        sender.send(listener.local_addr().unwrap()).unwrap();
+
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let _ = tokio::io::copy_bidirectional(&mut socket, &mut fake_stream()).await;
+      });
+    });
+
+    receiver.recv().unwrap()
+  }
+
+  fn call(function: &str, args: Vec<request::Value>) -> Request {
+    request::call16(Call { function: function.into(), args }.into())
+  }
+
+  #[test]
+  fn test_uring_connect() {
+    let addr = fake_server();
+
+    tokio_uring::start(async move {
+      let conn = UringConnection::connect(addr, None).await.unwrap();
+      assert_eq!(conn.tarantool_version(), "2.10.0");
+      assert!(!conn.is_closed());
+    });
+  }
+
+  #[test]
+  fn test_uring_round_trip() {
+    let addr = fake_server();
+
+    tokio_uring::start(async move {
+      let conn = UringConnection::connect(addr, None).await.unwrap();
+
+      conn.ping().await.unwrap();
+      // fake server answers legacy call with its arguments
+      let resp = conn.perform(call("echo", vec![ 1u64.into(), 2u64.into() ])).await.unwrap();
+      let tuples: Vec<(u64,)> = resp.unpack_body::<TupleBody<_>>().unwrap();
+      assert_eq!(tuples, vec![ (1,), (2,) ]);
+    });
+  }
+
+  #[test]
+  fn test_uring_close() {
+    let addr = fake_server();
+
+    tokio_uring::start(async move {
+      let conn = UringConnection::connect(addr, None).await.unwrap();
+
+      // fake server closes connection on reset
+      let reset = Call { function: "reset".into(), args: Vec::new() };
+      assert!(conn.perform(request::call(reset)).await.is_err());
+      assert!(conn.is_closed());
+      assert!(conn.ping().await.is_err());
+    });
+  }
+}
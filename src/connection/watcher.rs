@@ -0,0 +1,424 @@
+use std::{
+  collections::HashMap,
+  marker::PhantomData,
+  pin::Pin,
+  sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+  task::{Context, Poll, Waker},
+};
+
+use futures_core::Stream;
+use rmpv::Value as MsgValue;
+use serde::de::DeserializeOwned;
+use tokio::sync::mpsc;
+
+use crate::iproto::{
+  constants::Field,
+  request::{self, Request, Unwatch, Watch},
+  response::Response,
+  types::Error,
+};
+
+use super::Connection;
+
+/// the newest value which is not read by subscriber yet, older ones are overwritten
+#[derive(Debug, Default)]
+struct Slot {
+  value: Option<MsgValue>,
+  waker: Option<Waker>,
+  /// stream is dropped
+  closed: bool,
+}
+
+impl Slot {
+  fn put(&mut self, value: MsgValue) {
+    self.value = Some(value);
+    if let Some(waker) = self.waker.take() {
+      waker.wake();
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+struct WatchedKey {
+  /// value of the last event, it is sent to new subscribers
+  last: Option<MsgValue>,
+  subscribers: Vec<Arc<Mutex<Slot>>>,
+}
+
+/**
+  This is registry of watched keys of connection.
+
+  Every key is watched once regardless of number of subscribers.
+  Events are acknowledged as soon as they are dispatched,
+  keys are watched again after reconnection.
+*/
+#[derive(Debug)]
+pub(crate) struct Watchers {
+  keys: Mutex<HashMap<String, WatchedKey>>,
+  /// watch and unwatch requests which are written by connection server
+  requests: mpsc::UnboundedSender<Request>,
+  /// connection server is gone, streams end once their values are read
+  closed: AtomicBool,
+}
+
+impl Watchers {
+  pub(crate) fn new() -> (Arc<Watchers>, mpsc::UnboundedReceiver<Request>) {
+    let (requests, receiver) = mpsc::unbounded_channel();
+    let watchers = Watchers { keys: Mutex::default(), requests, closed: AtomicBool::new(false) };
+    (Arc::new(watchers), receiver)
+  }
+
+  fn subscribe(self: &Arc<Self>, key: &str) -> WatchStream {
+    let slot = Arc::new(Mutex::new(Slot::default()));
+
+    let mut keys = self.keys.lock().unwrap();
+    let watched = keys.entry(key.into()).or_insert_with(|| {
+      self.send(request::watch(Watch { key: key.into() }));
+      WatchedKey::default()
+    });
+
+    slot.lock().unwrap().value = watched.last.clone();
+    watched.subscribers.push(slot.clone());
+
+    WatchStream { key: key.into(), slot, watchers: self.clone() }
+  }
+
+  /// unwatches key if it has no subscribers left
+  fn release(&self, key: &str) {
+    let mut keys = self.keys.lock().unwrap();
+    if let Some(watched) = keys.get_mut(key) {
+      watched.subscribers.retain(|subscriber| !subscriber.lock().unwrap().closed);
+      if watched.subscribers.is_empty() {
+        keys.remove(key);
+        self.send(request::unwatch(Unwatch { key: key.into() }));
+      }
+    }
+  }
+
+  /// passes event to subscribers of its key and acknowledges it
+  pub(crate) fn dispatch(&self, event: &Response) -> Result<(), Error> {
+    let body = match &event.body {
+      Some(body) => rmpv::decode::read_value(&mut &body[..])?,
+      None => return Err(Error::UnexpectedValue(Field::EventKey)),
+    };
+
+    let field = |field: Field| body.as_map()
+      .and_then(|fields| fields.iter().find(|(key, _)| key.as_u64() == Some(field as u64)))
+      .map(|(_, value)| value);
+
+    let key = field(Field::EventKey)
+      .and_then(MsgValue::as_str)
+      .ok_or(Error::UnexpectedValue(Field::EventKey))?;
+    let data = field(Field::EventData).cloned().unwrap_or(MsgValue::Nil);
+
+    let mut keys = self.keys.lock().unwrap();
+    // key is unwatched already
+    let watched = match keys.get_mut(key) {
+      Some(watched) => watched,
+      None => return Ok(()),
+    };
+
+    watched.subscribers.retain(|subscriber| {
+      let mut slot = subscriber.lock().unwrap();
+      if !slot.closed {
+        slot.put(data.clone());
+      }
+      !slot.closed
+    });
+    watched.last = Some(data);
+
+    match watched.subscribers.is_empty() {
+      true => {
+        keys.remove(key);
+        self.send(request::unwatch(Unwatch { key: key.into() }));
+      },
+      false => self.send(request::watch(Watch { key: key.into() })),
+    }
+
+    Ok(())
+  }
+
+  /// server forgets watchers on disconnect, so they are sent again
+  pub(crate) fn rewatch(&self) {
+    for key in self.keys.lock().unwrap().keys() {
+      self.send(request::watch(Watch { key: key.clone() }));
+    }
+  }
+
+  /// ends streams of all keys, it is called by connection server on shutdown
+  pub(crate) fn close(&self) {
+    self.closed.store(true, Ordering::SeqCst);
+    for watched in self.keys.lock().unwrap().values() {
+      for subscriber in &watched.subscribers {
+        if let Some(waker) = subscriber.lock().unwrap().waker.take() {
+          waker.wake();
+        }
+      }
+    }
+  }
+
+  fn send(&self, req: Request) {
+    // receiver is gone only with connection server
+    let _ = self.requests.send(req);
+  }
+}
+
+/**
+  This is stream of values broadcast by tarantool with box.broadcast.
+
+  The current value of key comes first, then it comes on every change.
+  If value changes faster than stream is read, only the newest value is kept,
+  so slow reader skips intermediate values instead of buffering them.
+  Key is unwatched when the last stream of it is dropped.
+  Stream ends when connection is closed.
+
+  Example:
+  ```rust,no_run
+    # use futures_util::StreamExt;
+    # use alopecosa::{Connection, Error, ValuePath};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let mut status = conn.watch("box.status");
+    while let Some(status) = status.next().await {
+      let is_ro = status.get_path_as::<bool>("is_ro")?;
+    }
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug)]
+pub struct WatchStream {
+  key: String,
+  slot: Arc<Mutex<Slot>>,
+  watchers: Arc<Watchers>,
+}
+
+impl WatchStream {
+  pub fn key(&self) -> &str {
+    &self.key
+  }
+}
+
+impl Stream for WatchStream {
+  type Item = MsgValue;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<MsgValue>> {
+    let mut slot = self.slot.lock().unwrap();
+    match slot.value.take() {
+      Some(value) => Poll::Ready(Some(value)),
+      None if self.watchers.closed.load(Ordering::SeqCst) => Poll::Ready(None),
+      None => {
+        slot.waker = Some(cx.waker().clone());
+        Poll::Pending
+      },
+    }
+  }
+}
+
+impl Drop for WatchStream {
+  fn drop(&mut self) {
+    self.slot.lock().unwrap().closed = true;
+    self.watchers.release(&self.key);
+  }
+}
+
+impl Connection {
+  /// watches key, it is supported by tarantool 2.10+
+  pub fn watch(&self, key: &str) -> WatchStream {
+    self.watchers.subscribe(key)
+  }
+
+  /// watches key and decodes its values into T, see TypedEvents
+  pub fn watch_as<T>(&self, key: &str) -> TypedEvents<WatchStream, T>
+    where T: DeserializeOwned
+  {
+    TypedEvents::new(self.watch(key))
+  }
+}
+
+/**
+  This is stream of broadcast values decoded into T.
+
+  Value of key which was never broadcast is nil,
+  so T should be Option if key may be missing.
+  Values which can't be decoded are yielded as errors
+  and stream goes on with the next value.
+
+  Example:
+  ```rust,no_run
+    # use futures_util::StreamExt;
+    # use serde::Deserialize;
+    # use alopecosa::Connection;
+    # fn apply<T>(_: T) {}
+    # async fn f(conn: &Connection) {
+    #[derive(Deserialize)]
+    struct Flags { new_checkout: bool }
+
+    let mut flags = conn.watch_as::<Option<Flags>>("config.flags");
+    while let Some(flags) = flags.next().await {
+      match flags {
+        Ok(flags) => apply(flags),
+        Err(err) => log::warn!("bad config.flags: {}", err),
+      }
+    }
+    # }
+  ```
+*/
+pub struct TypedEvents<S, T> {
+  values: S,
+  _type: PhantomData<fn() -> T>,
+}
+
+impl<S, T> TypedEvents<S, T>
+  where S: Stream<Item = MsgValue> + Unpin,
+        T: DeserializeOwned,
+{
+  pub fn new(values: S) -> TypedEvents<S, T> {
+    TypedEvents { values, _type: PhantomData }
+  }
+
+  pub fn into_inner(self) -> S {
+    self.values
+  }
+}
+
+impl<S, T> Stream for TypedEvents<S, T>
+  where S: Stream<Item = MsgValue> + Unpin,
+        T: DeserializeOwned,
+{
+  type Item = Result<T, Error>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    Pin::new(&mut self.values).poll_next(cx)
+      .map(|value| value.map(|value| decode_event(&value)))
+  }
+}
+
+/// decodes value of broadcast event
+fn decode_event<T>(value: &MsgValue) -> Result<T, Error>
+  where T: DeserializeOwned
+{
+  let mut buf: Vec<u8> = Vec::new();
+  rmpv::encode::write_value(&mut buf, value)?;
+
+  rmp_serde::from_slice(&buf).map_err(Error::ParseError)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::VecDeque, future::poll_fn, time::Duration};
+
+  use serde::Deserialize;
+
+  use crate::{
+    connection::{
+      reconnect::ReconnectPolicy,
+      retry::Backoff,
+      transport::tests::{fake_connection, fake_connector},
+    },
+    iproto::request::Call,
+  };
+
+  use super::*;
+
+  struct Values(VecDeque<MsgValue>);
+
+  impl Stream for Values {
+    type Item = MsgValue;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<MsgValue>> {
+      Poll::Ready(self.0.pop_front())
+    }
+  }
+
+  #[derive(Debug, PartialEq, Deserialize)]
+  struct Flags {
+    new_checkout: bool,
+    limit: u32,
+  }
+
+  #[tokio::test]
+  async fn test_watch() {
+    let conn = fake_connection().await;
+
+    // every event is acknowledged, fake server sends three of them,
+    // values which are not read yet are overwritten by newer ones
+    let mut status = conn.watch("box.status");
+    let mut values: Vec<u64> = Vec::new();
+    while values.last() != Some(&3) {
+      values.push(poll_fn(|cx| Pin::new(&mut status).poll_next(cx)).await.unwrap().as_u64().unwrap());
+    }
+    assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // the last value is sent to new subscriber at once
+    let mut typed = conn.watch_as::<u64>("box.status");
+    let last = poll_fn(|cx| Pin::new(&mut typed).poll_next(cx)).await.unwrap();
+    assert_eq!(last.unwrap(), 3);
+
+    drop(status);
+    assert_eq!(conn.watchers.keys.lock().unwrap().len(), 1);
+    assert_eq!(conn.watchers.keys.lock().unwrap()["box.status"].subscribers.len(), 1);
+    drop(typed);
+    assert!(conn.watchers.keys.lock().unwrap().is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_watch_ends_with_connection() {
+    let conn = fake_connector(1)
+      .with_reconnect_policy(ReconnectPolicy::new()
+        .with_backoff(Backoff::new(1).with_initial(Duration::from_millis(1)).with_jitter(0.0)))
+      .connect().await.unwrap();
+
+    let mut status = conn.watch("box.status");
+    assert!(poll_fn(|cx| Pin::new(&mut status).poll_next(cx)).await.is_some());
+
+    // fake server is gone and there is no server to reconnect to
+    let reset = Call { function: "reset".into(), args: Vec::new() };
+    assert!(conn.call::<()>(reset).await.is_err());
+
+    let ended = tokio::time::timeout(Duration::from_secs(5), async {
+      while poll_fn(|cx| Pin::new(&mut status).poll_next(cx)).await.is_some() {}
+    }).await;
+    assert!(ended.is_ok());
+    assert!(conn.is_closed());
+  }
+
+  #[tokio::test]
+  async fn test_watch_as() {
+    let conn = fake_connection().await;
+
+    // values of fake server are numbers, so they can't be decoded as flags
+    let mut flags = conn.watch_as::<Option<Flags>>("config.flags");
+    let value = poll_fn(|cx| Pin::new(&mut flags).poll_next(cx)).await.unwrap();
+    assert!(matches!(value, Err(Error::ParseError(_))));
+
+    let mut numbers = conn.watch_as::<u64>("config.flags");
+    let value = poll_fn(|cx| Pin::new(&mut numbers).poll_next(cx)).await.unwrap();
+    assert!(value.unwrap() >= 1);
+  }
+
+  #[tokio::test]
+  async fn test_typed_events() {
+    let flags = |new_checkout: bool, limit| MsgValue::Map(vec![
+      ("new_checkout".into(), new_checkout.into()),
+      ("limit".into(), limit),
+    ]);
+
+    let mut events: TypedEvents<_, Option<Flags>> = TypedEvents::new(Values(vec![
+      MsgValue::Nil,
+      flags(true, 10.into()),
+      flags(false, "many".into()),
+      flags(false, 20.into()),
+    ].into()));
+
+    let mut decoded = Vec::new();
+    while let Some(flags) = poll_fn(|cx| Pin::new(&mut events).poll_next(cx)).await {
+      decoded.push(flags.ok());
+    }
+
+    assert_eq!(decoded, vec![
+      Some(None),
+      Some(Some(Flags { new_checkout: true, limit: 10 })),
+      None,
+      Some(Some(Flags { new_checkout: false, limit: 20 })),
+    ]);
+  }
+}
@@ -0,0 +1,164 @@
+use std::{
+  io,
+  net::SocketAddr,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use futures_util::{Sink, Stream, ready};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::{
+  client_async,
+  tungstenite::{self, Message},
+};
+
+use super::transport::{BoxedTransport, TcpTransport, TransportConnector};
+
+/**
+  This transport runs iproto over websocket,
+  every binary message carries iproto packet.
+
+  Tcp connection is opened to connector address,
+  url is used for websocket handshake (host and path).
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connector, WebSocketTransport};
+    # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+    let conn = Connector::new("10.0.0.1:443".parse()?)
+      .with_transport(WebSocketTransport::new("ws://proxy.local/tarantool"))
+      .connect().await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Clone)]
+pub struct WebSocketTransport {
+  url: String,
+}
+
+impl WebSocketTransport {
+  pub fn new<S: Into<String>>(url: S) -> WebSocketTransport {
+    WebSocketTransport { url: url.into() }
+  }
+}
+
+#[async_trait]
+impl TransportConnector for WebSocketTransport {
+  async fn connect(&self, addr: SocketAddr) -> io::Result<BoxedTransport> {
+    let tcp = TcpTransport.connect(addr).await?;
+    let (ws, _) = client_async(self.url.as_str(), tcp).await
+      .map_err(into_io_error)?;
+
+    Ok(Box::new(WebSocketStream::new(ws)))
+  }
+}
+
+/// Adapts websocket messages to byte stream.
+pub struct WebSocketStream<S> {
+  inner: tokio_tungstenite::WebSocketStream<S>,
+  read_buf: Vec<u8>,
+  read_pos: usize,
+}
+
+impl<S> WebSocketStream<S> {
+  pub fn new(inner: tokio_tungstenite::WebSocketStream<S>) -> WebSocketStream<S> {
+    WebSocketStream { inner, read_buf: Vec::new(), read_pos: 0 }
+  }
+}
+
+impl<S> AsyncRead for WebSocketStream<S>
+  where S: AsyncRead + AsyncWrite + Unpin
+{
+  fn poll_read(
+    mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+  ) -> Poll<io::Result<()>> {
+    loop {
+      if self.read_pos < self.read_buf.len() {
+        let len = buf.remaining().min(self.read_buf.len() - self.read_pos);
+        buf.put_slice(&self.read_buf[self.read_pos..self.read_pos + len]);
+        self.read_pos += len;
+        return Poll::Ready(Ok(()));
+      }
+
+      match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+        Some(Ok(Message::Binary(data))) => {
+          self.read_buf = data;
+          self.read_pos = 0;
+        },
+        // eof
+        Some(Ok(Message::Close(_))) | None => return Poll::Ready(Ok(())),
+        // pings are answered by tungstenite itself
+        Some(Ok(Message::Text(_))) => log::warn!("skipping unexpected text message"),
+        Some(Ok(_)) => {},
+        Some(Err(err)) => return Poll::Ready(Err(into_io_error(err))),
+      }
+    }
+  }
+}
+
+impl<S> AsyncWrite for WebSocketStream<S>
+  where S: AsyncRead + AsyncWrite + Unpin
+{
+  fn poll_write(
+    mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    ready!(Pin::new(&mut self.inner).poll_ready(cx)).map_err(into_io_error)?;
+    Pin::new(&mut self.inner).start_send(Message::Binary(buf.to_vec()))
+      .map_err(into_io_error)?;
+    Poll::Ready(Ok(buf.len()))
+  }
+
+  fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    Pin::new(&mut self.inner).poll_flush(cx).map_err(into_io_error)
+  }
+
+  fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    Pin::new(&mut self.inner).poll_close(cx).map_err(into_io_error)
+  }
+}
+
+fn into_io_error(err: tungstenite::Error) -> io::Error {
+  match err {
+    tungstenite::Error::Io(err) => err,
+    err => io::Error::other(err),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use futures_util::{SinkExt, StreamExt};
+  use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+  use tokio_tungstenite::accept_async;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_websocket_stream() {
+    let (client, server) = duplex(4096);
+
+    tokio::spawn(async move {
+      let mut ws = accept_async(server).await.unwrap();
+      ws.send(Message::Binary(b"greeting".to_vec())).await.unwrap();
+      while let Some(Ok(msg)) = ws.next().await {
+        if msg.is_binary() {
+          ws.send(msg).await.unwrap();
+        }
+      }
+    });
+
+    let (ws, _) = client_async("ws://localhost/", client).await.unwrap();
+    let mut stream = WebSocketStream::new(ws);
+
+    let mut buf = [0u8; 5];
+    stream.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"greet");
+
+    stream.write_all(b"packet").await.unwrap();
+    stream.flush().await.unwrap();
+
+    let mut buf = [0u8; 9];
+    stream.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ingpacket");
+  }
+}
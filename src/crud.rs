@@ -0,0 +1,449 @@
+/*!
+  This module contains client of tarantool/crud module.
+
+  Crud functions return `{metadata, rows}` or nil with error,
+  handle calls them and decodes rows into given type,
+  error of crud is returned as Error::Crud, see CrudError::of.
+
+  Example:
+  ```rust,no_run
+    # use serde::Deserialize;
+    # use alopecosa::{Conditions, Connection, CrudOptions, CrudRows, Error, IntoTuple, TarantoolClient, Value};
+    # #[derive(Deserialize)]
+    # struct User { id: u64, bucket_id: Option<u64>, name: String, age: u64 }
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let users = conn.crud("users");
+
+    let _: Vec<User> = users.insert(( 1u64, Value::Null, "ann", 30u64 ).into_tuple(), CrudOptions::new()).await?;
+
+    let adults: Vec<User> = users.select(
+      Conditions::new().ge("age", 18u64).eq("name", "ann"),
+      CrudOptions::new().with_first(10),
+    ).await?;
+
+    let page: CrudRows<User> = users.select_rows(Conditions::new(), CrudOptions::new().with_first(100)).await?;
+    let fields: Vec<&str> = page.metadata.iter().map(|field| field.name.as_str()).collect();
+    # Ok(()) }
+  ```
+*/
+
+use std::{fmt, marker::PhantomData, time::Duration};
+
+use serde::{Deserialize, Deserializer, de::{DeserializeOwned, IgnoredAny, SeqAccess, Visitor}};
+
+use crate::{
+  client::TarantoolClient,
+  iproto::{
+    request::{Call, IntoKey, Value},
+    types::Error,
+  },
+};
+
+/// Field of crud result metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CrudField {
+  pub name: String,
+  #[serde(rename = "type")]
+  pub kind: String,
+  #[serde(default)]
+  pub is_nullable: bool,
+}
+
+/// Rows returned by crud with their format.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CrudRows<T> {
+  #[serde(default)]
+  pub metadata: Vec<CrudField>,
+  pub rows: Vec<T>,
+}
+
+/// Result of batch operation, rows which failed are reported by errors.
+#[derive(Debug, Clone)]
+pub struct CrudBatch<T> {
+  pub rows: Vec<T>,
+  pub errors: Vec<CrudError>,
+}
+
+/// This is error object of crud, class is e.g. InsertError or SelectError.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CrudError {
+  pub class_name: String,
+  #[serde(rename = "err")]
+  pub message: String,
+  /// lua file and line where error was raised
+  #[serde(default)]
+  pub file: Option<String>,
+  #[serde(default)]
+  pub line: Option<u64>,
+  #[serde(default)]
+  pub stack: Option<String>,
+}
+
+impl fmt::Display for CrudError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.class_name, self.message)
+  }
+}
+
+impl CrudError {
+  /// crud error carried by error of handle
+  pub fn of(err: &Error) -> Option<CrudError> {
+    match err.root() {
+      Error::Crud(err) => Some(err.clone()),
+      _ => None,
+    }
+  }
+}
+
+/**
+  This is builder of crud select conditions,
+  field is name or number of field or name of index.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Conditions {
+  conditions: Vec<Value>,
+}
+
+impl Conditions {
+  pub fn new() -> Conditions {
+    Conditions::default()
+  }
+
+  pub fn eq<F: Into<Value>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.with("==", field, value)
+  }
+
+  pub fn lt<F: Into<Value>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.with("<", field, value)
+  }
+
+  pub fn le<F: Into<Value>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.with("<=", field, value)
+  }
+
+  pub fn gt<F: Into<Value>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.with(">", field, value)
+  }
+
+  pub fn ge<F: Into<Value>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.with(">=", field, value)
+  }
+
+  /// condition with any operator supported by crud
+  pub fn with<F: Into<Value>, V: Into<Value>>(mut self, operator: &str, field: F, value: V) -> Self {
+    self.conditions.push(Value::Array(vec![ operator.into(), field.into(), value.into() ]));
+    self
+  }
+}
+
+impl From<Conditions> for Value {
+  fn from(conditions: Conditions) -> Value {
+    Value::Array(conditions.conditions)
+  }
+}
+
+/// This is options of crud call, they are passed as opts table.
+#[derive(Debug, Clone, Default)]
+pub struct CrudOptions {
+  options: Vec<(Value, Value)>,
+}
+
+impl CrudOptions {
+  pub fn new() -> CrudOptions {
+    CrudOptions::default()
+  }
+
+  pub fn with_timeout(self, timeout: Duration) -> Self {
+    self.with("timeout", timeout.as_secs_f64())
+  }
+
+  pub fn with_bucket_id(self, bucket_id: u64) -> Self {
+    self.with("bucket_id", bucket_id)
+  }
+
+  /// fields which are returned, primary key fields are returned anyway
+  pub fn with_fields(self, fields: &[&str]) -> Self {
+    self.with("fields", Value::Array(fields.iter().map(|&field| field.into()).collect()))
+  }
+
+  /// limit of select
+  pub fn with_first(self, first: i64) -> Self {
+    self.with("first", first)
+  }
+
+  /// tuple after which select continues
+  pub fn with_after(self, tuple: Vec<Value>) -> Self {
+    self.with("after", Value::Array(tuple))
+  }
+
+  /// option which has no method
+  pub fn with<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+    self.options.retain(|(key, _)| !matches!(key, Value::Str(key) if key == name));
+    self.options.push((name.into(), value.into()));
+    self
+  }
+}
+
+impl From<CrudOptions> for Value {
+  fn from(options: CrudOptions) -> Value {
+    Value::Map(options.options)
+  }
+}
+
+/// result of crud function is result or nil with error
+struct Reply<R, E> {
+  result: Option<R>,
+  error: Option<E>,
+}
+
+impl<'de, R, E> Deserialize<'de> for Reply<R, E>
+  where R: Deserialize<'de>, E: Deserialize<'de>
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>
+  {
+    struct ReplyVisitor<R, E>(PhantomData<(R, E)>);
+
+    impl<'de, R, E> Visitor<'de> for ReplyVisitor<R, E>
+      where R: Deserialize<'de>, E: Deserialize<'de>
+    {
+      type Value = Reply<R, E>;
+
+      fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("result of crud function")
+      }
+
+      fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where A: SeqAccess<'de>
+      {
+        let result = seq.next_element::<Option<R>>()?.flatten();
+        let error = seq.next_element::<Option<E>>()?.flatten();
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(Reply { result, error })
+      }
+    }
+
+    deserializer.deserialize_seq(ReplyVisitor(PhantomData))
+  }
+}
+
+/// This is handle of space of crud module, see TarantoolClient::crud.
+#[derive(Debug)]
+pub struct Crud<'c, C> {
+  client: &'c C,
+  space: String,
+}
+
+impl<'c, C> Crud<'c, C>
+  where C: TarantoolClient
+{
+  pub(crate) fn new(client: &'c C, space: &str) -> Crud<'c, C> {
+    Crud { client, space: space.into() }
+  }
+
+  pub fn space(&self) -> &str {
+    &self.space
+  }
+
+  /// inserts tuple and returns it, crud.insert
+  pub async fn insert<T>(&self, tuple: Vec<Value>, options: CrudOptions) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned
+  {
+    Ok(self.call("crud.insert", vec![ Value::Array(tuple) ], options).await?.rows)
+  }
+
+  /// inserts or replaces tuple and returns it, crud.replace
+  pub async fn replace<T>(&self, tuple: Vec<Value>, options: CrudOptions) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned
+  {
+    Ok(self.call("crud.replace", vec![ Value::Array(tuple) ], options).await?.rows)
+  }
+
+  /// gets tuple by primary key, crud.get
+  pub async fn get<T, K>(&self, key: K, options: CrudOptions) -> Result<Option<T>, Error>
+    where T: DeserializeOwned, K: IntoKey
+  {
+    Ok(self.call("crud.get", vec![ Value::Array(key.into_key()) ], options).await?.rows.into_iter().next())
+  }
+
+  /// selects tuples by conditions, crud.select
+  pub async fn select<T>(&self, conditions: Conditions, options: CrudOptions) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned
+  {
+    Ok(self.select_rows(conditions, options).await?.rows)
+  }
+
+  /// selects tuples by conditions with their metadata, crud.select
+  pub async fn select_rows<T>(&self, conditions: Conditions, options: CrudOptions) -> Result<CrudRows<T>, Error>
+    where T: DeserializeOwned
+  {
+    self.call("crud.select", vec![ conditions.into() ], options).await
+  }
+
+  /// updates tuple by primary key and returns it, operations are like ones of Update, crud.update
+  pub async fn update<T, K>(
+    &self, key: K, operations: Vec<Vec<Value>>, options: CrudOptions,
+  ) -> Result<Option<T>, Error>
+    where T: DeserializeOwned, K: IntoKey
+  {
+    let operations = Value::Array(operations.into_iter().map(Value::Array).collect());
+    Ok(self.call("crud.update", vec![ Value::Array(key.into_key()), operations ], options).await?.rows.into_iter().next())
+  }
+
+  /// deletes tuple by primary key and returns it, crud.delete
+  pub async fn delete<T, K>(&self, key: K, options: CrudOptions) -> Result<Option<T>, Error>
+    where T: DeserializeOwned, K: IntoKey
+  {
+    Ok(self.call("crud.delete", vec![ Value::Array(key.into_key()) ], options).await?.rows.into_iter().next())
+  }
+
+  /**
+    inserts tuples in batch, crud.insert_many,
+    tuples which failed are reported by errors and the rest are inserted
+    unless stop_on_error or rollback_on_error options are set
+  */
+  pub async fn insert_many<T>(&self, tuples: Vec<Vec<Value>>, options: CrudOptions) -> Result<CrudBatch<T>, Error>
+    where T: DeserializeOwned
+  {
+    let tuples = Value::Array(tuples.into_iter().map(Value::Array).collect());
+    let reply: Reply<CrudRows<T>, Vec<CrudError>> = self.client.call(Call {
+      function: "crud.insert_many".into(),
+      args: vec![ self.space.as_str().into(), tuples, options.into() ],
+    }).await?;
+
+    Ok(CrudBatch {
+      rows: reply.result.map(|result| result.rows).unwrap_or_default(),
+      errors: reply.error.unwrap_or_default(),
+    })
+  }
+
+  async fn call<T>(&self, function: &str, mut args: Vec<Value>, options: CrudOptions) -> Result<CrudRows<T>, Error>
+    where T: DeserializeOwned
+  {
+    args.insert(0, self.space.as_str().into());
+    args.push(options.into());
+
+    let reply: Reply<CrudRows<T>, CrudError> = self.client.call(Call {
+      function: function.into(),
+      args,
+    }).await?;
+
+    match (reply.result, reply.error) {
+      (_, Some(err)) => Err(Error::Crud(err)),
+      (Some(result), None) => Ok(result),
+      (None, None) => Ok(CrudRows { metadata: Vec::new(), rows: Vec::new() }),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use crate::{IntoTuple, testing::FakeClient};
+
+  use super::*;
+
+  fn rows(rows: Vec<Value>) -> Value {
+    Value::Map(vec![
+      ("metadata".into(), Value::Array(vec![
+        Value::Map(vec![ ("name".into(), "id".into()), ("type".into(), "unsigned".into()) ]),
+        Value::Map(vec![
+          ("name".into(), "name".into()), ("type".into(), "string".into()), ("is_nullable".into(), true.into()),
+        ]),
+      ])),
+      ("rows".into(), Value::Array(rows)),
+    ])
+  }
+
+  fn error(class_name: &str, err: &str) -> Value {
+    Value::Map(vec![
+      ("class_name".into(), class_name.into()), ("err".into(), err.into()),
+      ("file".into(), "crud/insert.lua".into()), ("line".into(), 42u64.into()),
+    ])
+  }
+
+  fn option<'a>(options: &'a Value, name: &str) -> Option<&'a Value> {
+    match options {
+      Value::Map(options) => options.iter()
+        .find(|(key, _)| matches!(key, Value::Str(key) if key == name))
+        .map(|(_, value)| value),
+      _ => None,
+    }
+  }
+
+  #[tokio::test]
+  async fn test_crud() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let client = {
+      let (insert_calls, select_calls) = (calls.clone(), calls.clone());
+      FakeClient::new()
+        .with_function("crud.insert", move |args| {
+          insert_calls.lock().unwrap().push(args.clone());
+          match &args[1] {
+            Value::Array(tuple) if matches!(tuple[0], Value::UInt(1)) => Ok(vec![ rows(vec![ Value::Array(tuple.clone()) ]) ]),
+            _ => Ok(vec![ Value::Null, error("InsertError", "Duplicate key exists") ]),
+          }
+        })
+        .with_function("crud.select", move |args| {
+          select_calls.lock().unwrap().push(args.clone());
+          Ok(vec![ rows(vec![ Value::Array(( 1u64, "ann" ).into_tuple()), Value::Array(( 2u64, "bob" ).into_tuple()) ]) ])
+        })
+        .with_function("crud.get", |_| Ok(vec![ rows(Vec::new()) ]))
+    };
+
+    let users = client.crud("users");
+
+    let inserted: Vec<(u64, String)> = users.insert(( 1u64, "ann" ).into_tuple(), CrudOptions::new()).await.unwrap();
+    assert_eq!(inserted, vec![ (1, "ann".into()) ]);
+
+    let err = users.insert::<(u64, String)>(( 2u64, "bob" ).into_tuple(), CrudOptions::new()).await.unwrap_err();
+    assert_eq!(CrudError::of(&err), Some(CrudError {
+      class_name: "InsertError".into(), message: "Duplicate key exists".into(),
+      file: Some("crud/insert.lua".into()), line: Some(42), stack: None,
+    }));
+    assert!(matches!(err.root(), Error::Crud(err) if err.class_name == "InsertError"));
+    assert_eq!(err.code(), None);
+
+    let page: CrudRows<(u64, String)> = users.select_rows(
+      Conditions::new().ge("id", 1u64).eq("name", "ann"),
+      CrudOptions::new().with_first(2).with_first(10).with_fields(&[ "name" ]),
+    ).await.unwrap();
+    assert_eq!(page.rows.len(), 2);
+    assert_eq!(page.metadata[1], CrudField { name: "name".into(), kind: "string".into(), is_nullable: true });
+
+    let select = calls.lock().unwrap().last().cloned().unwrap();
+    assert!(matches!(&select[0], Value::Str(space) if space == "users"));
+    match &select[1] {
+      Value::Array(conditions) => {
+        assert_eq!(conditions.len(), 2);
+        assert!(matches!(&conditions[0], Value::Array(condition) if matches!(
+          condition.as_slice(),
+          [ Value::Str(op), Value::Str(field), Value::UInt(1) ] if op == ">=" && field == "id"
+        )));
+      },
+      conditions => panic!("unexpected conditions {:?}", conditions),
+    }
+    assert!(matches!(option(&select[2], "first"), Some(Value::Int(10))));
+    assert!(matches!(option(&select[2], "fields"), Some(Value::Array(fields)) if fields.len() == 1));
+
+    let missing: Option<(u64, String)> = users.get(3u64, CrudOptions::new()).await.unwrap();
+    assert_eq!(missing, None);
+  }
+
+  #[tokio::test]
+  async fn test_crud_insert_many() {
+    let client = FakeClient::new()
+      .with_function("crud.insert_many", |_| Ok(vec![
+        rows(vec![ Value::Array(( 1u64, "ann" ).into_tuple()) ]),
+        Value::Array(vec![ error("InsertManyError", "Duplicate key exists") ]),
+      ]));
+
+    let batch: CrudBatch<(u64, String)> = client.crud("users").insert_many(vec![
+      ( 1u64, "ann" ).into_tuple(),
+      ( 1u64, "bob" ).into_tuple(),
+    ], CrudOptions::new()).await.unwrap();
+    assert_eq!(batch.rows, vec![ (1, "ann".into()) ]);
+    assert_eq!(batch.errors[0].class_name, "InsertManyError");
+  }
+}
@@ -0,0 +1,290 @@
+/*!
+  This module contains lightweight mapping between structs and spaces.
+
+  Entity is usually derived with `derive` feature:
+  ```rust,no_run
+    # #[cfg(feature = "derive")]
+    # async fn f(conn: &alopecosa::Connection) -> Result<(), alopecosa::Error> {
+    # use serde::Deserialize;
+    # use alopecosa::{Entity, entity::Entity as _};
+    #[derive(Entity, Deserialize)]
+    #[space = "users"]
+    #[index(primary = "id")]
+    struct User {
+      id: u64,
+      name: String,
+    }
+
+    let user = User::find(conn, vec![ 1u64.into() ]).await?;
+    User { id: 2, name: "bob".into() }.save(conn).await?;
+    # Ok(()) }
+  ```
+*/
+
+use async_trait::async_trait;
+use serde::de::{DeserializeOwned, IgnoredAny};
+
+use crate::{
+  client::TarantoolClient,
+  iproto::{
+    request::{Delete, Replace, Update, Value},
+    types::Error,
+    update::diff_tuples,
+  },
+};
+
+/**
+  This trait maps struct to tuple of named space.
+
+  Space id is resolved by name on every call via `TarantoolClient::space_id`,
+  primary index is always index 0.
+*/
+#[async_trait]
+pub trait Entity: DeserializeOwned + Send + Sync + Sized {
+  /// name of space
+  const SPACE: &'static str;
+
+  /// names of fields in tuple order
+  const FIELDS: &'static [&'static str];
+
+  /// values of primary key fields
+  fn primary_key(&self) -> Vec<Value>;
+
+  /// all fields in tuple order
+  fn to_tuple(&self) -> Vec<Value>;
+
+  /// finds entity by primary key
+  async fn find<C>(client: &C, key: Vec<Value>) -> Result<Option<Self>, Error>
+    where C: TarantoolClient
+  {
+    let space_id = client.space_id(Self::SPACE).await?;
+    client.get(space_id, 0, key).await
+  }
+
+  /// inserts or replaces entity
+  async fn save<C>(&self, client: &C) -> Result<(), Error>
+    where C: TarantoolClient
+  {
+    let space_id = client.space_id(Self::SPACE).await?;
+    let _: Vec<IgnoredAny> = client.replace(Replace {
+      space_id, tuple: self.to_tuple(),
+    }).await?;
+    Ok(())
+  }
+
+  /**
+    updates only fields which differ from old state of entity,
+    returns false if nothing was changed.
+    Primary key can't be updated, it is InvalidKey error if it differs
+
+    Example:
+    ```rust,no_run
+      # #[cfg(feature = "derive")]
+      # async fn f(conn: &alopecosa::Connection, mut user: User) -> Result<(), alopecosa::Error> {
+      # use alopecosa::entity::Entity;
+      let old = user.clone();
+      user.name = "alice".into();
+      user.save_changes(conn, &old).await?;
+      # Ok(()) }
+      # #[cfg(feature = "derive")]
+      # #[derive(Clone, alopecosa::Entity, serde::Deserialize)]
+      # #[space = "users"]
+      # #[index(primary = "id")]
+      # struct User { id: u64, name: String }
+    ```
+  */
+  async fn save_changes<C>(&self, client: &C, old: &Self) -> Result<bool, Error>
+    where C: TarantoolClient
+  {
+    if !diff_tuples(&old.primary_key(), self.primary_key())?.is_empty() {
+      return Err(Error::InvalidKey("primary key of entity is changed, save it instead".into()));
+    }
+
+    let ops = diff_tuples(&old.to_tuple(), self.to_tuple())?;
+    if ops.is_empty() {
+      return Ok(false);
+    }
+
+    let space_id = client.space_id(Self::SPACE).await?;
+    let _: Vec<IgnoredAny> = client.update(Update {
+      space_id, index_id: 0, index_base: 0,
+      key: old.primary_key(),
+      tuple: ops.into_iter().map(Into::into).collect(),
+    }).await?;
+    Ok(true)
+  }
+
+  /// deletes entity by its primary key, returns false if it was not found
+  async fn delete<C>(&self, client: &C) -> Result<bool, Error>
+    where C: TarantoolClient
+  {
+    let space_id = client.space_id(Self::SPACE).await?;
+    let deleted: Vec<IgnoredAny> = client.delete(Delete {
+      space_id, index_id: 0, key: self.primary_key(),
+    }).await?;
+    Ok(!deleted.is_empty())
+  }
+
+  /**
+    assigns changed fields by name and returns updated entity
+
+    Example:
+    ```rust,no_run
+      # #[cfg(feature = "derive")]
+      # async fn f(conn: &alopecosa::Connection) -> Result<(), alopecosa::Error> {
+      # use alopecosa::entity::Entity;
+      let user = User::update_partial(conn, vec![ 1u64.into() ], vec![
+        ("name", "alice".into()),
+      ]).await?;
+      # Ok(()) }
+      # #[cfg(feature = "derive")]
+      # #[derive(alopecosa::Entity, serde::Deserialize)]
+      # #[space = "users"]
+      # #[index(primary = "id")]
+      # struct User { id: u64, name: String }
+    ```
+  */
+  async fn update_partial<C>(
+    client: &C, key: Vec<Value>, changes: Vec<(&str, Value)>,
+  ) -> Result<Option<Self>, Error>
+    where C: TarantoolClient
+  {
+    let mut ops: Vec<Vec<Value>> = Vec::with_capacity(changes.len());
+
+    for (name, value) in changes {
+      let position = Self::FIELDS.iter()
+        .position(|field| *field == name)
+        .ok_or_else(|| Error::InvalidUpdateOp(format!(
+          "unknown field {} of {}", name, Self::SPACE,
+        )))?;
+
+      ops.push(vec![ Value::Str("=".into()), Value::UInt(position as u64), value ]);
+    }
+
+    let space_id = client.space_id(Self::SPACE).await?;
+    let updated: Vec<Self> = client.update(Update {
+      space_id, index_id: 0, index_base: 0,
+      key, tuple: ops,
+    }).await?;
+
+    Ok(updated.into_iter().next())
+  }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+  use serde::Deserialize;
+
+  use crate::testing::FakeClient;
+
+  use super::*;
+
+  #[derive(crate::Entity, Deserialize, Debug, PartialEq)]
+  #[space = "users"]
+  #[index(primary = "id")]
+  struct User {
+    id: u64,
+    name: String,
+  }
+
+  #[tokio::test]
+  async fn test_entity() {
+    let client = FakeClient::new()
+      .with_space(512, vec![ 0 ])
+      .with_space_name(512, "users");
+
+    let user = User { id: 1, name: "alice".into() };
+    user.save(&client).await.unwrap();
+
+    let found = User::find(&client, vec![ 1u64.into() ]).await.unwrap();
+    assert_eq!(found, Some(User { id: 1, name: "alice".into() }));
+
+    let updated = User::update_partial(&client, vec![ 1u64.into() ], vec![
+      ("name", "bob".into()),
+    ]).await.unwrap();
+    assert_eq!(updated, Some(User { id: 1, name: "bob".into() }));
+
+    assert!(User::update_partial(&client, vec![ 1u64.into() ], vec![
+      ("age", 1u64.into()),
+    ]).await.is_err());
+
+    let renamed = User { id: 1, name: "carol".into() };
+    assert!(renamed.save_changes(&client, &User { id: 1, name: "bob".into() }).await.unwrap());
+    assert!(!renamed.save_changes(&client, &renamed).await.unwrap());
+    assert_eq!(User::find(&client, vec![ 1u64.into() ]).await.unwrap(), Some(renamed));
+
+    let moved = User { id: 2, name: "carol".into() };
+    assert!(matches!(
+      moved.save_changes(&client, &User { id: 1, name: "carol".into() }).await,
+      Err(Error::InvalidKey(_)),
+    ));
+    assert_eq!(User::find(&client, vec![ 2u64.into() ]).await.unwrap(), None);
+
+    assert!(user.delete(&client).await.unwrap());
+    assert!(!user.delete(&client).await.unwrap());
+    assert_eq!(User::find(&client, vec![ 1u64.into() ]).await.unwrap(), None);
+  }
+
+  #[derive(crate::Entity, crate::Space, Deserialize, Debug, PartialEq)]
+  #[space(name = "user_emails", index(primary = "id"), index(secondary = "email"))]
+  struct UserEmail {
+    id: u64,
+    email: String,
+  }
+
+  #[tokio::test]
+  async fn test_space_accessor() {
+    let client = FakeClient::new()
+      .with_space(513, vec![ 0 ])
+      .with_index(513, 1, vec![ 1 ])
+      .with_space_name(513, "user_emails")
+      .with_index_name(513, 1, "email");
+
+    for (id, email) in [ (1, "a@b.c"), (2, "a@b.c"), (3, "d@e.f") ] {
+      UserEmail { id, email: email.into() }.save(&client).await.unwrap();
+    }
+
+    assert_eq!(UserEmails::SPACE, "user_emails");
+    assert_eq!(
+      UserEmails::get(&client, 3).await.unwrap(),
+      Some(UserEmail { id: 3, email: "d@e.f".into() }),
+    );
+    assert_eq!(UserEmails::get(&client, 4).await.unwrap(), None);
+
+    let found = UserEmails::by_email(&client, "a@b.c".into()).await.unwrap();
+    assert_eq!(found.iter().map(|user| user.id).collect::<Vec<_>>(), vec![ 1, 2 ]);
+  }
+
+  #[derive(crate::ToTuple, crate::FromTuple, Debug, PartialEq)]
+  struct Order {
+    id: u64,
+    #[tuple(index = 2)]
+    item: String,
+    count: u32,
+    #[tuple(skip)]
+    cached: bool,
+  }
+
+  #[tokio::test]
+  async fn test_tuple_derive() {
+    use crate::{IntoTuple, iproto::{constants::Iterator, request::{Insert, Select}}};
+
+    let client = FakeClient::new().with_space(514, vec![ 0 ]);
+    let order = Order { id: 1, item: "tea".into(), count: 2, cached: true };
+
+    let tuple = order.into_tuple();
+    assert!(matches!(tuple.as_slice(), [
+      Value::UInt(1), Value::Null, Value::Str(item), Value::UInt(2),
+    ] if item == "tea"));
+
+    let inserted: Vec<Order> = client.insert(Insert { space_id: 514, tuple }).await.unwrap();
+    assert_eq!(inserted[0].item, "tea");
+    let orders: Vec<Order> = client.select(Select {
+      space_id: 514, index_id: 0,
+      limit: u32::MAX, offset: 0,
+      iterator: Iterator::All,
+      keys: Vec::new(),
+    }).await.unwrap();
+    assert_eq!(orders, vec![ Order { id: 1, item: "tea".into(), count: 2, cached: false } ]);
+  }
+}
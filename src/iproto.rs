@@ -3,3 +3,7 @@ pub mod constants;
 pub mod types;
 pub mod response;
 pub mod request;
+pub mod path;
+pub mod update;
+pub mod serialize;
+pub mod redaction;
@@ -2,13 +2,29 @@
   This module contains all known tarantool constants.
 */
 
+use std::{fmt, str::FromStr};
+
 use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::{self, Visitor}};
 
 /**
   Every error tarantool code is ERROR_BITMASK | error_number.
 */
 pub const ERROR_BITMASK: isize = 1 << 15;
 
+/// IPROTO_FLAGS bit, request commits transaction chain.
+pub const FLAG_COMMIT: u64 = 0x01;
+/// IPROTO_FLAGS bit, transaction waits for synchronous replication.
+pub const FLAG_WAIT_SYNC: u64 = 0x02;
+/// IPROTO_FLAGS bit, transaction waits for replica acknowledgement.
+pub const FLAG_WAIT_ACK: u64 = 0x04;
+
+/// id of _vspace system view, spaces visible to user.
+pub const VSPACE_ID: u64 = 281;
+/// id of _vindex system view, indexes visible to user.
+pub const VINDEX_ID: u64 = 289;
+
 /**
   It represents all known tarantool response codes.
 
@@ -246,13 +262,65 @@ pub enum Code {
   ErrorTooEarlySubscribe              = ERROR_BITMASK | 220,
   ErrorSQLCantAddAutoinc              = ERROR_BITMASK | 221,
   ErrorQuorumWait                     = ERROR_BITMASK | 222,
-
+  ErrorInterferingPromote             = ERROR_BITMASK | 223,
+  ErrorElectionDisabled               = ERROR_BITMASK | 224,
+  ErrorTxnRollback                    = ERROR_BITMASK | 225,
+  ErrorNotLeader                      = ERROR_BITMASK | 226,
+  ErrorSyncQueueUnclaimed             = ERROR_BITMASK | 227,
+  ErrorSyncQueueForeign               = ERROR_BITMASK | 228,
+  ErrorUnableToProcessInStream        = ERROR_BITMASK | 229,
+  ErrorUnableToProcessOutOfStream     = ERROR_BITMASK | 230,
+  ErrorTransactionTimeout             = ERROR_BITMASK | 231,
+  ErrorActiveTimer                    = ERROR_BITMASK | 232,
+  ErrorTupleFieldCountLimit           = ERROR_BITMASK | 233,
+  ErrorCreateConstraint               = ERROR_BITMASK | 234,
+  ErrorFieldConstraintFailed          = ERROR_BITMASK | 235,
+  ErrorTupleConstraintFailed          = ERROR_BITMASK | 236,
+  ErrorCreateForeignKey               = ERROR_BITMASK | 237,
+  ErrorForeignKeyIntegrity            = ERROR_BITMASK | 238,
+  ErrorFieldForeignKeyFailed          = ERROR_BITMASK | 239,
+  ErrorComplexForeignKeyFailed        = ERROR_BITMASK | 240,
+  ErrorWrongSpaceUpgradeOptions       = ERROR_BITMASK | 241,
+  ErrorNoElectionQuorum               = ERROR_BITMASK | 242,
+  ErrorSSL                            = ERROR_BITMASK | 243,
+  ErrorSplitBrain                     = ERROR_BITMASK | 244,
+  ErrorOldTerm                        = ERROR_BITMASK | 245,
+  ErrorInterferingElections           = ERROR_BITMASK | 246,
+  ErrorIteratorPosition               = ERROR_BITMASK | 247,
 }
 
 #[allow(dead_code)]
 impl Code {
   /// Is shortcut for code != Code::Ok
   pub fn is_err(self) -> bool { self != Code::Ok }
+
+  /// code of error number of stack record, e.g. 3 is ErrorTupleFound
+  pub fn from_errcode(errcode: u64) -> Option<Code> {
+    num_traits::FromPrimitive::from_u64(errcode | ERROR_BITMASK as u64)
+  }
+
+  /**
+    transient errors, request may succeed on retry:
+    timeouts, read only or loading instance, transaction conflicts
+  */
+  pub fn is_retryable(self) -> bool {
+    matches!(self,
+      Code::ErrorTimeout | Code::ErrorSyncQuorumTimeout |
+      Code::ErrorReadonly | Code::ErrorNotLeader | Code::ErrorLoading |
+      Code::ErrorTransactionConflict | Code::ErrorSyncRollback |
+      Code::ErrorNoConnection | Code::ErrorMemoryIssue
+    )
+  }
+
+  /// schema was changed since request was prepared
+  pub fn is_schema_changed(self) -> bool {
+    matches!(self, Code::ErrorWrongSchemaVersion)
+  }
+
+  /// tuple with the same unique key exists
+  pub fn is_duplicate_key(self) -> bool {
+    matches!(self, Code::ErrorTupleFound)
+  }
 }
 
 impl Default for Code {
@@ -264,6 +332,11 @@ impl Default for Code {
 
   It also implements num_derive::{FromPrimitive, ToPrimitive},
   so you can also convert it to int types.
+
+  Note: since streams are supported Rollback is rollback of interactive transaction (0x10),
+  confirm and rollback of synchronous replication (0x28, 0x29) are RaftConfirm and RaftRollback.
+  Confirm is kept as deprecated alias of RaftConfirm, Rollback can't be kept,
+  so code which matched Rollback of replication has to use RaftRollback.
 */
 #[derive(
   Debug, Clone, Copy,
@@ -284,15 +357,31 @@ pub enum RequestType {
   Execute         = 0x0b,
   Nop             = 0x0c,
   Prepare         = 0x0d,
-  Confirm         = 0x28,
-  Rollback        = 0x29,
+  Begin           = 0x0e,
+  Commit          = 0x0f,
+  Rollback        = 0x10,
+  RaftConfirm     = 0x28,
+  RaftRollback    = 0x29,
   Ping            = 0x40,
   Join            = 0x41,
   Subscribe       = 0x42,
   VoteDeprecated  = 0x43,
   Vote            = 0x44,
   FetchSnapshot   = 0x45,
-  Register        = 0x46
+  Register        = 0x46,
+  Id              = 0x49,
+  Watch           = 0x4a,
+  Unwatch         = 0x4b,
+  /// pushed by server to watcher, it is not request
+  Event           = 0x4c,
+  /// value pushed by box.session.push before response, it is not request
+  Chunk           = 0x80,
+}
+
+#[allow(non_upper_case_globals)]
+impl RequestType {
+  #[deprecated(note = "use RaftConfirm, Confirm of synchronous replication is renamed")]
+  pub const Confirm: RequestType = RequestType::RaftConfirm;
 }
 
 /**
@@ -317,12 +406,14 @@ pub enum Field {
   GroupID       = 0x07,
   TSN           = 0x08,
   Flags         = 0x09,
+  StreamID      = 0x0a,
   SpaceID       = 0x10,
   IndexID       = 0x11,
   Limit         = 0x12,
   Offset        = 0x13,
   Iterator      = 0x14,
   IndexBase     = 0x15,
+  FetchPosition = 0x1f,
   Key           = 0x20,
   Tuple         = 0x21,
   FunctionName  = 0x22,
@@ -335,11 +426,14 @@ pub enum Field {
   Ballot        = 0x29,
   TupleMeta     = 0x2a,
   Options       = 0x2b,
+  AfterPosition = 0x2e,
+  AfterTuple    = 0x2f,
   Data          = 0x30,
   Error24       = 0x31,
   Metadata      = 0x32,
   BindMetadata  = 0x33,
   BindCount     = 0x34,
+  Position      = 0x35,
   SqlText       = 0x40,
   SqlBind       = 0x41,
   SqlInfo       = 0x42,
@@ -348,6 +442,16 @@ pub enum Field {
   IDFilter      = 0x51,
   Error         = 0x52,
   Term          = 0x53,
+  Version       = 0x54,
+  Features      = 0x55,
+  Timeout       = 0x56,
+  EventKey      = 0x57,
+  EventData     = 0x58,
+  TxnIsolation  = 0x59,
+  AuthType      = 0x5b,
+  SpaceName     = 0x5e,
+  IndexName     = 0x5f,
+  TupleFormats  = 0x60,
 }
 
 /**
@@ -355,6 +459,58 @@ pub enum Field {
 
   It also implements num_derive::{FromPrimitive, ToPrimitive},
   so you can also convert it to int types.
+
+  Bitset iterators are used with bitset indexes,
+  Overlaps and Neighbor are used with rtree indexes,
+  Np and Pp are used with tree indexes, tarantool 2.11+.
+
+  Iterator is parsed from its name as in lua, e.g. "GE" or "bits_all_set",
+  comparison operators like ">=" are accepted too. It is serialized as its name
+  and deserialized from name or number, so it may be used in configuration.
+
+  Example:
+  ```rust
+    # use alopecosa::{Error, Iterator, RequestType, Select};
+    # fn main() -> Result<(), Error> {
+    assert_eq!("bits_all_set".parse::<Iterator>(), Ok(Iterator::BitsAllSet));
+    assert_eq!(">=".parse::<Iterator>(), Ok(Iterator::Ge));
+    assert!("nearest".parse::<Iterator>().is_err());
+
+    // all points inside rectangle
+    let overlaps = Select::builder(513)
+      .index(1)
+      .iterator(Iterator::Overlaps)
+      .key(( 0.0, 0.0, 10.0, 10.0 ))
+      .limit(100);
+    assert_eq!(overlaps.clone().body().iterator, Iterator::Overlaps);
+
+    // tuples which have both 1st and 3rd bits set
+    let flagged = Select::builder(514)
+      .index(1)
+      .iterator(Iterator::BitsAllSet)
+      .key(( 0b101u64, ))
+      .limit(100);
+    assert_eq!(flagged.clone().body().iterator, Iterator::BitsAllSet);
+
+    for req in vec![ overlaps.build(), flagged.build() ] {
+      assert_eq!(req.header.request, RequestType::Select);
+      req.pack(&mut Vec::new())?;
+    }
+    # Ok(()) }
+  ```
+
+  Rtree select on server:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, Iterator, Select, Value};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let points: Vec<(u64, (f64, f64))> = conn.select(Select {
+      space_id: 513, index_id: 1,
+      limit: 100, offset: 0,
+      iterator: Iterator::Overlaps,
+      keys: vec![ Value::from(&[ 0.0, 0.0, 10.0, 10.0 ][..]) ],
+    }).await?;
+    # Ok(()) }
+  ```
 */
 #[derive(
   Debug, Clone, Copy,
@@ -362,14 +518,163 @@ pub enum Field {
   FromPrimitive, ToPrimitive,
 )]
 pub enum Iterator {
+  /// key == x ASC order
   Eq            = 0,
-	Req           = 1,
-	All           = 2,
-	Lt            = 3,
-	Le            = 4,
-	Ge            = 5,
-	Gt            = 6,
-	BitsAllSet    = 7,
-	BitsAnySet    = 8,
-	BitsAllNotSet = 9,
+  /// key == x DESC order
+  Req           = 1,
+  /// all tuples
+  All           = 2,
+  /// key < x
+  Lt            = 3,
+  /// key <= x
+  Le            = 4,
+  /// key >= x
+  Ge            = 5,
+  /// key > x
+  Gt            = 6,
+  /// all bits from x are set in key
+  BitsAllSet    = 7,
+  /// at least one bit from x is set in key
+  BitsAnySet    = 8,
+  /// all bits are not set
+  BitsAllNotSet = 9,
+  /// key overlaps x (rtree)
+  Overlaps      = 10,
+  /// tuples in distance ascending order from specified point (rtree)
+  Neighbor      = 11,
+  /// next prefix, tuples with keys greater than prefix x
+  Np            = 12,
+  /// previous prefix, tuples with keys less than prefix x in descending order
+  Pp            = 13,
+}
+
+impl Iterator {
+  const NAMES: [(Iterator, &'static str); 14] = [
+    (Iterator::Eq, "EQ"), (Iterator::Req, "REQ"), (Iterator::All, "ALL"),
+    (Iterator::Lt, "LT"), (Iterator::Le, "LE"), (Iterator::Ge, "GE"), (Iterator::Gt, "GT"),
+    (Iterator::BitsAllSet, "BITS_ALL_SET"), (Iterator::BitsAnySet, "BITS_ANY_SET"),
+    (Iterator::BitsAllNotSet, "BITS_ALL_NOT_SET"),
+    (Iterator::Overlaps, "OVERLAPS"), (Iterator::Neighbor, "NEIGHBOR"),
+    (Iterator::Np, "NP"), (Iterator::Pp, "PP"),
+  ];
+
+  /// name of iterator as in lua, e.g. "BITS_ALL_SET"
+  pub fn as_str(self) -> &'static str {
+    Self::NAMES.iter()
+      .find(|(iterator, _)| *iterator == self)
+      .map(|(_, name)| *name)
+      .unwrap_or_default()
+  }
+}
+
+impl fmt::Display for Iterator {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// This is error of iterator parsing, it keeps unknown name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIteratorError {
+  pub name: String,
+}
+
+impl fmt::Display for ParseIteratorError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "unknown iterator {:?}", self.name)
+  }
+}
+
+impl std::error::Error for ParseIteratorError {}
+
+impl FromStr for Iterator {
+  type Err = ParseIteratorError;
+
+  /// names are case insensitive
+  fn from_str(name: &str) -> Result<Iterator, ParseIteratorError> {
+    let alias = match name.trim() {
+      "=" | "==" => Some(Iterator::Eq),
+      "<" => Some(Iterator::Lt),
+      "<=" => Some(Iterator::Le),
+      ">=" => Some(Iterator::Ge),
+      ">" => Some(Iterator::Gt),
+      _ => None,
+    };
+
+    alias
+      .or_else(|| Self::NAMES.iter()
+        .find(|(_, known)| known.eq_ignore_ascii_case(name.trim()))
+        .map(|(iterator, _)| *iterator))
+      .ok_or_else(|| ParseIteratorError { name: name.into() })
+  }
+}
+
+impl Serialize for Iterator {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.as_str())
+  }
+}
+
+impl<'de> Deserialize<'de> for Iterator {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Iterator, D::Error> {
+    struct IteratorVisitor;
+
+    impl Visitor<'_> for IteratorVisitor {
+      type Value = Iterator;
+
+      fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("iterator name or number")
+      }
+
+      fn visit_str<E: de::Error>(self, v: &str) -> Result<Iterator, E> {
+        v.parse().map_err(E::custom)
+      }
+
+      fn visit_u64<E: de::Error>(self, v: u64) -> Result<Iterator, E> {
+        Iterator::from_u64(v).ok_or_else(|| E::custom(format!("unknown iterator {}", v)))
+      }
+
+      fn visit_i64<E: de::Error>(self, v: i64) -> Result<Iterator, E> {
+        Iterator::from_i64(v).ok_or_else(|| E::custom(format!("unknown iterator {}", v)))
+      }
+    }
+
+    deserializer.deserialize_any(IteratorVisitor)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_iterator_names() {
+    for &(iterator, name) in Iterator::NAMES.iter() {
+      assert_eq!(iterator.to_string(), name);
+      assert_eq!(name.parse::<Iterator>(), Ok(iterator));
+      assert_eq!(name.to_lowercase().parse::<Iterator>(), Ok(iterator));
+    }
+    assert_eq!(">=".parse::<Iterator>(), Ok(Iterator::Ge));
+    assert_eq!(Iterator::from_u64(13), Some(Iterator::Pp));
+    assert_eq!(
+      "GTE".parse::<Iterator>().unwrap_err().to_string(),
+      "unknown iterator \"GTE\"",
+    );
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+      iterator: Iterator,
+    }
+
+    let config: Config = serde_json::from_str(r#"{ "iterator": "bits_any_set" }"#).unwrap();
+    assert_eq!(config.iterator, Iterator::BitsAnySet);
+    assert_eq!(serde_json::to_string(&config).unwrap(), r#"{"iterator":"BITS_ANY_SET"}"#);
+
+    let config: Config = serde_json::from_str(r#"{ "iterator": 5 }"#).unwrap();
+    assert_eq!(config.iterator, Iterator::Ge);
+    assert!(serde_json::from_str::<Config>(r#"{ "iterator": 42 }"#).is_err());
+
+    let packed = rmp_serde::to_vec(&Iterator::Np).unwrap();
+    assert_eq!(rmp_serde::from_slice::<Iterator>(&packed).unwrap(), Iterator::Np);
+  }
 }
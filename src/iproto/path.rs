@@ -0,0 +1,143 @@
+/*!
+  This module contains path based access into msgpack values.
+*/
+
+use rmpv::Value;
+use serde::de::DeserializeOwned;
+
+use super::types::Error;
+
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+  Key(&'a str),
+  Index(usize),
+}
+
+/// splits path like "a[2].b" into segments, it is none for malformed path
+fn segments(path: &str) -> Option<Vec<Segment<'_>>> {
+  let mut segments = Vec::new();
+
+  for part in path.split('.') {
+    let (key, mut rest) = match part.find('[') {
+      Some(pos) => part.split_at(pos),
+      None => (part, ""),
+    };
+
+    if !key.is_empty() {
+      segments.push(Segment::Key(key));
+    } else if rest.is_empty() {
+      return None;
+    }
+
+    while !rest.is_empty() {
+      let end = rest.find(']')?;
+      segments.push(Segment::Index(rest[1..end].trim().parse().ok()?));
+      rest = &rest[end + 1..];
+      if !rest.is_empty() && !rest.starts_with('[') {
+        return None;
+      }
+    }
+  }
+
+  Some(segments)
+}
+
+/// map keys are matched as strings or as integers
+fn key_matches(key: &Value, segment: &str) -> bool {
+  match key {
+    Value::String(key) => key.as_str() == Some(segment),
+    Value::Integer(key) => key.to_string() == segment,
+    _ => false,
+  }
+}
+
+/**
+  This trait allows you to navigate nested values of call and eval results.
+
+  Path consists of map keys separated by dots and zero based array indexes in brackets.
+
+  Example:
+  ```rust
+    # use rmpv::Value;
+    # use alopecosa::{Error, ValuePath};
+    # fn main() -> Result<(), Error> {
+    let user = Value::Map(vec![
+      ( "addresses".into(), Value::Array(vec![
+        Value::Map(vec![ ( "city".into(), "Paris".into() ), ( "zip".into(), 75001.into() ) ]),
+      ]) ),
+    ]);
+
+    let city = user.get_path("addresses[0].city").and_then(Value::as_str);
+    let zip: Option<u32> = user.get_path_as("addresses[0].zip")?;
+    assert_eq!(( city, zip ), ( Some("Paris"), Some(75001) ));
+    # Ok(()) }
+  ```
+*/
+pub trait ValuePath {
+  /// value at path, it is none if path doesn't exist or is malformed
+  fn get_path(&self, path: &str) -> Option<&Value>;
+
+  /// value at path deserialized into T
+  fn get_path_as<T>(&self, path: &str) -> Result<Option<T>, Error>
+    where T: DeserializeOwned;
+}
+
+impl ValuePath for Value {
+  fn get_path(&self, path: &str) -> Option<&Value> {
+    segments(path)?.into_iter()
+      .try_fold(self, |value, segment| match (segment, value) {
+        (Segment::Key(segment), Value::Map(fields)) => fields.iter()
+          .find(|(key, _)| key_matches(key, segment))
+          .map(|(_, value)| value),
+        (Segment::Index(index), Value::Array(items)) => items.get(index),
+        _ => None,
+      })
+  }
+
+  fn get_path_as<T>(&self, path: &str) -> Result<Option<T>, Error>
+    where T: DeserializeOwned
+  {
+    let value = match self.get_path(path) {
+      Some(value) => value,
+      None => return Ok(None),
+    };
+
+    let mut buf: Vec<u8> = Vec::new();
+    rmpv::encode::write_value(&mut buf, value)?;
+
+    Ok(Some(rmp_serde::from_slice(&buf).map_err(Error::ParseError)?))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_get_path() {
+    let value = Value::Map(vec![
+      ("users".into(), Value::Array(vec![
+        Value::Map(vec![ ("name".into(), "ann".into()) ]),
+        Value::Map(vec![
+          ("name".into(), "bob".into()),
+          ("tags".into(), Value::Array(vec![ Value::Array(vec![ 1.into(), 2.into() ]) ])),
+        ]),
+      ])),
+      (42.into(), "answer".into()),
+    ]);
+
+    assert_eq!(value.get_path("users[1].name").and_then(Value::as_str), Some("bob"));
+    assert_eq!(value.get_path("users[1].tags[0][1]"), Some(&Value::from(2)));
+    assert_eq!(value.get_path("42").and_then(Value::as_str), Some("answer"));
+    assert_eq!(value.get_path("users[2].name"), None);
+    assert_eq!(value.get_path("users.name"), None);
+    assert_eq!(value.get_path("users[x]"), None);
+    assert_eq!(value.get_path("users[0]name"), None);
+
+    let tags: Option<Vec<u32>> = value.get_path_as("users[1].tags[0]").unwrap();
+    assert_eq!(tags, Some(vec![ 1, 2 ]));
+    let missing: Option<String> = value.get_path_as("users[0].email").unwrap();
+    assert_eq!(missing, None);
+    assert!(value.get_path_as::<u32>("users[0].name").is_err());
+  }
+}
@@ -0,0 +1,182 @@
+use std::{
+  collections::hash_map::RandomState,
+  hash::{BuildHasher, Hasher},
+  iter::Peekable,
+  str::Chars,
+  sync::OnceLock,
+};
+
+use super::request::Value;
+
+/**
+  This sets how values are written to request log.
+
+  Sql text and lua code are logged with literals replaced by "?"
+  and comments removed unless values are logged as is.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redaction {
+  /// values are replaced with "?"
+  Redact,
+  /**
+    values are replaced with short keyed hash, so equal values may be correlated,
+    key is random for every process, so hashes can't be matched against guessed values
+  */
+  Hash,
+  /// values are logged as is, it must not be used with sensitive data
+  Plain,
+}
+
+fn hash_key() -> &'static RandomState {
+  static KEY: OnceLock<RandomState> = OnceLock::new();
+  KEY.get_or_init(RandomState::new)
+}
+
+fn value(value: &Value, redaction: Redaction) -> String {
+  match redaction {
+    Redaction::Redact => "?".into(),
+    Redaction::Plain => format!("{:?}", value),
+    Redaction::Hash => {
+      let mut packed: Vec<u8> = Vec::new();
+      if value.pack(&mut packed).is_err() {
+        return "?".into();
+      }
+
+      let mut hasher = hash_key().build_hasher();
+      hasher.write(&packed);
+      format!("#{:08x}", hasher.finish() as u32)
+    },
+  }
+}
+
+/// formats values for request log
+pub(crate) fn values(values: &[Value], redaction: Redaction) -> String {
+  let values: Vec<String> = values.iter().map(|v| value(v, redaction)).collect();
+  format!("[{}]", values.join(", "))
+}
+
+/// skips lua long bracket like [==[ ... ]==] after its first '[', returns false if it is not one
+fn skip_long_bracket(chars: &mut Peekable<Chars<'_>>) -> bool {
+  let mut lookahead = chars.clone();
+  let mut level = 0;
+  while lookahead.next_if_eq(&'=').is_some() {
+    level += 1;
+  }
+  if lookahead.next() != Some('[') {
+    return false;
+  }
+
+  // closing bracket is ']' followed by the same number of '=' and ']'
+  let mut equals: Option<usize> = None;
+  for c in lookahead.by_ref() {
+    equals = match (c, equals) {
+      (']', Some(count)) if count == level => break,
+      (']', _) => Some(0),
+      ('=', Some(count)) => Some(count + 1),
+      _ => None,
+    };
+  }
+
+  *chars = lookahead;
+  true
+}
+
+/// replaces string and number literals of sql or lua code with "?" and removes comments
+pub(crate) fn sanitize(code: &str, redaction: Redaction) -> String {
+  if redaction == Redaction::Plain {
+    return code.into();
+  }
+
+  let mut sanitized = String::with_capacity(code.len());
+  let mut chars = code.chars().peekable();
+  let mut prev: Option<char> = None;
+
+  while let Some(c) = chars.next() {
+    match c {
+      '\'' | '"' => {
+        // double quotes are sql identifiers, but they are lua strings
+        let quote = c;
+        while let Some(c) = chars.next() {
+          if c == '\\' { chars.next(); continue; }
+          if c != quote { continue; }
+          if chars.peek() == Some(&quote) { chars.next(); continue; }
+          break;
+        }
+        sanitized.push('?');
+      },
+      '[' if skip_long_bracket(&mut chars) => sanitized.push('?'),
+      '-' if chars.peek() == Some(&'-') => {
+        chars.next();
+        // lua long comment --[[ ... ]] or line comment of sql and lua
+        let long = chars.next_if_eq(&'[').is_some() && skip_long_bracket(&mut chars);
+        if !long {
+          while chars.next_if(|&c| c != '\n').is_some() {}
+        }
+      },
+      '/' if chars.peek() == Some(&'*') => {
+        chars.next();
+        let mut prev = None;
+        for c in chars.by_ref() {
+          if prev == Some('*') && c == '/' { break; }
+          prev = Some(c);
+        }
+      },
+      c if c.is_ascii_digit() && !prev.is_some_and(|p| p.is_alphanumeric() || p == '_') => {
+        while let Some(&c) = chars.peek() {
+          if !(c.is_ascii_alphanumeric() || c == '.') { break; }
+          chars.next();
+        }
+        sanitized.push('?');
+      },
+      c => sanitized.push(c),
+    }
+
+    prev = Some(c);
+  }
+
+  sanitized
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::iproto::request::{Body, Call, Execute, Prepare};
+
+  use super::*;
+
+  #[test]
+  fn test_redaction() {
+    assert_eq!(
+      sanitize("SELECT * FROM t WHERE name = 'O''Neil' AND id > 42 AND c2 = x1", Redaction::Redact),
+      "SELECT * FROM t WHERE name = ? AND id > ? AND c2 = x1",
+    );
+    assert_eq!(sanitize("return box.space.t:get(\"a\\\"b\")", Redaction::Hash), "return box.space.t:get(?)");
+    assert_eq!(sanitize("SELECT 1", Redaction::Plain), "SELECT 1");
+    assert_eq!(
+      sanitize("box.execute([[SELECT 'x']], {[=[pass]]word]=]}) -- token\nreturn 1", Redaction::Redact),
+      "box.execute(?, {?}) \nreturn ?",
+    );
+    assert_eq!(
+      sanitize("--[==[ secret\n]] ]==] t[1] -- ", Redaction::Redact),
+      " t[?] ",
+    );
+    assert_eq!(sanitize("SELECT /* secret */ a - -1 FROM t", Redaction::Redact), "SELECT  a - -? FROM t");
+
+    let call = Call { function: "user.find".into(), args: vec![ "secret".into(), 1u64.into() ] };
+    assert_eq!(call.describe(Redaction::Redact), "user.find [?, ?]");
+
+    let hashed = call.describe(Redaction::Hash);
+    assert_eq!(hashed.len(), "user.find [#01234567, #01234567]".len());
+    assert_eq!(hashed, call.describe(Redaction::Hash));
+    assert_ne!(value(&"secret".into(), Redaction::Hash), value(&"secret2".into(), Redaction::Hash));
+    assert!(!hashed.contains("secret"));
+
+    let execute = Execute {
+      expr: Prepare::SQL("SELECT * FROM users WHERE email = 'a@b.c'".into()),
+      sql_bind: vec![ "x".into() ], options: Vec::new(),
+    };
+    assert_eq!(
+      execute.describe(Redaction::Redact),
+      "SELECT * FROM users WHERE email = ? binds=[?]",
+    );
+  }
+}
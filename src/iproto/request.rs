@@ -2,19 +2,30 @@
   This module contains structs for requests.
 */
 use uuid::Uuid;
-use chrono::NaiveDateTime;
-use std::{io::Write, convert::TryInto};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use std::{
+  borrow::Cow,
+  cmp::Ordering,
+  collections::{BTreeMap, HashMap, VecDeque},
+  convert::TryFrom,
+  fmt,
+  hash::{Hash, Hasher},
+  io::{self, Cursor, Read, Write},
+};
 
 use super::{
-  constants::{Field, RequestType, Iterator},
+  constants::{Field, RequestType, Iterator, FLAG_COMMIT},
+  response::{StackRecord, TarantoolError},
   types::Error,
 };
-use num_traits::ToPrimitive;
+#[cfg(feature = "otel")]
+use crate::connection::telemetry::TracePropagation;
+use super::redaction::{self, Redaction};
 use rmp::encode::{
   write_array_len, write_map_len, write_sint,
   write_str, write_str_len, write_uint, write_ext_meta
 };
-use byteorder::{LittleEndian, WriteBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use rust_decimal::Decimal;
 
 
@@ -30,6 +41,7 @@ macro_rules! req_func {
 req_func!(auth, Auth);
 req_func!(select, Select);
 req_func!(call, Call);
+req_func!(call16, Call16);
 req_func!(insert, Insert);
 req_func!(replace, Replace);
 req_func!(update, Update);
@@ -39,6 +51,17 @@ req_func!(upsert, Upsert);
 req_func!(prepare, Prepare);
 req_func!(execute, Execute);
 req_func!(execute_select, Execute);
+req_func!(subscribe, Subscribe);
+req_func!(id, Id);
+req_func!(begin, Begin);
+req_func!(watch, Watch);
+req_func!(unwatch, Unwatch);
+
+/// paged select is select request with position fields
+#[allow(dead_code)]
+pub fn paged_select(body: PagedSelect) -> Request {
+  Request::new(RequestType::Select, body)
+}
 
 #[allow(dead_code)]
 pub fn ping() -> Request {
@@ -48,13 +71,59 @@ pub fn ping() -> Request {
   }
 }
 
+/// unprepare is prepare request with statement id only
+#[allow(dead_code)]
+pub fn unprepare(body: Unprepare) -> Request {
+  Request::new(RequestType::Prepare, body)
+}
+
+#[allow(dead_code)]
+pub fn commit() -> Request {
+  Request::new(RequestType::Commit, Commit)
+}
+
+#[allow(dead_code)]
+pub fn rollback() -> Request {
+  Request::new(RequestType::Rollback, Rollback)
+}
+
 /**
   This trait represents tarantool query body.
 
   If you want to make custom request body, you should implement it.
+
+  Body is packed into buffer of writer, which is reused between requests.
+  Buffer is `Vec<u8>` rather than generic `Write`: request keeps its body as `Box<dyn Body>`,
+  so pack can't be generic, and writer reserves space in buffer and truncates it
+  if packing fails, which `dyn Write` doesn't allow.
 */
 pub trait Body: std::fmt::Debug + Send {
-  fn pack(&self) -> Result<Vec<u8>, Error>;
+  /// appends packed body to buffer, rmp::encode functions write into it as into any Write
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error>;
+
+  /// allows body to carry trace context, it is no-op by default
+  #[cfg(feature = "otel")]
+  fn inject_trace(&mut self, _propagation: TracePropagation, _traceparent: &str) {}
+
+  /// short description of body for request log, it is empty by default
+  fn describe(&self, _redaction: Redaction) -> String {
+    String::new()
+  }
+
+  /// space and index which request is addressed to, they are used in error context
+  fn target(&self) -> (Option<String>, Option<String>) {
+    (None, None)
+  }
+
+  /// tuple which request stores, it is checked against max tuple size
+  fn tuple(&self) -> Option<&[Value]> {
+    None
+  }
+
+  /// function which request calls, it is used to classify request for retries
+  fn function(&self) -> Option<&str> {
+    None
+  }
 }
 
 /**
@@ -66,6 +135,9 @@ pub struct Request {
   body: Box<dyn Body>,
 }
 
+/// size prefix of request is u32 with its marker
+const SIZE_PREFIX_LEN: usize = 5;
+
 #[allow(dead_code)]
 impl Request {
 
@@ -77,23 +149,183 @@ impl Request {
     }
   }
 
-  /// Allows you to pack request.
-  pub fn pack<W>(&self, w: &mut W) -> Result<(), Error>
-    where W: Write
-  {
+  /**
+    appends packed request to buffer, nothing is appended on error.
+
+    Size prefix is packed as u32 of fixed length like net.box does,
+    so header and body are packed in place and the prefix is patched after them.
+  */
+  pub fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let start = buf.len();
+    buf.extend_from_slice(&[ 0xce, 0, 0, 0, 0 ]);
+
+    let packed = self.header.pack(buf)
+      .and_then(|_| self.body.pack(buf))
+      .and_then(|_| u32::try_from(buf.len() - start - SIZE_PREFIX_LEN)
+        .map_err(|_| Error::EncodeError("request exceeds 4GiB".into())));
+
+    match packed {
+      Ok(size) => {
+        buf[start + 1..start + SIZE_PREFIX_LEN].copy_from_slice(&size.to_be_bytes());
+        Ok(())
+      },
+      Err(err) => {
+        buf.truncate(start);
+        Err(err)
+      },
+    }
+  }
+
+  /**
+    attaches additional header field by raw field id,
+    so header fields not modeled by this crate yet may be used
+
+    Note: request type and sync fields can't be overridden
+
+    Example:
+    ```rust,no_run
+      # use alopecosa::{Connection, Error, Select, request};
+      # async fn f(conn: &Connection, select_body: Select) -> Result<(), Error> {
+      const STREAM_ID: u64 = 0x0a;
+
+      let req = request::select(select_body)
+        .with_header_field(STREAM_ID, 1u64);
+      let resp = conn.perform(req).await?;
+      # Ok(()) }
+    ```
+  */
+  pub fn with_header_field<V: Into<Value>>(mut self, field: u64, value: V) -> Self {
+    self.header.extra.push((field, value.into()));
+    self
+  }
 
-    let header = self.header.pack()?;
-    let body = self.body.pack()?;
+  /// marks request as safe to retry, see RetryPolicy of connection
+  pub fn with_idempotent(mut self) -> Self {
+    self.header.idempotent = true;
+    self
+  }
+
+  #[cfg(feature = "otel")]
+  pub(crate) fn inject_trace(&mut self, propagation: TracePropagation, traceparent: &str) {
+    self.body.inject_trace(propagation, traceparent)
+  }
 
-    let size = header.len() + body.len();
+  pub(crate) fn describe(&self, redaction: Redaction) -> String {
+    self.body.describe(redaction)
+  }
 
-    rmp::encode::write_uint(w, size as u64)?;
+  pub(crate) fn target(&self) -> (Option<String>, Option<String>) {
+    self.body.target()
+  }
 
-    w.write_all(header.as_slice())?;
-    w.write_all(body.as_slice())?;
+  pub(crate) fn tuple(&self) -> Option<&[Value]> {
+    self.body.tuple()
+  }
 
-    Ok(())
+  pub(crate) fn function(&self) -> Option<&str> {
+    self.body.function()
+  }
+
+  /**
+    packs body once, so request may be sent several times,
+    description for request log is taken with given redaction
+  */
+  pub(crate) fn replay(self, redaction: Option<Redaction>) -> Result<Replay, Error> {
+    let mut data: Vec<u8> = Vec::new();
+    self.body.pack(&mut data)?;
+
+    let body = Packed {
+      data,
+      description: redaction.map(|redaction| self.body.describe(redaction)).unwrap_or_default(),
+      target: self.body.target(),
+      tuple: self.body.tuple().map(<[Value]>::to_vec),
+    };
+    Ok(Replay { header: self.header, body })
+  }
+}
+
+fn pack_datetime<W>(w: &mut W, time: &DateTime<FixedOffset>) -> Result<(), Error>
+  where W: Write,
+{
+  // Get the number of seconds and nanoseconds since the UNIX epoch
+  let seconds = time.timestamp();
+  let nanoseconds = time.timestamp_subsec_nanos();
+  // Offset is stored in minutes, time zone index is not known
+  let tzoffset = (time.offset().local_minus_utc() / 60) as i16;
+  let tzindex = 0;
+  // Write ext metadata with type 4 and size 16
+  // https://www.tarantool.io/en/doc/latest/dev_guide/internals/msgpack_extensions/#the-datetime-type
+  write_ext_meta(w, 16, 4)?;
+  // Write seconds as little-endian i64
+  w.write_i64::<LittleEndian>(seconds)?;
+  // Write nanoseconds as little-endian u32
+  w.write_u32::<LittleEndian>(nanoseconds)?;
+  // Write time zone offset as little-endian i16
+  w.write_i16::<LittleEndian>(tzoffset)?;
+  // Write time zone index as little-endian u16
+  w.write_u16::<LittleEndian>(tzindex)?;
+
+  Ok(())
+}
+
+/**
+  packs error as MP_ERROR with its stack,
+  error without stack (constructed on client side) is packed as ClientError
+*/
+fn pack_error<W>(w: &mut W, err: &TarantoolError) -> Result<(), Error>
+  where W: Write,
+{
+  let client = [ StackRecord {
+    err_type: "ClientError".into(),
+    message: err.message.clone(),
+    ..Default::default()
+  } ];
+  let stack = match err.stack.is_empty() {
+    true => &client[..],
+    false => err.stack.as_slice(),
+  };
+
+  let mut buf: Vec<u8> = Vec::new();
+  write_map_len(&mut buf, 1)?;
+  write_uint(&mut buf, 0)?; // MP_ERROR_STACK
+  write_array_len(&mut buf, pack_len(stack.len())?)?;
+
+  for record in stack {
+    write_map_len(&mut buf, if record.fields.is_empty() { 6 } else { 7 })?;
+    write_uint(&mut buf, 0)?;
+    write_str(&mut buf, &record.err_type)?;
+    write_uint(&mut buf, 1)?;
+    write_str(&mut buf, &record.file)?;
+    write_uint(&mut buf, 2)?;
+    write_uint(&mut buf, record.line)?;
+    write_uint(&mut buf, 3)?;
+    write_str(&mut buf, &record.message)?;
+    write_uint(&mut buf, 4)?;
+    write_uint(&mut buf, record.errno)?;
+    write_uint(&mut buf, 5)?;
+    write_uint(&mut buf, record.errcode)?;
+    if !record.fields.is_empty() {
+      write_uint(&mut buf, 6)?; // MP_ERROR_FIELDS
+      buf.extend_from_slice(&record.fields);
+    }
   }
+
+  write_ext_meta(w, pack_len(buf.len())?, 3)?;
+  w.write_all(&buf)?;
+
+  Ok(())
+}
+
+/// error of malformed value which is decoded
+fn invalid_ext<T: std::fmt::Display>(what: &str, value: T) -> Error {
+  io::Error::new(io::ErrorKind::InvalidData, format!("invalid {} {}", what, value)).into()
+}
+
+/// converts collection length to msgpack one, it fails for collections larger than u32::MAX
+fn pack_len(len: usize) -> Result<u32, Error> {
+  u32::try_from(len).map_err(|_| Error::EncodeError(format!(
+    "collection of {} elements is too large to pack", len,
+  )))
 }
 
 /// This represents header of request.
@@ -101,29 +333,200 @@ impl Request {
 pub struct Header {
   pub request: RequestType,
   pub sync: u64,
+  /// requests of one stream are processed sequentially
+  pub stream_id: Option<u64>,
+  /// id of transaction chain request belongs to
+  pub tsn: Option<u64>,
+  /// IPROTO_FLAGS, e.g. FLAG_COMMIT, it is not packed if empty
+  pub flags: u64,
+  /// additional fields packed after request type and sync
+  pub extra: Vec<(u64, Value)>,
+  /// sync is set by caller, so connection doesn't generate it
+  pub(crate) fixed_sync: bool,
+  /// request may be retried by retry policy of connection, it is not packed
+  pub idempotent: bool,
+  /// generation of connection stream of request is opened on, it is not packed
+  pub(crate) generation: Option<u64>,
+  /// span of retried request, it is started once before attempts
+  #[cfg(feature = "otel")]
+  pub(crate) trace: Option<opentelemetry::Context>,
 }
 
 #[allow(dead_code)]
 impl Header {
   /// Allows you to construct header.
   fn new(request: RequestType) -> Header {
-    Header { request, sync: 0 }
+    Header {
+      request, sync: 0,
+      stream_id: None, tsn: None, flags: 0,
+      extra: Vec::new(),
+      fixed_sync: false,
+      idempotent: false,
+      generation: None,
+      #[cfg(feature = "otel")]
+      trace: None,
+    }
   }
 
   /// Allows you to pack header.
-  fn pack(&self) -> Result<Vec<u8>, Error> {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
     // think that request will be u32 and sync u64
-    let mut buf: Vec<u8> = Vec::with_capacity(18);
+    buf.reserve(18);
+
+    let fields = [
+      (Field::StreamID, self.stream_id),
+      (Field::TSN, self.tsn),
+      (Field::Flags, Some(self.flags).filter(|&flags| flags != 0)),
+    ];
+    let fields = || fields.iter().filter_map(|&(field, value)| Some((field, value?)));
+
+    write_map_len(buf, pack_len(2 + fields().count() + self.extra.len())?)?;
+
+    write_uint(buf, Field::RequestType as u64)?;
+    write_uint(buf, self.request as u64)?;
 
-    write_map_len(&mut buf, 2)?;
+    write_uint(buf, Field::Sync as u64)?;
+    write_uint(buf, self.sync)?;
 
-    write_uint(&mut buf, Field::RequestType.to_u64().unwrap())?;
-    write_uint(&mut buf, self.request.to_u64().unwrap())?;
+    for (field, value) in fields() {
+      write_uint(buf, field as u64)?;
+      write_uint(buf, value)?;
+    }
+
+    for (field, value) in self.extra.iter() {
+      let overrides = *field == Field::RequestType as u64 || *field == Field::Sync as u64
+        || fields().any(|(f, _)| f as u64 == *field);
+      if overrides {
+        return Err(Error::UnexpectedField(*field));
+      }
+      write_uint(buf, *field)?;
+      value.pack(buf)?;
+    }
+
+    Ok(())
+  }
+}
+
+/**
+  This is builder of request with custom header.
+
+  Built request is packed as any other request,
+  but its sync is not replaced by connection.
+
+  Note: explicit sync must not collide with syncs of other pending requests,
+  such request is rejected with Error::SyncInUse. Connection generates syncs
+  starting from one and skips syncs which are in flight.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, Insert, RequestBuilder, RequestType};
+    # async fn f(conn: &Connection, insert_body: Insert, tsn: u64) -> Result<(), Error> {
+    let req = RequestBuilder::new(RequestType::Insert, insert_body)
+      .with_sync(1 << 62)
+      .with_stream_id(7)
+      .with_transaction(tsn, false)
+      .build();
+    let resp = conn.perform(req).await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug)]
+pub struct RequestBuilder {
+  request: Request,
+}
+
+impl RequestBuilder {
+  pub fn new<B: Body + 'static>(request: RequestType, body: B) -> RequestBuilder {
+    RequestBuilder { request: Request::new(request, body) }
+  }
+
+  pub fn with_sync(mut self, sync: u64) -> Self {
+    self.request.header.sync = sync;
+    self.request.header.fixed_sync = true;
+    self
+  }
+
+  pub fn with_stream_id(mut self, stream_id: u64) -> Self {
+    self.request.header.stream_id = Some(stream_id);
+    self
+  }
+
+  /// marks request as part of transaction chain, the last request of chain commits it
+  pub fn with_transaction(mut self, tsn: u64, commit: bool) -> Self {
+    self.request.header.tsn = Some(tsn);
+    match commit {
+      true => self.request.header.flags |= FLAG_COMMIT,
+      false => self.request.header.flags &= !FLAG_COMMIT,
+    }
+    self
+  }
+
+  /// sets IPROTO_FLAGS as is
+  pub fn with_flags(mut self, flags: u64) -> Self {
+    self.request.header.flags = flags;
+    self
+  }
+
+  /// same as Request::with_header_field
+  pub fn with_header_field<V: Into<Value>>(mut self, field: u64, value: V) -> Self {
+    self.request = self.request.with_header_field(field, value);
+    self
+  }
+
+  pub fn with_idempotent(mut self) -> Self {
+    self.request = self.request.with_idempotent();
+    self
+  }
+
+  pub fn build(self) -> Request {
+    self.request
+  }
+}
 
-    write_uint(&mut buf, Field::Sync.to_u64().unwrap())?;
-    write_uint(&mut buf, self.sync)?;
+/// Body packed in advance, it is sent by retries of request.
+#[derive(Debug, Clone)]
+pub(crate) struct Packed {
+  data: Vec<u8>,
+  description: String,
+  target: (Option<String>, Option<String>),
+  tuple: Option<Vec<Value>>,
+}
+
+impl Body for Packed {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.extend_from_slice(&self.data);
+    Ok(())
+  }
+
+  fn describe(&self, _redaction: Redaction) -> String {
+    self.description.clone()
+  }
+
+  fn target(&self) -> (Option<String>, Option<String>) {
+    self.target.clone()
+  }
+
+  fn tuple(&self) -> Option<&[Value]> {
+    self.tuple.as_deref()
+  }
+}
+
+/// Request which may be sent several times, see Request::replay.
+#[derive(Debug)]
+pub(crate) struct Replay {
+  header: Header,
+  body: Packed,
+}
+
+impl Replay {
+  pub(crate) fn request(&self) -> Request {
+    Request { header: self.header.clone(), body: Box::new(self.body.clone()) }
+  }
+}
 
-    Ok(buf)
+impl From<Request> for RequestBuilder {
+  fn from(request: Request) -> Self {
+    RequestBuilder { request }
   }
 }
 
@@ -140,9 +543,124 @@ pub enum Value {
   Bool(bool), Null,
   Str(String), Bin(Vec<u8>),
   Array(Vec<Value>),
+  /// map of key value pairs in given order, e.g. lua table or options
+  Map(Vec<(Value, Value)>),
   Uuid(Uuid), 
-  DateTime(NaiveDateTime),Decimal(Decimal)
-  
+  DateTime(NaiveDateTime),Decimal(Decimal),
+  /// datetime which keeps its offset, naive datetime is packed as UTC one
+  DateTimeTz(DateTime<FixedOffset>),
+  Interval(Interval),
+  /// error object (MP_ERROR) which is passed as argument or stored in tuple, tarantool 2.10+
+  Error(TarantoolError),
+}
+
+/**
+  This represents tarantool datetime interval,
+  it is packed as MP_EXT with type 6.
+  Durations of chrono and std are converted into it,
+  e.g. `conn.call(Call { function: "shift".into(), args: (Duration::from_secs(60),).into_tuple() })`.
+
+  see more here
+  https://www.tarantool.io/en/doc/latest/dev_guide/internals/msgpack_extensions/#the-interval-type
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Interval {
+  pub year: i64,
+  pub month: i64,
+  pub week: i64,
+  pub day: i64,
+  pub hour: i64,
+  pub min: i64,
+  pub sec: i64,
+  pub nsec: i64,
+  pub adjust: IntervalAdjust,
+}
+
+/// Day adjustment mode used in month and year arithmetic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum IntervalAdjust {
+  Excess = 0,
+  #[default]
+  None = 1,
+  Last = 2,
+}
+
+impl Interval {
+  fn from_seconds(seconds: i64, nsec: i64) -> Interval {
+    Interval {
+      day: seconds / 86400,
+      hour: seconds % 86400 / 3600,
+      min: seconds % 3600 / 60,
+      sec: seconds % 60,
+      nsec,
+      ..Default::default()
+    }
+  }
+
+  fn pack<W>(&self, w: &mut W) -> Result<(), Error>
+    where W: Write,
+  {
+    let fields = [
+      self.year, self.month, self.week, self.day,
+      self.hour, self.min, self.sec, self.nsec,
+    ];
+
+    // every field is uint8 key followed by MP_INT value, zero fields are omitted
+    let mut buf: Vec<u8> = Vec::with_capacity(1 + 10 * 9);
+    buf.push(0);
+
+    for (key, &value) in fields.iter().enumerate() {
+      if value == 0 { continue; }
+      buf.push(key as u8);
+      write_sint(&mut buf, value)?;
+      buf[0] += 1;
+    }
+
+    if self.adjust != IntervalAdjust::None {
+      buf.push(8);
+      write_uint(&mut buf, self.adjust as u64)?;
+      buf[0] += 1;
+    }
+
+    write_ext_meta(w, pack_len(buf.len())?, 6)?;
+    w.write_all(&buf)?;
+
+    Ok(())
+  }
+
+  fn unpack(data: &[u8]) -> Result<Interval, Error> {
+    let mut cur = Cursor::new(data);
+    let mut interval = Interval::default();
+
+    for _ in 0..cur.read_u8()? {
+      let key = cur.read_u8()?;
+      let value: i64 = rmp::decode::read_int(&mut cur)?;
+
+      let field = match key {
+        0 => &mut interval.year,
+        1 => &mut interval.month,
+        2 => &mut interval.week,
+        3 => &mut interval.day,
+        4 => &mut interval.hour,
+        5 => &mut interval.min,
+        6 => &mut interval.sec,
+        7 => &mut interval.nsec,
+        8 => {
+          interval.adjust = match value {
+            0 => IntervalAdjust::Excess,
+            1 => IntervalAdjust::None,
+            2 => IntervalAdjust::Last,
+            _ => return Err(invalid_ext("interval adjust", value)),
+          };
+          continue;
+        },
+        _ => return Err(invalid_ext("interval field", key)),
+      };
+      *field = value;
+    }
+
+    Ok(interval)
+  }
 }
 
 macro_rules! impl_value_from_as {
@@ -183,12 +701,63 @@ impl From<NaiveDateTime> for Value {
     Value::DateTime(value)
   }
 }
+impl From<TarantoolError> for Value {
+  fn from(value: TarantoolError) -> Self {
+    Value::Error(value)
+  }
+}
+
+impl From<DateTime<FixedOffset>> for Value {
+  fn from(value: DateTime<FixedOffset>) -> Self {
+    Value::DateTimeTz(value)
+  }
+}
+
+impl From<DateTime<Utc>> for Value {
+  fn from(value: DateTime<Utc>) -> Self {
+    Value::DateTimeTz(value.fixed_offset())
+  }
+}
+
 impl From<Decimal> for Value {
   fn from(value: Decimal) -> Self {
     Value::Decimal(value)
   }
 }
 
+impl From<Interval> for Value {
+  fn from(value: Interval) -> Self {
+    Value::Interval(value)
+  }
+}
+
+/// duration is split into days, hours, minutes, seconds and nanoseconds of the same sign
+impl From<chrono::Duration> for Interval {
+  fn from(value: chrono::Duration) -> Self {
+    Interval::from_seconds(value.num_seconds(), value.subsec_nanos().into())
+  }
+}
+
+/// durations longer than i64::MAX seconds are saturated
+impl From<std::time::Duration> for Interval {
+  fn from(value: std::time::Duration) -> Self {
+    let seconds = i64::try_from(value.as_secs()).unwrap_or(i64::MAX);
+    Interval::from_seconds(seconds, value.subsec_nanos().into())
+  }
+}
+
+impl From<chrono::Duration> for Value {
+  fn from(value: chrono::Duration) -> Self {
+    Value::Interval(value.into())
+  }
+}
+
+impl From<std::time::Duration> for Value {
+  fn from(value: std::time::Duration) -> Self {
+    Value::Interval(value.into())
+  }
+}
+
 
 impl From<bool> for Value {
   fn from(value: bool) -> Self {
@@ -230,12 +799,8 @@ impl<T: Into<Value>> From<Option<T>> for Value {
 }
 
 impl<T: Into<Value>> From<Vec<T>> for Value {
-  fn from(mut value: Vec<T>) -> Self {
-    let mut new_vec = Vec::with_capacity(value.len());
-    for i in 0..value.len() {
-      new_vec.push(value.remove(i).into());
-    }
-    Value::Array(new_vec)
+  fn from(value: Vec<T>) -> Self {
+    Value::Array(value.into_tuple())
   }
 }
 
@@ -250,859 +815,714 @@ impl<T> From<&[T]> for Value
   }
 }
 
-/**
-  This trait provides shortcuts for Vec<Value>.
+impl<K, V> From<HashMap<K, V>> for Value
+  where K: Into<Value>, V: Into<Value>
+{
+  fn from(value: HashMap<K, V>) -> Self {
+    Value::Map(value.into_iter()
+      .map(|(key, value)| (key.into(), value.into()))
+      .collect()
+    )
+  }
+}
 
-  instead of
-  ```rust
-    vec![ Value::Int(1), Value::Str("test") ]
-  ```
-  you can use
-  ```rust
-    ( 1, "test" ).to_tuple()
-  ```
+/// pairs are packed in key order
+impl<K, V> From<BTreeMap<K, V>> for Value
+  where K: Into<Value>, V: Into<Value>
+{
+  fn from(value: BTreeMap<K, V>) -> Self {
+    Value::Map(value.into_iter()
+      .map(|(key, value)| (key.into(), value.into()))
+      .collect()
+    )
+  }
+}
 
-  It works for slice, vec, and tuples with up to 10 elements.
-*/
-pub trait IntoTuple {
-  fn into_tuple(self) -> Vec<Value>;
+/// decodes msgpack value, tarantool extensions become their variants
+impl TryFrom<rmpv::Value> for Value {
+  type Error = Error;
+
+  fn try_from(value: rmpv::Value) -> Result<Self, Error> {
+    Ok(match value {
+      rmpv::Value::Nil => Value::Null,
+      rmpv::Value::Boolean(val) => Value::Bool(val),
+      rmpv::Value::Integer(val) => match (val.as_u64(), val.as_i64()) {
+        (Some(val), _) => Value::UInt(val),
+        (None, Some(val)) => Value::Int(val),
+        (None, None) => return Err(invalid_ext("integer", val)),
+      },
+      rmpv::Value::F32(val) => Value::F32(val),
+      rmpv::Value::F64(val) => Value::F64(val),
+      rmpv::Value::String(val) => Value::Str(val.into_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "string is not valid utf-8"))?),
+      rmpv::Value::Binary(val) => Value::Bin(val),
+      rmpv::Value::Array(vals) => Value::Array(vals.into_iter()
+        .map(Value::try_from)
+        .collect::<Result<_, _>>()?),
+      rmpv::Value::Map(pairs) => Value::Map(pairs.into_iter()
+        .map(|(key, val)| Ok((Value::try_from(key)?, Value::try_from(val)?)))
+        .collect::<Result<_, Error>>()?),
+      rmpv::Value::Ext(ty, data) => Value::unpack_ext(ty, &data)?,
+    })
+  }
 }
 
-impl<T> IntoTuple for Vec<T> where T: Into<Value> {
-  fn into_tuple(mut self) -> Vec<Value> {
-    let mut new_vec = Vec::with_capacity(self.len());
-    for i in 0..self.len() {
-      new_vec.push(self.remove(i).into());
+impl Value {
+  /// name of value type as tarantool calls it, e.g. "unsigned" or "map"
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Value::Int(_) => "integer",
+      Value::UInt(_) => "unsigned",
+      Value::F32(_) => "float",
+      Value::F64(_) => "double",
+      Value::Bool(_) => "boolean",
+      Value::Null => "nil",
+      Value::Str(_) => "string",
+      Value::Bin(_) => "varbinary",
+      Value::Array(_) => "array",
+      Value::Map(_) => "map",
+      Value::Uuid(_) => "uuid",
+      Value::DateTime(_) | Value::DateTimeTz(_) => "datetime",
+      Value::Decimal(_) => "decimal",
+      Value::Interval(_) => "interval",
+      Value::Error(_) => "error",
     }
-    new_vec
   }
-}
 
-impl<T> IntoTuple for &[T]
-  where T: Into<Value> + Clone
-{
-  fn into_tuple(self) -> Vec<Value> {
-    self.iter().map(|v| v.clone().into()).collect()
+  pub fn is_null(&self) -> bool {
+    matches!(self, Value::Null)
   }
-}
 
-impl IntoTuple for ()
-{
-  fn into_tuple(self) -> Vec<Value> {
-    Vec::new()
+  pub fn as_bool(&self) -> Option<bool> {
+    match *self {
+      Value::Bool(val) => Some(val),
+      _ => None,
+    }
   }
-}
 
-impl<T1> IntoTuple for (T1,)
-  where T1: Into<Value>
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![ self.0.into() ]
+  /// integer which fits into u64
+  pub fn as_u64(&self) -> Option<u64> {
+    match *self {
+      Value::UInt(val) => Some(val),
+      Value::Int(val) => u64::try_from(val).ok(),
+      _ => None,
+    }
   }
-}
 
-impl<T1, T2> IntoTuple for (T1, T2)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![ self.0.into(), self.1.into() ]
+  /// integer which fits into i64
+  pub fn as_i64(&self) -> Option<i64> {
+    match *self {
+      Value::Int(val) => Some(val),
+      Value::UInt(val) => i64::try_from(val).ok(),
+      _ => None,
+    }
   }
-}
 
-impl<T1, T2, T3> IntoTuple for (T1, T2, T3)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![ self.0.into(), self.1.into(), self.2.into() ]
+  pub fn as_f64(&self) -> Option<f64> {
+    match *self {
+      Value::F64(val) => Some(val),
+      Value::F32(val) => Some(val.into()),
+      _ => None,
+    }
   }
-}
 
-impl<T1, T2, T3, T4> IntoTuple for (T1, T2, T3, T4)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-    ]
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Value::Str(val) => Some(val),
+      _ => None,
+    }
   }
-}
 
+  pub fn as_array(&self) -> Option<&[Value]> {
+    match self {
+      Value::Array(vals) => Some(vals),
+      _ => None,
+    }
+  }
 
-impl<T1, T2, T3, T4, T5> IntoTuple for (T1, T2, T3, T4, T5)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-    ]
+  pub fn as_map(&self) -> Option<&[(Value, Value)]> {
+    match self {
+      Value::Map(pairs) => Some(pairs),
+      _ => None,
+    }
   }
-}
 
-impl<T1, T2, T3, T4, T5, T6> IntoTuple for (T1, T2, T3, T4, T5, T6)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-    ]
+  /// integer of any sign, it is used to compare Int with UInt
+  fn as_i128(&self) -> Option<i128> {
+    match *self {
+      Value::Int(val) => Some(val.into()),
+      Value::UInt(val) => Some(val.into()),
+      _ => None,
+    }
   }
 }
 
-impl<T1, T2, T3, T4, T5, T6, T7> IntoTuple for (T1, T2, T3, T4, T5, T6, T7)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-    ]
+/**
+  Integers are equal regardless of their variant, e.g. `Value::Int(1) == Value::UInt(1)`.
+
+  Floats are widened to f64 and compared with f64::total_cmp,
+  so NaN is equal to itself and -0.0 is not equal to 0.0,
+  it makes Value Eq and allows it to be hash key.
+  Integers are never equal to floats.
+
+  Naive datetime is taken as UTC one, datetimes are equal if they are the same instant.
+*/
+impl PartialEq for Value {
+  fn eq(&self, other: &Value) -> bool {
+    match (self, other) {
+      (Value::Map(a), Value::Map(b)) => a == b,
+      (Value::Interval(a), Value::Interval(b)) => a == b,
+      (Value::Error(a), Value::Error(b)) => a == b,
+      _ => self.partial_cmp(other) == Some(Ordering::Equal),
+    }
   }
 }
 
-impl<T1, T2, T3, T4, T5, T6, T7, T8> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-    ]
+impl Eq for Value {}
+
+impl Hash for Value {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    match self {
+      Value::Int(_) | Value::UInt(_) => self.as_i128().hash(state),
+      &Value::F32(val) => f64::from(val).to_bits().hash(state),
+      &Value::F64(val) => val.to_bits().hash(state),
+      Value::Bool(val) => val.hash(state),
+      Value::Null => {},
+      Value::Str(val) => val.hash(state),
+      Value::Bin(val) => val.hash(state),
+      Value::Array(vals) => vals.hash(state),
+      Value::Map(pairs) => pairs.hash(state),
+      Value::Uuid(val) => val.hash(state),
+      Value::DateTime(val) => val.hash(state),
+      Value::DateTimeTz(val) => val.naive_utc().hash(state),
+      Value::Decimal(val) => val.hash(state),
+      Value::Interval(val) => val.hash(state),
+      Value::Error(val) => val.hash(state),
+    }
   }
 }
 
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-    ]
+/**
+  Values of the same type are ordered, see PartialEq for integers, floats and datetimes.
+  Arrays are ordered lexicographically, maps, intervals and errors are only equal or not.
+*/
+impl PartialOrd for Value {
+  fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+    match (self, other) {
+      (Value::Int(_) | Value::UInt(_), Value::Int(_) | Value::UInt(_)) =>
+        Some(self.as_i128()?.cmp(&other.as_i128()?)),
+      (Value::F32(_) | Value::F64(_), Value::F32(_) | Value::F64(_)) =>
+        Some(self.as_f64()?.total_cmp(&other.as_f64()?)),
+      (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+      (Value::Null, Value::Null) => Some(Ordering::Equal),
+      (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+      (Value::Bin(a), Value::Bin(b)) => Some(a.cmp(b)),
+      (Value::Array(a), Value::Array(b)) => a.partial_cmp(b),
+      (Value::Uuid(a), Value::Uuid(b)) => Some(a.cmp(b)),
+      (Value::DateTime(a), Value::DateTime(b)) => Some(a.cmp(b)),
+      (Value::DateTime(a), Value::DateTimeTz(b)) => Some(a.cmp(&b.naive_utc())),
+      (Value::DateTimeTz(a), Value::DateTime(b)) => Some(a.naive_utc().cmp(b)),
+      (Value::DateTimeTz(a), Value::DateTimeTz(b)) => Some(a.cmp(b)),
+      (Value::Decimal(a), Value::Decimal(b)) => Some(a.cmp(b)),
+      (Value::Map(_), Value::Map(_)) | (Value::Interval(_), Value::Interval(_))
+        | (Value::Error(_), Value::Error(_)) if self == other => Some(Ordering::Equal),
+      _ => None,
+    }
   }
 }
 
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-    T15: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-      self.14.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-    T15: Into<Value>,
-    T16: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-      self.14.into(),
-      self.15.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-    T15: Into<Value>,
-    T16: Into<Value>,
-    T17: Into<Value>,
-{
-  fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-      self.14.into(),
-      self.15.into(),
-      self.16.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-    T15: Into<Value>,
-    T16: Into<Value>,
-    T17: Into<Value>,
-    T18: Into<Value>,
-{
+/**
+  Value is displayed as JSON, binary is base64 encoded, uuids and datetimes are strings,
+  decimals are numbers, intervals are objects of their non-zero fields
+  and errors are their messages. Non-string map keys are displayed as strings.
+*/
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Value::Int(val) => write!(f, "{}", val),
+      Value::UInt(val) => write!(f, "{}", val),
+      Value::F32(val) => write!(f, "{}", val),
+      Value::F64(val) => write!(f, "{}", val),
+      Value::Bool(val) => write!(f, "{}", val),
+      Value::Null => f.write_str("null"),
+      Value::Str(val) => write_json_str(f, val),
+      Value::Bin(val) => write_json_str(f, &base64::encode(val)),
+      Value::Array(vals) => {
+        f.write_str("[")?;
+        for (i, val) in vals.iter().enumerate() {
+          if i > 0 {
+            f.write_str(", ")?;
+          }
+          write!(f, "{}", val)?;
+        }
+        f.write_str("]")
+      },
+      Value::Map(pairs) => {
+        f.write_str("{")?;
+        for (i, (key, val)) in pairs.iter().enumerate() {
+          if i > 0 {
+            f.write_str(", ")?;
+          }
+          match key {
+            Value::Str(key) => write_json_str(f, key)?,
+            key => write_json_str(f, &key.to_string())?,
+          }
+          write!(f, ": {}", val)?;
+        }
+        f.write_str("}")
+      },
+      Value::Uuid(val) => write!(f, "\"{}\"", val),
+      Value::DateTime(val) => write!(f, "\"{}\"", val.format(NAIVE_DATETIME_FORMAT)),
+      Value::DateTimeTz(val) => write!(f, "\"{}\"", val.to_rfc3339()),
+      Value::Decimal(val) => write!(f, "{}", val),
+      Value::Interval(val) => {
+        let fields = [
+          ("year", val.year), ("month", val.month), ("week", val.week), ("day", val.day),
+          ("hour", val.hour), ("min", val.min), ("sec", val.sec), ("nsec", val.nsec),
+        ];
+        f.write_str("{")?;
+        for (i, (name, val)) in fields.iter().filter(|(_, val)| *val != 0).enumerate() {
+          if i > 0 {
+            f.write_str(", ")?;
+          }
+          write!(f, "\"{}\": {}", name, val)?;
+        }
+        f.write_str("}")
+      },
+      Value::Error(err) => write_json_str(f, &err.message),
+    }
+  }
+}
+
+/// naive datetime is displayed as RFC 3339 one without offset
+const NAIVE_DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+fn write_json_str(f: &mut fmt::Formatter<'_>, val: &str) -> fmt::Result {
+  f.write_str(&serde_json::Value::from(val).to_string())
+}
+
+macro_rules! impl_int_try_from_value {
+  ($type:ident) => {
+    /// integer is converted if it fits into type, otherwise Error::OutOfRange is returned
+    impl TryFrom<Value> for $type {
+      type Error = Error;
+
+      fn try_from(value: Value) -> Result<Self, Error> {
+        let out_of_range = |value: i128| Error::OutOfRange { expected: stringify!($type), value };
+        match value {
+          Value::UInt(val) => $type::try_from(val).map_err(|_| out_of_range(val as i128)),
+          Value::Int(val) => $type::try_from(val).map_err(|_| out_of_range(val as i128)),
+          value => Err(Error::TypeMismatch { expected: stringify!($type), found: value.type_name() }),
+        }
+      }
+    }
+  };
+}
+
+impl_int_try_from_value!(u64);
+impl_int_try_from_value!(usize);
+impl_int_try_from_value!(u32);
+impl_int_try_from_value!(u16);
+impl_int_try_from_value!(u8);
+
+impl_int_try_from_value!(i64);
+impl_int_try_from_value!(isize);
+impl_int_try_from_value!(i32);
+impl_int_try_from_value!(i16);
+impl_int_try_from_value!(i8);
+
+macro_rules! impl_variant_try_from_value {
+  ($variant:ident, $type:ty) => {
+    impl TryFrom<Value> for $type {
+      type Error = Error;
+
+      fn try_from(value: Value) -> Result<Self, Error> {
+        match value {
+          Value::$variant(val) => Ok(val),
+          value => Err(Error::TypeMismatch { expected: stringify!($type), found: value.type_name() }),
+        }
+      }
+    }
+  };
+}
+
+impl_variant_try_from_value!(Bool, bool);
+impl_variant_try_from_value!(Str, String);
+impl_variant_try_from_value!(Bin, Vec<u8>);
+impl_variant_try_from_value!(Array, Vec<Value>);
+impl_variant_try_from_value!(Map, Vec<(Value, Value)>);
+impl_variant_try_from_value!(Uuid, Uuid);
+impl_variant_try_from_value!(Decimal, Decimal);
+impl_variant_try_from_value!(Interval, Interval);
+impl_variant_try_from_value!(Error, TarantoolError);
+
+/// float is widened
+impl TryFrom<Value> for f64 {
+  type Error = Error;
+
+  fn try_from(value: Value) -> Result<Self, Error> {
+    value.as_f64().ok_or(Error::TypeMismatch { expected: "f64", found: value.type_name() })
+  }
+}
+
+impl TryFrom<Value> for f32 {
+  type Error = Error;
+
+  fn try_from(value: Value) -> Result<Self, Error> {
+    match value {
+      Value::F32(val) => Ok(val),
+      value => Err(Error::TypeMismatch { expected: "f32", found: value.type_name() }),
+    }
+  }
+}
+
+/// datetime with offset is converted to UTC
+impl TryFrom<Value> for NaiveDateTime {
+  type Error = Error;
+
+  fn try_from(value: Value) -> Result<Self, Error> {
+    match value {
+      Value::DateTime(time) => Ok(time),
+      Value::DateTimeTz(time) => Ok(time.naive_utc()),
+      value => Err(Error::TypeMismatch { expected: "NaiveDateTime", found: value.type_name() }),
+    }
+  }
+}
+
+/// naive datetime is taken as UTC one
+impl TryFrom<Value> for DateTime<FixedOffset> {
+  type Error = Error;
+
+  fn try_from(value: Value) -> Result<Self, Error> {
+    match value {
+      Value::DateTimeTz(time) => Ok(time),
+      Value::DateTime(time) => Ok(time.and_utc().fixed_offset()),
+      value => Err(Error::TypeMismatch { expected: "DateTime", found: value.type_name() }),
+    }
+  }
+}
+
+/// numbers become integers if they fit, objects become maps with string keys
+#[cfg(feature = "json")]
+impl From<serde_json::Value> for Value {
+  fn from(value: serde_json::Value) -> Self {
+    use serde_json::Value as Json;
+
+    match value {
+      Json::Null => Value::Null,
+      Json::Bool(val) => Value::Bool(val),
+      Json::Number(val) => val.as_u64().map(Value::UInt)
+        .or_else(|| val.as_i64().map(Value::Int))
+        .unwrap_or_else(|| Value::F64(val.as_f64().unwrap_or(f64::NAN))),
+      Json::String(val) => Value::Str(val),
+      Json::Array(vals) => Value::Array(vals.into_iter().map(Value::from).collect()),
+      Json::Object(pairs) => Value::Map(pairs.into_iter()
+        .map(|(key, val)| (Value::Str(key), Value::from(val)))
+        .collect()),
+    }
+  }
+}
+
+/**
+  converts value as it is displayed, but decimals become strings to keep their precision.
+  Intervals, errors and infinite or NaN floats can't be converted.
+*/
+#[cfg(feature = "json")]
+impl TryFrom<Value> for serde_json::Value {
+  type Error = Error;
+
+  fn try_from(value: Value) -> Result<Self, Error> {
+    use serde_json::Value as Json;
+
+    let mismatch = |value: &Value| Error::TypeMismatch { expected: "json value", found: value.type_name() };
+
+    Ok(match value {
+      Value::Int(val) => val.into(),
+      Value::UInt(val) => val.into(),
+      Value::F32(_) | Value::F64(_) => value.as_f64()
+        .and_then(serde_json::Number::from_f64)
+        .map(Json::Number)
+        .ok_or_else(|| mismatch(&value))?,
+      Value::Bool(val) => Json::Bool(val),
+      Value::Null => Json::Null,
+      Value::Str(val) => Json::String(val),
+      Value::Bin(val) => Json::String(base64::encode(val)),
+      Value::Array(vals) => Json::Array(vals.into_iter()
+        .map(Json::try_from)
+        .collect::<Result<_, _>>()?),
+      Value::Map(pairs) => Json::Object(pairs.into_iter()
+        .map(|(key, val)| {
+          let key = match key {
+            Value::Str(key) => key,
+            key => key.to_string(),
+          };
+          Ok((key, Json::try_from(val)?))
+        })
+        .collect::<Result<_, Error>>()?),
+      Value::Uuid(val) => Json::String(val.to_string()),
+      Value::DateTime(val) => Json::String(val.format(NAIVE_DATETIME_FORMAT).to_string()),
+      Value::DateTimeTz(val) => Json::String(val.to_rfc3339()),
+      Value::Decimal(val) => Json::String(val.to_string()),
+      value @ Value::Interval(_) | value @ Value::Error(_) => return Err(mismatch(&value)),
+    })
+  }
+}
+
+/**
+  This trait provides shortcuts for Vec<Value>.
+
+  instead of
+  ```rust
+    # use alopecosa::{IntoTuple, Value};
+    # let tuple =
+    vec![ Value::Int(1), Value::Str("test".into()) ]
+    # ;
+  ```
+  you can use
+  ```rust
+    # use alopecosa::{IntoTuple, Value};
+    # let tuple = vec![ Value::Int(1), Value::Str("test".into()) ];
+    # assert_eq!(tuple,
+    ( 1, "test" ).into_tuple()
+    # );
+  ```
+
+  It works for vec, deque, slice, array and tuples with up to 32 elements,
+  references to arrays and tuples are cloned.
+*/
+pub trait IntoTuple {
+  fn into_tuple(self) -> Vec<Value>;
+}
+
+impl<T> IntoTuple for Vec<T> where T: Into<Value> {
   fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-      self.14.into(),
-      self.15.into(),
-      self.16.into(),
-      self.17.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-    T15: Into<Value>,
-    T16: Into<Value>,
-    T17: Into<Value>,
-    T18: Into<Value>,
-    T19: Into<Value>,
-{
+    self.into_iter().map(Into::into).collect()
+  }
+}
+
+impl<T> IntoTuple for VecDeque<T> where T: Into<Value> {
   fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-      self.14.into(),
-      self.15.into(),
-      self.16.into(),
-      self.17.into(),
-      self.18.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19, T20> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19, T20)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-    T15: Into<Value>,
-    T16: Into<Value>,
-    T17: Into<Value>,
-    T18: Into<Value>,
-    T19: Into<Value>,
-    T20: Into<Value>,
-{
+    self.into_iter().map(Into::into).collect()
+  }
+}
+
+impl<T, const N: usize> IntoTuple for [T; N] where T: Into<Value> {
   fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-      self.14.into(),
-      self.15.into(),
-      self.16.into(),
-      self.17.into(),
-      self.18.into(),
-      self.19.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19, T20, T21> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19, T20, T21)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-    T15: Into<Value>,
-    T16: Into<Value>,
-    T17: Into<Value>,
-    T18: Into<Value>,
-    T19: Into<Value>,
-    T20: Into<Value>,
-    T21: Into<Value>,
+    IntoIterator::into_iter(self).map(Into::into).collect()
+  }
+}
+
+/// owned slice is moved, borrowed one is cloned
+impl<T> IntoTuple for Cow<'_, [T]>
+  where T: Into<Value> + Clone
 {
   fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-      self.14.into(),
-      self.15.into(),
-      self.16.into(),
-      self.17.into(),
-      self.18.into(),
-      self.19.into(),
-      self.20.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19, T20, T21, T22> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19, T20, T21, T22)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-    T15: Into<Value>,
-    T16: Into<Value>,
-    T17: Into<Value>,
-    T18: Into<Value>,
-    T19: Into<Value>,
-    T20: Into<Value>,
-    T21: Into<Value>,
-    T22: Into<Value>,
+    match self {
+      Cow::Owned(vals) => vals.into_tuple(),
+      Cow::Borrowed(vals) => vals.into_tuple(),
+    }
+  }
+}
+
+impl<T> IntoTuple for &[T]
+  where T: Into<Value> + Clone
 {
   fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-      self.14.into(),
-      self.15.into(),
-      self.16.into(),
-      self.17.into(),
-      self.18.into(),
-      self.19.into(),
-      self.20.into(),
-      self.21.into(),
-    ]
-  }
-}
-
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19, T20, T21, T22, T23> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19, T20, T21, T22, T23)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-    T15: Into<Value>,
-    T16: Into<Value>,
-    T17: Into<Value>,
-    T18: Into<Value>,
-    T19: Into<Value>,
-    T20: Into<Value>,
-    T21: Into<Value>,
-    T22: Into<Value>,
-    T23: Into<Value>,
+    self.iter().map(|v| v.clone().into()).collect()
+  }
+}
+
+/// shared slice and array of references are cloned
+impl<T, const N: usize> IntoTuple for &[T; N]
+  where T: Into<Value> + Clone
 {
   fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-      self.14.into(),
-      self.15.into(),
-      self.16.into(),
-      self.17.into(),
-      self.18.into(),
-      self.19.into(),
-      self.20.into(),
-      self.21.into(),
-      self.22.into(),
-    ]
-  }
-}
-
-impl<T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19, T20, T21, T22, T23, T24> IntoTuple for (T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16, T17, T18, T19, T20, T21, T22, T23, T24)
-  where
-    T1: Into<Value>,
-    T2: Into<Value>,
-    T3: Into<Value>,
-    T4: Into<Value>,
-    T5: Into<Value>,
-    T6: Into<Value>,
-    T7: Into<Value>,
-    T8: Into<Value>,
-    T9: Into<Value>,
-    T10: Into<Value>,
-    T11: Into<Value>,
-    T12: Into<Value>,
-    T13: Into<Value>,
-    T14: Into<Value>,
-    T15: Into<Value>,
-    T16: Into<Value>,
-    T17: Into<Value>,
-    T18: Into<Value>,
-    T19: Into<Value>,
-    T20: Into<Value>,
-    T21: Into<Value>,
-    T22: Into<Value>,
-    T23: Into<Value>,
-    T24: Into<Value>,
+    self.as_slice().into_tuple()
+  }
+}
+
+impl IntoTuple for ()
 {
   fn into_tuple(self) -> Vec<Value> {
-    vec![
-      self.0.into(),
-      self.1.into(),
-      self.2.into(),
-      self.3.into(),
-      self.4.into(),
-      self.5.into(),
-      self.6.into(),
-      self.7.into(),
-      self.8.into(),
-      self.9.into(),
-      self.10.into(),
-      self.11.into(),
-      self.12.into(),
-      self.13.into(),
-      self.14.into(),
-      self.15.into(),
-      self.16.into(),
-      self.17.into(),
-      self.18.into(),
-      self.19.into(),
-      self.20.into(),
-      self.21.into(),
-      self.22.into(),
-      self.23.into(),
-    ]
+    Vec::new()
+  }
+}
+
+/// implements IntoTuple for tuple of given types and for reference to it
+macro_rules! impl_into_tuple {
+  ($($name:ident . $index:tt),+) => {
+    impl<$($name),+> IntoTuple for ($($name,)+)
+      where $($name: Into<Value>),+
+    {
+      fn into_tuple(self) -> Vec<Value> {
+        vec![ $(self.$index.into()),+ ]
+      }
+    }
+
+    impl<$($name),+> IntoTuple for &($($name,)+)
+      where $($name: Into<Value> + Clone),+
+    {
+      fn into_tuple(self) -> Vec<Value> {
+        vec![ $(self.$index.clone().into()),+ ]
+      }
+    }
+  };
+}
+
+/// implements IntoTuple for tuples of every arity from the first one
+macro_rules! impl_into_tuples {
+  ($($name:ident . $index:tt),+ ;) => {
+    impl_into_tuple!($($name.$index),+);
+  };
+  ($($name:ident . $index:tt),+ ; $next:ident . $next_index:tt $(, $rest:ident . $rest_index:tt)*) => {
+    impl_into_tuple!($($name.$index),+);
+    impl_into_tuples!($($name.$index),+, $next.$next_index ; $($rest.$rest_index),*);
+  };
+}
+
+impl_into_tuples!(T1.0 ; T2.1, T3.2, T4.3, T5.4, T6.5, T7.6, T8.7, T9.8, T10.9, T11.10, T12.11, T13.12, T14.13, T15.14, T16.15, T17.16, T18.17, T19.18, T20.19, T21.20, T22.21, T23.22, T24.23, T25.24, T26.25, T27.26, T28.27, T29.28, T30.29, T31.30, T32.31);
+
+/**
+  This trait converts key of select, update and delete into its parts.
+
+  Scalar is key of one part, tuples, arrays and vecs are keys of their elements
+  and NoKey is empty key, which matches all tuples.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, NoKey, SelectOptions, TarantoolClient};
+    # type User = (u64, String, u32);
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    # let users = conn.space("users");
+    let user: Option<User> = users.get(1u64).await?;
+    let anns: Vec<User> = users.index("name_age").select(( "ann", 30u32 ), SelectOptions::new()).await?;
+    let all: Vec<User> = users.select(NoKey, SelectOptions::new()).await?;
+    # Ok(()) }
+  ```
+*/
+pub trait IntoKey {
+  fn into_key(self) -> Vec<Value>;
+}
+
+impl<T: IntoTuple> IntoKey for T {
+  fn into_key(self) -> Vec<Value> {
+    self.into_tuple()
+  }
+}
+
+/// Empty key, see IntoKey.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoKey;
+
+impl IntoKey for NoKey {
+  fn into_key(self) -> Vec<Value> {
+    Vec::new()
   }
 }
 
+macro_rules! impl_scalar_into_key {
+  ($($type:ty),+ $(,)?) => {
+    $(
+      impl IntoKey for $type {
+        fn into_key(self) -> Vec<Value> {
+          vec![ self.into() ]
+        }
+      }
+    )+
+  };
+}
+
+impl_scalar_into_key!(
+  u64, usize, u32, u16, i64, isize, i32, i16, i8, f32, f64, bool,
+  String, &str, Uuid, Decimal, NaiveDateTime, DateTime<FixedOffset>, DateTime<Utc>, Value,
+);
+
 impl Value {
-  fn pack<W>(&self, w: &mut W) -> Result<(), Error>
+  /**
+    decodes one msgpack value from reader,
+    decimal, uuid, datetime and interval extensions are supported.
+
+    Datetime with zero offset is decoded as naive UTC DateTime,
+    datetime with other offset is DateTimeTz which keeps the offset,
+    timezone index isn't kept.
+  */
+  pub fn unpack<R>(reader: &mut R) -> Result<Value, Error>
+    where R: Read,
+  {
+    Value::try_from(rmpv::decode::read_value(reader)?)
+  }
+
+  pub(crate) fn unpack_ext(ty: i8, data: &[u8]) -> Result<Value, Error> {
+    match ty {
+      1 => Value::unpack_decimal(data).map(Value::Decimal),
+      2 => Uuid::from_slice(data)
+        .map(Value::Uuid)
+        .map_err(|_| invalid_ext("uuid of length", data.len())),
+      // datetime with zero offset is decoded as naive UTC one
+      4 => Value::unpack_datetime(data).map(|time| match time.offset().local_minus_utc() {
+        0 => Value::DateTime(time.naive_utc()),
+        _ => Value::DateTimeTz(time),
+      }),
+      6 => Interval::unpack(data).map(Value::Interval),
+      3 => TarantoolError::unpack_ext(data).map(Value::Error),
+      _ => Err(invalid_ext("extension type", ty)),
+    }
+  }
+
+  /// scale is followed by packed BCD digits, the last nibble is sign
+  pub(crate) fn unpack_decimal(data: &[u8]) -> Result<Decimal, Error> {
+    let mut cur = Cursor::new(data);
+    let scale: i32 = rmp::decode::read_int(&mut cur)?;
+
+    let (&last, bcd) = data[cur.position() as usize..].split_last()
+      .ok_or_else(|| invalid_ext("decimal of length", data.len()))?;
+    let digits = bcd.iter()
+      .flat_map(|byte| [ byte >> 4, byte & 0x0f ])
+      .chain(std::iter::once(last >> 4));
+
+    let mut mantissa: i128 = 0;
+    for digit in digits {
+      if digit > 9 {
+        return Err(invalid_ext("decimal digit", digit));
+      }
+      mantissa = mantissa.checked_mul(10)
+        .and_then(|mantissa| mantissa.checked_add(digit.into()))
+        .ok_or_else(|| invalid_ext("decimal of length", data.len()))?;
+    }
+    if matches!(last & 0x0f, 0x0b | 0x0d) {
+      mantissa = -mantissa;
+    }
+
+    // negative scale means trailing zeros, e.g. 1e10
+    let (mantissa, scale) = match u32::try_from(scale) {
+      Ok(scale) => (Some(mantissa), scale),
+      Err(_) => (10i128.checked_pow(scale.unsigned_abs())
+        .and_then(|exp| mantissa.checked_mul(exp)), 0),
+    };
+
+    mantissa
+      .and_then(|mantissa| Decimal::try_from_i128_with_scale(mantissa, scale).ok())
+      .ok_or_else(|| invalid_ext("decimal scale", scale))
+  }
+
+  /// seconds are followed by optional nanoseconds, tzoffset and tzindex
+  pub(crate) fn unpack_datetime(data: &[u8]) -> Result<DateTime<FixedOffset>, Error> {
+    let mut cur = Cursor::new(data);
+    let seconds = cur.read_i64::<LittleEndian>()?;
+    let (nanoseconds, tzoffset) = match data.len() {
+      8 => (0, 0),
+      16 => (cur.read_u32::<LittleEndian>()?, cur.read_i16::<LittleEndian>()?),
+      len => return Err(invalid_ext("datetime of length", len)),
+    };
+
+    let time = DateTime::from_timestamp(seconds, nanoseconds)
+      .ok_or_else(|| invalid_ext("datetime", seconds))?;
+    FixedOffset::east_opt(i32::from(tzoffset) * 60)
+      .map(|offset| time.with_timezone(&offset))
+      .ok_or_else(|| invalid_ext("datetime offset", tzoffset))
+  }
+
+  pub(crate) fn pack<W>(&self, w: &mut W) -> Result<(), Error>
     where W: Write,
   {
     match self {
@@ -1115,9 +1535,16 @@ impl Value {
       Value::Str(val) => { rmp::encode::write_str(w, val.as_str())?; },
       Value::Bin(val) => { rmp::encode::write_bin(w, val.as_slice())?; },
       Value::Array(vals) => {
-        rmp::encode::write_array_len(w, vals.len() as u32)?;
+        rmp::encode::write_array_len(w, pack_len(vals.len())?)?;
         for val in vals.iter() { val.pack(w)?; }
       },
+      Value::Map(pairs) => {
+        rmp::encode::write_map_len(w, pack_len(pairs.len())?)?;
+        for (key, val) in pairs.iter() {
+          key.pack(w)?;
+          val.pack(w)?;
+        }
+      },
 
       // UUID
       Value::Uuid(val) => {
@@ -1137,27 +1564,14 @@ impl Value {
          //println!("Encoded Bytes: {:?}", buffer);
     },
       // DateTime
-      Value::DateTime(val) => {
-
-        // Get the number of seconds and nanoseconds since the UNIX epoch
-        let seconds = val.timestamp();
-        let nanoseconds = val.timestamp_subsec_nanos();
-        // Assuming no time zone offset or index
-        let tzoffset = 0;
-        let tzindex = 0;
-        // Write ext metadata with type 4 and size 16
-        // https://www.tarantool.io/en/doc/latest/dev_guide/internals/msgpack_extensions/#the-datetime-type
-        write_ext_meta(w, 16, 4)?;
-        // Write seconds as little-endian i64
-        w.write_i64::<LittleEndian>(seconds)?;
-        // Write nanoseconds as little-endian u32
-        w.write_u32::<LittleEndian>(nanoseconds)?;
-        // Write time zone offset as little-endian i16
-        w.write_i16::<LittleEndian>(tzoffset)?;
-        // Write time zone index as little-endian u16
-        w.write_u16::<LittleEndian>(tzindex)?;
+      Value::DateTime(val) => { pack_datetime(w, &val.and_utc().fixed_offset())?; },
+      Value::DateTimeTz(val) => { pack_datetime(w, val)?; },
 
-      },
+      // Interval
+      Value::Interval(val) => { val.pack(w)?; },
+
+      // Error
+      Value::Error(val) => { pack_error(w, val)?; },
 
 
 
@@ -1189,7 +1603,8 @@ impl Value {
       let mut digits = Vec::new();
       for c in decimal_str.chars() {
           // Convert each character into a u8 value
-          let digit = c.to_digit(10).unwrap() as u8;
+          let digit = c.to_digit(10)
+            .ok_or_else(|| Error::EncodeError(format!("invalid decimal digit {:?}", c)))? as u8;
           // Push the digit into the vector
           digits.push(digit);
       }
@@ -1231,8 +1646,10 @@ impl Value {
       }
 
       // Write the MessagePack representation
-      rmp::encode::write_ext_meta(w, (num_bytes+2).try_into().unwrap(), 1)?; // MP_EXT with type 1
-      rmp::encode::write_u8(w, scale as u8)?; // Scale as MP_UINT
+      rmp::encode::write_ext_meta(w, pack_len(num_bytes + 2)?, 1)?; // MP_EXT with type 1
+      let scale = u8::try_from(scale)
+        .map_err(|_| Error::EncodeError(format!("decimal scale {} overflows", scale)))?;
+      rmp::encode::write_u8(w, scale)?; // Scale as MP_UINT
       w.write_all(&bcd)?; // PackedDecimal (BCD bytes)
 
 
@@ -1261,164 +1678,704 @@ pub struct Select {
   pub keys: Vec<Value>,
 }
 
-impl Body for Select {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(
-      1 + 6 + (5 * 5) +
-      (1 + self.keys.len() * 5)
-    );
-    let buf = &mut data;
+impl Default for Select {
+  /// selects all tuples of primary index by Eq iterator
+  fn default() -> Self {
+    Select {
+      space_id: 0, index_id: 0,
+      limit: u32::MAX, offset: 0,
+      iterator: Iterator::Eq,
+      keys: Vec::new(),
+    }
+  }
+}
 
-    write_map_len(buf, 6)?;
+impl Select {
+  /// builder of select from space, see SelectBuilder
+  pub fn builder(space_id: u64) -> SelectBuilder {
+    SelectBuilder { body: Select { space_id, ..Default::default() } }
+  }
 
-    write_uint(buf, Field::SpaceID.to_u64().unwrap())?;
-    write_uint(buf, self.space_id)?;
+  /**
+    selects up to limit tuples in descending order
+    starting from key inclusively (Le iterator).
+
+    Empty key means starting from the last tuple of index.
+  */
+  pub fn reverse(space_id: u64, index_id: u64, keys: Vec<Value>, limit: u32) -> Select {
+    Select {
+      space_id, index_id,
+      limit, offset: 0,
+      iterator: Iterator::Le,
+      keys,
+    }
+  }
 
-    write_uint(buf, Field::IndexID.to_u64().unwrap())?;
-    write_uint(buf, self.index_id)?;
+  /// same as Select::reverse, but key itself is excluded (Lt iterator).
+  pub fn before(space_id: u64, index_id: u64, keys: Vec<Value>, limit: u32) -> Select {
+    Select {
+      iterator: Iterator::Lt,
+      ..Select::reverse(space_id, index_id, keys, limit)
+    }
+  }
 
-    write_uint(buf, Field::Limit.to_u64().unwrap())?;
-    write_uint(buf, self.limit as u64)?;
+  /**
+    selects last n tuples of index in descending order,
+    it is usual "latest N records" query.
+  */
+  pub fn last(space_id: u64, index_id: u64, n: u32) -> Select {
+    Select::reverse(space_id, index_id, Vec::new(), n)
+  }
 
-    write_uint(buf, Field::Offset.to_u64().unwrap())?;
-    write_uint(buf, self.offset as u64)?;
+  /**
+    checks key against part count of selected index.
+
+    Key may be a prefix of multipart index key, but not longer than it.
+    Eq with partial key matches every tuple with such prefix,
+    so it is reported to log as it is often unintended.
+  */
+  pub fn check_key(&self, part_count: usize) -> Result<(), Error> {
+    if self.keys.len() > part_count {
+      return Err(Error::InvalidKey(format!(
+        "key has {} parts, but index {} of space {} has only {}",
+        self.keys.len(), self.index_id, self.space_id, part_count,
+      )));
+    }
 
-    write_uint(buf, Field::Iterator.to_u64().unwrap())?;
-    write_uint(buf, self.iterator.to_u64().unwrap())?;
+    if self.iterator == Iterator::Eq && !self.keys.is_empty() && self.keys.len() < part_count {
+      log::warn!(
+        "select from space {} index {} uses Eq with partial key ({} of {} parts), \
+        it will match all tuples with such prefix",
+        self.space_id, self.index_id, self.keys.len(), part_count,
+      );
+    }
 
-    write_uint(buf, Field::Key.to_u64().unwrap())?;
-    write_array_len(buf, self.keys.len() as u32)?;
-    for key in self.keys.iter() { key.pack(buf)?; }
+    Ok(())
+  }
 
-    Ok(data)
+  /// returns true if key is a prefix of multipart index key
+  pub fn is_partial_key(&self, part_count: usize) -> bool {
+    self.keys.len() < part_count
   }
 }
 
+/**
+  This is builder of select, by default all tuples of primary index
+  equal to key are selected and empty key selects whole space.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, Iterator, Select};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let req = Select::builder(512)
+      .index(1)
+      .iterator(Iterator::Ge)
+      .key(( 18u32, ))
+      .limit(100)
+      .build();
+    let resp = conn.perform(req).await?;
+    # Ok(()) }
+  ```
+*/
 #[derive(Debug, Clone)]
-pub struct Call {
-  pub function: String,
-  pub args: Vec<Value>,
+pub struct SelectBuilder {
+  body: Select,
 }
 
-impl Body for Call {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(
-      1 + 2 +
-      (1 + self.function.len()) +
-      (1 + self.args.len() * 5)
-    );
-    let buf = &mut data;
-
-    write_map_len(buf, 2)?;
+impl SelectBuilder {
+  pub fn index(mut self, index_id: u64) -> Self {
+    self.body.index_id = index_id;
+    self
+  }
 
-    write_uint(buf, Field::FunctionName.to_u64().unwrap())?;
-    write_str(buf, self.function.as_str())?;
+  pub fn iterator(mut self, iterator: Iterator) -> Self {
+    self.body.iterator = iterator;
+    self
+  }
 
-    write_uint(buf, Field::Tuple.to_u64().unwrap())?;
-    write_array_len(buf, self.args.len() as u32)?;
-    for arg in self.args.iter() { arg.pack(buf)?; }
+  pub fn limit(mut self, limit: u32) -> Self {
+    self.body.limit = limit;
+    self
+  }
 
-    Ok(data)
+  pub fn offset(mut self, offset: u32) -> Self {
+    self.body.offset = offset;
+    self
   }
-}
 
-#[derive(Debug, Clone)]
-pub struct Auth {
-  pub user: String,
-  pub scramble: Vec<u8>,
-}
+  /// descending order starting from key inclusively, see Select::reverse
+  pub fn reverse(self) -> Self {
+    self.iterator(Iterator::Le)
+  }
 
-impl Body for Auth {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(
-      1 + 2 +
-      (1 + self.user.len()) +
-      (1 + self.scramble.len())
-    );
-    let buf = &mut data;
+  /// descending order starting before key, see Select::before
+  pub fn before(self) -> Self {
+    self.iterator(Iterator::Lt)
+  }
 
-    write_map_len(buf, 2)?;
+  pub fn key<K: IntoKey>(mut self, key: K) -> Self {
+    self.body.keys = key.into_key();
+    self
+  }
 
-    write_uint(buf, Field::UserName.to_u64().unwrap())?;
-    write_str(buf, self.user.as_str())?;
+  /**
+    key which may be a prefix of multipart index key,
+    it is checked against part count of index by Select::check_key
+  */
+  pub fn prefix_key<K: IntoKey>(mut self, key: K, part_count: usize) -> Result<Self, Error> {
+    self.body.keys = key.into_key();
+    self.body.check_key(part_count)?;
+    Ok(self)
+  }
 
-    write_uint(buf, Field::Tuple.to_u64().unwrap())?;
-    write_array_len(buf, 2)?;
-    write_str(buf, "chap-sha1")?;
-    write_str_len(buf, self.scramble.len() as u32)?;
-    data.extend_from_slice(&self.scramble);
+  /// built body, it is handy for TarantoolClient::select
+  pub fn body(self) -> Select {
+    self.body
+  }
 
-    Ok(data)
+  pub fn build(self) -> Request {
+    select(self.body)
   }
 }
 
-#[derive(Debug, Clone)]
+impl Select {
+  /// packs fields of select into map which has extra fields after them
+  fn pack_fields(&self, buf: &mut Vec<u8>, extra: u32) -> Result<(), Error> {
+    buf.reserve(
+      1 + 6 + (5 * 5) +
+      (1 + self.keys.len() * 5)
+    );
+
+    write_map_len(buf, 6 + extra)?;
+
+    write_uint(buf, Field::SpaceID as u64)?;
+    write_uint(buf, self.space_id)?;
+
+    write_uint(buf, Field::IndexID as u64)?;
+    write_uint(buf, self.index_id)?;
+
+    write_uint(buf, Field::Limit as u64)?;
+    write_uint(buf, self.limit as u64)?;
+
+    write_uint(buf, Field::Offset as u64)?;
+    write_uint(buf, self.offset as u64)?;
+
+    write_uint(buf, Field::Iterator as u64)?;
+    write_uint(buf, self.iterator as u64)?;
+
+    write_uint(buf, Field::Key as u64)?;
+    write_array_len(buf, pack_len(self.keys.len())?)?;
+    for key in self.keys.iter() { key.pack(buf)?; }
+
+    Ok(())
+  }
+}
+
+impl Body for Select {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    self.pack_fields(buf, 0)
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    format!(
+      "space={} index={} iterator={:?} key={}",
+      self.space_id, self.index_id, self.iterator, redaction::values(&self.keys, redaction),
+    )
+  }
+
+  fn target(&self) -> (Option<String>, Option<String>) {
+    (Some(self.space_id.to_string()), Some(self.index_id.to_string()))
+  }
+}
+
+/**
+  This is select which pages tuples by positions (tarantool 2.11+).
+
+  Position fields are kept out of Select, so it is packed as before for older tarantool.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct PagedSelect {
+  pub select: Select,
+  /// position returned by previous page, select starts after it
+  pub after_position: Option<Vec<u8>>,
+  /// select starts after this tuple, it is alternative to after_position
+  pub after_tuple: Option<Vec<Value>>,
+  /// asks tarantool to return position of the last selected tuple
+  pub fetch_position: bool,
+}
+
+impl From<Select> for PagedSelect {
+  fn from(select: Select) -> Self {
+    PagedSelect { select, ..Default::default() }
+  }
+}
+
+impl Body for PagedSelect {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let extra =
+      self.after_position.is_some() as u32 +
+      self.after_tuple.is_some() as u32 +
+      self.fetch_position as u32;
+    self.select.pack_fields(buf, extra)?;
+
+    if let Some(position) = &self.after_position {
+      write_uint(buf, Field::AfterPosition as u64)?;
+      write_str_len(buf, pack_len(position.len())?)?;
+      buf.extend_from_slice(position);
+    }
+
+    if let Some(tuple) = &self.after_tuple {
+      write_uint(buf, Field::AfterTuple as u64)?;
+      write_array_len(buf, pack_len(tuple.len())?)?;
+      for field in tuple.iter() { field.pack(buf)?; }
+    }
+
+    if self.fetch_position {
+      write_uint(buf, Field::FetchPosition as u64)?;
+      rmp::encode::write_bool(buf, true)?;
+    }
+
+    Ok(())
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    self.select.describe(redaction)
+  }
+
+  fn target(&self) -> (Option<String>, Option<String>) {
+    self.select.target()
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Call {
+  pub function: String,
+  pub args: Vec<Value>,
+}
+
+impl Body for Call {
+  #[cfg(feature = "otel")]
+  fn inject_trace(&mut self, propagation: TracePropagation, traceparent: &str) {
+    if propagation == TracePropagation::CallArgument {
+      self.args.push(Value::Str(traceparent.into()));
+    }
+  }
+
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    pack_call(buf, &self.function, &self.args)
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    format!("{} {}", self.function, redaction::values(&self.args, redaction))
+  }
+
+  fn function(&self) -> Option<&str> {
+    Some(&self.function)
+  }
+}
+
+/**
+  This is call of tarantool 1.6 and 1.7, it is packed as Call.
+
+  Every value returned by function is converted into tuple,
+  e.g. `return 1, { 2, 3 }` gives `[[1], [2, 3]]`,
+  so result should be decoded as sequence of tuples.
+  See Connector::with_legacy_call to send all calls as Call16.
+*/
+#[derive(Debug, Clone)]
+pub struct Call16 {
+  pub function: String,
+  pub args: Vec<Value>,
+}
+
+impl From<Call> for Call16 {
+  fn from(call: Call) -> Self {
+    Call16 { function: call.function, args: call.args }
+  }
+}
+
+impl Body for Call16 {
+  #[cfg(feature = "otel")]
+  fn inject_trace(&mut self, propagation: TracePropagation, traceparent: &str) {
+    if propagation == TracePropagation::CallArgument {
+      self.args.push(Value::Str(traceparent.into()));
+    }
+  }
+
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    pack_call(buf, &self.function, &self.args)
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    format!("{} {}", self.function, redaction::values(&self.args, redaction))
+  }
+
+  fn function(&self) -> Option<&str> {
+    Some(&self.function)
+  }
+}
+
+fn pack_call(buf: &mut Vec<u8>, function: &str, args: &[Value]) -> Result<(), Error> {
+  buf.reserve(
+    1 + 2 +
+    (1 + function.len()) +
+    (1 + args.len() * 5)
+  );
+
+  write_map_len(buf, 2)?;
+
+  write_uint(buf, Field::FunctionName as u64)?;
+  write_str(buf, function)?;
+
+  write_uint(buf, Field::Tuple as u64)?;
+  write_array_len(buf, pack_len(args.len())?)?;
+  for arg in args.iter() { arg.pack(buf)?; }
+
+  Ok(())
+}
+
+/**
+  This is body of auth request.
+
+  Field `method` was added with pap-sha256 support,
+  so struct is non exhaustive now and it is made by `Auth::new`.
+
+  Example:
+  ```rust
+    # use alopecosa::{Auth, AuthMethod, request};
+    # let scramble = b"password".to_vec();
+    let req = request::auth(Auth::new("user", scramble).with_method(AuthMethod::PapSha256));
+  ```
+*/
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Auth {
+  pub user: String,
+  /// scramble of password for chap-sha1 or password itself for pap-sha256
+  pub scramble: Vec<u8>,
+  pub method: AuthMethod,
+}
+
+impl Auth {
+  /// auth with chap-sha1 scramble
+  pub fn new<S: Into<String>>(user: S, scramble: Vec<u8>) -> Auth {
+    Auth { user: user.into(), scramble, method: AuthMethod::default() }
+  }
+
+  pub fn with_method(mut self, method: AuthMethod) -> Self {
+    self.method = method;
+    self
+  }
+}
+
+impl Body for Auth {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(
+      1 + 2 +
+      (1 + self.user.len()) +
+      (1 + self.scramble.len())
+    );
+
+    write_map_len(buf, 2)?;
+
+    write_uint(buf, Field::UserName as u64)?;
+    write_str(buf, self.user.as_str())?;
+
+    write_uint(buf, Field::Tuple as u64)?;
+    write_array_len(buf, 2)?;
+    write_str(buf, self.method.name())?;
+    write_str_len(buf, pack_len(self.scramble.len())?)?;
+    buf.extend_from_slice(&self.scramble);
+
+    Ok(())
+  }
+}
+
+/// This is method of auth request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AuthMethod {
+  /// password is scrambled with salt of greeting
+  #[default]
+  ChapSha1,
+  /**
+    password is sent as is, it is required by tarantool EE
+    with strong password policy and must be used over encrypted transport
+  */
+  PapSha256,
+}
+
+impl AuthMethod {
+  pub fn name(&self) -> &'static str {
+    match self {
+      AuthMethod::ChapSha1 => "chap-sha1",
+      AuthMethod::PapSha256 => "pap-sha256",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<AuthMethod> {
+    match name {
+      "chap-sha1" => Some(AuthMethod::ChapSha1),
+      "pap-sha256" => Some(AuthMethod::PapSha256),
+      _ => None,
+    }
+  }
+}
+
+/**
+  This is id request, it is supported by tarantool 2.10+.
+
+  Server answers with its protocol version, features
+  and auth method (tarantool 2.11+).
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Id {
+  pub version: u64,
+  pub features: Vec<u64>,
+}
+
+impl Body for Id {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(
+      1 + 10 + (2 + self.features.len() * 9)
+    );
+
+    write_map_len(buf, 2)?;
+
+    write_uint(buf, Field::Version as u64)?;
+    write_uint(buf, self.version)?;
+
+    write_uint(buf, Field::Features as u64)?;
+    write_array_len(buf, pack_len(self.features.len())?)?;
+    for &feature in self.features.iter() { write_uint(buf, feature)?; }
+
+    Ok(())
+  }
+}
+
+/**
+  This is watch request, it is supported by tarantool 2.10+.
+
+  It subscribes to key or acknowledges the last event of key,
+  server answers with event instead of response.
+*/
+#[derive(Debug, Clone)]
+pub struct Watch {
+  pub key: String,
+}
+
+impl Body for Watch {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    pack_event_key(buf, &self.key)
+  }
+}
+
+/// This is unwatch request, there is no answer to it.
+#[derive(Debug, Clone)]
+pub struct Unwatch {
+  pub key: String,
+}
+
+impl Body for Unwatch {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    pack_event_key(buf, &self.key)
+  }
+}
+
+fn pack_event_key(buf: &mut Vec<u8>, key: &str) -> Result<(), Error> {
+  buf.reserve(1 + 1 + 5 + key.len());
+
+  write_map_len(buf, 1)?;
+
+  write_uint(buf, Field::EventKey as u64)?;
+  write_str(buf, key)?;
+
+  Ok(())
+}
+
+/// Isolation level of transaction, it is set by begin request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TxnIsolation {
+  /// isolation level is set by box.cfg.txn_isolation
+  #[default]
+  Default = 0,
+  ReadCommitted = 1,
+  ReadConfirmed = 2,
+  BestEffort = 3,
+}
+
+/**
+  This is begin request, it starts interactive transaction of stream.
+
+  Transaction is rolled back if it is not committed in timeout seconds,
+  default timeout is set by box.cfg.txn_timeout.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Begin {
+  pub timeout: Option<f64>,
+  pub isolation: TxnIsolation,
+}
+
+impl Body for Begin {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(1 + 10 + 2);
+
+    let isolation = self.isolation != TxnIsolation::Default;
+    write_map_len(buf, self.timeout.is_some() as u32 + isolation as u32)?;
+
+    if let Some(timeout) = self.timeout {
+      write_uint(buf, Field::Timeout as u64)?;
+      rmp::encode::write_f64(buf, timeout)?;
+    }
+
+    if isolation {
+      write_uint(buf, Field::TxnIsolation as u64)?;
+      write_uint(buf, self.isolation as u64)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// This is commit request, it commits transaction of stream.
+#[derive(Debug, Clone)]
+pub struct Commit;
+
+impl Body for Commit {
+  fn pack(&self, _buf: &mut Vec<u8>) -> Result<(), Error> {
+    Ok(())
+  }
+}
+
+/// This is rollback request, it rolls back transaction of stream.
+#[derive(Debug, Clone)]
+pub struct Rollback;
+
+impl Body for Rollback {
+  fn pack(&self, _buf: &mut Vec<u8>) -> Result<(), Error> {
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone)]
 pub struct Insert {
   pub space_id: u64,
   pub tuple: Vec<Value>,
 }
 
 impl Body for Insert {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(
       1 + 2 + 5 +
       (1 + self.tuple.len() * 5)
     );
-    let buf = &mut data;
 
     write_map_len(buf, 2)?;
 
-    write_uint(buf, Field::SpaceID.to_u64().unwrap())?;
+    write_uint(buf, Field::SpaceID as u64)?;
     write_uint(buf, self.space_id)?;
 
-    write_uint(buf, Field::Tuple.to_u64().unwrap())?;
-    write_array_len(buf, self.tuple.len() as u32)?;
+    write_uint(buf, Field::Tuple as u64)?;
+    write_array_len(buf, pack_len(self.tuple.len())?)?;
     for v in self.tuple.iter() {v.pack(buf)?; }
 
-    Ok(data)
+    Ok(())
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    format!("space={} tuple={}", self.space_id, redaction::values(&self.tuple, redaction))
+  }
+
+  fn target(&self) -> (Option<String>, Option<String>) {
+    (Some(self.space_id.to_string()), None)
+  }
+
+  fn tuple(&self) -> Option<&[Value]> {
+    Some(&self.tuple)
   }
 }
 
 #[allow(dead_code)]
 pub type Replace = Insert;
 
+/**
+  Update request, tuple contains update operations.
+
+  index_base sets numbering of fields in operations:
+  0 is default for iproto, 1 is consistent with lua.
+
+  Example:
+  ```rust
+    # use alopecosa::{IntoTuple, Update, Value};
+    let update = Update::new(512, 0, ( 1u64, ).into_tuple(), vec![
+      vec![ "=".into(), 2.into(), "ann".into() ],
+    ]).with_index_base(1);
+    assert_eq!(update.index_base, 1);
+  ```
+*/
 #[derive(Debug, Clone)]
 pub struct Update {
   pub space_id: u64,
   pub index_id: u64,
+  pub index_base: u64,
   pub key: Vec<Value>,
   pub tuple: Vec<Vec<Value>>,
 }
 
+impl Update {
+  /// update of tuple by key of index, fields of operations are counted from zero
+  pub fn new(space_id: u64, index_id: u64, key: Vec<Value>, ops: Vec<Vec<Value>>) -> Update {
+    Update { space_id, index_id, index_base: 0, key, tuple: ops }
+  }
+
+  /// fields of operations are counted from index_base, e.g. 1 as in lua
+  pub fn with_index_base(mut self, index_base: u64) -> Self {
+    self.index_base = index_base;
+    self
+  }
+}
+
 impl Body for Update {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(
-      1 + 4 + (5 * 2) +
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(
+      1 + 5 + (5 * 3) +
       (1 + self.key.len() * 5) +
       (1 + self.tuple.len() * (1 + 5 * 3))
     );
-    let buf = &mut data;
 
-    write_map_len(buf, 4)?;
+    write_map_len(buf, 5)?;
 
-    write_uint(buf, Field::SpaceID.to_u64().unwrap())?;
+    write_uint(buf, Field::SpaceID as u64)?;
     write_uint(buf, self.space_id)?;
 
-    write_uint(buf, Field::IndexID.to_u64().unwrap())?;
+    write_uint(buf, Field::IndexID as u64)?;
     write_uint(buf, self.index_id)?;
 
-    write_uint(buf, Field::Key.to_u64().unwrap())?;
-    write_array_len(buf, self.key.len() as u32)?;
+    write_uint(buf, Field::IndexBase as u64)?;
+    write_uint(buf, self.index_base)?;
+
+    write_uint(buf, Field::Key as u64)?;
+    write_array_len(buf, pack_len(self.key.len())?)?;
     for v in self.key.iter() { v.pack(buf)?; }
 
-    write_uint(buf, Field::Tuple.to_u64().unwrap())?;
-    write_array_len(buf, self.tuple.len() as u32)?;
+    write_uint(buf, Field::Tuple as u64)?;
+    write_array_len(buf, pack_len(self.tuple.len())?)?;
     for update in self.tuple.iter() {
-      write_array_len(buf, update.len() as u32)?;
+      write_array_len(buf, pack_len(update.len())?)?;
       for v in update.iter() { v.pack(buf)?; }
     }
 
-    Ok(data)
+    Ok(())
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    format!(
+      "space={} index={} key={} ops={}",
+      self.space_id, self.index_id, redaction::values(&self.key, redaction), self.tuple.len(),
+    )
+  }
+
+  fn target(&self) -> (Option<String>, Option<String>) {
+    (Some(self.space_id.to_string()), Some(self.index_id.to_string()))
   }
 }
 
@@ -1429,94 +2386,305 @@ pub struct Delete {
   pub key: Vec<Value>,
 }
 
-impl Body for Delete {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(
-      1 + 3 + (5 * 2) + (1 + self.key.len() * 5)
+impl Body for Delete {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(
+      1 + 3 + (5 * 2) + (1 + self.key.len() * 5)
+    );
+
+    write_map_len(buf, 3)?;
+
+    write_uint(buf, Field::SpaceID as u64)?;
+    write_uint(buf, self.space_id)?;
+
+    write_uint(buf, Field::IndexID as u64)?;
+    write_uint(buf, self.index_id)?;
+
+    write_uint(buf, Field::Key as u64)?;
+    write_array_len(buf, pack_len(self.key.len())?)?;
+    for v in self.key.iter() { v.pack(buf)?; }
+
+    Ok(())
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    format!(
+      "space={} index={} key={}",
+      self.space_id, self.index_id, redaction::values(&self.key, redaction),
+    )
+  }
+
+  fn target(&self) -> (Option<String>, Option<String>) {
+    (Some(self.space_id.to_string()), Some(self.index_id.to_string()))
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Eval {
+  pub expr: String,
+  pub args: Vec<Value>,
+}
+
+impl Body for Eval {
+  #[cfg(feature = "otel")]
+  fn inject_trace(&mut self, propagation: TracePropagation, traceparent: &str) {
+    if propagation == TracePropagation::CallArgument {
+      self.args.push(Value::Str(traceparent.into()));
+    }
+  }
+
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(
+      1 + 2 +
+      (1 + self.expr.len()) +
+      (1 + self.args.len() * 5)
+    );
+
+    write_map_len(buf, 2)?;
+
+    write_uint(buf, Field::Expr as u64)?;
+    write_str(buf, &self.expr)?;
+
+    write_uint(buf, Field::Tuple as u64)?;
+    write_array_len(buf, pack_len(self.args.len())?)?;
+    for v in self.args.iter() { v.pack(buf)?; }
+
+    Ok(())
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    format!("{} {}", redaction::sanitize(&self.expr, redaction), redaction::values(&self.args, redaction))
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Upsert {
+  pub space_id: u64,
+  pub index_base: u64,
+  pub ops: Vec<Vec<Value>>,
+  pub tuple: Vec<Value>,
+}
+
+impl Body for Upsert {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(
+      1 + 4 +
+      (1 + self.tuple.len() * 5) +
+      (1 + self.ops.len() * (1 + 5 * 3))
+    );
+
+    write_map_len(buf, 4)?;
+
+    write_uint(buf, Field::SpaceID as u64)?;
+    write_uint(buf, self.space_id)?;
+
+    write_uint(buf, Field::IndexBase as u64)?;
+    write_uint(buf, self.index_base)?;
+
+    write_uint(buf, Field::Ops as u64)?;
+    write_array_len(buf, pack_len(self.ops.len())?)?;
+    for update in self.ops.iter() {
+      write_array_len(buf, pack_len(update.len())?)?;
+      for v in update.iter() { v.pack(buf)?; }
+    }
+
+    write_uint(buf, Field::Tuple as u64)?;
+    write_array_len(buf, pack_len(self.tuple.len())?)?;
+    for v in self.tuple.iter() { v.pack(buf)?; }
+
+    Ok(())
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    format!(
+      "space={} tuple={} ops={}",
+      self.space_id, redaction::values(&self.tuple, redaction), self.ops.len(),
+    )
+  }
+
+  fn target(&self) -> (Option<String>, Option<String>) {
+    (Some(self.space_id.to_string()), None)
+  }
+
+  fn tuple(&self) -> Option<&[Value]> {
+    Some(&self.tuple)
+  }
+}
+
+/// replica id -> lsn
+pub type Vclock = BTreeMap<u32, u64>;
+
+/**
+  This is replication subscribe request.
+
+  Anonymous replica doesn't need to be registered in _cluster,
+  so it is suitable for change data capture.
+*/
+#[derive(Debug, Clone)]
+pub struct Subscribe {
+  pub instance_uuid: Uuid,
+  pub cluster_uuid: Option<Uuid>,
+  /// position to start replication from
+  pub vclock: Vclock,
+  pub anon: bool,
+  /// replica ids whose rows should not be sent
+  pub id_filter: Vec<u32>,
+}
+
+impl Body for Subscribe {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(
+      1 + 5 + (1 + 36) * 2 +
+      (1 + self.vclock.len() * 10) +
+      (1 + self.id_filter.len() * 5)
     );
-    let buf = &mut data;
 
-    write_map_len(buf, 3)?;
+    let map_len = 4 + self.cluster_uuid.is_some() as u32;
+    write_map_len(buf, map_len)?;
 
-    write_uint(buf, Field::SpaceID.to_u64().unwrap())?;
-    write_uint(buf, self.space_id)?;
+    write_uint(buf, Field::InstanceUUID as u64)?;
+    write_str(buf, &self.instance_uuid.to_string())?;
 
-    write_uint(buf, Field::IndexID.to_u64().unwrap())?;
-    write_uint(buf, self.index_id)?;
+    if let Some(cluster_uuid) = &self.cluster_uuid {
+      write_uint(buf, Field::ClusterUUID as u64)?;
+      write_str(buf, &cluster_uuid.to_string())?;
+    }
 
-    write_uint(buf, Field::Key.to_u64().unwrap())?;
-    write_array_len(buf, self.key.len() as u32)?;
-    for v in self.key.iter() { v.pack(buf)?; }
+    write_uint(buf, Field::Vclock as u64)?;
+    write_map_len(buf, pack_len(self.vclock.len())?)?;
+    for (&replica_id, &lsn) in self.vclock.iter() {
+      write_uint(buf, replica_id as u64)?;
+      write_uint(buf, lsn)?;
+    }
+
+    write_uint(buf, Field::ReplicaAnon as u64)?;
+    rmp::encode::write_bool(buf, self.anon)?;
+
+    write_uint(buf, Field::IDFilter as u64)?;
+    write_array_len(buf, pack_len(self.id_filter.len())?)?;
+    for &id in self.id_filter.iter() { write_uint(buf, id as u64)?; }
 
-    Ok(data)
+    Ok(())
   }
 }
 
+/**
+  This wraps request body addressing space and index by name,
+  it is supported by tarantool 3.0+.
+
+  Space and index ids of wrapped body are replaced with names.
+*/
 #[derive(Debug, Clone)]
-pub struct Eval {
-  pub expr: String,
-  pub args: Vec<Value>,
+pub struct ByName<B> {
+  pub space: String,
+  pub index: Option<String>,
+  pub body: B,
 }
 
-impl Body for Eval {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(
-      1 + 2 +
-      (1 + self.expr.len()) +
-      (1 + self.args.len() * 5)
-    );
-    let buf = &mut data;
+impl<B> ByName<B> {
+  pub fn new<S: Into<String>>(space: S, body: B) -> ByName<B> {
+    ByName { space: space.into(), index: None, body }
+  }
 
-    write_map_len(buf, 2)?;
+  pub fn with_index<S: Into<String>>(mut self, index: S) -> Self {
+    self.index = Some(index.into());
+    self
+  }
+}
 
-    write_uint(buf, Field::Expr.to_u64().unwrap())?;
-    write_str(buf, &self.expr)?;
+impl<B> Body for ByName<B>
+  where B: Body
+{
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let mut packed: Vec<u8> = Vec::new();
+    self.body.pack(&mut packed)?;
+
+    // fields are copied as they are packed, only ids are replaced with names
+    let not_map = |_| Error::EncodeError("request body is not a map".into());
+    let mut rest = packed.as_slice();
+    let len = rmp::decode::read_map_len(&mut rest).map_err(not_map)?;
+
+    buf.reserve(packed.len() + self.space.len());
+    write_map_len(buf, len)?;
+    for _ in 0..len {
+      let key: u64 = rmp::decode::read_int(&mut rest)
+        .map_err(|err| Error::EncodeError(err.to_string()))?;
+      let field = rest;
+      rmpv::decode::read_value_ref(&mut rest)
+        .map_err(|err| Error::EncodeError(err.to_string()))?;
+      let field = &field[..field.len() - rest.len()];
+
+      match (key, &self.index) {
+        (key, _) if key == Field::SpaceID as u64 => {
+          write_uint(buf, Field::SpaceName as u64)?;
+          write_str(buf, &self.space)?;
+        },
+        (key, Some(index)) if key == Field::IndexID as u64 => {
+          write_uint(buf, Field::IndexName as u64)?;
+          write_str(buf, index)?;
+        },
+        (key, _) => {
+          write_uint(buf, key)?;
+          buf.extend_from_slice(field);
+        },
+      }
+    }
 
-    write_uint(buf, Field::Tuple.to_u64().unwrap())?;
-    write_array_len(buf, self.args.len() as u32)?;
-    for v in self.args.iter() { v.pack(buf)?; }
+    Ok(())
+  }
 
-    Ok(data)
+  fn describe(&self, redaction: Redaction) -> String {
+    match &self.index {
+      Some(index) => format!("{} space_name={} index_name={}", self.body.describe(redaction), self.space, index),
+      None => format!("{} space_name={}", self.body.describe(redaction), self.space),
+    }
   }
-}
 
-#[derive(Debug, Clone)]
-pub struct Upsert {
-  pub space_id: u64,
-  pub index_base: u64,
-  pub ops: Vec<Vec<Value>>,
-  pub tuple: Vec<Value>,
-}
+  fn target(&self) -> (Option<String>, Option<String>) {
+    let (_, index) = self.body.target();
+    (Some(self.space.clone()), self.index.clone().or(index))
+  }
 
-impl Body for Upsert {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(
-      1 + 4 +
-      (1 + self.tuple.len() * 5) +
-      (1 + self.ops.len() * (1 + 5 * 3))
-    );
-    let buf = &mut data;
+  fn tuple(&self) -> Option<&[Value]> {
+    self.body.tuple()
+  }
+}
 
-    write_map_len(buf, 4)?;
+/// Bodies which are addressed to space and index.
+pub(crate) trait Target {
+  fn set_target(&mut self, space_id: u64, index_id: u64);
+}
 
-    write_uint(buf, Field::SpaceID.to_u64().unwrap())?;
-    write_uint(buf, self.space_id)?;
+impl Target for Select {
+  fn set_target(&mut self, space_id: u64, index_id: u64) {
+    self.space_id = space_id;
+    self.index_id = index_id;
+  }
+}
 
-    write_uint(buf, Field::IndexBase.to_u64().unwrap())?;
-    write_uint(buf, self.index_base)?;
+impl Target for Insert {
+  fn set_target(&mut self, space_id: u64, _index_id: u64) {
+    self.space_id = space_id;
+  }
+}
 
-    write_uint(buf, Field::Ops.to_u64().unwrap())?;
-    write_array_len(buf, self.ops.len() as u32)?;
-    for update in self.ops.iter() {
-      write_array_len(buf, update.len() as u32)?;
-      for v in update.iter() { v.pack(buf)?; }
-    }
+impl Target for Update {
+  fn set_target(&mut self, space_id: u64, index_id: u64) {
+    self.space_id = space_id;
+    self.index_id = index_id;
+  }
+}
 
-    write_uint(buf, Field::Tuple.to_u64().unwrap())?;
-    write_array_len(buf, self.tuple.len() as u32)?;
-    for v in self.tuple.iter() { v.pack(buf)?; }
+impl Target for Delete {
+  fn set_target(&mut self, space_id: u64, index_id: u64) {
+    self.space_id = space_id;
+    self.index_id = index_id;
+  }
+}
 
-    Ok(data)
+impl Target for Upsert {
+  fn set_target(&mut self, space_id: u64, _index_id: u64) {
+    self.space_id = space_id;
   }
 }
 
@@ -1524,8 +2692,8 @@ impl Body for Upsert {
 pub struct Ping;
 
 impl Body for Ping {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    Ok(Vec::new())
+  fn pack(&self, _buf: &mut Vec<u8>) -> Result<(), Error> {
+    Ok(())
   }
 }
 
@@ -1543,11 +2711,11 @@ impl Prepare {
   {
     match self {
       &Self::StatementID(id) => {
-        write_uint(w, Field::StmtID.to_u64().unwrap())?;
+        write_uint(w, Field::StmtID as u64)?;
         write_sint(w, id)?;
       },
       Self::SQL(stmt) => {
-        write_uint(w, Field::SqlText.to_u64().unwrap())?;
+        write_uint(w, Field::SqlText as u64)?;
         write_str(w, &stmt)?;
       },
     };
@@ -1564,16 +2732,37 @@ impl Prepare {
 }
 
 impl Body for Prepare {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(1 + self.pair_size_hint());
-
-    let buf = &mut data;
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(1 + self.pair_size_hint());
 
     write_map_len(buf, 1)?;
 
     self.pack_pair(buf)?;
 
-    Ok(data)
+    Ok(())
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    match self {
+      Self::StatementID(id) => format!("stmt={}", id),
+      Self::SQL(sql) => redaction::sanitize(sql, redaction),
+    }
+  }
+}
+
+/// Frees statement prepared on server, it is sent as prepare request.
+#[derive(Debug, Clone, Copy)]
+pub struct Unprepare {
+  pub stmt_id: i64,
+}
+
+impl Body for Unprepare {
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    Prepare::StatementID(self.stmt_id).pack(buf)
+  }
+
+  fn describe(&self, _redaction: Redaction) -> String {
+    format!("unprepare stmt={}", self.stmt_id)
   }
 }
 
@@ -1585,29 +2774,38 @@ pub struct Execute {
 }
 
 impl Body for Execute {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(
+  #[cfg(feature = "otel")]
+  fn inject_trace(&mut self, propagation: TracePropagation, traceparent: &str) {
+    if let (TracePropagation::SqlComment, Prepare::SQL(sql)) = (propagation, &mut self.expr) {
+      *sql = format!("/*traceparent='{}'*/ {}", traceparent, sql);
+    }
+  }
+
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(
       1 + self.expr.pair_size_hint() +
       (1 + 1 + 5 * self.sql_bind.len()) +
       (1 + 1 + 5 * self.options.len())
     );
 
-    let buf = &mut data;
-
     write_map_len(buf, 3)?;
 
     self.expr.pack_pair(buf)?;
 
-    write_uint(buf, Field::SqlBind.to_u64().unwrap())?;
-    write_array_len(buf, self.sql_bind.len() as u32)?;
+    write_uint(buf, Field::SqlBind as u64)?;
+    write_array_len(buf, pack_len(self.sql_bind.len())?)?;
     for v in self.sql_bind.iter() { v.pack(buf)?; }
 
-    write_uint(buf, Field::Options.to_u64().unwrap())?;
-    write_array_len(buf, self.options.len() as u32)?;
+    write_uint(buf, Field::Options as u64)?;
+    write_array_len(buf, pack_len(self.options.len())?)?;
 
     for v in self.options.iter() { v.pack(buf)?; }
 
-    Ok(data)
+    Ok(())
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    format!("{} binds={}", self.expr.describe(redaction), redaction::values(&self.sql_bind, redaction))
   }
 }
 
@@ -1621,33 +2819,46 @@ pub struct ExecuteSelect {
 }
 
 impl Body for ExecuteSelect {
-  fn pack(&self) -> Result<Vec<u8>, Error> {
-    let mut data: Vec<u8> = Vec::with_capacity(
+  #[cfg(feature = "otel")]
+  fn inject_trace(&mut self, propagation: TracePropagation, traceparent: &str) {
+    if let (TracePropagation::SqlComment, Prepare::SQL(sql)) = (propagation, &mut self.expr) {
+      *sql = format!("/*traceparent='{}'*/ {}", traceparent, sql);
+    }
+  }
+
+  fn pack(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.reserve(
       1 + self.expr.pair_size_hint() +
       (1 + 1 + 5 * self.sql_bind.len()) +
       (1 + 1 + 5 * self.options.len())
     );
 
-    let buf = &mut data;
-
     write_map_len(buf, 3)?;
 
     self.expr.pack_pair(buf)?;
 
-    write_uint(buf, Field::SqlBind.to_u64().unwrap())?;
-    write_array_len(buf, self.sql_bind.len() as u32)?;
+    write_uint(buf, Field::SqlBind as u64)?;
+    write_array_len(buf, pack_len(self.sql_bind.len())?)?;
     for v in self.sql_bind.iter() { v.pack(buf)?; }
 
-    write_uint(buf, Field::Options.to_u64().unwrap())?;
-    write_array_len(buf, self.options.len() as u32)?;
+    write_uint(buf, Field::Options as u64)?;
+    write_array_len(buf, pack_len(self.options.len())?)?;
     for v in self.options.iter() { v.pack(buf)?; }
 
-    Ok(data)
+    Ok(())
+  }
+
+  fn describe(&self, redaction: Redaction) -> String {
+    format!("{} binds={}", self.expr.describe(redaction), redaction::values(&self.sql_bind, redaction))
   }
 }
 
 #[cfg(test)]
 mod tests {
+  use std::convert::TryInto;
+
+  use proptest::{collection, prelude::*};
+
   use super::*;
 
   #[test]
@@ -1670,7 +2881,7 @@ mod tests {
     assert_eq!(
       &buf,
       &[
-        29, 130, 0, 1, 1, 207, 0, 0, 0, 1, 0, 0, 0,
+        206, 0, 0, 0, 29, 130, 0, 1, 1, 207, 0, 0, 0, 1, 0, 0, 0,
         99, 134, 16, 205, 2, 0, 17, 0, 18, 123, 19,
         0, 20, 0, 32, 145, 1,
       ],
@@ -1678,6 +2889,111 @@ mod tests {
 
   }
 
+  #[test]
+  fn test_select_builder() {
+    let body = Select::builder(512).body();
+    assert_eq!((body.space_id, body.index_id), (512, 0));
+    assert_eq!((body.limit, body.offset), (u32::MAX, 0));
+    assert_eq!(body.iterator, Iterator::Eq);
+    assert!(body.keys.is_empty());
+
+    let body = Select::builder(512).key(( 5u64, )).limit(3).reverse().body();
+    assert_eq!(body.iterator, Select::reverse(512, 0, body.keys.clone(), 3).iterator);
+    assert_eq!(Select::builder(512).before().body().iterator, Iterator::Lt);
+
+    // scalar key is key of one part, NoKey selects whole index
+    assert_eq!(Select::builder(512).key(5u64).body().keys, vec![ Value::UInt(5) ]);
+    assert_eq!(Select::builder(512).key("ann").body().keys, vec![ Value::from("ann") ]);
+    assert!(Select::builder(512).key(NoKey).body().keys.is_empty());
+    let body = Select::builder(512).prefix_key(5u64, 2).unwrap().body();
+    assert_eq!(body.keys, vec![ Value::UInt(5) ]);
+    assert!(Select::builder(512).prefix_key(( 1u64, 2u64, 3u64 ), 2).is_err());
+
+    let mut req = Select::builder(512)
+      .index(0)
+      .iterator(Iterator::Eq)
+      .key(( 1u64, ))
+      .limit(123)
+      .build();
+    req.header.sync = u32::MAX as u64 + 100;
+
+    let mut buf: Vec<u8> = Vec::new();
+    req.pack(&mut buf).unwrap();
+    assert_eq!(buf, vec![
+      206, 0, 0, 0, 29, 130, 0, 1, 1, 207, 0, 0, 0, 1, 0, 0, 0,
+      99, 134, 16, 205, 2, 0, 17, 0, 18, 123, 19,
+      0, 20, 0, 32, 145, 1,
+    ]);
+  }
+
+  #[test]
+  fn test_select_pagination() {
+    let body = PagedSelect {
+      select: Select { space_id: 512, limit: 2, ..Default::default() },
+      after_position: Some(b"pos".to_vec()),
+      fetch_position: true,
+      ..Default::default()
+    };
+    let mut buf: Vec<u8> = Vec::new();
+    body.pack(&mut buf).unwrap();
+    assert_eq!(buf, vec![
+      136, 16, 205, 2, 0, 17, 0, 18, 2, 19, 0, 20, 0, 32, 144,
+      46, 163, b'p', b'o', b's', 31, 195,
+    ]);
+
+    let body = PagedSelect {
+      select: Select { space_id: 512, limit: 2, ..Default::default() },
+      after_tuple: Some(vec![ Value::UInt(1) ]),
+      ..Default::default()
+    };
+    let mut buf: Vec<u8> = Vec::new();
+    body.pack(&mut buf).unwrap();
+    assert_eq!(buf, vec![
+      135, 16, 205, 2, 0, 17, 0, 18, 2, 19, 0, 20, 0, 32, 144,
+      47, 145, 1,
+    ]);
+  }
+
+  #[test]
+  fn test_select_check_key() {
+    let mut req = Select {
+      space_id: 512, index_id: 1,
+      limit: 10, offset: 0,
+      iterator: Iterator::Eq,
+      keys: vec![ Value::UInt(1) ],
+    };
+
+    assert!(req.check_key(2).is_ok());
+    assert!(req.is_partial_key(2));
+    assert!(!req.is_partial_key(1));
+
+    req.keys.push(Value::UInt(2));
+    req.keys.push(Value::UInt(3));
+    assert!(req.check_key(2).is_err());
+
+    let body = Select::builder(512).index(1)
+      .iterator(Iterator::Ge)
+      .prefix_key(( 1u64, ), 2).unwrap()
+      .body();
+    assert_eq!(body.keys.len(), 1);
+    assert!(matches!(
+      Select::builder(512).prefix_key(( 1u64, 2u64, 3u64 ), 2),
+      Err(Error::InvalidKey(_)),
+    ));
+  }
+
+  #[test]
+  fn test_select_reverse() {
+    let req = Select::last(512, 0, 10);
+    assert_eq!(req.iterator, Iterator::Le);
+    assert!(req.keys.is_empty());
+    assert_eq!(req.limit, 10);
+
+    let req = Select::before(512, 0, vec![ Value::UInt(5) ], 3);
+    assert_eq!(req.iterator, Iterator::Lt);
+    assert_eq!(req.keys.len(), 1);
+  }
+
   #[test]
   fn test_call() {
     let mut req = call(Call {
@@ -1694,7 +3010,7 @@ mod tests {
     assert_eq!(
       &buf,
       &[
-        23, 130, 0, 10, 1, 207, 0, 0, 0, 1, 0, 0, 0, 99,
+        206, 0, 0, 0, 23, 130, 0, 10, 1, 207, 0, 0, 0, 1, 0, 0, 0, 99,
         130, 34, 164, 116, 101, 115, 116, 33, 145, 123,
       ],
     )
@@ -1711,7 +3027,488 @@ mod tests {
 
     req.pack(&mut buf).unwrap();
 
-    assert_eq!(&buf, &[13, 130, 0, 2, 1, 0, 130, 16, 205, 2, 0, 33, 145, 2]);
+    assert_eq!(&buf, &[206, 0, 0, 0, 13, 130, 0, 2, 1, 0, 130, 16, 205, 2, 0, 33, 145, 2]);
+  }
+
+  #[test]
+  fn test_size_prefix() {
+    let mut buf: Vec<u8> = Vec::new();
+    ping().pack(&mut buf).unwrap();
+    let first = buf.len();
+
+    let req = insert(Insert {
+      space_id: 512,
+      tuple: vec![ Value::Str("x".repeat(70_000)) ],
+    });
+    req.pack(&mut buf).unwrap();
+
+    let size = u32::from_be_bytes(buf[first + 1..first + 5].try_into().unwrap()) as usize;
+    assert_eq!(buf[first], 0xce);
+    assert_eq!(size, buf.len() - first - 5);
+
+    let packed = buf.clone();
+    let req = ping().with_header_field(Field::Sync as u64, 5u64);
+    assert!(req.pack(&mut buf).is_err());
+    assert_eq!(buf, packed);
+  }
+
+  #[test]
+  fn test_update() {
+    let req = update(Update {
+      space_id: 512, index_id: 0, index_base: 1,
+      key: vec![ Value::UInt(1) ],
+      tuple: vec![ vec![ Value::Str("=".into()), Value::UInt(2), Value::UInt(3) ] ],
+    });
+
+    let mut buf: Vec<u8> = Vec::new();
+
+    req.pack(&mut buf).unwrap();
+
+    assert_eq!(
+      &buf,
+      &[
+        206, 0, 0, 0, 24, 130, 0, 4, 1, 0, 133, 16, 205, 2, 0, 17, 0,
+        21, 1, 32, 145, 1, 33, 145, 147, 161, 61, 2, 3,
+      ],
+    );
+  }
+
+  #[test]
+  fn test_extra_header_fields() {
+    let req = ping().with_header_field(0x0a, 5u64);
+
+    let mut buf: Vec<u8> = Vec::new();
+    req.pack(&mut buf).expect("pack error");
+    assert_eq!(&buf, &[206, 0, 0, 0, 7, 131, 0, 64, 1, 0, 10, 5]);
+
+    let req = ping().with_header_field(Field::Sync as u64, 5u64);
+    assert!(req.pack(&mut buf).is_err());
+  }
+
+  #[test]
+  fn test_transaction_requests() {
+    let req = RequestBuilder::from(begin(Begin { timeout: Some(0.5), isolation: TxnIsolation::ReadCommitted }))
+      .with_stream_id(1)
+      .build();
+
+    let mut buf: Vec<u8> = Vec::new();
+    req.pack(&mut buf).expect("pack error");
+    assert_eq!(&buf, &[
+      206, 0, 0, 0, 20, 131, 0, 14, 1, 0, 10, 1,
+      130, 0x56, 0xcb, 0x3f, 0xe0, 0, 0, 0, 0, 0, 0, 0x59, 1,
+    ]);
+
+    let mut buf: Vec<u8> = Vec::new();
+    begin(Begin::default()).pack(&mut buf).expect("pack error");
+    assert_eq!(&buf, &[206, 0, 0, 0, 6, 130, 0, 14, 1, 0, 128]);
+
+    let mut buf: Vec<u8> = Vec::new();
+    rollback().pack(&mut buf).expect("pack error");
+    assert_eq!(&buf, &[206, 0, 0, 0, 5, 130, 0, 16, 1, 0]);
+  }
+
+  #[test]
+  fn test_request_builder() {
+    let req = RequestBuilder::new(RequestType::Ping, Ping)
+      .with_sync(3)
+      .with_stream_id(5)
+      .with_transaction(9, true)
+      .build();
+    assert!(req.header.fixed_sync);
+
+    let mut buf: Vec<u8> = Vec::new();
+    req.pack(&mut buf).expect("pack error");
+    assert_eq!(&buf, &[206, 0, 0, 0, 11, 133, 0, 64, 1, 3, 10, 5, 8, 9, 9, 1]);
+
+    let req = RequestBuilder::from(ping())
+      .with_stream_id(5)
+      .with_header_field(Field::StreamID as u64, 6u64)
+      .build();
+    assert!(req.pack(&mut buf).is_err());
+  }
+
+  #[test]
+  fn test_by_name() {
+    let body = ByName::new("users", Select {
+      space_id: 0, index_id: 0,
+      limit: 1, offset: 0,
+      iterator: Iterator::Eq,
+      keys: vec![ Value::UInt(1) ],
+    }).with_index("primary");
+
+    let mut packed: Vec<u8> = Vec::new();
+    body.pack(&mut packed).unwrap();
+    let fields = rmpv::decode::read_value(&mut packed.as_slice()).unwrap();
+    let fields = fields.as_map().unwrap();
+
+    let get = |field: Field| fields.iter()
+      .find(|(key, _)| key.as_u64() == Some(field as u64))
+      .map(|(_, value)| value.clone());
+
+    assert_eq!(get(Field::SpaceID), None);
+    assert_eq!(get(Field::IndexID), None);
+    assert_eq!(get(Field::SpaceName), Some("users".into()));
+    assert_eq!(get(Field::IndexName), Some("primary".into()));
+    assert_eq!(get(Field::Limit), Some(1.into()));
+  }
+
+  #[test]
+  fn test_interval_pack() {
+    let mut buf: Vec<u8> = Vec::new();
+    Value::Interval(Interval {
+      day: 1, sec: -2, adjust: IntervalAdjust::Last,
+      ..Default::default()
+    }).pack(&mut buf).unwrap();
+
+    assert_eq!(&buf, &[199, 7, 6, 3, 3, 1, 6, 254, 8, 2]);
+  }
+
+  #[test]
+  fn test_interval_from_duration() {
+    let interval = Interval::from(std::time::Duration::new(90061, 5));
+    assert_eq!(interval, Interval { day: 1, hour: 1, min: 1, sec: 1, nsec: 5, ..Default::default() });
+
+    let interval = Interval::from(chrono::Duration::milliseconds(-3_723_500));
+    assert_eq!(interval, Interval { hour: -1, min: -2, sec: -3, nsec: -500_000_000, ..Default::default() });
+  }
+
+  #[test]
+  fn test_datetime_tz_pack() {
+    let time = DateTime::parse_from_rfc3339("2023-11-14T22:13:20.000000005+03:00").unwrap();
+    let mut buf: Vec<u8> = Vec::new();
+    Value::from(time).pack(&mut buf).unwrap();
+
+    let mut expected = vec![ 0xd8, 4 ];
+    expected.extend_from_slice(&1_699_989_200i64.to_le_bytes());
+    expected.extend_from_slice(&5u32.to_le_bytes());
+    expected.extend_from_slice(&180i16.to_le_bytes());
+    expected.extend_from_slice(&0u16.to_le_bytes());
+    assert_eq!(buf, expected);
+
+    match Value::unpack(&mut buf.as_slice()).unwrap() {
+      Value::DateTimeTz(val) => {
+        assert_eq!(val, time);
+        assert_eq!(val.offset().local_minus_utc(), 3 * 3600);
+      },
+      val => panic!("unexpected value {:?}", val),
+    }
+  }
+
+  #[test]
+  fn test_error_pack() {
+    let mut buf: Vec<u8> = Vec::new();
+    Value::from(TarantoolError::new("not enough money")).pack(&mut buf).unwrap();
+
+    let err = match Value::unpack(&mut buf.as_slice()).unwrap() {
+      Value::Error(err) => err,
+      val => panic!("unexpected value {:?}", val),
+    };
+    assert_eq!(err.message, "not enough money");
+    assert_eq!(err.stack.len(), 1);
+    assert_eq!(err.stack[0].err_type, "ClientError");
+
+    // stack is kept as is
+    let mut repacked: Vec<u8> = Vec::new();
+    Value::Error(err).pack(&mut repacked).unwrap();
+    assert_eq!(buf, repacked);
+
+    // additional fields of custom error survive round trip
+    let mut fields: Vec<u8> = Vec::new();
+    rmpv::encode::write_value(&mut fields, &rmpv::Value::Map(vec![
+      ("name".into(), "NOT_ENOUGH_MONEY".into()),
+    ])).unwrap();
+    let custom = TarantoolError {
+      message: "not enough money".into(),
+      stack: vec![ StackRecord { err_type: "CustomError".into(), fields, ..Default::default() } ],
+    };
+
+    let mut buf: Vec<u8> = Vec::new();
+    Value::from(custom.clone()).pack(&mut buf).unwrap();
+    let err = match Value::unpack(&mut buf.as_slice()).unwrap() {
+      Value::Error(err) => err,
+      val => panic!("unexpected value {:?}", val),
+    };
+    assert_eq!(err.stack, custom.stack);
+    assert_eq!(err.stack[0].field("name"), Some("NOT_ENOUGH_MONEY".into()));
+    assert_eq!(err.stack[0].field("reason"), None);
+  }
+
+  #[test]
+  fn test_value_unpack() {
+    let created = DateTime::from_timestamp(1_700_000_000, 5).unwrap().naive_utc();
+    let value = Value::Array(vec![
+      Value::Int(-5), Value::UInt(7), Value::Null, Value::Bool(true), Value::F64(0.5),
+      "name".into(), Value::Bin(vec![ 1, 2 ]),
+      Value::Map(vec![ ("age".into(), 30.into()) ]),
+      Value::Uuid(Uuid::nil()),
+      Value::Decimal("-12.034".parse().unwrap()),
+      Value::DateTime(created),
+      Value::Interval(Interval { month: 2, nsec: -3, adjust: IntervalAdjust::Last, ..Default::default() }),
+    ]);
+
+    let mut buf: Vec<u8> = Vec::new();
+    value.pack(&mut buf).unwrap();
+
+    let unpacked = Value::unpack(&mut buf.as_slice()).unwrap();
+    let mut repacked: Vec<u8> = Vec::new();
+    unpacked.pack(&mut repacked).unwrap();
+    assert_eq!(buf, repacked);
+
+    // decimals packed by tarantool, the latter has negative scale
+    let decimal = |data: &[u8]| match Value::unpack(&mut &data[..]).unwrap() {
+      Value::Decimal(val) => val,
+      val => panic!("unexpected value {:?}", val),
+    };
+    assert_eq!(decimal(&[ 0xc7, 4, 1, 2, 0x01, 0x23, 0x4d ]), "-12.34".parse().unwrap());
+    assert_eq!(decimal(&[ 0xd5, 1, 0xfe, 0x1c ]), "100".parse().unwrap());
+
+    assert!(Value::unpack(&mut &[ 0xd4, 42, 0 ][..]).is_err());
+  }
+
+  #[test]
+  fn test_value_try_into() {
+    assert_eq!(u8::try_from(Value::UInt(7)).unwrap(), 7);
+    assert_eq!(i32::try_from(Value::UInt(7)).unwrap(), 7);
+    assert_eq!(u64::try_from(Value::Int(7)).unwrap(), 7);
+    assert!(matches!(
+      u8::try_from(Value::UInt(300)).unwrap_err(),
+      Error::OutOfRange { expected: "u8", value: 300 },
+    ));
+    assert!(matches!(
+      u64::try_from(Value::Int(-1)).unwrap_err(),
+      Error::OutOfRange { expected: "u64", value: -1 },
+    ));
+    assert_eq!(
+      i8::try_from(Value::Int(-200)).unwrap_err().to_string(),
+      "integer -200 is out of range of i8",
+    );
+    assert!(matches!(
+      u32::try_from(Value::from("7")).unwrap_err(),
+      Error::TypeMismatch { expected: "u32", found: "string" },
+    ));
+    assert!(matches!(
+      String::try_from(Value::Null).unwrap_err(),
+      Error::TypeMismatch { expected: "String", found: "nil" },
+    ));
+
+    assert_eq!(String::try_from(Value::from("name")).unwrap(), "name");
+    assert!(bool::try_from(Value::Bool(true)).unwrap());
+    assert_eq!(f64::try_from(Value::F32(0.5)).unwrap(), 0.5);
+    assert_eq!(Uuid::try_from(Value::Uuid(Uuid::nil())).unwrap(), Uuid::nil());
+    let decimal: Decimal = "-1.5".parse().unwrap();
+    assert_eq!(Decimal::try_from(Value::Decimal(decimal)).unwrap(), decimal);
+    let vals = Vec::<Value>::try_from(Value::Array(vec![ Value::Null ])).unwrap();
+    assert!(vals.len() == 1 && vals[0].is_null());
+
+    let time = DateTime::parse_from_rfc3339("2024-01-02T03:04:05+03:00").unwrap();
+    assert_eq!(NaiveDateTime::try_from(Value::DateTimeTz(time)).unwrap(), time.naive_utc());
+    assert_eq!(
+      DateTime::<FixedOffset>::try_from(Value::DateTime(time.naive_utc())).unwrap(),
+      time,
+    );
+
+    let value = Value::Map(vec![ ("ids".into(), Value::Array(vec![ 1u64.into(), 2u64.into() ])) ]);
+    let (key, ids) = &value.as_map().unwrap()[0];
+    assert_eq!(key.as_str(), Some("ids"));
+    let ids: Vec<u64> = ids.as_array().unwrap().iter().filter_map(Value::as_u64).collect();
+    assert_eq!(ids, vec![ 1, 2 ]);
+    assert_eq!(Value::UInt(u64::MAX).as_i64(), None);
+    assert!(Value::Null.is_null());
+  }
+
+  #[test]
+  fn test_value_eq() {
+    assert_eq!(Value::Int(1), Value::UInt(1));
+    assert_eq!(Value::F32(0.5), Value::F64(0.5));
+    assert_eq!(Value::F64(f64::NAN), Value::F64(f64::NAN));
+    assert_ne!(Value::F64(0.0), Value::F64(-0.0));
+    assert_ne!(Value::UInt(1), Value::F64(1.0));
+    assert_ne!(Value::from("1"), Value::UInt(1));
+
+    let time = DateTime::parse_from_rfc3339("2024-01-02T03:04:05+03:00").unwrap();
+    assert_eq!(Value::DateTimeTz(time), Value::DateTime(time.naive_utc()));
+
+    let map = |age: u64| Value::Map(vec![ ("age".into(), age.into()) ]);
+    assert_eq!(Value::Array(vec![ map(1), Value::Null ]), Value::Array(vec![ map(1), Value::Null ]));
+    assert_ne!(map(1), map(2));
+
+    let mut counts: HashMap<Value, u32> = HashMap::new();
+    for key in [ Value::Int(1), Value::UInt(1), Value::F32(1.0), Value::F64(1.0), "a".into() ] {
+      *counts.entry(key).or_default() += 1;
+    }
+    assert_eq!(counts[&Value::UInt(1)], 2);
+    assert_eq!(counts[&Value::F64(1.0)], 2);
+    assert_eq!(counts.len(), 3);
+
+    assert!(Value::Int(-1) < Value::UInt(0));
+    assert!(Value::UInt(u64::MAX) > Value::Int(i64::MAX));
+    assert!(Value::from("a") < Value::from("b"));
+    assert!(Value::Array(vec![ 1u64.into(), 2u64.into() ]) < Value::Array(vec![ 1u64.into(), 3u64.into() ]));
+    assert_eq!(Value::UInt(1).partial_cmp(&Value::from("1")), None);
+    assert_eq!(map(1).partial_cmp(&map(2)), None);
+    assert_eq!(map(1).partial_cmp(&map(1)), Some(Ordering::Equal));
+  }
+
+  #[test]
+  fn test_value_display() {
+    let time = DateTime::parse_from_rfc3339("2024-01-02T03:04:05+03:00").unwrap();
+    let value = Value::Array(vec![
+      Value::Int(-5), Value::UInt(7), Value::Null, Value::Bool(true), Value::F64(0.5),
+      "a \"b\"".into(), Value::Bin(vec![ 1, 2 ]),
+      Value::Map(vec![ ("age".into(), 30.into()), (1u64.into(), Value::Array(Vec::new())) ]),
+      Value::Uuid(Uuid::nil()),
+      Value::Decimal("-12.034".parse().unwrap()),
+      Value::DateTime(time.naive_utc()),
+      Value::DateTimeTz(time),
+      Value::Interval(Interval { month: 2, nsec: -3, ..Default::default() }),
+    ]);
+
+    assert_eq!(value.to_string(), concat!(
+      r#"[-5, 7, null, true, 0.5, "a \"b\"", "AQI=", {"age": 30, "1": []}, "#,
+      r#""00000000-0000-0000-0000-000000000000", -12.034, "#,
+      r#""2024-01-02T00:04:05", "2024-01-02T03:04:05+03:00", {"month": 2, "nsec": -3}]"#,
+    ));
+    assert_eq!(Value::from("a\\b\n\t\u{1}ä").to_string(), r#""a\\b\n\t\u0001ä""#);
+  }
+
+  #[cfg(feature = "json")]
+  #[test]
+  fn test_value_json() {
+    let json = serde_json::json!({ "id": 1, "delta": -2, "score": 0.5, "tags": [ "a", null, true ] });
+
+    let value = Value::from(json.clone());
+    assert_eq!(value, Value::Map(vec![
+      ("delta".into(), Value::Int(-2)),
+      ("id".into(), Value::UInt(1)),
+      ("score".into(), Value::F64(0.5)),
+      ("tags".into(), Value::Array(vec![ "a".into(), Value::Null, Value::Bool(true) ])),
+    ]));
+    assert_eq!(serde_json::Value::try_from(value).unwrap(), json);
+
+    let decimal = Value::Decimal("12.50".parse().unwrap());
+    assert_eq!(serde_json::Value::try_from(decimal).unwrap(), serde_json::json!("12.50"));
+    assert!(matches!(
+      serde_json::Value::try_from(Value::F64(f64::NAN)).unwrap_err(),
+      Error::TypeMismatch { expected: "json value", found: "double" },
+    ));
+    assert!(serde_json::Value::try_from(Value::Interval(Interval::default())).is_err());
+  }
+
+  #[test]
+  fn test_into_tuple() {
+    // every length keeps all elements in order
+    for len in 0..64u64 {
+      let vals: Vec<u64> = (0..len).collect();
+      let expected: Vec<Value> = vals.iter().map(|&val| Value::UInt(val)).collect();
+
+      assert_eq!(vals.clone().into_tuple(), expected);
+      assert_eq!(Value::from(vals.clone()), Value::Array(expected.clone()));
+      assert_eq!(vals.as_slice().into_tuple(), expected);
+      assert_eq!(vals.iter().copied().collect::<VecDeque<_>>().into_tuple(), expected);
+      assert_eq!(Cow::Borrowed(vals.as_slice()).into_tuple(), expected);
+      assert_eq!(Cow::<[u64]>::Owned(vals).into_tuple(), expected);
+    }
+
+    assert_eq!([ "a", "b" ].into_tuple(), vec![ Value::from("a"), Value::from("b") ]);
+    assert_eq!((&[ 1u64, 2 ]).into_tuple(), vec![ Value::UInt(1), Value::UInt(2) ]);
+
+    let pair = ( 1u64, "a".to_string() );
+    assert_eq!((&pair).into_tuple(), vec![ Value::UInt(1), Value::from("a") ]);
+    assert_eq!(pair.into_tuple(), vec![ Value::UInt(1), Value::from("a") ]);
+
+    let wide = (
+      0u32, 1u32, 2u32, 3u32, 4u32, 5u32, 6u32, 7u32, 8u32, 9u32, 10u32, 11u32, 12u32, 13u32, 14u32, 15u32,
+      16u32, 17u32, 18u32, 19u32, 20u32, 21u32, 22u32, 23u32, 24u32, 25u32, 26u32, 27u32, 28u32, 29u32, 30u32, "last",
+    );
+    let tuple = wide.into_tuple();
+    assert_eq!(tuple.len(), 32);
+    assert_eq!(tuple[30], Value::UInt(30));
+    assert_eq!(tuple[31], Value::from("last"));
+    assert_eq!([0u32; 0].into_tuple(), Vec::<Value>::new());
+  }
+
+  /// arbitrary values with nested arrays and maps
+  fn any_value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+      Just(Value::Null),
+      any::<bool>().prop_map(Value::Bool),
+      any::<i64>().prop_map(Value::Int),
+      any::<u64>().prop_map(Value::UInt),
+      any::<f64>().prop_map(Value::F64),
+      ".*".prop_map(Value::Str),
+      collection::vec(any::<u8>(), 0..32).prop_map(Value::Bin),
+      any::<u128>().prop_map(|val| Value::Uuid(Uuid::from_u128(val))),
+      (any::<i64>(), 0u32..=28).prop_map(|(num, scale)| Value::Decimal(Decimal::new(num, scale))),
+      (-62_135_596_800i64..253_402_300_799, 0u32..1_000_000_000).prop_map(|(secs, nsecs)| {
+        Value::DateTime(DateTime::from_timestamp(secs, nsecs).unwrap().naive_utc())
+      }),
+    ];
+
+    leaf.prop_recursive(3, 32, 8, |inner| prop_oneof![
+      collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+      collection::vec((inner.clone(), inner), 0..8).prop_map(Value::Map),
+    ])
+  }
+
+  proptest! {
+    #[test]
+    fn prop_into_tuple_keeps_elements(vals in collection::vec(any::<i64>(), 0..256)) {
+      let expected: Vec<Value> = vals.iter().map(|&val| Value::Int(val)).collect();
+
+      prop_assert_eq!(vals.clone().into_tuple(), expected.clone());
+      prop_assert_eq!(Value::from(vals.clone()), Value::Array(expected.clone()));
+      prop_assert_eq!(vals.as_slice().into_tuple(), expected.clone());
+      prop_assert_eq!(vals.iter().copied().collect::<VecDeque<_>>().into_tuple(), expected.clone());
+      prop_assert_eq!(Cow::Borrowed(vals.as_slice()).into_tuple(), expected.clone());
+      prop_assert_eq!(Cow::<[i64]>::Owned(vals).into_tuple(), expected);
+    }
+
+    #[test]
+    fn prop_pack_unpack(vals in collection::vec(any_value(), 0..16)) {
+      let tuple = vals.clone().into_tuple();
+
+      let mut buf: Vec<u8> = Vec::new();
+      Value::Array(tuple).pack(&mut buf).unwrap();
+      prop_assert_eq!(Value::unpack(&mut buf.as_slice()).unwrap(), Value::Array(vals));
+    }
+  }
+
+  #[test]
+  fn test_into_key() {
+    assert_eq!(1u64.into_key(), vec![ Value::UInt(1) ]);
+    assert_eq!("ann".into_key(), vec![ Value::from("ann") ]);
+    assert_eq!(( "ann", 2u32 ).into_key(), vec![ Value::from("ann"), Value::UInt(2) ]);
+    assert_eq!(vec![ Value::UInt(1) ].into_key(), vec![ Value::UInt(1) ]);
+    assert_eq!(Value::Null.into_key(), vec![ Value::Null ]);
+    assert!(NoKey.into_key().is_empty());
+    assert!(().into_key().is_empty());
+  }
+
+  #[test]
+  fn test_unprepare() {
+    let mut req = unprepare(Unprepare { stmt_id: 7 });
+    req.header.sync = 1;
+
+    let mut buf: Vec<u8> = Vec::new();
+    req.pack(&mut buf).unwrap();
+
+    assert_eq!(&buf, &[ 206, 0, 0, 0, 8, 130, 0, 13, 1, 1, 129, 67, 7 ]);
+  }
+
+  #[test]
+  fn test_map_pack() {
+    let mut opts = BTreeMap::new();
+    opts.insert("timeout", 1u32);
+    opts.insert("limit", 10u32);
+
+    let mut buf: Vec<u8> = Vec::new();
+    Value::from(opts).pack(&mut buf).unwrap();
+
+    let mut expected: Vec<u8> = Vec::new();
+    rmpv::encode::write_value(&mut expected, &rmpv::Value::Map(vec![
+      ("limit".into(), 10.into()), ("timeout".into(), 1.into()),
+    ])).unwrap();
+    assert_eq!(buf, expected);
   }
 
 }
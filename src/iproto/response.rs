@@ -4,22 +4,39 @@
 
 use std::{
   collections::HashMap,
+  convert::TryFrom,
   io::{self, Cursor, Read},
   marker::PhantomData,
+  ops::Index,
+  sync::Arc,
 };
 
-use super::{constants::{Code, Field}, types::Error};
+use super::{constants::{Code, Field, RequestType, ERROR_BITMASK}, request, types::Error};
 
+use bytes::Bytes;
 use num_traits::FromPrimitive;
 use rmp::decode::{read_array_len, read_int, read_map_len};
 use rmpv::{Value, decode::read_value};
 use serde::de::DeserializeOwned;
 
+/**
+  Body of response.
+
+  It is a zero-copy slice of receive buffer,
+  `vec-body` feature makes it an owned copy as before.
+*/
+#[cfg(not(feature = "vec-body"))]
+pub type ResponseBody = Bytes;
+
+/// Body of response, it is copied out of receive buffer.
+#[cfg(feature = "vec-body")]
+pub type ResponseBody = Vec<u8>;
+
 /// This is representation of tarantool response.
 #[derive(Debug, Clone)]
 pub struct Response {
   pub header: Header,
-  pub body: Option<Vec<u8>>,
+  pub body: Option<ResponseBody>,
 }
 
 #[allow(dead_code)]
@@ -33,7 +50,8 @@ impl Response {
 
     let header = Header::unpack(&mut  reader)?;
 
-    let mut body: Vec<u8> = Vec::with_capacity(size as usize);
+    // size is not trusted, body grows as it is read
+    let mut body: Vec<u8> = Vec::new();
 
     reader.read_to_end(&mut body)?;
 
@@ -43,6 +61,34 @@ impl Response {
 
     body.shrink_to_fit();
     //print!("header:{:#?}", header);
+    #[cfg(not(feature = "vec-body"))]
+    let body = body.into();
+    Ok(Response { header, body: Some(body) })
+  }
+
+  /// parses response from complete frame, body refers to frame without copying
+  pub fn from_frame(frame: Bytes) -> Result<Self, Error> {
+    let mut reader = Cursor::new(&frame[..]);
+    let size: u64 = read_int(&mut reader)?;
+    let start = reader.position() as usize;
+
+    let end = start.checked_add(size as usize)
+      .filter(|&end| end <= frame.len())
+      .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+    let mut reader = Cursor::new(&frame[start..end]);
+    let header = Header::unpack(&mut reader)?;
+    let body_start = start + reader.position() as usize;
+
+    if body_start == end {
+      return Ok(Response { header, body: None });
+    }
+
+    #[cfg(not(feature = "vec-body"))]
+    let body = frame.slice(body_start..end);
+    #[cfg(feature = "vec-body")]
+    let body = frame[body_start..end].to_vec();
+
     Ok(Response { header, body: Some(body) })
   }
 
@@ -80,11 +126,17 @@ impl Response {
 }
 
 /// Representation of response header.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone)]
 pub struct Header {
+  /// error codes unknown to this crate are ErrorUnknown, see raw_code
   pub code: Code,
+  /// IPROTO_REQUEST_TYPE as is
+  pub raw_code: u64,
   pub sync: u64,
   pub schema: u64,
+  pub stream_id: Option<u64>,
+  /// fields not modeled by header, including unknown ones
+  pub extra: Vec<(u64, Value)>,
 }
 
 #[allow(dead_code)]
@@ -96,26 +148,56 @@ impl Header {
 
     for _ in 0..read_map_len(reader)? {
       let raw_field: u64 = read_int(reader)?;
-      let field: Field = FromPrimitive::from_u64(raw_field)
-        .ok_or(Error::UnexpectedField(raw_field))?;
+      let field: Option<Field> = FromPrimitive::from_u64(raw_field);
 
       match field {
-        Field::RequestType => {
-          let value = read_int(reader)?;
-          header.code = FromPrimitive::from_u64(value)
-            .ok_or(Error::UnexpectedValue(field))?;
-        },
-        Field::Sync => { header.sync = read_int(reader)? },
-        Field::SchemaVersion => { header.schema = read_int(reader)? },
-        _ => {
-          log::debug!("skipping value due to unexpected field {:?}", field);
-          read_value(reader)?;
+        Some(Field::RequestType) => {
+          header.raw_code = read_int(reader)?;
+          header.code = match FromPrimitive::from_u64(header.raw_code) {
+            Some(code) => code,
+            None if header.raw_code & ERROR_BITMASK as u64 != 0 => Code::ErrorUnknown,
+            None if header.is_event() || header.is_chunk() => Code::Ok,
+            None => return Err(Error::UnexpectedValue(Field::RequestType)),
+          };
         },
+        Some(Field::Sync) => { header.sync = read_int(reader)? },
+        Some(Field::SchemaVersion) => { header.schema = read_int(reader)? },
+        Some(Field::StreamID) => { header.stream_id = Some(read_int(reader)?) },
+        _ => { header.extra.push((raw_field, read_value(reader)?)); },
       }
     }
 
     Ok(header)
   }
+
+  pub fn is_ok(&self) -> bool {
+    !self.code.is_err()
+  }
+
+  /// true for event pushed to watcher
+  pub fn is_event(&self) -> bool {
+    self.raw_code == RequestType::Event as u64
+  }
+
+  /// true for value pushed by box.session.push, it has sync of request
+  pub fn is_chunk(&self) -> bool {
+    self.raw_code == RequestType::Chunk as u64
+  }
+
+  /// tarantool error number without ERROR_BITMASK, none for successful response
+  pub fn error_code(&self) -> Option<u64> {
+    match self.code.is_err() {
+      true => Some(self.raw_code & !(ERROR_BITMASK as u64)),
+      false => None,
+    }
+  }
+
+  /// value of field which is not modeled by header
+  pub fn field(&self, field: u64) -> Option<&Value> {
+    self.extra.iter()
+      .find(|(f, _)| *f == field)
+      .map(|(_, value)| value)
+  }
 }
 
 /**
@@ -128,7 +210,83 @@ pub trait BodyDecoder {
   fn unpack(body: &[u8]) -> Result<Self::Result, Error>;
 }
 
-#[derive(Debug, Default, Clone)]
+/**
+  deserializes value at cursor into T.
+
+  Serde impls of Decimal, Uuid and NaiveDateTime don't know tarantool extensions,
+  so if plain deserialization fails, value is retried with extensions replaced:
+  decimal becomes string, uuid becomes bytes, datetime becomes
+  ISO 8601 string (RFC 3339 one if it has offset), error becomes its message
+  and tuple with format (sent with tuple extension features) becomes array of its fields.
+*/
+fn deserialize<T>(cur: &mut Cursor<&[u8]>) -> Result<T, Error>
+  where T: DeserializeOwned
+{
+  let start = cur.position();
+  let err = match rmp_serde::decode::from_read::<_, T>(cur.by_ref()) {
+    Ok(value) => return Ok(value),
+    Err(err) => err,
+  };
+
+  cur.set_position(start);
+  let mut value = read_value(cur)?;
+  if !replace_extensions(&mut value)? {
+    return Err(Error::ParseError(err));
+  }
+
+  let mut buf: Vec<u8> = Vec::new();
+  rmpv::encode::write_value(&mut buf, &value)?;
+  rmp_serde::from_slice::<T>(&buf).map_err(Error::ParseError)
+}
+
+/// replaces decimal, uuid, datetime, error and tuple extensions, returns whether any is found
+fn replace_extensions(value: &mut Value) -> Result<bool, Error> {
+  let replaced = match value {
+    Value::Array(values) => {
+      let mut found = false;
+      for value in values.iter_mut() {
+        found |= replace_extensions(value)?;
+      }
+      return Ok(found);
+    },
+    Value::Map(pairs) => {
+      let mut found = false;
+      for (key, value) in pairs.iter_mut() {
+        found |= replace_extensions(key)?;
+        found |= replace_extensions(value)?;
+      }
+      return Ok(found);
+    },
+    Value::Ext(MP_DECIMAL, data) => Value::from(request::Value::unpack_decimal(data)?.to_string()),
+    Value::Ext(MP_UUID, data) => Value::Binary(std::mem::take(data)),
+    Value::Ext(MP_ERROR, data) => Value::from(TarantoolError::unpack_ext(data)?.message),
+    Value::Ext(MP_TUPLE, data) => {
+      let mut reader = Cursor::new(data.as_slice());
+      let _format_id: u64 = read_int(&mut reader)?;
+      let mut tuple = read_value(&mut reader)?;
+      replace_extensions(&mut tuple)?;
+      tuple
+    },
+    Value::Ext(MP_DATETIME, data) => {
+      let time = request::Value::unpack_datetime(data)?;
+      match time.offset().local_minus_utc() {
+        0 => Value::from(time.naive_utc().format("%Y-%m-%dT%H:%M:%S%.f").to_string()),
+        _ => Value::from(time.to_rfc3339()),
+      }
+    },
+    _ => return Ok(false),
+  };
+
+  *value = replaced;
+  Ok(true)
+}
+
+/**
+  This is error of stack sent by tarantool 2.4+.
+
+  Note: fields may be added, so record should be built with ..Default::default()
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct StackRecord {
   pub err_type: String,
   pub file: String,
@@ -136,16 +294,114 @@ pub struct StackRecord {
   pub message: String,
   pub errno: u64,
   pub errcode: u64,
+  /// additional fields (MP_ERROR_FIELDS) packed as msgpack map, it is empty without them
+  pub fields: Vec<u8>,
+}
+
+impl StackRecord {
+  /// additional field of error, e.g. name of custom error or failed privilege
+  pub fn field(&self, name: &str) -> Option<Value> {
+    match read_value(&mut self.fields.as_slice()).ok()? {
+      Value::Map(fields) => fields.into_iter()
+        .find(|(key, _)| key.as_str() == Some(name))
+        .map(|(_, value)| value),
+      _ => None,
+    }
+  }
 }
 
 
 /// This is representation of error returned from tarantool.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TarantoolError {
   pub message: String,
   pub stack: Vec<StackRecord>,
 }
 
+impl TarantoolError {
+  /// constructs error without stack, it is used for errors detected on client side.
+  pub fn new<S: Into<String>>(message: S) -> TarantoolError {
+    TarantoolError { message: message.into(), stack: Vec::new() }
+  }
+
+  /**
+    code of the latest error of stack, it is none without stack (tarantool < 2.4)
+    or for codes unknown to this crate, see Error::code for code of response
+  */
+  pub fn code(&self) -> Option<Code> {
+    Code::from_errcode(self.stack.first()?.errcode)
+  }
+
+  /**
+    unpacks error map of IPROTO_ERROR or MP_ERROR extension,
+    stack is its only known field
+
+    see more here
+    https://www.tarantool.io/en/doc/latest/dev_guide/internals/msgpack_extensions/#msgpack-ext-error
+  */
+  fn unpack_stack<R>(reader: &mut R) -> Result<Vec<StackRecord>, Error>
+    where R: Read
+  {
+    let mut stack: Vec<StackRecord> = Vec::new();
+
+    for _ in 0..read_map_len(reader)? {
+      if read_int::<u64, _>(reader)? != 0 { // field stack
+        read_value(reader)?;
+        continue;
+      }
+
+      let stack_len = read_array_len(reader)?;
+      stack = Vec::with_capacity(stack_len as usize);
+
+      for _ in 0..stack_len {
+        let mut stack_record = StackRecord::default();
+
+        for _ in 0..read_map_len(reader)? {
+          match read_int::<u64, _>(reader)? {
+            0 => { stack_record.err_type = read_string(reader)?; },
+            1 => { stack_record.file = read_string(reader)?; },
+            2 => { stack_record.line = read_int(reader)?; },
+            3 => { stack_record.message = read_string(reader)?; },
+            4 => { stack_record.errno = read_int(reader)?; }
+            5 => { stack_record.errcode = read_int(reader)?; }
+            6 => {
+              let fields = read_value(reader)?;
+              rmpv::encode::write_value(&mut stack_record.fields, &fields)?;
+            },
+            _ => { read_value(reader)?; },
+          }
+        }
+
+        stack.push(stack_record);
+      }
+    }
+
+    Ok(stack)
+  }
+
+  /// unpacks payload of MP_ERROR extension, message is taken from the latest error
+  pub(crate) fn unpack_ext(data: &[u8]) -> Result<TarantoolError, Error> {
+    let stack = TarantoolError::unpack_stack(&mut Cursor::new(data))?;
+    let message = stack.first()
+      .map(|record| record.message.clone())
+      .unwrap_or_default();
+
+    Ok(TarantoolError { message, stack })
+  }
+}
+
+fn read_string<R>(reader: &mut R) -> Result<String, Error>
+  where R: Read
+{
+  let str_len = rmp::decode::read_str_len(reader)?;
+  let mut buf: Vec<u8> = vec![ 0; str_len as usize ];
+  reader.read_exact(&mut buf)?;
+  String::from_utf8(buf).map_err(|_| io::Error::new(
+    io::ErrorKind::InvalidInput,
+    "invalid ut8 string",
+  ).into())
+}
+
 /// This is decoder for error body.
 pub struct ErrorBody;
 
@@ -162,17 +418,6 @@ impl BodyDecoder for ErrorBody {
       message: String::new(), stack: Vec::new(),
     };
 
-    let read_string = |reader: &mut Cursor<&[u8]>| -> Result<String, Error> {
-      let str_len = rmp::decode::read_str_len(reader)?;
-      let mut buf: Vec<u8> = Vec::new();
-      buf.resize(str_len as usize, 0);
-      reader.read_exact(&mut buf)?;
-      String::from_utf8(buf).map_err(|_| io::Error::new(
-        io::ErrorKind::InvalidInput,
-        "invalid ut8 string",
-      ).into())
-    };
-
     for _ in 0..map_len {
       let raw_field: u64 = read_int(reader)?;
       let field: Field = FromPrimitive::from_u64(raw_field)
@@ -180,42 +425,7 @@ impl BodyDecoder for ErrorBody {
 
       match field {
         Field::Error24 => { body.message = read_string(reader)? },
-
-        // see more here
-        // https://www.tarantool.io/en/doc/latest/dev_guide/internals/msgpack_extensions/#msgpack-ext-error
-        Field::Error => {
-          let map_len = read_map_len(reader)?;
-          for _ in 0..map_len {
-            if read_int::<u64, _>(reader)? != 0 { // field stack
-              read_value(reader)?;
-              continue;
-            }
-
-            let stack_len = read_array_len(reader)?;
-
-            let mut stack: Vec<StackRecord> = Vec::with_capacity(stack_len as usize);
-
-            for _ in 0..stack_len {
-              let mut stack_record = StackRecord::default();
-
-              for _ in 0..read_map_len(reader)? {
-                match read_int::<u64, _>(reader)? {
-                  0 => { stack_record.err_type = read_string(reader)?; },
-                  1 => { stack_record.file = read_string(reader)?; },
-                  2 => { stack_record.line = read_int(reader)?; },
-                  3 => { stack_record.message = read_string(reader)?; },
-                  4 => { stack_record.errno = read_int(reader)?; }
-                  5 => { stack_record.errcode = read_int(reader)?; }
-                  _ => { read_value(reader)?; },
-                }
-              }
-
-              stack.push(stack_record);
-            }
-
-            body.stack = stack;
-          }
-        },
+        Field::Error => { body.stack = TarantoolError::unpack_stack(reader)?; },
 
         _ => {
           log::debug!("skipping value due to unexpected field {:?}", field);
@@ -238,28 +448,21 @@ impl<T> BodyDecoder for TupleBody<T>
   type Result = T;
 
   fn unpack(body: &[u8]) -> Result<T, Error> {
-    
     let mut cur = Cursor::new(body);
-    
-    let map_len = read_map_len(&mut cur)?;
-    //println!("map_len: {:#?}",map_len );
-    if map_len != 1 {
-      return Err(io::Error::new(
-        io::ErrorKind::Other, "expected 1 field",
-      ).into());
-    }
-
-    let raw_field: u64 = read_int(&mut cur)?;
-    let field: Field = FromPrimitive::from_u64(raw_field)
-      .ok_or(Error::UnexpectedField(raw_field))?;
-     //println!("field:{:?} ", cur);
-    
-    match field {
-      Field::Data =>
-        rmp_serde::decode::from_read::<_, T>(cur)
-          .map_err(Error::ParseError),
-      _ => Err(Error::UnexpectedField(raw_field)),
+    let cur = &mut cur;
+
+    let mut data: Option<T> = None;
+
+    // tuple formats (tarantool 3.0+) and unknown fields are skipped
+    for _ in 0..read_map_len(cur)? {
+      let raw_field: u64 = read_int(cur)?;
+      match FromPrimitive::from_u64(raw_field) {
+        Some(Field::Data) => data = Some(deserialize::<T>(cur)?),
+        _ => { read_value(cur)?; },
+      }
     }
+
+    data.ok_or(Error::UnexpectedField(Field::Data as u64))
   }
 }
 
@@ -302,8 +505,7 @@ impl<T> BodyDecoder for TupleBodySelect<T>
           Field::Data => {
                 //let value = read_value(cur)?;
                 //println!("Field: {:#?}", field);
-                field_data = Some(rmp_serde::decode::from_read::<_, T>(cur.by_ref())
-                  .map_err(Error::ParseError)?);
+                field_data = Some(deserialize::<T>(cur)?);
           }
           _ => {
             #[warn(unused_must_use)]
@@ -322,6 +524,675 @@ impl<T> BodyDecoder for TupleBodySelect<T>
 
 }
 
+/// This is page of tuples selected with `fetch_position`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+  pub tuples: Vec<T>,
+  /**
+    position of the last tuple of page, it is passed as `after_position`
+    to select the next page. It is none if page is empty.
+  */
+  pub position: Option<Vec<u8>>,
+}
+
+/// This is decoder for response body of select with `fetch_position`.
+pub struct PageBody<T>(PhantomData<T>)
+  where T: DeserializeOwned;
+
+impl<T> BodyDecoder for PageBody<T>
+  where T: DeserializeOwned
+{
+  type Result = Page<T>;
+
+  fn unpack(body: &[u8]) -> Result<Page<T>, Error> {
+    let mut cur = Cursor::new(body);
+    let cur = &mut cur;
+
+    let mut tuples: Option<Vec<T>> = None;
+    let mut position: Option<Vec<u8>> = None;
+
+    for _ in 0..read_map_len(cur)? {
+      let raw_field: u64 = read_int(cur)?;
+      match FromPrimitive::from_u64(raw_field) {
+        Some(Field::Data) => tuples = Some(deserialize::<Vec<T>>(cur)?),
+        Some(Field::Position) => position = match read_value(cur)? {
+          Value::String(pos) => Some(pos.as_bytes().to_vec()),
+          Value::Binary(pos) => Some(pos),
+          _ => return Err(Error::UnexpectedValue(Field::Position)),
+        },
+        _ => { read_value(cur)?; },
+      }
+    }
+
+    Ok(Page {
+      tuples: tuples.ok_or(Error::UnexpectedField(Field::Data as u64))?,
+      position,
+    })
+  }
+}
+
+/// Description of SQL result column taken from IPROTO_METADATA.
+#[derive(Debug, Default, Clone)]
+pub struct ColumnMeta {
+  pub name: String,
+  pub field_type: String,
+}
+
+impl ColumnMeta {
+  pub(crate) fn from_value(value: &Value) -> Result<Self, Error> {
+    let fields = value.as_map()
+      .ok_or(Error::UnexpectedValue(Field::Metadata))?;
+
+    let mut column = ColumnMeta::default();
+
+    for (key, value) in fields.iter() {
+      match key.as_u64() {
+        // IPROTO_FIELD_NAME
+        Some(0) => {
+          column.name = value.as_str()
+            .ok_or(Error::UnexpectedValue(Field::Metadata))?
+            .into();
+        },
+        // IPROTO_FIELD_TYPE
+        Some(1) => {
+          column.field_type = value.as_str()
+            .ok_or(Error::UnexpectedValue(Field::Metadata))?
+            .into();
+        },
+        _ => {},
+      }
+    }
+
+    Ok(column)
+  }
+}
+
+/**
+  This is decoder for response body from Execute Select SQL,
+  which maps every row onto T by column names.
+
+  Unlike TupleBodySelect it matches columns to struct fields
+  (serde names and aliases), so order of columns in query doesn't matter.
+  Tarantool returns unquoted column names in upper case,
+  so columns without exactly named field match fields ignoring case.
+*/
+pub struct SQLNamedBody<T>(PhantomData<T>)
+  where T: DeserializeOwned;
+
+impl<T> BodyDecoder for SQLNamedBody<T>
+  where T: DeserializeOwned
+{
+  type Result = Vec<T>;
+
+  fn unpack(body: &[u8]) -> Result<Vec<T>, Error> {
+    let mut reader = Cursor::new(body);
+    let reader = &mut reader;
+
+    let mut columns: Option<Vec<ColumnMeta>> = None;
+    let mut rows: Option<Value> = None;
+
+    for _ in 0..read_map_len(reader)? {
+      let raw_field: u64 = read_int(reader)?;
+
+      match FromPrimitive::from_u64(raw_field) {
+        Some(Field::Metadata) => {
+          let meta = read_value(reader)?;
+          let meta = meta.as_array()
+            .ok_or(Error::UnexpectedValue(Field::Metadata))?;
+
+          columns = Some(meta.iter()
+            .map(ColumnMeta::from_value)
+            .collect::<Result<_, _>>()?);
+        },
+        Some(Field::Data) => { rows = Some(read_value(reader)?); },
+        _ => {
+          log::debug!("skipping value due to unexpected field {}", raw_field);
+          read_value(reader)?;
+        },
+      }
+    }
+
+    let columns = columns.ok_or(Error::UnexpectedValue(Field::Metadata))?;
+    let rows = match rows {
+      Some(Value::Array(rows)) => rows,
+      _ => return Err(Error::UnexpectedValue(Field::Data)),
+    };
+
+    let rows = rows.iter()
+      .map(|row| row.as_array().map(Vec::as_slice).ok_or(Error::UnexpectedValue(Field::Data)))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    SQLNamedBody::decode_rows(&columns, rows)
+  }
+}
+
+impl<T> SQLNamedBody<T>
+  where T: DeserializeOwned
+{
+  /// maps rows onto T by names of their columns, exact match of field is preferred over one ignoring case
+  pub(crate) fn decode_rows<'a, R>(columns: &[ColumnMeta], rows: R) -> Result<Vec<T>, Error>
+    where R: IntoIterator<Item = &'a [Value]>
+  {
+    let fields = struct_fields::<T>();
+    let names: Vec<&str> = columns.iter()
+      .map(|column| fields.iter()
+        .find(|field| **field == column.name)
+        .or_else(|| fields.iter().find(|field| field.eq_ignore_ascii_case(&column.name)))
+        .copied()
+        .unwrap_or(&column.name))
+      .collect();
+
+    // every row is deserialized as map of column names to its values
+    let mut buf: Vec<u8> = Vec::new();
+    rows.into_iter().map(|row| {
+      if row.len() != names.len() {
+        return Err(Error::UnexpectedValue(Field::Data));
+      }
+
+      buf.clear();
+      rmp::encode::write_map_len(&mut buf, row.len() as u32)?;
+      for (name, value) in names.iter().zip(row.iter()) {
+        rmp::encode::write_str(&mut buf, name)?;
+        rmpv::encode::write_value(&mut buf, value)?;
+      }
+
+      deserialize::<T>(&mut Cursor::new(buf.as_slice()))
+    }).collect()
+  }
+}
+
+/// fields of struct, they are taken by deserializer which stops once struct is requested
+fn struct_fields<T: DeserializeOwned>() -> &'static [&'static str] {
+  use serde::de::{self, Deserializer, Visitor};
+
+  struct Fields<'a>(&'a mut &'static [&'static str]);
+
+  impl<'de, 'a> Deserializer<'de> for Fields<'a> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+      Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+      self, _name: &'static str, fields: &'static [&'static str], _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+      *self.0 = fields;
+      Err(de::Error::custom("fields are taken"))
+    }
+
+    serde::forward_to_deserialize_any! {
+      bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+      bytes byte_buf option unit unit_struct newtype_struct seq tuple
+      tuple_struct map enum identifier ignored_any
+    }
+  }
+
+  let mut fields: &'static [&'static str] = &[];
+  let _ = T::deserialize(Fields(&mut fields));
+  fields
+}
+
+/**
+  This is result of SQL statement with metadata of its columns.
+
+  Rows of SELECT are accessed by column names,
+  row count and autoincrement ids are set for DML.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, Execute, Prepare};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let result = conn.execute_result(Execute {
+      expr: Prepare::SQL("SELECT id, name FROM users".into()),
+      sql_bind: vec![],
+      options: vec![],
+    }).await?;
+
+    for row in result.rows() {
+      let id: u64 = row.get("ID")?;
+      let name: String = row.get_index(1)?;
+    }
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Default, Clone)]
+pub struct SqlResult {
+  columns: Vec<ColumnMeta>,
+  rows: Vec<Vec<Value>>,
+  row_count: Option<u64>,
+  autoincrement_ids: Vec<i64>,
+}
+
+impl SqlResult {
+  pub fn columns(&self) -> &[ColumnMeta] {
+    &self.columns
+  }
+
+  /// position of column, note that tarantool returns unquoted names in upper case
+  pub fn position(&self, name: &str) -> Option<usize> {
+    self.columns.iter().position(|column| column.name == name)
+  }
+
+  pub fn rows(&self) -> impl Iterator<Item = Row<'_>> + '_ {
+    self.rows.iter().map(move |values| Row { columns: &self.columns, values })
+  }
+
+  pub fn len(&self) -> usize {
+    self.rows.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.rows.is_empty()
+  }
+
+  /**
+    maps every row onto T by column names as SQLNamedBody does,
+    e.g. `#[derive(Deserialize)] struct User { id: u64, name: String }`
+    is decoded from columns ID and NAME
+  */
+  pub fn decode<T>(&self) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned
+  {
+    SQLNamedBody::decode_rows(&self.columns, self.rows.iter().map(Vec::as_slice))
+  }
+
+  /// count of rows changed by DML, it is none for SELECT
+  pub fn row_count(&self) -> Option<u64> {
+    self.row_count
+  }
+
+  /// ids generated by autoincrement fields on INSERT
+  pub fn autoincrement_ids(&self) -> &[i64] {
+    &self.autoincrement_ids
+  }
+
+  fn info(&mut self, info: &Value) -> Result<(), Error> {
+    let info = info.as_map()
+      .ok_or(Error::UnexpectedValue(Field::SqlInfo))?;
+
+    for (key, value) in info.iter() {
+      match key.as_u64() {
+        // SQL_INFO_ROW_COUNT
+        Some(0) => {
+          self.row_count = Some(value.as_u64()
+            .ok_or(Error::UnexpectedValue(Field::SqlInfo))?);
+        },
+        // SQL_INFO_AUTOINCREMENT_IDS
+        Some(1) => {
+          self.autoincrement_ids = value.as_array()
+            .ok_or(Error::UnexpectedValue(Field::SqlInfo))?
+            .iter()
+            .map(|id| id.as_i64().ok_or(Error::UnexpectedValue(Field::SqlInfo)))
+            .collect::<Result<_, _>>()?;
+        },
+        _ => {},
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// This is row of SqlResult, its values are decoded on access.
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+  columns: &'a [ColumnMeta],
+  values: &'a [Value],
+}
+
+impl<'a> Row<'a> {
+  pub fn values(&self) -> &'a [Value] {
+    self.values
+  }
+
+  pub fn value(&self, name: &str) -> Option<&'a Value> {
+    let index = self.columns.iter().position(|column| column.name == name)?;
+    self.values.get(index)
+  }
+
+  /// decodes value of column by name, unknown column is error
+  pub fn get<T>(&self, name: &str) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    let value = self.value(name).ok_or_else(|| io::Error::new(
+      io::ErrorKind::NotFound, format!("unknown column {}", name),
+    ))?;
+    Row::decode(value)
+  }
+
+  /// decodes value of column by position
+  pub fn get_index<T>(&self, index: usize) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    let value = self.values.get(index).ok_or_else(|| io::Error::new(
+      io::ErrorKind::NotFound, format!("unknown column {}", index),
+    ))?;
+    Row::decode(value)
+  }
+
+  fn decode<T>(value: &Value) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    let mut buf: Vec<u8> = Vec::new();
+    rmpv::encode::write_value(&mut buf, value)?;
+    deserialize::<T>(&mut Cursor::new(buf.as_slice()))
+  }
+}
+
+/// This is decoder for response body of SQL statement into SqlResult.
+pub struct SqlResultBody;
+
+impl BodyDecoder for SqlResultBody {
+  type Result = SqlResult;
+
+  fn unpack(body: &[u8]) -> Result<SqlResult, Error> {
+    let mut reader = Cursor::new(body);
+    let reader = &mut reader;
+
+    let mut result = SqlResult::default();
+
+    for _ in 0..read_map_len(reader)? {
+      let raw_field: u64 = read_int(reader)?;
+
+      match FromPrimitive::from_u64(raw_field) {
+        Some(Field::Metadata) => {
+          let meta = read_value(reader)?;
+          let meta = meta.as_array()
+            .ok_or(Error::UnexpectedValue(Field::Metadata))?;
+
+          result.columns = meta.iter()
+            .map(ColumnMeta::from_value)
+            .collect::<Result<_, _>>()?;
+        },
+        Some(Field::Data) => {
+          let rows = match read_value(reader)? {
+            Value::Array(rows) => rows,
+            _ => return Err(Error::UnexpectedValue(Field::Data)),
+          };
+
+          result.rows = rows.into_iter()
+            .map(|row| match row {
+              Value::Array(values) => Ok(values),
+              _ => Err(Error::UnexpectedValue(Field::Data)),
+            })
+            .collect::<Result<_, _>>()?;
+        },
+        Some(Field::SqlInfo) => result.info(&read_value(reader)?)?,
+        _ => {
+          log::debug!("skipping value due to unexpected field {}", raw_field);
+          read_value(reader)?;
+        },
+      }
+    }
+
+    Ok(result)
+  }
+}
+
+/// msgpack extension types of tarantool
+const MP_DECIMAL: i8 = 1;
+const MP_UUID: i8 = 2;
+const MP_ERROR: i8 = 3;
+const MP_DATETIME: i8 = 4;
+/// msgpack extension type of tuple with format (MP_TUPLE)
+const MP_TUPLE: i8 = 7;
+
+/// Description of tuple field taken from IPROTO_TUPLE_FORMATS.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FormatField {
+  pub name: String,
+  pub field_type: String,
+  pub is_nullable: bool,
+}
+
+impl FormatField {
+  pub(crate) fn from_value(value: &Value) -> Result<Self, Error> {
+    let fields = value.as_map()
+      .ok_or(Error::UnexpectedValue(Field::TupleFormats))?;
+
+    let mut field = FormatField::default();
+
+    for (key, value) in fields.iter() {
+      match key.as_str() {
+        Some("name") => {
+          field.name = value.as_str()
+            .ok_or(Error::UnexpectedValue(Field::TupleFormats))?
+            .into();
+        },
+        Some("type") => {
+          field.field_type = value.as_str()
+            .ok_or(Error::UnexpectedValue(Field::TupleFormats))?
+            .into();
+        },
+        Some("is_nullable") => {
+          field.is_nullable = value.as_bool()
+            .ok_or(Error::UnexpectedValue(Field::TupleFormats))?;
+        },
+        _ => {},
+      }
+    }
+
+    Ok(field)
+  }
+}
+
+pub type TupleFormat = Arc<Vec<FormatField>>;
+
+/**
+  This is tuple returned with its format.
+
+  Fields are accessed by position or by name,
+  names are known only when server sends tuple formats (tarantool 3.0+),
+  otherwise tuple behaves like plain array.
+
+  Example:
+  ```rust,no_run
+    # use rmpv::Value;
+    # use alopecosa::{Connection, Error, Select};
+    # async fn f(conn: &Connection, select: Select) -> Result<(), Error> {
+    let tuples = conn.select_formatted(select).await?;
+    let name = tuples[0]["name"].as_str();
+    let balance = tuples[0].get("balance").and_then(Value::as_u64);
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormattedTuple {
+  format: Option<TupleFormat>,
+  values: Vec<Value>,
+}
+
+impl FormattedTuple {
+  pub fn new(format: Option<TupleFormat>, values: Vec<Value>) -> FormattedTuple {
+    FormattedTuple { format, values }
+  }
+
+  pub fn format(&self) -> Option<&[FormatField]> {
+    self.format.as_deref().map(Vec::as_slice)
+  }
+
+  pub fn values(&self) -> &[Value] {
+    &self.values
+  }
+
+  pub fn into_values(self) -> Vec<Value> {
+    self.values
+  }
+
+  pub fn len(&self) -> usize {
+    self.values.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.values.is_empty()
+  }
+
+  /// position of field with given name
+  pub fn position(&self, name: &str) -> Option<usize> {
+    self.format.as_ref()?.iter().position(|field| field.name == name)
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Value> {
+    self.values.get(self.position(name)?)
+  }
+
+  /**
+    maps tuple onto T, by field names if format is known
+    and by positions otherwise
+  */
+  pub fn decode<T>(&self) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    let mut buf: Vec<u8> = Vec::new();
+
+    match &self.format {
+      Some(format) => {
+        let named = format.len().min(self.values.len());
+        rmp::encode::write_map_len(&mut buf, named as u32)?;
+        for (field, value) in format.iter().zip(self.values.iter()) {
+          rmp::encode::write_str(&mut buf, &field.name)?;
+          rmpv::encode::write_value(&mut buf, value)?;
+        }
+      },
+      None => {
+        rmpv::encode::write_value(&mut buf, &Value::Array(self.values.clone()))?;
+      },
+    }
+
+    deserialize::<T>(&mut Cursor::new(buf.as_slice()))
+  }
+}
+
+impl Index<usize> for FormattedTuple {
+  type Output = Value;
+
+  fn index(&self, index: usize) -> &Value {
+    &self.values[index]
+  }
+}
+
+impl Index<&str> for FormattedTuple {
+  type Output = Value;
+
+  fn index(&self, name: &str) -> &Value {
+    match self.get(name) {
+      Some(value) => value,
+      None => panic!("no field {:?} in tuple", name),
+    }
+  }
+}
+
+/**
+  This is decoder for response body with tuples,
+  which keeps IPROTO_TUPLE_FORMATS sent by tarantool 3.0+
+  so fields may be accessed by names.
+
+  Tuples without format (sent by older servers) are decoded as well.
+*/
+pub struct FormattedBody;
+
+impl FormattedBody {
+  fn tuple(value: Value, formats: &HashMap<u64, TupleFormat>) -> Result<FormattedTuple, Error> {
+    match value {
+      Value::Array(values) => Ok(FormattedTuple::new(None, values)),
+      Value::Ext(MP_TUPLE, data) => {
+        let mut reader = Cursor::new(data.as_slice());
+        let format_id: u64 = read_int(&mut reader)?;
+        let format = formats.get(&format_id)
+          .ok_or(Error::UnexpectedValue(Field::TupleFormats))?;
+
+        match read_value(&mut reader)? {
+          Value::Array(values) => Ok(FormattedTuple::new(Some(format.clone()), values)),
+          _ => Err(Error::UnexpectedValue(Field::Data)),
+        }
+      },
+      _ => Err(Error::UnexpectedValue(Field::Data)),
+    }
+  }
+
+  fn format(value: &Value) -> Result<TupleFormat, Error> {
+    // format may be sent as raw msgpack
+    let decoded;
+    let value = match value {
+      Value::Binary(raw) => {
+        decoded = read_value(&mut Cursor::new(raw.as_slice()))?;
+        &decoded
+      },
+      value => value,
+    };
+
+    let fields = value.as_array()
+      .ok_or(Error::UnexpectedValue(Field::TupleFormats))?;
+
+    Ok(Arc::new(fields.iter()
+      .map(FormatField::from_value)
+      .collect::<Result<_, _>>()?))
+  }
+}
+
+impl BodyDecoder for FormattedBody {
+  type Result = Vec<FormattedTuple>;
+
+  fn unpack(body: &[u8]) -> Result<Vec<FormattedTuple>, Error> {
+    let mut reader = Cursor::new(body);
+    let reader = &mut reader;
+
+    let mut formats: HashMap<u64, TupleFormat> = HashMap::new();
+    let mut data: Option<Value> = None;
+
+    for _ in 0..read_map_len(reader)? {
+      let raw_field: u64 = read_int(reader)?;
+
+      match FromPrimitive::from_u64(raw_field) {
+        Some(Field::TupleFormats) => {
+          let value = read_value(reader)?;
+          let value = value.as_map()
+            .ok_or(Error::UnexpectedValue(Field::TupleFormats))?;
+
+          for (id, format) in value.iter() {
+            let id = id.as_u64()
+              .ok_or(Error::UnexpectedValue(Field::TupleFormats))?;
+            formats.insert(id, FormattedBody::format(format)?);
+          }
+        },
+        Some(Field::Data) => { data = Some(read_value(reader)?); },
+        _ => {
+          log::debug!("skipping value due to unexpected field {}", raw_field);
+          read_value(reader)?;
+        },
+      }
+    }
+
+    match data {
+      Some(Value::Array(tuples)) => tuples.into_iter()
+        .map(|tuple| FormattedBody::tuple(tuple, &formats))
+        .collect(),
+      _ => Err(Error::UnexpectedValue(Field::Data)),
+    }
+  }
+}
+
+/**
+  This is decoder for response body with tuples into request values,
+  so tuples of dynamic schema may be read without serde structs.
+
+  Extensions are decoded as in `request::Value::unpack`.
+*/
+pub struct RawTupleBody;
+
+impl BodyDecoder for RawTupleBody {
+  type Result = Vec<Vec<request::Value>>;
+
+  fn unpack(body: &[u8]) -> Result<Self::Result, Error> {
+    FormattedBody::unpack(body)?.into_iter()
+      .map(|tuple| tuple.into_values().into_iter()
+        .map(request::Value::try_from)
+        .collect())
+      .collect()
+  }
+}
+
 /// This is representation of SQL response body.
 pub type SQLBody = HashMap<Field, Value>;
 
@@ -378,6 +1249,23 @@ mod tests {
       assert_eq!(&tuple, &[(1, 2, 3)]);
     }
 
+    #[test]
+    fn test_from_frame() {
+      let frame = Bytes::from_static(&[
+        206, 0, 0, 0, 34, 131, 0, 206, 0, 0, 0, 0, 1,
+        207, 0, 0, 0, 0, 0, 0, 0, 0, 5, 206, 0, 0, 0,
+        80, 129, 48, 221, 0, 0, 0, 1, 147, 1, 2, 3,
+      ]);
+      let resp = Response::from_frame(frame.clone()).unwrap();
+      let tuple: Vec<(u64, u64, u64)> = resp.unpack_body::<TupleBody<_>>().unwrap();
+      assert_eq!(&tuple, &[(1, 2, 3)]);
+
+      #[cfg(not(feature = "vec-body"))]
+      assert_eq!(resp.body.unwrap().as_ptr(), frame[28..].as_ptr());
+
+      assert!(Response::from_frame(frame.slice(..30)).is_err());
+    }
+
 
     #[test]
     fn test_call_body() {
@@ -392,6 +1280,29 @@ mod tests {
       assert_eq!(tuple, (123, 124));
     }
 
+    #[test]
+    fn test_header_fields() {
+      let mut header: Vec<u8> = Vec::new();
+      rmpv::encode::write_value(&mut header, &Value::Map(vec![
+        (0.into(), ((ERROR_BITMASK as u64) | 4000).into()),
+        (1.into(), 7.into()), (0x0a.into(), 3.into()),
+        (0x7f.into(), "custom".into()), (0x02.into(), 1.into()),
+      ])).unwrap();
+
+      let mut buf: Vec<u8> = Vec::new();
+      rmp::encode::write_u32(&mut buf, header.len() as u32).unwrap();
+      buf.extend(header);
+
+      let resp = Response::parse(&buf[..]).unwrap();
+      assert_eq!(resp.header.code, Code::ErrorUnknown);
+      assert!(!resp.header.is_ok());
+      assert_eq!(resp.header.error_code(), Some(4000));
+      assert_eq!(resp.header.sync, 7);
+      assert_eq!(resp.header.stream_id, Some(3));
+      assert_eq!(resp.header.field(0x7f).and_then(Value::as_str), Some("custom"));
+      assert_eq!(resp.header.field(Field::ReplicaID as u64), Some(&Value::from(1)));
+    }
+
     #[test]
     fn test_error_body() {
 
@@ -410,9 +1321,297 @@ mod tests {
       let resp = Response::parse(&buf[..]).unwrap();
 
       assert!(resp.header.code.is_err());
+      assert_eq!(resp.header.error_code(), Some(20));
+      assert_eq!(resp.header.field(Field::SchemaVersion as u64), None);
 
       let err = resp.unpack_body::<ErrorBody>().unwrap();
       assert_eq!(err.message, "Invalid MsgPack - packet body");
       assert_eq!(err.stack.len(), 1);
     }
+
+    #[test]
+    fn test_sql_named_body() {
+      #[derive(Debug, PartialEq, serde::Deserialize)]
+      struct Row {
+        #[serde(rename = "ID")]
+        id: u64,
+        #[serde(alias = "NAME")]
+        name: String,
+      }
+
+      let buf = [
+        130, // map of 2
+        50, 146, // metadata
+        130, 0, 164, 78, 65, 77, 69, 1, 166, 115, 116, 114, 105, 110, 103,
+        130, 0, 162, 73, 68, 1, 167, 105, 110, 116, 101, 103, 101, 114,
+        48, 146, // data
+        146, 161, 97, 1,
+        146, 161, 98, 2,
+      ];
+
+      let rows = SQLNamedBody::<Row>::unpack(&buf).unwrap();
+      assert_eq!(rows, vec![
+        Row { id: 1, name: "a".into() },
+        Row { id: 2, name: "b".into() },
+      ]);
+
+      #[derive(Debug, PartialEq, serde::Deserialize)]
+      struct User {
+        id: u64,
+        name: String,
+      }
+
+      let users = SQLNamedBody::<User>::unpack(&buf).unwrap();
+      assert_eq!(users, vec![
+        User { id: 1, name: "a".into() },
+        User { id: 2, name: "b".into() },
+      ]);
+    }
+
+    #[test]
+    fn test_tuple_body_extensions() {
+      let created = chrono::DateTime::from_timestamp(1_700_000_000, 5).unwrap().naive_utc();
+      let mut body: Vec<u8> = Vec::new();
+      rmp::encode::write_map_len(&mut body, 1).unwrap();
+      rmp::encode::write_uint(&mut body, 0x30).unwrap();
+      request::Value::Array(vec![
+        request::Value::Array(vec![
+          1.into(),
+          request::Value::Decimal("-12.034".parse().unwrap()),
+          request::Value::Uuid(uuid::Uuid::nil()),
+          request::Value::DateTime(created),
+        ]),
+      ]).pack(&mut body).unwrap();
+
+      type Row = (u64, rust_decimal::Decimal, uuid::Uuid, chrono::NaiveDateTime);
+      let rows = TupleBody::<Vec<Row>>::unpack(&body).unwrap();
+      assert_eq!(rows, vec![ (1, "-12.034".parse().unwrap(), uuid::Uuid::nil(), created) ]);
+
+      assert!(TupleBody::<Vec<(u64, String)>>::unpack(&body).is_err());
+
+      // error returned by function with error extension is decoded as its message
+      let mut body: Vec<u8> = Vec::new();
+      rmp::encode::write_map_len(&mut body, 1).unwrap();
+      rmp::encode::write_uint(&mut body, 0x30).unwrap();
+      request::Value::Array(vec![
+        request::Value::Error(TarantoolError::new("not enough money")),
+      ]).pack(&mut body).unwrap();
+      let (message,) = TupleBody::<(String,)>::unpack(&body).unwrap();
+      assert_eq!(message, "not enough money");
+
+      // tuple with format is decoded as its fields
+      let mut tuple: Vec<u8> = Vec::new();
+      rmp::encode::write_uint(&mut tuple, 42).unwrap();
+      request::Value::Array(vec![ 1.into(), request::Value::Uuid(uuid::Uuid::nil()) ])
+        .pack(&mut tuple).unwrap();
+      let mut body: Vec<u8> = Vec::new();
+      rmpv::encode::write_value(&mut body, &Value::Map(vec![
+        (0x30.into(), Value::Array(vec![ Value::Ext(MP_TUPLE, tuple) ])),
+      ])).unwrap();
+      let rows = TupleBody::<Vec<(u64, uuid::Uuid)>>::unpack(&body).unwrap();
+      assert_eq!(rows, vec![ (1, uuid::Uuid::nil()) ]);
+    }
+
+    #[test]
+    fn test_raw_tuple_body() {
+      let mut body: Vec<u8> = Vec::new();
+      rmpv::encode::write_value(&mut body, &Value::Map(vec![
+        (0x30.into(), Value::Array(vec![
+          Value::Array(vec![ 1.into(), "a".into(), Value::Ext(2, vec![ 0; 16 ]) ]),
+          Value::Array(vec![ 2.into(), Value::Nil ]),
+        ])),
+      ])).unwrap();
+
+      let tuples = RawTupleBody::unpack(&body).unwrap();
+      assert_eq!(tuples.len(), 2);
+      assert!(matches!(tuples[0].as_slice(), [
+        request::Value::UInt(1), request::Value::Str(name), request::Value::Uuid(uuid),
+      ] if name == "a" && uuid.is_nil()));
+      assert!(matches!(tuples[1].as_slice(), [ request::Value::UInt(2), request::Value::Null ]));
+    }
+
+    #[test]
+    fn test_page_body() {
+      let mut body: Vec<u8> = Vec::new();
+      rmpv::encode::write_value(&mut body, &Value::Map(vec![
+        (0x30.into(), Value::Array(vec![
+          Value::Array(vec![ 1.into(), "a".into() ]),
+          Value::Array(vec![ 2.into(), "b".into() ]),
+        ])),
+        (0x35.into(), "pos".into()),
+      ])).unwrap();
+
+      let page = PageBody::<(u64, String)>::unpack(&body).unwrap();
+      assert_eq!(page.tuples, vec![ (1, "a".into()), (2, "b".into()) ]);
+      assert_eq!(page.position, Some(b"pos".to_vec()));
+
+      let mut body: Vec<u8> = Vec::new();
+      rmpv::encode::write_value(&mut body, &Value::Map(vec![
+        (0x30.into(), Value::Array(vec![])),
+      ])).unwrap();
+
+      let page = PageBody::<(u64, String)>::unpack(&body).unwrap();
+      assert!(page.tuples.is_empty());
+      assert_eq!(page.position, None);
+    }
+
+    #[test]
+    fn test_sql_result_body() {
+      let buf = [
+        130, // map of 2
+        50, 146, // metadata
+        130, 0, 164, 78, 65, 77, 69, 1, 166, 115, 116, 114, 105, 110, 103,
+        130, 0, 162, 73, 68, 1, 167, 105, 110, 116, 101, 103, 101, 114,
+        48, 146, // data
+        146, 161, 97, 1,
+        146, 161, 98, 2,
+      ];
+
+      let result = SqlResultBody::unpack(&buf).unwrap();
+      assert_eq!(result.len(), 2);
+      assert_eq!(result.position("ID"), Some(1));
+      assert_eq!(result.row_count(), None);
+
+      let rows: Vec<(String, u64)> = result.rows()
+        .map(|row| Ok((row.get::<String>("NAME")?, row.get_index::<u64>(1)?)))
+        .collect::<Result<_, Error>>()
+        .unwrap();
+      assert_eq!(rows, vec![ ("a".into(), 1), ("b".into(), 2) ]);
+
+      #[derive(Debug, PartialEq, serde::Deserialize)]
+      struct User {
+        #[serde(rename = "ID")]
+        id: u64,
+        #[serde(rename = "NAME")]
+        name: String,
+      }
+      assert_eq!(result.decode::<User>().unwrap(), vec![
+        User { id: 1, name: "a".into() },
+        User { id: 2, name: "b".into() },
+      ]);
+
+      // upper case columns match lower case fields
+      #[derive(Debug, PartialEq, serde::Deserialize)]
+      struct LowerUser {
+        id: u64,
+        name: String,
+      }
+      assert_eq!(result.decode::<LowerUser>().unwrap(), vec![
+        LowerUser { id: 1, name: "a".into() },
+        LowerUser { id: 2, name: "b".into() },
+      ]);
+
+      let row = result.rows().next().unwrap();
+      assert!(row.get::<u64>("name").is_err());
+      assert!(row.get::<u64>("NAME").is_err());
+      assert_eq!(row.value("NAME").and_then(Value::as_str), Some("a"));
+
+      // sql info of DML
+      let buf = [ 129, 66, 130, 0, 2, 1, 146, 5, 6 ];
+      let result = SqlResultBody::unpack(&buf).unwrap();
+      assert!(result.is_empty());
+      assert_eq!(result.row_count(), Some(2));
+      assert_eq!(result.autoincrement_ids(), &[ 5, 6 ]);
+    }
+
+    #[test]
+    fn test_formatted_body() {
+      let format = vec![
+        FormatField { name: "id".into(), field_type: "unsigned".into(), is_nullable: false },
+        FormatField { name: "name".into(), field_type: "string".into(), is_nullable: false },
+      ];
+
+      let mut tuple: Vec<u8> = Vec::new();
+      rmp::encode::write_uint(&mut tuple, 42).unwrap();
+      rmpv::encode::write_value(&mut tuple, &Value::Array(vec![ 1.into(), "a".into() ])).unwrap();
+
+      let mut body: Vec<u8> = Vec::new();
+      rmpv::encode::write_value(&mut body, &Value::Map(vec![
+        (0x60.into(), Value::Map(vec![
+          (42.into(), Value::Array(vec![
+            Value::Map(vec![ ("name".into(), "id".into()), ("type".into(), "unsigned".into()) ]),
+            Value::Map(vec![ ("name".into(), "name".into()), ("type".into(), "string".into()) ]),
+          ])),
+        ])),
+        (0x30.into(), Value::Array(vec![
+          Value::Ext(MP_TUPLE, tuple),
+          Value::Array(vec![ 2.into(), "b".into() ]),
+        ])),
+      ])).unwrap();
+
+      let tuples = FormattedBody::unpack(&body).unwrap();
+      assert_eq!(tuples.len(), 2);
+
+      assert_eq!(tuples[0].format(), Some(format.as_slice()));
+      assert_eq!(tuples[0]["name"].as_str(), Some("a"));
+      assert_eq!(tuples[0][0].as_u64(), Some(1));
+
+      assert_eq!(tuples[1].format(), None);
+      assert_eq!(tuples[1].get("name"), None);
+      assert_eq!(tuples[1][1].as_str(), Some("b"));
+
+      #[derive(Debug, PartialEq, serde::Deserialize)]
+      struct Row { name: String, id: u64 }
+
+      assert_eq!(tuples[0].decode::<Row>().unwrap(), Row { id: 1, name: "a".into() });
+      assert_eq!(tuples[1].decode::<(u64, String)>().unwrap(), (2, "b".into()));
+    }
+
+    #[test]
+    fn test_tuple_formats_wire() {
+      // select body as tarantool 3.x encodes it with dml tuple extension
+      let body = [
+        0x82, // map of 2
+        0x60, 0x81, 0x01, 0x92, // IPROTO_TUPLE_FORMATS, format 1 of 2 fields
+        0x82, 0xa4, b'n', b'a', b'm', b'e', 0xa2, b'i', b'd',
+        0xa4, b't', b'y', b'p', b'e', 0xa8, b'u', b'n', b's', b'i', b'g', b'n', b'e', b'd',
+        0x82, 0xa4, b'n', b'a', b'm', b'e', 0xa4, b'n', b'a', b'm', b'e',
+        0xa4, b't', b'y', b'p', b'e', 0xa6, b's', b't', b'r', b'i', b'n', b'g',
+        0x30, 0x91, // IPROTO_DATA of 1 tuple
+        0xc7, 0x07, 0x07, 0x01, 0x92, 0x01, 0xa3, b'a', b'n', b'n', // MP_TUPLE of format 1
+      ];
+
+      assert_eq!(Field::TupleFormats as u64, 0x60);
+
+      let tuples = FormattedBody::unpack(&body).unwrap();
+      assert_eq!(tuples[0].format().map(<[FormatField]>::len), Some(2));
+      assert_eq!(tuples[0]["id"].as_u64(), Some(1));
+      assert_eq!(tuples[0]["name"].as_str(), Some("ann"));
+    }
+
+    #[test]
+    fn test_tuple_body_with_formats() {
+      // reply of server which agreed to dml and call tuple extensions
+      let mut tuple: Vec<u8> = Vec::new();
+      rmp::encode::write_uint(&mut tuple, 1).unwrap();
+      rmpv::encode::write_value(&mut tuple, &Value::Array(vec![ 1.into(), "ann".into() ])).unwrap();
+
+      let mut body: Vec<u8> = Vec::new();
+      rmpv::encode::write_value(&mut body, &Value::Map(vec![
+        (0x60.into(), Value::Map(vec![
+          (1.into(), Value::Array(vec![
+            Value::Map(vec![ ("name".into(), "id".into()), ("type".into(), "unsigned".into()) ]),
+            Value::Map(vec![ ("name".into(), "name".into()), ("type".into(), "string".into()) ]),
+          ])),
+        ])),
+        (0x30.into(), Value::Array(vec![ Value::Ext(MP_TUPLE, tuple) ])),
+        // field unknown to client
+        (0x7f.into(), Value::Nil),
+      ])).unwrap();
+
+      let rows = TupleBody::<Vec<(u64, String)>>::unpack(&body).unwrap();
+      assert_eq!(rows, vec![ (1, "ann".into()) ]);
+
+      let page = PageBody::<(u64, String)>::unpack(&body).unwrap();
+      assert_eq!(page.tuples, vec![ (1, "ann".into()) ]);
+
+      let tuples = FormattedBody::unpack(&body).unwrap();
+      assert_eq!(tuples[0]["name"].as_str(), Some("ann"));
+
+      // body without data is still an error
+      let mut body: Vec<u8> = Vec::new();
+      rmpv::encode::write_value(&mut body, &Value::Map(vec![ (0x7f.into(), Value::Nil) ])).unwrap();
+      assert!(matches!(TupleBody::<Vec<(u64, String)>>::unpack(&body), Err(Error::UnexpectedField(0x30))));
+      assert!(SqlResultBody::unpack(&body).unwrap().is_empty());
+    }
 }
@@ -0,0 +1,580 @@
+/*!
+  This module contains serde serializer into request values.
+
+  Structs are packed as arrays of their fields in declaration order,
+  so any `T: Serialize` may be used as tuple or call arguments.
+  Tarantool extension types are serialized through newtype wrappers,
+  other serializers see them as their inner values.
+
+  Example:
+  ```rust
+    # use rust_decimal::Decimal;
+    # use serde::Serialize;
+    # use uuid::Uuid;
+    # use alopecosa::{Connection, Error, MpDecimal, MpUuid, iproto::serialize};
+    # fn main() -> Result<(), Error> {
+    #[derive(Serialize)]
+    struct User {
+      id: u64,
+      name: String,
+      token: MpUuid,
+      balance: MpDecimal,
+    }
+
+    # let user = User { id: 1, name: "ann".into(), token: MpUuid(Uuid::nil()), balance: MpDecimal(Decimal::new(150, 2)) };
+    let tuple = serialize::to_tuple(&user)?;
+    assert_eq!(tuple.len(), 4);
+    # async fn insert(conn: &Connection, user: &User) -> Result<(), Error> {
+    conn.insert_struct::<_, (u64, String, Uuid, String)>(512, &user).await?;
+    # Ok(()) }
+    # Ok(()) }
+  ```
+
+  Value itself is Serialize and Deserialize, so it may be nested into
+  user structs as dynamic field. Its extensions are passed as ext structs
+  of rmp_serde, so they are packed as MP_EXT and decoded back.
+
+  ```rust,no_run
+    # use serde::Deserialize;
+    # use alopecosa::{Connection, Error, Select, Value};
+    # async fn f(conn: &Connection, select: Select) -> Result<(), Error> {
+    #[derive(Deserialize)]
+    struct Event {
+      id: u64,
+      payload: Value,
+    }
+
+    let events: Vec<Event> = conn.select(select).await?;
+    # Ok(()) }
+  ```
+*/
+
+use std::{convert::TryFrom, fmt::{self, Display}};
+
+use chrono::{DateTime, NaiveDateTime};
+use rmp_serde::MSGPACK_EXT_STRUCT_NAME;
+use rust_decimal::Decimal;
+use serde::{
+  Deserialize, Deserializer, Serialize, Serializer,
+  de::{self, MapAccess, SeqAccess, Visitor},
+  ser::{self, Impossible},
+};
+use uuid::Uuid;
+
+use super::{request::Value, types::Error};
+
+const UUID: &str = "$alopecosa::Uuid";
+const DECIMAL: &str = "$alopecosa::Decimal";
+const DATETIME: &str = "$alopecosa::Datetime";
+
+/// serializes value, structs and sequences become arrays
+pub fn to_value<T>(value: &T) -> Result<Value, Error>
+  where T: Serialize + ?Sized
+{
+  value.serialize(ValueSerializer)
+}
+
+/// serializes value which must become array, e.g. struct or tuple
+pub fn to_tuple<T>(value: &T) -> Result<Vec<Value>, Error>
+  where T: Serialize + ?Sized
+{
+  match to_value(value)? {
+    Value::Array(values) => Ok(values),
+    value => Err(Error::EncodeError(format!("tuple must be serialized as array, got {:?}", value))),
+  }
+}
+
+/// Uuid packed as MP_EXT uuid, it is serialized as 16 bytes by other serializers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MpUuid(pub Uuid);
+
+impl Serialize for MpUuid {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_newtype_struct(UUID, &Bytes(self.0.as_bytes()))
+  }
+}
+
+/// Decimal packed as MP_EXT decimal, it is serialized as string by other serializers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MpDecimal(pub Decimal);
+
+impl Serialize for MpDecimal {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_newtype_struct(DECIMAL, &self.0.to_string())
+  }
+}
+
+/**
+  Datetime packed as MP_EXT datetime, it is serialized
+  as seconds and nanoseconds since epoch by other serializers.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MpDatetime(pub NaiveDateTime);
+
+impl Serialize for MpDatetime {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let time = self.0.and_utc();
+    let time = (time.timestamp(), time.timestamp_subsec_nanos());
+    serializer.serialize_newtype_struct(DATETIME, &time)
+  }
+}
+
+/// Slice serialized as bytes instead of sequence.
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bytes(self.0)
+  }
+}
+
+/// extensions are serialized as ext structs, see module docs
+impl Serialize for Value {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    match self {
+      &Value::Int(val) => serializer.serialize_i64(val),
+      &Value::UInt(val) => serializer.serialize_u64(val),
+      &Value::F32(val) => serializer.serialize_f32(val),
+      &Value::F64(val) => serializer.serialize_f64(val),
+      &Value::Bool(val) => serializer.serialize_bool(val),
+      Value::Null => serializer.serialize_unit(),
+      Value::Str(val) => serializer.serialize_str(val),
+      Value::Bin(val) => serializer.serialize_bytes(val),
+      Value::Array(vals) => serializer.collect_seq(vals),
+      Value::Map(pairs) => serializer.collect_map(pairs.iter().map(|(key, val)| (key, val))),
+      ext => {
+        let (ty, data) = ext_payload(ext).map_err(ser::Error::custom)?;
+        serializer.serialize_newtype_struct(MSGPACK_EXT_STRUCT_NAME, &(ty, Bytes(&data)))
+      },
+    }
+  }
+}
+
+/// type and data of packed extension value
+fn ext_payload(value: &Value) -> Result<(i8, Vec<u8>), Error> {
+  let mut buf: Vec<u8> = Vec::new();
+  value.pack(&mut buf)?;
+
+  let mut rest = buf.as_slice();
+  let meta = rmp::decode::read_ext_meta(&mut rest)?;
+  Ok((meta.typeid, rest.to_vec()))
+}
+
+impl<'de> Deserialize<'de> for Value {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+    deserializer.deserialize_any(ValueVisitor)
+  }
+}
+
+/// Visitor of any msgpack value, ext structs become extension variants.
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+  type Value = Value;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("msgpack value")
+  }
+
+  fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> { Ok(Value::Bool(v)) }
+  fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> { Ok(Value::UInt(v)) }
+  fn visit_f32<E: de::Error>(self, v: f32) -> Result<Value, E> { Ok(Value::F32(v)) }
+  fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> { Ok(Value::F64(v)) }
+  fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> { Ok(v.into()) }
+  fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> { Ok(Value::Str(v)) }
+  fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Value, E> { Ok(v.into()) }
+  fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Value, E> { Ok(Value::Bin(v)) }
+  fn visit_none<E: de::Error>(self) -> Result<Value, E> { Ok(Value::Null) }
+  fn visit_unit<E: de::Error>(self) -> Result<Value, E> { Ok(Value::Null) }
+
+  /// non-negative integers are unsigned as in Value::unpack
+  fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+    match u64::try_from(v) {
+      Ok(v) => Ok(Value::UInt(v)),
+      Err(_) => Ok(Value::Int(v)),
+    }
+  }
+
+  fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+    deserializer.deserialize_any(self)
+  }
+
+  fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+    let (ty, ExtData(data)) = <(i8, ExtData)>::deserialize(deserializer)?;
+    Value::unpack_ext(ty, &data).map_err(de::Error::custom)
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+    let mut vals = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+    while let Some(val) = seq.next_element()? {
+      vals.push(val);
+    }
+    Ok(Value::Array(vals))
+  }
+
+  fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+    let mut pairs = Vec::with_capacity(map.size_hint().unwrap_or(0));
+    while let Some(pair) = map.next_entry()? {
+      pairs.push(pair);
+    }
+    Ok(Value::Map(pairs))
+  }
+}
+
+/// Data of ext struct deserialized from bytes.
+struct ExtData(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ExtData {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ExtData, D::Error> {
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+      type Value = ExtData;
+
+      fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ext data")
+      }
+
+      fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<ExtData, E> {
+        Ok(ExtData(v.to_vec()))
+      }
+
+      fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<ExtData, E> {
+        Ok(ExtData(v))
+      }
+    }
+
+    deserializer.deserialize_bytes(BytesVisitor)
+  }
+}
+
+impl ser::Error for Error {
+  fn custom<T: Display>(msg: T) -> Self {
+    Error::EncodeError(msg.to_string())
+  }
+}
+
+fn unsupported(what: &str) -> Error {
+  Error::EncodeError(format!("{} can't be serialized into tuple", what))
+}
+
+/// converts payload of extension newtype into its value
+fn extension(name: &str, value: Value) -> Result<Value, Error> {
+  let invalid = || Error::EncodeError(format!("invalid payload of {}", name));
+
+  match (name, value) {
+    (UUID, Value::Bin(bytes)) => Uuid::from_slice(&bytes)
+      .map(Value::Uuid)
+      .map_err(|_| invalid()),
+    (DECIMAL, Value::Str(decimal)) => decimal.parse()
+      .map(Value::Decimal)
+      .map_err(|_| invalid()),
+    (DATETIME, Value::Array(time)) => match time.as_slice() {
+      [ Value::Int(secs), Value::UInt(nsecs) ] =>
+        DateTime::from_timestamp(*secs, *nsecs as u32)
+          .map(|time| Value::DateTime(time.naive_utc()))
+          .ok_or_else(invalid),
+      _ => Err(invalid()),
+    },
+    (MSGPACK_EXT_STRUCT_NAME, Value::Array(ext)) => match ext.as_slice() {
+      [ ty, Value::Bin(data) ] => ty.as_i64()
+        .and_then(|ty| i8::try_from(ty).ok())
+        .ok_or_else(invalid)
+        .and_then(|ty| Value::unpack_ext(ty, data)),
+      _ => Err(invalid()),
+    },
+    _ => Err(invalid()),
+  }
+}
+
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+  type Ok = Value;
+  type Error = Error;
+
+  type SerializeSeq = ArraySerializer;
+  type SerializeTuple = ArraySerializer;
+  type SerializeTupleStruct = ArraySerializer;
+  type SerializeTupleVariant = Impossible<Value, Error>;
+  type SerializeMap = MapSerializer;
+  type SerializeStruct = ArraySerializer;
+  type SerializeStructVariant = Impossible<Value, Error>;
+
+  fn serialize_bool(self, v: bool) -> Result<Value, Error> { Ok(Value::Bool(v)) }
+  fn serialize_i8(self, v: i8) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_i16(self, v: i16) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_i32(self, v: i32) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_i64(self, v: i64) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_u8(self, v: u8) -> Result<Value, Error> { Ok(Value::UInt(v.into())) }
+  fn serialize_u16(self, v: u16) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_u32(self, v: u32) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_u64(self, v: u64) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_f32(self, v: f32) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_f64(self, v: f64) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_char(self, v: char) -> Result<Value, Error> { Ok(Value::Str(v.to_string())) }
+  fn serialize_str(self, v: &str) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> { Ok(v.into()) }
+  fn serialize_none(self) -> Result<Value, Error> { Ok(Value::Null) }
+  fn serialize_unit(self) -> Result<Value, Error> { Ok(Value::Null) }
+
+  fn serialize_some<T>(self, value: &T) -> Result<Value, Error>
+    where T: Serialize + ?Sized
+  {
+    value.serialize(self)
+  }
+
+  fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+    Ok(Value::Null)
+  }
+
+  /// unit variants are serialized as their names
+  fn serialize_unit_variant(
+    self, _name: &'static str, _index: u32, variant: &'static str,
+  ) -> Result<Value, Error> {
+    Ok(variant.into())
+  }
+
+  fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Value, Error>
+    where T: Serialize + ?Sized
+  {
+    match name {
+      UUID | DECIMAL | DATETIME | MSGPACK_EXT_STRUCT_NAME => extension(name, value.serialize(self)?),
+      _ => value.serialize(self),
+    }
+  }
+
+  fn serialize_newtype_variant<T>(
+    self, _name: &'static str, _index: u32, _variant: &'static str, _value: &T,
+  ) -> Result<Value, Error>
+    where T: Serialize + ?Sized
+  {
+    Err(unsupported("enum variant with data"))
+  }
+
+  fn serialize_seq(self, len: Option<usize>) -> Result<ArraySerializer, Error> {
+    Ok(ArraySerializer(Vec::with_capacity(len.unwrap_or(0))))
+  }
+
+  fn serialize_tuple(self, len: usize) -> Result<ArraySerializer, Error> {
+    self.serialize_seq(Some(len))
+  }
+
+  fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<ArraySerializer, Error> {
+    self.serialize_seq(Some(len))
+  }
+
+  fn serialize_tuple_variant(
+    self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize,
+  ) -> Result<Self::SerializeTupleVariant, Error> {
+    Err(unsupported("enum variant with data"))
+  }
+
+  fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, Error> {
+    Ok(MapSerializer { pairs: Vec::with_capacity(len.unwrap_or(0)), key: None })
+  }
+
+  fn serialize_struct(self, _name: &'static str, len: usize) -> Result<ArraySerializer, Error> {
+    self.serialize_seq(Some(len))
+  }
+
+  fn serialize_struct_variant(
+    self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize,
+  ) -> Result<Self::SerializeStructVariant, Error> {
+    Err(unsupported("enum variant with data"))
+  }
+}
+
+/// Collects items of sequences, tuples and structs into array.
+struct ArraySerializer(Vec<Value>);
+
+impl ser::SerializeSeq for ArraySerializer {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized
+  {
+    self.0.push(value.serialize(ValueSerializer)?);
+    Ok(())
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(Value::Array(self.0))
+  }
+}
+
+impl ser::SerializeTuple for ArraySerializer {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized
+  {
+    ser::SerializeSeq::serialize_element(self, value)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    ser::SerializeSeq::end(self)
+  }
+}
+
+impl ser::SerializeTupleStruct for ArraySerializer {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized
+  {
+    ser::SerializeSeq::serialize_element(self, value)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    ser::SerializeSeq::end(self)
+  }
+}
+
+/// field names are dropped, fields are positional in tuple
+impl ser::SerializeStruct for ArraySerializer {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized
+  {
+    ser::SerializeSeq::serialize_element(self, value)
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    ser::SerializeSeq::end(self)
+  }
+}
+
+/// Collects entries of maps into pairs, key is kept until its value comes.
+struct MapSerializer {
+  pairs: Vec<(Value, Value)>,
+  key: Option<Value>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+  type Ok = Value;
+  type Error = Error;
+
+  fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized
+  {
+    self.key = Some(key.serialize(ValueSerializer)?);
+    Ok(())
+  }
+
+  fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized
+  {
+    let key = self.key.take()
+      .ok_or_else(|| Error::EncodeError("map value is serialized before its key".into()))?;
+    self.pairs.push((key, value.serialize(ValueSerializer)?));
+    Ok(())
+  }
+
+  fn end(self) -> Result<Value, Error> {
+    Ok(Value::Map(self.pairs))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::{BTreeMap, HashMap};
+
+  use serde::{Deserialize, Serialize};
+
+  use super::*;
+
+  #[derive(Serialize)]
+  enum Role { Admin }
+
+  #[derive(Serialize)]
+  struct Address(String, u32);
+
+  #[derive(Serialize)]
+  struct User {
+    id: u64,
+    name: &'static str,
+    email: Option<String>,
+    role: Role,
+    address: Address,
+    token: MpUuid,
+    balance: MpDecimal,
+    created: MpDatetime,
+  }
+
+  fn packed(value: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    value.pack(&mut buf).unwrap();
+    buf
+  }
+
+  #[test]
+  fn test_to_tuple() {
+    let created = DateTime::from_timestamp(1_700_000_000, 5).unwrap().naive_utc();
+    let user = User {
+      id: 1, name: "ann", email: None, role: Role::Admin,
+      address: Address("Moscow".into(), 101000),
+      token: MpUuid(Uuid::nil()),
+      balance: MpDecimal("12.50".parse().unwrap()),
+      created: MpDatetime(created),
+    };
+
+    let tuple = to_tuple(&user).unwrap();
+    let expected: Vec<Value> = vec![
+      1u64.into(), "ann".into(), Value::Null, "Admin".into(),
+      Value::Array(vec![ "Moscow".into(), 101000u32.into() ]),
+      Value::Uuid(Uuid::nil()),
+      Value::Decimal("12.50".parse().unwrap()),
+      Value::DateTime(created),
+    ];
+    assert_eq!(packed(&Value::Array(tuple)), packed(&Value::Array(expected)));
+
+    assert!(to_tuple(&42u64).is_err());
+    assert!(to_tuple(&HashMap::<String, u64>::new()).is_err());
+    assert_eq!(packed(&to_value(&(1i32, vec![ 2u8 ])).unwrap()), packed(&Value::Array(vec![
+      1i32.into(), Value::Array(vec![ Value::UInt(2) ]),
+    ])));
+
+    let mut attrs = BTreeMap::new();
+    attrs.insert("age", 30u32);
+    attrs.insert("rank", 2u32);
+    assert_eq!(packed(&to_value(&(1u64, &attrs)).unwrap()), packed(&Value::Array(vec![
+      1u64.into(), attrs.clone().into(),
+    ])));
+  }
+
+  #[derive(Serialize, Deserialize)]
+  struct Event {
+    id: u64,
+    payload: Value,
+  }
+
+  #[test]
+  fn test_value_serde() {
+    let created = DateTime::from_timestamp(1_700_000_000, 5).unwrap().naive_utc();
+    let payload = Value::Array(vec![
+      Value::Int(-5), Value::UInt(7), Value::Null, Value::Bool(true), Value::F64(0.5),
+      "name".into(), Value::Bin(vec![ 1, 2 ]),
+      Value::Map(vec![ ("age".into(), 30.into()) ]),
+      Value::Uuid(Uuid::nil()),
+      Value::Decimal("-12.034".parse().unwrap()),
+      Value::DateTime(created),
+    ]);
+
+    // serialized value is packed as Value::pack does
+    let event = Event { id: 1, payload: payload.clone() };
+    let buf = rmp_serde::to_vec(&event).unwrap();
+    assert_eq!(buf, packed(&Value::Array(vec![ 1u64.into(), payload.clone() ])));
+
+    let decoded: Event = rmp_serde::from_slice(&buf).unwrap();
+    assert_eq!(decoded.id, 1);
+    assert_eq!(packed(&decoded.payload), packed(&payload));
+
+    assert_eq!(packed(&to_value(&payload).unwrap()), packed(&payload));
+  }
+}
@@ -1,6 +1,6 @@
-use std::{error, fmt::{Display, Debug}, io};
+use std::{error, fmt::{Display, Debug}, io, net::SocketAddr};
 use rmp::{decode::{NumValueReadError, ValueReadError}, encode::ValueWriteError};
-use crate::iproto::constants::Field;
+use crate::iproto::constants::{Field, RequestType};
 use serde_json::Error as SerdeJsonError;
 
 use super::{constants::Code, response::TarantoolError};
@@ -15,9 +15,124 @@ pub enum Error {
   TarantoolError(Code, TarantoolError),
   SerdeEncodeError(rmp_serde::encode::Error),
   JsonError(SerdeJsonError),
+  InvalidUpdateOp(String),
+  InvalidKey(String),
+  EncodeError(String),
+  /// value can't be converted into rust type, see TryFrom<Value> impls
+  TypeMismatch { expected: &'static str, found: &'static str },
+  /// integer value doesn't fit into rust integer type
+  OutOfRange { expected: &'static str, value: i128 },
+  /// sync set by RequestBuilder::with_sync is used by another request in flight, it is not sent
+  SyncInUse(u64),
+  /// request frame exceeds max request size of connection, it is not sent
+  RequestTooLarge { size: usize, limit: usize },
+  /// tuple exceeds max tuple size of connection, field is the largest one numbered from one
+  TupleTooLarge { size: usize, limit: usize, field: usize, field_size: usize },
+  /**
+    connection was lost while request was sent or queued,
+    request may be applied or not, so only idempotent ones are safe to retry
+  */
+  ConnectionReset,
+  /// connection is closed, request was not sent or its response was not received before close
+  ConnectionClosed,
+  /// error returned by crud function, see CrudError
+  Crud(crate::crud::CrudError),
+  /// error of request performed by connection with its context
+  Request(Box<ErrorContext>, Box<Error>),
 }
 
-impl error::Error for Error {}
+/// This describes request which failed, see Error::Request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorContext {
+  pub request: RequestType,
+  /// space id or name if request is addressed by names
+  pub space: Option<String>,
+  /// index id or name if request is addressed by names
+  pub index: Option<String>,
+  pub sync: u64,
+  pub addr: SocketAddr,
+}
+
+impl ErrorContext {
+  pub(crate) fn wrap(&self, err: Error) -> Error {
+    match err {
+      Error::Request(..) => err,
+      err => Error::Request(Box::new(self.clone()), Box::new(err)),
+    }
+  }
+}
+
+impl Display for ErrorContext {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "request={:?}", self.request)?;
+    if let Some(space) = &self.space {
+      write!(f, " space={}", space)?;
+    }
+    if let Some(index) = &self.index {
+      write!(f, " index={}", index)?;
+    }
+    write!(f, " sync={} addr={}", self.sync, self.addr)
+  }
+}
+
+impl Error {
+  /// error without request context
+  pub fn root(&self) -> &Error {
+    match self {
+      Error::Request(_, err) => err.root(),
+      err => err,
+    }
+  }
+
+  pub fn into_root(self) -> Error {
+    match self {
+      Error::Request(_, err) => err.into_root(),
+      err => err,
+    }
+  }
+
+  pub fn context(&self) -> Option<&ErrorContext> {
+    match self {
+      Error::Request(context, _) => Some(context),
+      _ => None,
+    }
+  }
+
+  /// code of error returned by tarantool, it is none for client side errors
+  pub fn code(&self) -> Option<Code> {
+    match self.root() {
+      Error::TarantoolError(code, _) => Some(*code),
+      _ => None,
+    }
+  }
+
+  /// see Code::is_retryable, connection reset is retryable too
+  pub fn is_retryable(&self) -> bool {
+    match self.root() {
+      Error::ConnectionReset => true,
+      err => matches!(err.code(), Some(code) if code.is_retryable()),
+    }
+  }
+
+  /// see Code::is_schema_changed
+  pub fn is_schema_changed(&self) -> bool {
+    matches!(self.code(), Some(code) if code.is_schema_changed())
+  }
+
+  /// see Code::is_duplicate_key
+  pub fn is_duplicate_key(&self) -> bool {
+    matches!(self.code(), Some(code) if code.is_duplicate_key())
+  }
+}
+
+impl error::Error for Error {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      Error::Request(_, err) => Some(err.as_ref()),
+      _ => None,
+    }
+  }
+}
 
 impl From<SerdeJsonError> for Error {
   fn from(err: SerdeJsonError) -> Error {
@@ -39,6 +154,32 @@ impl Display for Error {
         write!(f, "TarantoolError(code={:?}, err={:?})", code, err),
       Self::SerdeEncodeError(err) => Display::fmt(err, f),
       Self::JsonError(err) => Display::fmt(err, f),
+      Self::InvalidUpdateOp(reason) =>
+        write!(f, "invalid update operation: {}", reason),
+      Self::InvalidKey(reason) =>
+        write!(f, "invalid key: {}", reason),
+      Self::EncodeError(reason) =>
+        write!(f, "encode error: {}", reason),
+      Self::TypeMismatch { expected, found } =>
+        write!(f, "type mismatch: expected {}, found {}", expected, found),
+      Self::OutOfRange { expected, value } =>
+        write!(f, "integer {} is out of range of {}", value, expected),
+      Self::SyncInUse(sync) =>
+        write!(f, "sync {} is used by another request in flight", sync),
+      Self::RequestTooLarge { size, limit } =>
+        write!(f, "request of {} bytes exceeds max request size {}", size, limit),
+      Self::TupleTooLarge { size, limit, field, field_size } => write!(
+        f, "tuple of {} bytes exceeds max tuple size {}, field {} takes {} bytes",
+        size, limit, field, field_size,
+      ),
+      Self::ConnectionReset =>
+        write!(f, "connection reset while request was in flight"),
+      Self::ConnectionClosed =>
+        write!(f, "connection is closed"),
+      Self::Crud(err) =>
+        write!(f, "crud {}", err),
+      Self::Request(context, err) =>
+        write!(f, "{} ({})", err, context),
     }
   }
 }
@@ -83,6 +224,8 @@ impl From<ValueWriteError> for Error {
 
 #[cfg(test)]
 mod test {
+  use crate::iproto::response::StackRecord;
+
   use super::*;
 
   #[test]
@@ -109,5 +252,49 @@ mod test {
         stack: Vec::new(),
     });
     assert!(err.to_string().contains("ErrorAccessDenied"));
+
+    let err = Error::TypeMismatch { expected: "u64", found: "string" };
+    assert_eq!(err.to_string(), "type mismatch: expected u64, found string");
+
+    let context = ErrorContext {
+      request: RequestType::Select,
+      space: Some("users".into()), index: None,
+      sync: 7, addr: "127.0.0.1:3301".parse().unwrap(),
+    };
+    let err = context.wrap(context.wrap(Error::UnexpectedField(123)));
+    assert_eq!(
+      err.to_string(),
+      "unexpected field 123 (request=Select space=users sync=7 addr=127.0.0.1:3301)",
+    );
+    assert!(matches!(err.root(), Error::UnexpectedField(123)));
+    assert_eq!(err.context().map(|context| context.sync), Some(7));
+  }
+
+  #[test]
+  fn test_codes() {
+    let context = ErrorContext {
+      request: RequestType::Insert,
+      space: None, index: None,
+      sync: 1, addr: "127.0.0.1:3301".parse().unwrap(),
+    };
+    let err = context.wrap(Error::TarantoolError(Code::ErrorTupleFound, TarantoolError {
+      message: "Duplicate key exists".into(),
+      stack: vec![ StackRecord { errcode: 3, ..StackRecord::default() } ],
+    }));
+    assert_eq!(err.code(), Some(Code::ErrorTupleFound));
+    assert!(err.is_duplicate_key());
+    assert!(!err.is_retryable());
+
+    if let Error::TarantoolError(_, err) = err.root() {
+      assert_eq!(err.code(), Some(Code::ErrorTupleFound));
+    }
+
+    assert!(Error::TarantoolError(Code::ErrorReadonly, TarantoolError::new("ro")).is_retryable());
+    assert!(Error::TarantoolError(Code::ErrorWrongSchemaVersion, TarantoolError::new("schema"))
+      .is_schema_changed());
+    assert!(Error::ConnectionReset.is_retryable());
+    assert_eq!(Error::ConnectionReset.code(), None);
+    assert_eq!(Code::from_errcode(226), Some(Code::ErrorNotLeader));
+    assert_eq!(Code::from_errcode(10_000), None);
   }
 }
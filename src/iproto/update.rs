@@ -0,0 +1,455 @@
+/*!
+  This module contains typed helpers for update operations.
+*/
+
+use std::convert::TryFrom;
+
+use super::{request::{IntoTuple, Value}, types::Error};
+
+/**
+  checks operands of splice, offset and length should fit into i32
+  as tarantool reads them so, field of path is resolved by tarantool
+*/
+fn validate_splice(offset: i64, length: i64) -> Result<(), Error> {
+  for (name, value) in [ ("offset", offset), ("length", length) ] {
+    if i32::try_from(value).is_err() {
+      return Err(Error::InvalidUpdateOp(format!("splice {} {} doesn't fit into i32", name, value)));
+    }
+  }
+
+  Ok(())
+}
+
+/**
+  This is field designator of update operation.
+
+  Numbers are counted from zero by default, negative ones are counted from the end.
+  Strings are field names or JSON paths, e.g. "[3].address.city" or "tags[1]",
+  they are supported by tarantool 2.3+.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldRef {
+  Number(i64),
+  Path(String),
+}
+
+impl From<i64> for FieldRef {
+  fn from(field: i64) -> Self {
+    FieldRef::Number(field)
+  }
+}
+
+impl From<i32> for FieldRef {
+  fn from(field: i32) -> Self {
+    FieldRef::Number(field as i64)
+  }
+}
+
+impl From<&str> for FieldRef {
+  fn from(path: &str) -> Self {
+    FieldRef::Path(path.into())
+  }
+}
+
+impl From<String> for FieldRef {
+  fn from(path: String) -> Self {
+    FieldRef::Path(path)
+  }
+}
+
+impl From<FieldRef> for Value {
+  fn from(field: FieldRef) -> Self {
+    match field {
+      FieldRef::Number(field) => Value::Int(field),
+      FieldRef::Path(path) => Value::Str(path),
+    }
+  }
+}
+
+/**
+  This represents typed update operation.
+
+  Arithmetic operations accept numbers and decimals,
+  datetime fields also may be shifted by Interval.
+  Bitwise operations accept unsigned integers.
+
+  Example:
+  ```rust
+    # use rust_decimal_macros::dec;
+    # use alopecosa::{Connection, Error, Interval, IntoTuple, Update, UpdateOp, Value};
+    # fn main() -> Result<(), Error> {
+    let ops: Vec<Vec<Value>> = vec![
+      UpdateOp::add(2, dec!(1.5))?.into(),
+      UpdateOp::subtract(3, Interval { day: 1, ..Default::default() })?.into(),
+      UpdateOp::assign("address.city", "Paris").into(),
+    ];
+    assert!(UpdateOp::add(2, "1.5").is_err());
+    # async fn update(conn: &Connection, ops: Vec<Vec<Value>>) -> Result<(), Error> {
+
+    conn.update::<(u64, String)>(Update {
+      space_id: 512, index_id: 0, index_base: 0,
+      key: ( 1u64, ).into_tuple(),
+      tuple: ops,
+    }).await?;
+    # Ok(()) }
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Clone)]
+pub enum UpdateOp {
+  Assign(FieldRef, Value),
+  Add(FieldRef, Value),
+  Subtract(FieldRef, Value),
+  BitAnd(FieldRef, Value),
+  BitOr(FieldRef, Value),
+  BitXor(FieldRef, Value),
+  /**
+    field, offset, length and replacement,
+    it replaces length chars of string field starting from offset,
+    negative offset is counted from the end
+  */
+  Splice(FieldRef, i64, i64, String),
+  /// inserts value before field, value is appended if field is one past the last
+  Insert(FieldRef, Value),
+  /// deletes number of fields starting from field
+  Delete(FieldRef, u64),
+}
+
+impl UpdateOp {
+  pub fn assign<F: Into<FieldRef>, V: Into<Value>>(field: F, value: V) -> UpdateOp {
+    UpdateOp::Assign(field.into(), value.into())
+  }
+
+  pub fn add<F: Into<FieldRef>, V: Into<Value>>(field: F, value: V) -> Result<UpdateOp, Error> {
+    UpdateOp::Add(field.into(), value.into()).validated()
+  }
+
+  pub fn subtract<F: Into<FieldRef>, V: Into<Value>>(field: F, value: V) -> Result<UpdateOp, Error> {
+    UpdateOp::Subtract(field.into(), value.into()).validated()
+  }
+
+  pub fn bit_and<F: Into<FieldRef>, V: Into<Value>>(field: F, value: V) -> Result<UpdateOp, Error> {
+    UpdateOp::BitAnd(field.into(), value.into()).validated()
+  }
+
+  pub fn bit_or<F: Into<FieldRef>, V: Into<Value>>(field: F, value: V) -> Result<UpdateOp, Error> {
+    UpdateOp::BitOr(field.into(), value.into()).validated()
+  }
+
+  pub fn bit_xor<F: Into<FieldRef>, V: Into<Value>>(field: F, value: V) -> Result<UpdateOp, Error> {
+    UpdateOp::BitXor(field.into(), value.into()).validated()
+  }
+
+  pub fn splice<F, S>(field: F, offset: i64, length: i64, replacement: S) -> Result<UpdateOp, Error>
+    where F: Into<FieldRef>, S: Into<String>
+  {
+    UpdateOp::Splice(field.into(), offset, length, replacement.into()).validated()
+  }
+
+  pub fn insert<F: Into<FieldRef>, V: Into<Value>>(field: F, value: V) -> UpdateOp {
+    UpdateOp::Insert(field.into(), value.into())
+  }
+
+  pub fn delete<F: Into<FieldRef>>(field: F, count: u64) -> Result<UpdateOp, Error> {
+    UpdateOp::Delete(field.into(), count).validated()
+  }
+
+  /// checks operand of operation, constructors check it already
+  pub fn validate(&self) -> Result<(), Error> {
+    match self {
+      UpdateOp::Add(_, value) | UpdateOp::Subtract(_, value) => match value {
+        Value::Int(_) | Value::UInt(_) |
+        Value::F32(_) | Value::F64(_) |
+        Value::Decimal(_) | Value::Interval(_) => Ok(()),
+        value => Err(Error::InvalidUpdateOp(format!(
+          "{:?} can't be used in arithmetic operation", value,
+        ))),
+      },
+      UpdateOp::BitAnd(_, value) | UpdateOp::BitOr(_, value) | UpdateOp::BitXor(_, value) => match value {
+        Value::UInt(_) => Ok(()),
+        Value::Int(value) if *value >= 0 => Ok(()),
+        value => Err(Error::InvalidUpdateOp(format!(
+          "{:?} can't be used in bitwise operation", value,
+        ))),
+      },
+      UpdateOp::Delete(_, 0) => Err(Error::InvalidUpdateOp(
+        "delete operation should delete at least one field".into(),
+      )),
+      UpdateOp::Splice(_, offset, length, _) => validate_splice(*offset, *length),
+      _ => Ok(()),
+    }
+  }
+
+  fn validated(self) -> Result<UpdateOp, Error> {
+    self.validate()?;
+    Ok(self)
+  }
+}
+
+impl From<UpdateOp> for Vec<Value> {
+  fn from(op: UpdateOp) -> Self {
+    let (op, field, value) = match op {
+      UpdateOp::Assign(field, value) => ("=", field, value),
+      UpdateOp::Add(field, value) => ("+", field, value),
+      UpdateOp::Subtract(field, value) => ("-", field, value),
+      UpdateOp::BitAnd(field, value) => ("&", field, value),
+      UpdateOp::BitOr(field, value) => ("|", field, value),
+      UpdateOp::BitXor(field, value) => ("^", field, value),
+      UpdateOp::Insert(field, value) => ("!", field, value),
+      UpdateOp::Delete(field, count) => ("#", field, Value::UInt(count)),
+      UpdateOp::Splice(field, offset, length, replacement) => return vec![
+        Value::Str(":".into()), field.into(),
+        Value::Int(offset), Value::Int(length), Value::Str(replacement),
+      ],
+    };
+
+    vec![ Value::Str(op.into()), field.into(), value ]
+  }
+}
+
+/// starts builder of update operations
+pub fn ops() -> Ops {
+  Ops::default()
+}
+
+/**
+  This is builder of update operations,
+  operands are checked when operations are built.
+
+  Example:
+  ```rust
+    # use alopecosa::{Connection, Error, TarantoolClient, Value, ops};
+    # type User = (u64, String, u64);
+    # fn main() -> Result<(), Error> {
+    let ops = ops()
+      .assign(1, "ann")
+      .add("visits", 1)
+      .splice("[2].bio", 0, 0, "Hi! ")
+      .delete(5, 2)
+      .build()?;
+    assert_eq!(ops[0], vec![ Value::from("="), 1.into(), "ann".into() ]);
+    assert!(self::ops().add("visits", "one").build().is_err());
+    # async fn update(conn: &Connection, ops: Vec<Vec<Value>>) -> Result<(), Error> {
+
+    let users = conn.space("users");
+    let _: Option<User> = users.update(( 1u64, ), ops).await?;
+    # Ok(()) }
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Ops {
+  ops: Vec<UpdateOp>,
+}
+
+impl Ops {
+  pub fn assign<F: Into<FieldRef>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.push(UpdateOp::Assign(field.into(), value.into()))
+  }
+
+  pub fn add<F: Into<FieldRef>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.push(UpdateOp::Add(field.into(), value.into()))
+  }
+
+  pub fn subtract<F: Into<FieldRef>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.push(UpdateOp::Subtract(field.into(), value.into()))
+  }
+
+  pub fn bit_and<F: Into<FieldRef>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.push(UpdateOp::BitAnd(field.into(), value.into()))
+  }
+
+  pub fn bit_or<F: Into<FieldRef>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.push(UpdateOp::BitOr(field.into(), value.into()))
+  }
+
+  pub fn bit_xor<F: Into<FieldRef>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.push(UpdateOp::BitXor(field.into(), value.into()))
+  }
+
+  pub fn splice<F, S>(self, field: F, offset: i64, length: i64, replacement: S) -> Self
+    where F: Into<FieldRef>, S: Into<String>
+  {
+    self.push(UpdateOp::Splice(field.into(), offset, length, replacement.into()))
+  }
+
+  pub fn insert<F: Into<FieldRef>, V: Into<Value>>(self, field: F, value: V) -> Self {
+    self.push(UpdateOp::Insert(field.into(), value.into()))
+  }
+
+  pub fn delete<F: Into<FieldRef>>(self, field: F, count: u64) -> Self {
+    self.push(UpdateOp::Delete(field.into(), count))
+  }
+
+  pub fn push(mut self, op: UpdateOp) -> Self {
+    self.ops.push(op);
+    self
+  }
+
+  pub fn len(&self) -> usize {
+    self.ops.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.ops.is_empty()
+  }
+
+  /// checks operations and packs them as ops of Update or Upsert
+  pub fn build(self) -> Result<Vec<Vec<Value>>, Error> {
+    self.ops.into_iter()
+      .map(|op| Ok(op.validated()?.into()))
+      .collect()
+  }
+}
+
+/**
+  makes assign operations for fields of new tuple which differ from old one,
+  fields are numbered from zero.
+
+  Fields are compared by their msgpack, so 1u64 and 1i64 are equal.
+  Tuple can't lose fields by assignments, so shorter new tuple is an error.
+
+  Example:
+  ```rust
+    # use alopecosa::{Connection, Error, IntoTuple, Update, UpdateOp, Value, diff};
+    # fn main() -> Result<(), Error> {
+    let (old_user, new_user) = (( 1u64, "ann", 30u32 ), ( 1u64, "ann", 31u32 ));
+
+    let ops = diff(old_user, new_user)?;
+    assert!(matches!(&ops[..], [ UpdateOp::Assign(_, Value::UInt(31)) ]));
+    # async fn update(conn: &Connection, old_user: (u64, &str, u32), ops: Vec<UpdateOp>) -> Result<(), Error> {
+
+    if !ops.is_empty() {
+      conn.update::<(u64, String, u32)>(Update {
+        space_id: 512, index_id: 0, index_base: 0,
+        key: ( old_user.0, ).into_tuple(),
+        tuple: ops.into_iter().map(Into::into).collect(),
+      }).await?;
+    }
+    # Ok(()) }
+    # Ok(()) }
+  ```
+*/
+pub fn diff<T: IntoTuple>(old: T, new: T) -> Result<Vec<UpdateOp>, Error> {
+  diff_tuples(&old.into_tuple(), new.into_tuple())
+}
+
+/// same as diff, but for tuples
+pub fn diff_tuples(old: &[Value], new: Vec<Value>) -> Result<Vec<UpdateOp>, Error> {
+  if new.len() < old.len() {
+    return Err(Error::InvalidUpdateOp(format!(
+      "new tuple has {} fields, old one has {}", new.len(), old.len(),
+    )));
+  }
+
+  let (mut old_buf, mut new_buf): (Vec<u8>, Vec<u8>) = (Vec::new(), Vec::new());
+  let mut ops = Vec::new();
+
+  for (field, value) in new.into_iter().enumerate() {
+    if let Some(old) = old.get(field) {
+      old_buf.clear();
+      new_buf.clear();
+      old.pack(&mut old_buf)?;
+      value.pack(&mut new_buf)?;
+      if old_buf == new_buf {
+        continue;
+      }
+    }
+
+    ops.push(UpdateOp::Assign(FieldRef::Number(field as i64), value));
+  }
+
+  Ok(ops)
+}
+
+#[cfg(test)]
+mod tests {
+  use rust_decimal::Decimal;
+
+  use crate::iproto::request::Interval;
+
+  use super::*;
+
+  #[test]
+  fn test_splice() {
+    let op: Vec<Value> = UpdateOp::splice(1, -1, 0, "tail").unwrap().into();
+    assert_eq!(op.len(), 5);
+    assert!(matches!(&op[0], Value::Str(s) if s == ":"));
+    assert!(matches!(op[2], Value::Int(-1)));
+    assert!(matches!(&op[4], Value::Str(s) if s == "tail"));
+
+    assert!(UpdateOp::splice(1, 1, 1 << 40, "x").is_err());
+    assert!(UpdateOp::splice("bio", -1, 0, "!").is_ok());
+    assert!(UpdateOp::splice(1, i64::MAX, 0, "!").is_err());
+  }
+
+  #[test]
+  fn test_arith_ops() {
+    let op: Vec<Value> = UpdateOp::add(2, Decimal::new(15, 1)).unwrap().into();
+    assert!(matches!(&op[0], Value::Str(s) if s == "+"));
+    assert!(matches!(op[2], Value::Decimal(_)));
+
+    let op: Vec<Value> = UpdateOp::subtract(3, Interval {
+      day: 1, ..Default::default()
+    }).unwrap().into();
+    assert!(matches!(&op[0], Value::Str(s) if s == "-"));
+
+    assert!(UpdateOp::add(2, "1").is_err());
+
+    let op: Vec<Value> = UpdateOp::assign(1, "name").into();
+    assert!(matches!(&op[0], Value::Str(s) if s == "="));
+  }
+
+  #[test]
+  fn test_ops() {
+    let built = ops()
+      .assign("address.city", "Paris")
+      .add(2, 1)
+      .bit_or(3, 4u64)
+      .splice("[4].bio", -1, 0, "!")
+      .insert(-1, "last")
+      .delete(5, 2)
+      .build()
+      .unwrap();
+
+    let packed = |op: &Vec<Value>| {
+      let mut buf: Vec<u8> = Vec::new();
+      Value::Array(op.clone()).pack(&mut buf).unwrap();
+      rmpv::decode::read_value(&mut buf.as_slice()).unwrap().to_string()
+    };
+    assert_eq!(built.iter().map(packed).collect::<Vec<_>>(), vec![
+      r#"["=", "address.city", "Paris"]"#,
+      r#"["+", 2, 1]"#,
+      r#"["|", 3, 4]"#,
+      r#"[":", "[4].bio", -1, 0, "!"]"#,
+      r#"["!", -1, "last"]"#,
+      r##"["#", 5, 2]"##,
+    ]);
+
+    assert!(ops().bit_and(1, -1).build().is_err());
+    assert!(ops().add(1, "x").build().is_err());
+    assert!(ops().delete(1, 0).build().is_err());
+    assert!(ops().splice("bio", 0, -(1 << 40), "").build().is_err());
+    assert!(UpdateOp::bit_xor(1, 1.5).is_err());
+  }
+
+  #[test]
+  fn test_diff() {
+    let ops = diff(
+      ( 1u64, "ann", 30u32, None::<String> ),
+      ( 1u64, "ann", 31u32, Some("ann@example.com".to_string()) ),
+    ).unwrap();
+
+    let fields: Vec<i64> = ops.iter()
+      .map(|op| match op {
+        UpdateOp::Assign(FieldRef::Number(field), _) => *field,
+        op => panic!("unexpected operation {:?}", op),
+      })
+      .collect();
+    assert_eq!(fields, vec![ 2, 3 ]);
+
+    assert!(diff(( 1u64, 2u64 ), ( 1u64, 2u64 )).unwrap().is_empty());
+    assert_eq!(diff_tuples(&[ 1u64.into() ], vec![ 1u64.into(), 2u64.into() ]).unwrap().len(), 1);
+    assert!(diff_tuples(&[ 1u64.into(), 2u64.into() ], vec![ 1u64.into() ]).is_err());
+  }
+}
@@ -15,7 +15,7 @@ By the way, [alopecosa](https://en.wikipedia.org/wiki/Alopecosa) is the kind of
 
 ## Example
 
-```rust
+```rust,no_run
 use std::{
   error::Error,
   time::Duration,
@@ -55,46 +55,127 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
   Ok(())
 }
-
 ```
 
 ## Auth
 
-```rust
+```rust,no_run
+# use std::sync::Arc;
+# use alopecosa::{Connection, Connector};
+# async fn f() -> Result<(), Box<dyn std::error::Error>> {
 let addr = "127.0.0.1:3301".parse()?;
 let conn: Arc<Connection> = Connector::new(addr)
   .with_auth("user".into(), "password".into())
   .connect().await?;
+# Ok(()) }
 ```
 
 ## Connection tuning
 
-```rust
+```rust,no_run
+# use std::{sync::Arc, time::Duration};
+# use alopecosa::{Connection, Connector};
+# async fn f() -> Result<(), Box<dyn std::error::Error>> {
 let addr = "127.0.0.1:3301".parse()?;
 let conn: Arc<Connection> = Connector::new(addr)
   .with_connect_timeout(Duration::from_secs(10))
   .with_reconnect_interval(Duration::from_secs(1))
   .with_send_request_timeout(Duration::from_secs(10))
   .connect().await?;
+# Ok(()) }
 ```
 
 */
 
+extern crate self as alopecosa;
+
 pub mod iproto;
 pub mod connection;
+pub mod client;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod cdc;
+pub mod cluster;
+pub mod compare;
+pub mod crud;
+pub mod entity;
+pub mod pool;
+pub mod replicaset;
+pub mod schema;
+pub mod sequence;
+pub mod space;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod triggers;
+#[cfg(feature = "vshard")]
+pub mod vshard;
 
 pub use connection::{
   Connection,
-  connector::Connector,
+  access::{Privilege, Privileges, User},
+  backup::{BackupFile, BackupGuard},
+  connector::{ConnectPhase, Connector, PhaseTimeout},
+  export::ExportFormat,
+  features::{Feature, ProtocolFeatures},
+  health::{Health, HealthThresholds, Readiness},
+  labels::Labels,
+  import::{ImportOptions, ImportReport, RowError},
+  loader::{BatchError, LoadMode, LoadOptions, LoadProgress, LoadReport},
+  push::PushStream,
+  query_log::QUERY_LOG_TARGET,
+  raw::{RawConnection, RawEvent},
+  rate_limiter::RateLimiter,
+  reconnect::ReconnectPolicy,
+  retry::{Backoff, ErrorClass, RetryPolicy},
+  statements::{StatementCacheStats, StatementStats},
+  stream::{Stream, Transaction},
+  transport::{BoxedTransport, HostTransport, TcpTransport, Transport, TransportConnector},
+  url::UrlError,
+  watcher::{TypedEvents, WatchStream},
 };
 
+pub use client::TarantoolClient;
+pub use cluster::{Cluster, ShardResults};
+pub use crud::{Conditions, Crud, CrudBatch, CrudError, CrudField, CrudOptions, CrudRows};
+pub use pool::Pool;
+pub use replicaset::{Balancing, ReadPreference, ReplicaSet};
+pub use sequence::{Sequence, SequenceError};
+pub use space::{Index, SelectOptions, SelectStream, Space, SpaceSelect};
+pub use triggers::{TriggerKind, Triggers};
+
+#[cfg(feature = "websocket")]
+pub use connection::websocket::{WebSocketStream, WebSocketTransport};
+
+#[cfg(feature = "otel")]
+pub use connection::telemetry::TracePropagation;
+
+#[cfg(feature = "uring")]
+pub use connection::uring::UringConnection;
+
+#[cfg(feature = "vshard")]
+pub use vshard::Sharded;
+
+#[cfg(feature = "derive")]
+pub use alopecosa_derive::{Entity, FromTuple, Space, ToTuple};
+
+/// items used by code of derive macros
+#[doc(hidden)]
+pub mod __private {
+  pub use serde;
+}
+
 pub use iproto::{
   constants::*,
   request::{self,
-    Body, Value, IntoTuple,
-    Auth, Select, Call, Insert, Replace,
-    Update, Delete, Eval, Upsert,Prepare, Execute,
+    Body, Value, Interval, IntervalAdjust, IntoKey, IntoTuple, NoKey,
+    Auth, AuthMethod, Id, Watch, Unwatch, Begin, Commit, Rollback, TxnIsolation, Select, PagedSelect, SelectBuilder, Call, Call16, Insert, Replace,
+    Update, Delete, Eval, Upsert,Prepare, Unprepare, Execute,
+    Subscribe, Vclock, ByName, RequestBuilder,
   },
+  path::ValuePath,
+  redaction::Redaction,
   response::*,
-  types::Error,
+  serialize::{MpDatetime, MpDecimal, MpUuid},
+  update::{FieldRef, Ops, UpdateOp, diff, diff_tuples, ops},
+  types::{Error, ErrorContext},
 };
@@ -0,0 +1,399 @@
+/*!
+  This module contains pool of connections to one instance.
+
+  Pool is partitioned by role, every partition has its own connector,
+  so e.g. read only user and admin user may share one pool
+  while their privileges stay separated.
+  Pool of single partition is built with `with_connections`
+  and its connections are taken with `get`.
+
+  Connections are pinged periodically if health check is set,
+  connection which fails ping or is closed is replaced by the new one,
+  only one replacement of connection is made at a time.
+
+  Pool is TarantoolClient, its requests go to connections of default role.
+
+  Example:
+  ```rust,no_run
+    # use std::{net::SocketAddr, time::Duration};
+    # use alopecosa::{Connector, Error, Pool, Select};
+    # async fn f(addr: SocketAddr, ro_password: String, admin_password: String, select: Select) -> Result<(), Error> {
+    let pool = Pool::new()
+      .with_partition("reader", Connector::new(addr).with_auth("reader".into(), ro_password), 8)
+      .with_partition("admin", Connector::new(addr).with_auth("admin".into(), admin_password), 1)
+      .with_health_check(Duration::from_secs(5), Duration::from_secs(1))
+      .connect().await?;
+
+    let users: Vec<(u64, String)> = pool.checkout("reader").unwrap()
+      .select(select).await?;
+
+    let pool = Pool::new().with_connections(Connector::new(addr), 4).connect().await?;
+    pool.get().await?.ping().await?;
+    # Ok(()) }
+  ```
+*/
+
+use std::{
+  collections::HashMap,
+  io,
+  sync::{Arc, RwLock, Weak, atomic::{AtomicUsize, Ordering}},
+  time::Duration,
+};
+
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use serde::de::DeserializeOwned;
+use tokio::sync::Mutex;
+
+use crate::{
+  client::TarantoolClient,
+  connection::{Connection, connector::Connector},
+  iproto::{
+    request::{Call, Delete, Eval, Execute, Insert, PagedSelect, Replace, Select, Update, Upsert},
+    response::{Page, SQLBody},
+    types::Error,
+  },
+};
+
+/// role of partition added by `Pool::with_connections`
+pub const DEFAULT_ROLE: &str = "default";
+
+#[derive(Debug)]
+struct Partition {
+  connector: Connector,
+  size: usize,
+  connections: RwLock<Vec<Arc<Connection>>>,
+  /// lock of every connection which is held while it is replaced
+  slots: Vec<Mutex<()>>,
+  next: AtomicUsize,
+}
+
+impl Partition {
+  fn pick(&self) -> Option<(usize, Arc<Connection>)> {
+    let connections = self.connections.read().unwrap();
+    if connections.is_empty() {
+      return None;
+    }
+
+    let index = self.next.fetch_add(1, Ordering::Relaxed) % connections.len();
+    Some((index, connections[index].clone()))
+  }
+
+  /**
+    replaces connection at index unless it is already replaced,
+    concurrent replaces of the same connection wait for the first one and take its connection
+  */
+  async fn replace(&self, index: usize, dead: &Arc<Connection>) -> Result<Arc<Connection>, io::Error> {
+    let _slot = match self.slots.get(index) {
+      Some(slot) => slot.lock().await,
+      None => return Err(io::Error::from(io::ErrorKind::NotConnected)),
+    };
+
+    let current = self.connections.read().unwrap().get(index).cloned();
+    match current {
+      Some(current) if !Arc::ptr_eq(&current, dead) => return Ok(current),
+      Some(_) => {},
+      None => return Err(io::Error::from(io::ErrorKind::NotConnected)),
+    }
+
+    let conn = self.connector.clone().connect().await?;
+    let stale = std::mem::replace(&mut self.connections.write().unwrap()[index], conn.clone());
+
+    stale.close().await;
+    Ok(conn)
+  }
+
+  /// pings all connections at once, then replaces dead ones
+  async fn check(&self, timeout: Duration) {
+    let connections = self.connections.read().unwrap().clone();
+
+    let alive = join_all(connections.iter().map(|conn| async move {
+      !conn.is_closed() && matches!(
+        tokio::time::timeout(timeout, conn.ping()).await,
+        Ok(Ok(())),
+      )
+    })).await;
+
+    for ((index, conn), _) in connections.iter().enumerate().zip(alive).filter(|(_, alive)| !alive) {
+      if let Err(err) = self.replace(index, conn).await {
+        log::warn!("failed to replace dead connection of pool: {}", err);
+      }
+    }
+  }
+}
+
+/// This is pool of connections partitioned by role, see module docs.
+#[derive(Debug, Default)]
+pub struct Pool {
+  partitions: HashMap<String, Arc<Partition>>,
+  health_check: Option<(Duration, Duration)>,
+}
+
+impl Pool {
+  pub fn new() -> Pool {
+    Pool::default()
+  }
+
+  /**
+    adds partition of given size, role is attached to its connections as "role" label.
+    Partition with the same role is replaced.
+  */
+  pub fn with_partition<R>(mut self, role: R, connector: Connector, size: usize) -> Self
+    where R: Into<String>
+  {
+    let role = role.into();
+    let connector = connector.with_label("role", role.as_str());
+    let size = size.max(1);
+
+    self.partitions.insert(role, Arc::new(Partition {
+      connector, size,
+      connections: RwLock::new(Vec::new()),
+      slots: (0..size).map(|_| Mutex::new(())).collect(),
+      next: AtomicUsize::new(0),
+    }));
+    self
+  }
+
+  /// adds partition of default role, its connections are taken with `get`
+  pub fn with_connections(self, connector: Connector, size: usize) -> Self {
+    self.with_partition(DEFAULT_ROLE, connector, size)
+  }
+
+  /**
+    connections are pinged every interval after connect,
+    connection which doesn't respond in timeout is replaced
+  */
+  pub fn with_health_check(mut self, interval: Duration, timeout: Duration) -> Self {
+    self.health_check = Some((interval, timeout));
+    self
+  }
+
+  /**
+    opens connections of every partition and starts health check.
+    Health check is stopped once pool is dropped.
+  */
+  pub async fn connect(self) -> Result<Pool, io::Error> {
+    for partition in self.partitions.values() {
+      let mut connections = Vec::with_capacity(partition.size);
+      while connections.len() < partition.size {
+        connections.push(partition.connector.clone().connect().await?);
+      }
+      *partition.connections.write().unwrap() = connections;
+    }
+
+    if let Some((interval, timeout)) = self.health_check {
+      let partitions: Vec<Weak<Partition>> = self.partitions.values()
+        .map(Arc::downgrade)
+        .collect();
+      tokio::spawn(Self::health_check(partitions, interval, timeout));
+    }
+
+    Ok(self)
+  }
+
+  async fn health_check(partitions: Vec<Weak<Partition>>, interval: Duration, timeout: Duration) {
+    loop {
+      tokio::time::sleep(interval).await;
+
+      for partition in partitions.iter() {
+        match partition.upgrade() {
+          Some(partition) => partition.check(timeout).await,
+          None => return,
+        }
+      }
+    }
+  }
+
+  /// picks connection of role in round robin, it is none for unknown role
+  pub fn checkout(&self, role: &str) -> Option<Arc<Connection>> {
+    self.partitions.get(role)?.pick().map(|(_, conn)| conn)
+  }
+
+  /// picks connection of default role, closed connection is replaced on the way
+  pub async fn get(&self) -> Result<Arc<Connection>, io::Error> {
+    self.get_role(DEFAULT_ROLE).await
+  }
+
+  /// picks connection of role, closed connection is replaced on the way
+  pub async fn get_role(&self, role: &str) -> Result<Arc<Connection>, io::Error> {
+    let partition = self.partitions.get(role)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("unknown role {}", role)))?;
+    let (index, conn) = partition.pick()
+      .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+
+    if conn.is_closed() {
+      return partition.replace(index, &conn).await;
+    }
+    Ok(conn)
+  }
+
+  pub fn roles(&self) -> impl Iterator<Item = &str> {
+    self.partitions.keys().map(String::as_str)
+  }
+
+  /// closes every connection of pool concurrently, see Connection::close
+  pub async fn close(&self) {
+    let connections: Vec<Arc<Connection>> = self.partitions.values()
+      .flat_map(|partition| partition.connections.read().unwrap().clone())
+      .collect();
+
+    join_all(connections.iter().map(|conn| conn.close())).await;
+  }
+}
+
+#[async_trait]
+impl TarantoolClient for Pool {
+  async fn select<T>(&self, body: Select) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.get().await?.select(body).await
+  }
+
+  async fn select_page<T>(&self, body: PagedSelect) -> Result<Page<T>, Error>
+    where T: DeserializeOwned
+  {
+    self.get().await?.select_page(body).await
+  }
+
+  async fn insert<T>(&self, body: Insert) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.get().await?.insert(body).await
+  }
+
+  async fn replace<T>(&self, body: Replace) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.get().await?.replace(body).await
+  }
+
+  async fn update<T>(&self, body: Update) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.get().await?.update(body).await
+  }
+
+  async fn delete<T>(&self, body: Delete) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.get().await?.delete(body).await
+  }
+
+  async fn upsert(&self, body: Upsert) -> Result<(), Error> {
+    self.get().await?.upsert(body).await
+  }
+
+  async fn call<T>(&self, body: Call) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.get().await?.call(body).await
+  }
+
+  async fn eval<T>(&self, body: Eval) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.get().await?.eval(body).await
+  }
+
+  async fn execute(&self, body: Execute) -> Result<SQLBody, Error> {
+    self.get().await?.execute(body).await
+  }
+
+  async fn execute_select<T>(&self, body: Execute) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.get().await?.execute_select(body).await
+  }
+
+  async fn space_id(&self, name: &str) -> Result<u64, Error> {
+    self.get().await?.resolve_space(name).await
+  }
+
+  async fn index_id(&self, space_id: u64, name: &str) -> Result<u64, Error> {
+    self.get().await?.resolve_index(space_id, name).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::connection::transport::tests::fake_connector;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_partitions() {
+    let pool = Pool::new()
+      .with_partition("reader", fake_connector(2).with_auth("reader".into(), "ro".into()), 2)
+      .with_partition("admin", fake_connector(1).with_auth("admin".into(), "rw".into()), 1)
+      .connect().await.unwrap();
+
+    let mut roles: Vec<&str> = pool.roles().collect();
+    roles.sort_unstable();
+    assert_eq!(roles, vec![ "admin", "reader" ]);
+
+    let first = pool.checkout("reader").unwrap();
+    let second = pool.checkout("reader").unwrap();
+    assert!(!Arc::ptr_eq(&first, &second));
+    assert!(Arc::ptr_eq(&first, &pool.checkout("reader").unwrap()));
+    assert_eq!(first.labels().get("role"), Some("reader"));
+
+    let admin = pool.checkout("admin").unwrap();
+    assert_eq!(admin.labels().get("role"), Some("admin"));
+    admin.ping().await.unwrap();
+
+    assert!(pool.checkout("guest").is_none());
+    assert!(pool.get().await.is_err());
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn test_replace_closed() {
+    let pool = Pool::new()
+      .with_connections(fake_connector(3), 1)
+      .with_health_check(Duration::from_millis(10), Duration::from_secs(1))
+      .connect().await.unwrap();
+
+    let first = pool.get().await.unwrap();
+    first.ping().await.unwrap();
+    first.close().await;
+
+    let second = pool.get().await.unwrap();
+    assert!(!Arc::ptr_eq(&first, &second));
+    assert!(Arc::ptr_eq(&second, &pool.get().await.unwrap()));
+
+    // health check replaces connection, it is run here instead of waiting for background one
+    second.close().await;
+    pool.partitions[DEFAULT_ROLE].check(Duration::from_secs(1)).await;
+    let third = pool.checkout(DEFAULT_ROLE).unwrap();
+    assert!(!third.is_closed());
+    assert!(!Arc::ptr_eq(&second, &third));
+    third.ping().await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_check_replaces_dead() {
+    let pool = Pool::new().with_connections(fake_connector(3), 2).connect().await.unwrap();
+    let partition = &pool.partitions[DEFAULT_ROLE];
+    let before = partition.connections.read().unwrap().clone();
+    before[1].close().await;
+
+    partition.check(Duration::from_secs(1)).await;
+    let after = partition.connections.read().unwrap().clone();
+    assert!(Arc::ptr_eq(&before[0], &after[0]));
+    assert!(!Arc::ptr_eq(&before[1], &after[1]));
+    after[1].ping().await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_single_replace() {
+    // the only spare stream is enough, the second connect would fail
+    let pool = Pool::new().with_connections(fake_connector(2), 1).connect().await.unwrap();
+    pool.get().await.unwrap().close().await;
+
+    let (first, second) = tokio::join!(pool.get(), pool.get());
+    assert!(Arc::ptr_eq(&first.unwrap(), &second.unwrap()));
+
+    // pool is client of its default role
+    let upsert = Upsert { space_id: 512, index_base: 0, ops: Vec::new(), tuple: Vec::new() };
+    TarantoolClient::upsert(&pool, upsert).await.unwrap();
+  }
+}
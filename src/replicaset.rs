@@ -0,0 +1,485 @@
+/*!
+  This module contains routing of requests across replica set.
+
+  Client of replica set is named ReplicaSet like Pool and Cluster, not ReplicaSetClient.
+  Master is discovered by polling box.info.ro only,
+  discovery by box.status watcher is not implemented yet.
+*/
+
+use std::{
+  collections::{HashMap, HashSet},
+  sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+  time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+
+use crate::{
+  client::TarantoolClient,
+  iproto::{
+    request::{
+      Call, Delete, Eval, Execute, Insert,
+      PagedSelect, Replace, Select, Update, Upsert,
+    },
+    response::{Page, SQLBody},
+    types::Error,
+  },
+};
+
+/// maximal upstream lag of instance, stopped replication is reported as infinite lag
+const REPLICATION_LAG_EXPR: &str = r#"
+  local lag = 0
+  for _, replica in pairs(box.info.replication) do
+    local upstream = replica.upstream
+    if upstream ~= nil then
+      if upstream.status ~= 'follow' then return math.huge end
+      lag = math.max(lag, upstream.lag or 0)
+    end
+  end
+  return lag
+"#;
+
+const READ_ONLY_EXPR: &str = "return box.info.ro";
+
+const UNKNOWN_LAG: u64 = u64::MAX;
+
+/// This describes where reads may be served from.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ReadPreference {
+  /// always read from master
+  #[default]
+  Master,
+  /// read from any replica, master is used only when there are no replicas
+  PreferReplica,
+  /// read from replica which lags behind master not more than given duration,
+  /// master is used when there is no such replica
+  MaxStaleness(Duration),
+}
+
+/// This describes how replica is picked among suitable ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Balancing {
+  #[default]
+  RoundRobin,
+  /// replica with the lowest round trip measured by refresh_lag,
+  /// replicas which were not measured yet are used last
+  LowestLatency,
+}
+
+#[derive(Debug)]
+struct Instance<C> {
+  client: C,
+  /// lag in microseconds measured by refresh_lag
+  lag: AtomicU64,
+  /// round trip in microseconds measured by refresh_lag
+  latency: AtomicU64,
+}
+
+impl<C> Instance<C> {
+  fn new(client: C) -> Instance<C> {
+    Instance { client, lag: AtomicU64::new(UNKNOWN_LAG), latency: AtomicU64::new(UNKNOWN_LAG) }
+  }
+}
+
+/**
+  This is client over master and its replicas.
+
+  Writes, sql and calls of functions which are not marked read only always go to master,
+  selects and read only calls are routed according to read preference of space
+  or the one passed with request.
+
+  Master is the first instance until refresh_master finds writable one,
+  so failover is followed by polling box.info.ro.
+
+  Example:
+  ```rust,no_run
+    # use std::{sync::Arc, time::Duration};
+    # use alopecosa::{Balancing, Connection, ReadPreference, ReplicaSet};
+    # const BALANCES: u64 = 512;
+    # const PROFILES: u64 = 513;
+    # fn f(master: Arc<Connection>, replica: Arc<Connection>) {
+    let rs = ReplicaSet::new(master)
+      .with_replica(replica)
+      .with_read_preference(ReadPreference::PreferReplica)
+      .with_balancing(Balancing::LowestLatency)
+      .with_read_only_function("get_profile")
+      .with_space_read_preference(BALANCES, ReadPreference::Master)
+      .with_space_read_preference(PROFILES, ReadPreference::MaxStaleness(Duration::from_secs(1)));
+
+    tokio::spawn(async move {
+      loop {
+        rs.refresh_master().await;
+        rs.refresh_lag().await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+      }
+    });
+    # }
+  ```
+*/
+#[derive(Debug)]
+pub struct ReplicaSet<C> {
+  instances: Vec<Instance<C>>,
+  /// index of master in instances
+  master: AtomicUsize,
+  default_read: ReadPreference,
+  space_reads: HashMap<u64, ReadPreference>,
+  read_only_functions: HashSet<String>,
+  balancing: Balancing,
+  next_replica: AtomicUsize,
+}
+
+impl<C> ReplicaSet<C>
+  where C: TarantoolClient
+{
+  pub fn new(master: C) -> ReplicaSet<C> {
+    ReplicaSet {
+      instances: vec![ Instance::new(master) ],
+      master: AtomicUsize::new(0),
+      default_read: ReadPreference::default(),
+      space_reads: HashMap::new(),
+      read_only_functions: HashSet::new(),
+      balancing: Balancing::default(),
+      next_replica: AtomicUsize::new(0),
+    }
+  }
+
+  /// adds replica, its lag is unknown until refresh_lag
+  pub fn with_replica(mut self, replica: C) -> Self {
+    self.instances.push(Instance::new(replica));
+    self
+  }
+
+  pub fn with_balancing(mut self, balancing: Balancing) -> Self {
+    self.balancing = balancing;
+    self
+  }
+
+  /// calls of function are routed as reads with default read preference
+  pub fn with_read_only_function(mut self, name: &str) -> Self {
+    self.read_only_functions.insert(name.into());
+    self
+  }
+
+  /// read preference for spaces without their own one
+  pub fn with_read_preference(mut self, preference: ReadPreference) -> Self {
+    self.default_read = preference;
+    self
+  }
+
+  pub fn with_space_read_preference(mut self, space_id: u64, preference: ReadPreference) -> Self {
+    self.space_reads.insert(space_id, preference);
+    self
+  }
+
+  pub fn master(&self) -> &C {
+    &self.instances[self.master.load(Ordering::Relaxed)].client
+  }
+
+  pub fn read_preference(&self, space_id: u64) -> ReadPreference {
+    self.space_reads.get(&space_id).copied().unwrap_or(self.default_read)
+  }
+
+  /**
+    measures replication lag and round trip of every instance,
+    unreachable instances get unknown lag and latency
+  */
+  pub async fn refresh_lag(&self) {
+    for instance in self.instances.iter() {
+      let start = Instant::now();
+      let (lag, latency) = match instance.client.eval::<(f64,)>(Eval {
+        expr: REPLICATION_LAG_EXPR.into(),
+        args: Vec::new(),
+      }).await {
+        Ok((lag,)) if lag.is_finite() && lag >= 0.0 => ((lag * 1e6) as u64, start.elapsed().as_micros() as u64),
+        Ok(_) => (UNKNOWN_LAG, start.elapsed().as_micros() as u64),
+        Err(err) => {
+          log::warn!("can't get replication lag: {}", err);
+          (UNKNOWN_LAG, UNKNOWN_LAG)
+        },
+      };
+
+      instance.lag.store(lag, Ordering::Relaxed);
+      instance.latency.store(latency, Ordering::Relaxed);
+    }
+  }
+
+  /**
+    asks box.info.ro of every instance and makes the first writable one master,
+    master is kept if there is no writable instance, e.g. during failover
+  */
+  pub async fn refresh_master(&self) {
+    for (i, instance) in self.instances.iter().enumerate() {
+      match instance.client.eval::<(bool,)>(Eval {
+        expr: READ_ONLY_EXPR.into(),
+        args: Vec::new(),
+      }).await {
+        Ok((false,)) => {
+          if self.master.swap(i, Ordering::Relaxed) != i {
+            log::info!("master of replica set is instance {}", i);
+          }
+          return;
+        },
+        Ok((true,)) => {},
+        Err(err) => log::warn!("can't get read only status: {}", err),
+      }
+    }
+  }
+
+  /// picks instance for read with given preference
+  pub fn route(&self, preference: ReadPreference) -> &C {
+    let max_lag = match preference {
+      ReadPreference::Master => return self.master(),
+      ReadPreference::PreferReplica => None,
+      ReadPreference::MaxStaleness(staleness) => Some(staleness.as_micros() as u64),
+    };
+
+    let master = self.master.load(Ordering::Relaxed);
+    let candidates: Vec<&Instance<C>> = self.instances.iter().enumerate()
+      .filter(|&(i, _)| i != master)
+      .map(|(_, replica)| replica)
+      .filter(|replica| match max_lag {
+        None => true,
+        Some(max_lag) => {
+          let lag = replica.lag.load(Ordering::Relaxed);
+          lag != UNKNOWN_LAG && lag <= max_lag
+        },
+      })
+      .collect();
+
+    let replica = match self.balancing {
+      Balancing::RoundRobin if !candidates.is_empty() => {
+        let next = self.next_replica.fetch_add(1, Ordering::Relaxed);
+        candidates[next % candidates.len()]
+      },
+      Balancing::LowestLatency => match candidates.into_iter()
+        .min_by_key(|replica| replica.latency.load(Ordering::Relaxed)) {
+        Some(replica) => replica,
+        None => return self.master(),
+      },
+      _ => return self.master(),
+    };
+    &replica.client
+  }
+
+  /// selects with read preference overriding the one of space
+  pub async fn select_with<T>(&self, body: Select, preference: ReadPreference) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.route(preference).select(body).await
+  }
+
+  /// calls function with read preference, e.g. on replica
+  pub async fn call_with<T>(&self, body: Call, preference: ReadPreference) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.route(preference).call(body).await
+  }
+}
+
+#[async_trait]
+impl<C> TarantoolClient for ReplicaSet<C>
+  where C: TarantoolClient
+{
+  async fn select<T>(&self, body: Select) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    let preference = self.read_preference(body.space_id);
+    self.route(preference).select(body).await
+  }
+
+  async fn select_page<T>(&self, body: PagedSelect) -> Result<Page<T>, Error>
+    where T: DeserializeOwned
+  {
+    let preference = self.read_preference(body.select.space_id);
+    self.route(preference).select_page(body).await
+  }
+
+  async fn insert<T>(&self, body: Insert) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.master().insert(body).await
+  }
+
+  async fn replace<T>(&self, body: Replace) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.master().replace(body).await
+  }
+
+  async fn update<T>(&self, body: Update) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.master().update(body).await
+  }
+
+  async fn delete<T>(&self, body: Delete) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.master().delete(body).await
+  }
+
+  async fn upsert(&self, body: Upsert) -> Result<(), Error> {
+    self.master().upsert(body).await
+  }
+
+  async fn call<T>(&self, body: Call) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    match self.read_only_functions.contains(&body.function) {
+      true => self.route(self.default_read).call(body).await,
+      false => self.master().call(body).await,
+    }
+  }
+
+  async fn eval<T>(&self, body: Eval) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.master().eval(body).await
+  }
+
+  async fn execute(&self, body: Execute) -> Result<SQLBody, Error> {
+    self.master().execute(body).await
+  }
+
+  async fn execute_select<T>(&self, body: Execute) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.master().execute_select(body).await
+  }
+
+  async fn space_id(&self, name: &str) -> Result<u64, Error> {
+    self.master().space_id(name).await
+  }
+
+  async fn index_id(&self, space_id: u64, name: &str) -> Result<u64, Error> {
+    self.master().index_id(space_id, name).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, atomic::AtomicBool};
+
+  use crate::{IntoTuple, iproto::{constants::Iterator, request::Value}, testing::FakeClient};
+
+  use super::*;
+
+  fn instance(name: &str, lag: f64) -> FakeClient {
+    read_only_instance(name, lag, Arc::new(AtomicBool::new(false)))
+  }
+
+  fn read_only_instance(name: &str, lag: f64, read_only: Arc<AtomicBool>) -> FakeClient {
+    let name = name.to_string();
+    FakeClient::new()
+      .with_space(512, vec![ 0 ])
+      .with_space(513, vec![ 0 ])
+      .with_eval(REPLICATION_LAG_EXPR, move |_| Ok(vec![ Value::F64(lag) ]))
+      .with_eval(READ_ONLY_EXPR, move |_| Ok(vec![ Value::Bool(read_only.load(Ordering::Relaxed)) ]))
+      .with_function("whoami", move |_| Ok(vec![ Value::Str(name.clone()) ]))
+  }
+
+  async fn whoami(client: &FakeClient) -> String {
+    let (name,): (String,) = client.call(Call {
+      function: "whoami".into(), args: Vec::new(),
+    }).await.unwrap();
+    name
+  }
+
+  #[tokio::test]
+  async fn test_read_preference() {
+    let rs = ReplicaSet::new(instance("master", 0.0))
+      .with_replica(instance("fresh", 0.1))
+      .with_replica(instance("stale", 10.0))
+      .with_read_preference(ReadPreference::PreferReplica)
+      .with_space_read_preference(513, ReadPreference::Master);
+
+    let fresh = ReadPreference::MaxStaleness(Duration::from_secs(1));
+
+    // lag is unknown before refresh
+    assert_eq!(whoami(rs.route(fresh)).await, "master");
+
+    rs.refresh_lag().await;
+
+    for _ in 0..3 {
+      assert_eq!(whoami(rs.route(fresh)).await, "fresh");
+    }
+    assert_eq!(whoami(rs.route(ReadPreference::Master)).await, "master");
+    assert_ne!(whoami(rs.route(ReadPreference::PreferReplica)).await, "master");
+
+    assert_eq!(rs.read_preference(512), ReadPreference::PreferReplica);
+    assert_eq!(rs.read_preference(513), ReadPreference::Master);
+
+    // writes go to master, so master sees them and replicas don't
+    let _: Vec<(u64,)> = rs.insert(Insert {
+      space_id: 513, tuple: ( 1u64, ).into_tuple(),
+    }).await.unwrap();
+
+    let found: Vec<(u64,)> = rs.select(Select {
+      space_id: 513, index_id: 0,
+      limit: 1, offset: 0,
+      iterator: Iterator::Eq,
+      keys: ( 1u64, ).into_tuple(),
+    }).await.unwrap();
+    assert_eq!(found, vec![ (1,) ]);
+  }
+
+  #[tokio::test]
+  async fn test_read_only_call() {
+    let rs = ReplicaSet::new(instance("master", 0.0))
+      .with_replica(instance("replica", 0.0))
+      .with_read_preference(ReadPreference::PreferReplica);
+
+    assert_eq!(whoami(rs.master()).await, "master");
+    let call = || Call { function: "whoami".into(), args: Vec::new() };
+
+    let (name,): (String,) = rs.call(call()).await.unwrap();
+    assert_eq!(name, "master");
+    let (name,): (String,) = rs.call_with(call(), ReadPreference::PreferReplica).await.unwrap();
+    assert_eq!(name, "replica");
+
+    let rs = rs.with_read_only_function("whoami");
+    let (name,): (String,) = rs.call(call()).await.unwrap();
+    assert_eq!(name, "replica");
+  }
+
+  #[tokio::test]
+  async fn test_lowest_latency() {
+    let rs = ReplicaSet::new(instance("master", 0.0))
+      .with_replica(instance("far", 0.0))
+      .with_replica(instance("near", 0.0))
+      .with_balancing(Balancing::LowestLatency);
+
+    rs.refresh_lag().await;
+    rs.instances[1].latency.store(5_000, Ordering::Relaxed);
+    rs.instances[2].latency.store(100, Ordering::Relaxed);
+
+    for _ in 0..3 {
+      assert_eq!(whoami(rs.route(ReadPreference::PreferReplica)).await, "near");
+    }
+  }
+
+  #[tokio::test]
+  async fn test_master_discovery() {
+    let first_ro = Arc::new(AtomicBool::new(false));
+    let second_ro = Arc::new(AtomicBool::new(true));
+    let rs = ReplicaSet::new(read_only_instance("first", 0.0, first_ro.clone()))
+      .with_replica(read_only_instance("second", 0.0, second_ro.clone()))
+      .with_read_preference(ReadPreference::PreferReplica);
+
+    rs.refresh_master().await;
+    assert_eq!(whoami(rs.master()).await, "first");
+
+    // failover
+    first_ro.store(true, Ordering::Relaxed);
+    second_ro.store(false, Ordering::Relaxed);
+    rs.refresh_master().await;
+    assert_eq!(whoami(rs.master()).await, "second");
+    assert_eq!(whoami(rs.route(ReadPreference::PreferReplica)).await, "first");
+
+    // master is kept while there is no writable instance
+    second_ro.store(true, Ordering::Relaxed);
+    rs.refresh_master().await;
+    assert_eq!(whoami(rs.master()).await, "second");
+  }
+}
@@ -0,0 +1,350 @@
+/*!
+  This module contains checking of schema required by application.
+
+  Application declares spaces, fields and indexes it relies on,
+  they are verified against live schema, e.g. at startup,
+  so missing migrations are found before the first failed request.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, schema::{self, IndexSpec, SpaceSpec}};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let diff = schema::expect()
+      .with_space(SpaceSpec::new("users")
+        .with_field("id", "unsigned")
+        .with_field("email", "string")
+        .with_index(IndexSpec::new("primary", &[ "id" ]))
+        .with_index(IndexSpec::new("email", &[ "email" ]).with_unique(true)))
+      .verify(conn).await?;
+
+    if !diff.is_empty() {
+      panic!("schema mismatch:\n{}", diff);
+    }
+    # Ok(()) }
+  ```
+*/
+
+use std::fmt;
+
+use rmpv::Value as MsgValue;
+
+use crate::{
+  compare::parse_parts,
+  connection::Connection,
+  iproto::{
+    constants::{Field, Iterator, VINDEX_ID, VSPACE_ID},
+    request::Select,
+    response::FormatField,
+    types::Error,
+  },
+};
+
+const VSPACE_NAME_INDEX: u64 = 2;
+const VSPACE_ID_FIELD: usize = 0;
+const VSPACE_FORMAT_FIELD: usize = 6;
+const VINDEX_NAME_FIELD: usize = 2;
+const VINDEX_OPTS_FIELD: usize = 4;
+const VINDEX_PARTS_FIELD: usize = 5;
+
+/// starts declaration of expected schema
+pub fn expect() -> Expectation {
+  Expectation::default()
+}
+
+/// This is expected index, its parts are field names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexSpec {
+  name: String,
+  parts: Vec<String>,
+  unique: Option<bool>,
+}
+
+impl IndexSpec {
+  pub fn new(name: &str, parts: &[&str]) -> IndexSpec {
+    IndexSpec {
+      name: name.into(),
+      parts: parts.iter().map(|&part| part.into()).collect(),
+      unique: None,
+    }
+  }
+
+  /// uniqueness is not checked unless it is set
+  pub fn with_unique(mut self, unique: bool) -> Self {
+    self.unique = Some(unique);
+    self
+  }
+}
+
+/// This is expected space, field types are compared case insensitively.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpaceSpec {
+  name: String,
+  fields: Vec<(String, String)>,
+  indexes: Vec<IndexSpec>,
+}
+
+impl SpaceSpec {
+  pub fn new(name: &str) -> SpaceSpec {
+    SpaceSpec { name: name.into(), fields: Vec::new(), indexes: Vec::new() }
+  }
+
+  pub fn with_field(mut self, name: &str, field_type: &str) -> Self {
+    self.fields.push((name.into(), field_type.into()));
+    self
+  }
+
+  pub fn with_index(mut self, index: IndexSpec) -> Self {
+    self.indexes.push(index);
+    self
+  }
+}
+
+/// Difference between expected and live schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+  MissingSpace { space: String },
+  MissingField { space: String, field: String },
+  FieldType { space: String, field: String, expected: String, actual: String },
+  MissingIndex { space: String, index: String },
+  /// parts are field names, unnamed fields are named by their numbers from one
+  IndexParts { space: String, index: String, expected: Vec<String>, actual: Vec<String> },
+  IndexUnique { space: String, index: String, expected: bool },
+}
+
+impl fmt::Display for Mismatch {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Mismatch::MissingSpace { space } =>
+        write!(f, "space {} is missing", space),
+      Mismatch::MissingField { space, field } =>
+        write!(f, "field {}.{} is missing", space, field),
+      Mismatch::FieldType { space, field, expected, actual } =>
+        write!(f, "field {}.{} is {}, expected {}", space, field, actual, expected),
+      Mismatch::MissingIndex { space, index } =>
+        write!(f, "index {}.{} is missing", space, index),
+      Mismatch::IndexParts { space, index, expected, actual } =>
+        write!(f, "index {}.{} has parts {:?}, expected {:?}", space, index, actual, expected),
+      Mismatch::IndexUnique { space, index, expected: true } =>
+        write!(f, "index {}.{} is not unique", space, index),
+      Mismatch::IndexUnique { space, index, expected: false } =>
+        write!(f, "index {}.{} is unique", space, index),
+    }
+  }
+}
+
+/// This is result of schema verification, it is empty if schema matches.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaDiff {
+  pub mismatches: Vec<Mismatch>,
+}
+
+impl SchemaDiff {
+  pub fn is_empty(&self) -> bool {
+    self.mismatches.is_empty()
+  }
+}
+
+/// mismatches are formatted one per line
+impl fmt::Display for SchemaDiff {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, mismatch) in self.mismatches.iter().enumerate() {
+      if i > 0 {
+        f.write_str("\n")?;
+      }
+      write!(f, "{}", mismatch)?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug)]
+struct LiveIndex {
+  name: String,
+  parts: Vec<usize>,
+  unique: bool,
+}
+
+#[derive(Debug)]
+struct LiveSpace {
+  format: Vec<FormatField>,
+  indexes: Vec<LiveIndex>,
+}
+
+/// This is declaration of expected schema, see module docs.
+#[derive(Debug, Clone, Default)]
+pub struct Expectation {
+  spaces: Vec<SpaceSpec>,
+}
+
+impl Expectation {
+  pub fn with_space(mut self, space: SpaceSpec) -> Self {
+    self.spaces.push(space);
+    self
+  }
+
+  /// fetches schema of expected spaces and compares it with expectation
+  pub async fn verify(&self, conn: &Connection) -> Result<SchemaDiff, Error> {
+    let mut diff = SchemaDiff::default();
+
+    for spec in self.spaces.iter() {
+      let live = live_space(conn, &spec.name).await?;
+      check_space(spec, live.as_ref(), &mut diff.mismatches);
+    }
+
+    Ok(diff)
+  }
+}
+
+async fn live_space(conn: &Connection, name: &str) -> Result<Option<LiveSpace>, Error> {
+  let spaces = conn.select_formatted(Select {
+    space_id: VSPACE_ID, index_id: VSPACE_NAME_INDEX,
+    limit: 1, offset: 0,
+    iterator: Iterator::Eq,
+    keys: vec![ name.into() ],
+  }).await?;
+
+  let space = match spaces.into_iter().next() {
+    Some(space) => space,
+    None => return Ok(None),
+  };
+
+  let space_id = space.values().get(VSPACE_ID_FIELD)
+    .and_then(MsgValue::as_u64)
+    .ok_or(Error::UnexpectedValue(Field::Data))?;
+
+  let format = match space.values().get(VSPACE_FORMAT_FIELD) {
+    Some(MsgValue::Array(fields)) => fields.iter()
+      .map(FormatField::from_value)
+      .collect::<Result<_, _>>()?,
+    _ => return Err(Error::UnexpectedValue(Field::TupleFormats)),
+  };
+
+  let indexes = conn.select_formatted(Select {
+    space_id: VINDEX_ID, index_id: 0,
+    limit: u32::MAX, offset: 0,
+    iterator: Iterator::Eq,
+    keys: vec![ space_id.into() ],
+  }).await?;
+
+  let indexes = indexes.iter()
+    .map(|index| {
+      let values = index.values();
+      let name = values.get(VINDEX_NAME_FIELD)
+        .and_then(MsgValue::as_str)
+        .ok_or(Error::UnexpectedValue(Field::Data))?;
+      let parts = values.get(VINDEX_PARTS_FIELD)
+        .ok_or(Error::UnexpectedValue(Field::Data))?;
+      let unique = values.get(VINDEX_OPTS_FIELD)
+        .and_then(MsgValue::as_map)
+        .and_then(|opts| opts.iter().find(|(key, _)| key.as_str() == Some("unique")))
+        .and_then(|(_, unique)| unique.as_bool())
+        .unwrap_or(true);
+
+      Ok(LiveIndex { name: name.into(), parts: parse_parts(parts)?, unique })
+    })
+    .collect::<Result<_, Error>>()?;
+
+  Ok(Some(LiveSpace { format, indexes }))
+}
+
+fn check_space(spec: &SpaceSpec, live: Option<&LiveSpace>, mismatches: &mut Vec<Mismatch>) {
+  let space = spec.name.clone();
+  let live = match live {
+    Some(live) => live,
+    None => return mismatches.push(Mismatch::MissingSpace { space }),
+  };
+
+  for (field, expected) in spec.fields.iter() {
+    match live.format.iter().find(|f| f.name == *field) {
+      None => mismatches.push(Mismatch::MissingField {
+        space: space.clone(), field: field.clone(),
+      }),
+      Some(actual) if !actual.field_type.eq_ignore_ascii_case(expected) => {
+        mismatches.push(Mismatch::FieldType {
+          space: space.clone(), field: field.clone(),
+          expected: expected.clone(), actual: actual.field_type.clone(),
+        })
+      },
+      Some(_) => {},
+    }
+  }
+
+  for index in spec.indexes.iter() {
+    let actual = match live.indexes.iter().find(|i| i.name == index.name) {
+      Some(actual) => actual,
+      None => {
+        mismatches.push(Mismatch::MissingIndex { space: space.clone(), index: index.name.clone() });
+        continue;
+      },
+    };
+
+    let parts: Vec<String> = actual.parts.iter()
+      .map(|&part| match live.format.get(part) {
+        Some(field) => field.name.clone(),
+        None => (part + 1).to_string(),
+      })
+      .collect();
+
+    if parts != index.parts {
+      mismatches.push(Mismatch::IndexParts {
+        space: space.clone(), index: index.name.clone(),
+        expected: index.parts.clone(), actual: parts,
+      });
+    }
+
+    if let Some(unique) = index.unique {
+      if unique != actual.unique {
+        mismatches.push(Mismatch::IndexUnique {
+          space: space.clone(), index: index.name.clone(), expected: unique,
+        });
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn field(name: &str, field_type: &str) -> FormatField {
+    FormatField { name: name.into(), field_type: field_type.into(), is_nullable: false }
+  }
+
+  #[test]
+  fn test_check_space() {
+    let live = LiveSpace {
+      format: vec![ field("id", "unsigned"), field("email", "string") ],
+      indexes: vec![
+        LiveIndex { name: "primary".into(), parts: vec![ 0 ], unique: true },
+        LiveIndex { name: "email".into(), parts: vec![ 1, 2 ], unique: false },
+      ],
+    };
+
+    let spec = SpaceSpec::new("users")
+      .with_field("id", "Unsigned")
+      .with_field("email", "integer")
+      .with_field("name", "string")
+      .with_index(IndexSpec::new("primary", &[ "id" ]).with_unique(true))
+      .with_index(IndexSpec::new("email", &[ "email" ]).with_unique(true))
+      .with_index(IndexSpec::new("name", &[ "name" ]));
+
+    let mut mismatches = Vec::new();
+    check_space(&spec, Some(&live), &mut mismatches);
+
+    let users = || "users".to_string();
+    assert_eq!(mismatches, vec![
+      Mismatch::FieldType {
+        space: users(), field: "email".into(),
+        expected: "integer".into(), actual: "string".into(),
+      },
+      Mismatch::MissingField { space: users(), field: "name".into() },
+      Mismatch::IndexParts {
+        space: users(), index: "email".into(),
+        expected: vec![ "email".into() ], actual: vec![ "email".into(), "3".into() ],
+      },
+      Mismatch::IndexUnique { space: users(), index: "email".into(), expected: true },
+      Mismatch::MissingIndex { space: users(), index: "name".into() },
+    ]);
+    assert_eq!(mismatches[3].to_string(), "index users.email is not unique");
+  }
+}
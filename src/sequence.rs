@@ -0,0 +1,215 @@
+/*!
+  This module contains helpers for sequences.
+
+  Sequences are accessed through box.sequence with eval,
+  missing sequence is reported as ErrorNoSuchSequence like other tarantool errors.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, SequenceError, TarantoolClient};
+    # async fn reset_ids() {}
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let seq = conn.sequence("user_id_seq");
+    let id = seq.next().await?;
+
+    match seq.next().await {
+      Err(err) if SequenceError::of(&err) == Some(SequenceError::Exhausted) => reset_ids().await,
+      result => println!("{:?}", result),
+    }
+    # Ok(()) }
+  ```
+*/
+
+use serde::de::IgnoredAny;
+
+use crate::{
+  client::TarantoolClient,
+  iproto::{
+    constants::Code,
+    request::{Eval, Value},
+    types::Error,
+  },
+};
+
+macro_rules! sequence_expr {
+  ($body:literal) => {
+    concat!(
+      "local name = ... ",
+      "local seq = box.sequence[name] ",
+      "if seq == nil then box.error(box.error.NO_SUCH_SEQUENCE, name) end ",
+      $body,
+    )
+  };
+}
+
+pub(crate) const NEXT_EXPR: &str = sequence_expr!("return seq:next()");
+pub(crate) const CURRENT_EXPR: &str = sequence_expr!("return seq:current()");
+pub(crate) const SET_EXPR: &str = sequence_expr!("seq:set(select(2, ...))");
+pub(crate) const RESET_EXPR: &str = sequence_expr!("seq:reset()");
+
+/// Errors of sequence operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceError {
+  Missing,
+  /// sequence reached its limit and it doesn't cycle
+  Exhausted,
+  /// current value is asked before the first next
+  NotStarted,
+}
+
+impl SequenceError {
+  pub fn of(err: &Error) -> Option<SequenceError> {
+    match err.root() {
+      Error::TarantoolError(Code::ErrorNoSuchSequence, _) => Some(SequenceError::Missing),
+      Error::TarantoolError(Code::ErrorSequenceOverflow, _) => Some(SequenceError::Exhausted),
+      Error::TarantoolError(Code::ErrorSequenceNotStarted, _) => Some(SequenceError::NotStarted),
+      _ => None,
+    }
+  }
+}
+
+/// This is handle of sequence, see TarantoolClient::sequence.
+#[derive(Debug)]
+pub struct Sequence<'c, C> {
+  client: &'c C,
+  name: String,
+}
+
+impl<'c, C> Sequence<'c, C>
+  where C: TarantoolClient
+{
+  pub(crate) fn new(client: &'c C, name: &str) -> Sequence<'c, C> {
+    Sequence { client, name: name.into() }
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// generates next value
+  pub async fn next(&self) -> Result<i64, Error> {
+    let (value,): (i64,) = self.eval(NEXT_EXPR, Vec::new()).await?;
+    Ok(value)
+  }
+
+  /// last generated value, it is supported by tarantool 2.4+
+  pub async fn current(&self) -> Result<i64, Error> {
+    let (value,): (i64,) = self.eval(CURRENT_EXPR, Vec::new()).await?;
+    Ok(value)
+  }
+
+  /// sets last generated value, so next one follows it
+  pub async fn set(&self, value: i64) -> Result<(), Error> {
+    let _: Vec<IgnoredAny> = self.eval(SET_EXPR, vec![ value.into() ]).await?;
+    Ok(())
+  }
+
+  /// makes sequence start over
+  pub async fn reset(&self) -> Result<(), Error> {
+    let _: Vec<IgnoredAny> = self.eval(RESET_EXPR, Vec::new()).await?;
+    Ok(())
+  }
+
+  async fn eval<T>(&self, expr: &str, mut args: Vec<Value>) -> Result<T, Error>
+    where T: serde::de::DeserializeOwned
+  {
+    args.insert(0, self.name.as_str().into());
+    self.client.eval(Eval { expr: expr.into(), args }).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, atomic::{AtomicI64, Ordering}};
+
+  use crate::{
+    connection::{Connection, connector::Connector},
+    iproto::response::TarantoolError,
+    testing::FakeClient,
+  };
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_sequence() {
+    let value = Arc::new(AtomicI64::new(0));
+    let exists = |args: &[Value]| match &args[0] {
+      Value::Str(name) if name == "id_seq" => Ok(()),
+      _ => Err(Error::TarantoolError(
+        Code::ErrorNoSuchSequence,
+        TarantoolError::new("Sequence does not exist"),
+      )),
+    };
+
+    let client = {
+      let (next, set) = (value.clone(), value.clone());
+      FakeClient::new()
+        .with_eval(NEXT_EXPR, move |args| {
+          exists(&args)?;
+          match next.fetch_add(1, Ordering::SeqCst) + 1 {
+            value if value > 3 => Err(Error::TarantoolError(
+              Code::ErrorSequenceOverflow,
+              TarantoolError::new("Sequence 'id_seq' has overflowed"),
+            )),
+            value => Ok(vec![ value.into() ]),
+          }
+        })
+        .with_eval(SET_EXPR, move |args| {
+          exists(&args)?;
+          match args[1] {
+            Value::Int(value) => set.store(value, Ordering::SeqCst),
+            Value::UInt(value) => set.store(value as i64, Ordering::SeqCst),
+            _ => unreachable!(),
+          }
+          Ok(Vec::new())
+        })
+    };
+
+    let seq = client.sequence("id_seq");
+    assert_eq!(seq.next().await.unwrap(), 1);
+    assert_eq!(seq.next().await.unwrap(), 2);
+
+    seq.set(2).await.unwrap();
+    assert_eq!(seq.next().await.unwrap(), 3);
+
+    let err = seq.next().await.unwrap_err();
+    assert_eq!(SequenceError::of(&err), Some(SequenceError::Exhausted));
+
+    let err = client.sequence("missing").next().await.unwrap_err();
+    assert_eq!(SequenceError::of(&err), Some(SequenceError::Missing));
+  }
+
+  #[tokio::test]
+  async fn test_tnt_sequence() {
+    let addr = "127.0.0.1:3301".parse().unwrap();
+    let conn: Arc<Connection> = Connector::new(addr)
+      .connect().await.unwrap();
+
+    let _: Vec<IgnoredAny> = conn.eval(Eval {
+      expr: "box.schema.sequence.create(..., { if_not_exists = true, max = 3 })".into(),
+      args: vec![ "alopecosa_seq".into() ],
+    }).await.unwrap();
+
+    let seq = conn.sequence("alopecosa_seq");
+    seq.reset().await.unwrap();
+    assert_eq!(seq.next().await.unwrap(), 1);
+    assert_eq!(seq.current().await.unwrap(), 1);
+
+    seq.set(2).await.unwrap();
+    assert_eq!(seq.next().await.unwrap(), 3);
+    let err = seq.next().await.unwrap_err();
+    assert_eq!(SequenceError::of(&err), Some(SequenceError::Exhausted));
+
+    seq.reset().await.unwrap();
+    let err = seq.current().await.unwrap_err();
+    assert_eq!(SequenceError::of(&err), Some(SequenceError::NotStarted));
+
+    let err = conn.sequence("alopecosa_missing_seq").next().await.unwrap_err();
+    assert_eq!(SequenceError::of(&err), Some(SequenceError::Missing));
+
+    let _: Vec<IgnoredAny> = conn.eval(Eval {
+      expr: "box.sequence[...]:drop()".into(),
+      args: vec![ "alopecosa_seq".into() ],
+    }).await.unwrap();
+  }
+}
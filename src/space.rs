@@ -0,0 +1,632 @@
+/*!
+  This module contains handles of spaces and indexes addressed by names.
+
+  Names are resolved with `TarantoolClient::space_id` and `index_id`
+  on every call, connection caches them, see Connection::reload_schema.
+  Results are decoded tuple by tuple.
+
+  Example:
+  ```rust,no_run
+    # use futures_util::StreamExt;
+    # use alopecosa::{Connection, Error, Iterator, NoKey, SelectOptions, TarantoolClient, UpdateOp};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let users = conn.space("users");
+
+    let user: (u64, String, u32) = users.insert(( 1u64, "ann", 30u32 )).await?;
+    let user: Option<(u64, String, u32)> = users.get(1u64).await?;
+
+    let adults: Vec<(u64, String, u32)> = users.index("age")
+      .select(18u32, SelectOptions::new().with_iterator(Iterator::Ge).with_limit(100))
+      .await?;
+    let latest: Vec<(u64, String, u32)> = users.select_rev(NoKey, 10).await?;
+
+    let updated: Option<(u64, String, u32)> = users
+      .update(1u64, vec![ UpdateOp::add(2, 1u32)? ]).await?;
+
+    users.upsert(( 2u64, "bob", 20u32 ), vec![ UpdateOp::add(2, 1u32)? ]).await?;
+    let deleted: Option<(u64, String, u32)> = users.delete(2u64).await?;
+
+    let oldest: Option<(u64, String, u32)> = users.index("age").max().await?;
+    let count = users.index("age").count(( 18u32, ), Iterator::Ge).await?;
+
+    let mut all = users.select_stream::<(u64, String, u32), _>(NoKey, 1000);
+    while let Some(user) = all.next().await {
+      let (id, name, age) = user?;
+    }
+    # Ok(()) }
+  ```
+*/
+
+use std::{
+  collections::VecDeque,
+  fmt,
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use serde::de::DeserializeOwned;
+
+use crate::{
+  client::TarantoolClient,
+  iproto::{
+    constants::{Field, Iterator},
+    request::{Call, Delete, Insert, IntoKey, IntoTuple, PagedSelect, Replace, Select, Update, Upsert, Value},
+    response::Page,
+    types::Error,
+  },
+};
+
+/// Options of select, by default all tuples equal to key are selected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectOptions {
+  pub limit: u32,
+  pub offset: u32,
+  pub iterator: Iterator,
+}
+
+impl Default for SelectOptions {
+  fn default() -> Self {
+    SelectOptions { limit: u32::MAX, offset: 0, iterator: Iterator::Eq }
+  }
+}
+
+impl SelectOptions {
+  pub fn new() -> SelectOptions {
+    SelectOptions::default()
+  }
+
+  pub fn with_limit(mut self, limit: u32) -> Self {
+    self.limit = limit;
+    self
+  }
+
+  pub fn with_offset(mut self, offset: u32) -> Self {
+    self.offset = offset;
+    self
+  }
+
+  pub fn with_iterator(mut self, iterator: Iterator) -> Self {
+    self.iterator = iterator;
+    self
+  }
+}
+
+/// This is space handle, see module docs.
+#[derive(Debug)]
+pub struct Space<'c, C> {
+  client: &'c C,
+  name: String,
+}
+
+impl<'c, C> Space<'c, C>
+  where C: TarantoolClient
+{
+  pub(crate) fn new(client: &'c C, name: &str) -> Space<'c, C> {
+    Space { client, name: name.into() }
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub async fn id(&self) -> Result<u64, Error> {
+    self.client.space_id(&self.name).await
+  }
+
+  pub fn index(&self, name: &str) -> Index<'c, C> {
+    Index { client: self.client, space: self.name.clone(), name: Some(name.into()) }
+  }
+
+  pub fn primary(&self) -> Index<'c, C> {
+    Index { client: self.client, space: self.name.clone(), name: None }
+  }
+
+  /// selects by primary key
+  pub async fn select<T, K>(&self, key: K, opts: SelectOptions) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned + Send, K: IntoKey
+  {
+    self.primary().select(key, opts).await
+  }
+
+  /// selects by primary key in descending order, see Index::select_rev
+  pub async fn select_rev<T, K>(&self, key: K, limit: u32) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned + Send, K: IntoKey
+  {
+    self.primary().select_rev(key, limit).await
+  }
+
+  /// builder of select from space, see SpaceSelect
+  pub fn select_builder(&self) -> SpaceSelect<'c, C> {
+    SpaceSelect { index: self.primary(), keys: Vec::new(), opts: SelectOptions::new() }
+  }
+
+  /// streams tuples by primary key, see Index::select_stream
+  pub fn select_stream<T, K>(&self, key: K, batch_size: u32) -> SelectStream<'c, T>
+    where T: DeserializeOwned + Send + 'c, K: IntoKey
+  {
+    self.primary().select_stream(key, batch_size)
+  }
+
+  pub async fn get<T, K>(&self, key: K) -> Result<Option<T>, Error>
+    where T: DeserializeOwned + Send, K: IntoKey
+  {
+    self.primary().get(key).await
+  }
+
+  pub async fn insert<T, V>(&self, tuple: V) -> Result<T, Error>
+    where T: DeserializeOwned + Send, V: IntoTuple
+  {
+    let tuples: Vec<T> = self.client.insert(Insert {
+      space_id: self.id().await?,
+      tuple: tuple.into_tuple(),
+    }).await?;
+
+    tuples.into_iter().next().ok_or(Error::UnexpectedValue(Field::Data))
+  }
+
+  pub async fn replace<T, V>(&self, tuple: V) -> Result<T, Error>
+    where T: DeserializeOwned + Send, V: IntoTuple
+  {
+    let tuples: Vec<T> = self.client.replace(Replace {
+      space_id: self.id().await?,
+      tuple: tuple.into_tuple(),
+    }).await?;
+
+    tuples.into_iter().next().ok_or(Error::UnexpectedValue(Field::Data))
+  }
+
+  /// updates by primary key, it is none if tuple doesn't exist
+  pub async fn update<T, K, O>(&self, key: K, ops: O) -> Result<Option<T>, Error>
+    where T: DeserializeOwned + Send, K: IntoKey,
+          O: IntoIterator, O::Item: Into<Vec<Value>>,
+  {
+    self.primary().update(key, ops).await
+  }
+
+  pub async fn upsert<V, O>(&self, tuple: V, ops: O) -> Result<(), Error>
+    where V: IntoTuple, O: IntoIterator, O::Item: Into<Vec<Value>>
+  {
+    self.client.upsert(Upsert {
+      space_id: self.id().await?,
+      index_base: 0,
+      ops: ops.into_iter().map(Into::into).collect(),
+      tuple: tuple.into_tuple(),
+    }).await
+  }
+
+  /// deletes by primary key, it is none if tuple doesn't exist
+  pub async fn delete<T, K>(&self, key: K) -> Result<Option<T>, Error>
+    where T: DeserializeOwned + Send, K: IntoKey
+  {
+    self.primary().delete(key).await
+  }
+}
+
+/**
+  This is builder of select addressed by names, it is counterpart of
+  Select::builder for space handle. Primary index is used by default.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, Iterator, TarantoolClient};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let adults: Vec<(u64, String, u32)> = conn.space("users").select_builder()
+      .index("age")
+      .iterator(Iterator::Ge)
+      .key(( 18u32, ))
+      .limit(100)
+      .fetch().await?;
+    # Ok(()) }
+  ```
+*/
+#[derive(Debug)]
+pub struct SpaceSelect<'c, C> {
+  index: Index<'c, C>,
+  keys: Vec<Value>,
+  opts: SelectOptions,
+}
+
+impl<'c, C> SpaceSelect<'c, C>
+  where C: TarantoolClient
+{
+  pub fn index(mut self, name: &str) -> Self {
+    self.index.name = Some(name.into());
+    self
+  }
+
+  pub fn iterator(mut self, iterator: Iterator) -> Self {
+    self.opts.iterator = iterator;
+    self
+  }
+
+  pub fn limit(mut self, limit: u32) -> Self {
+    self.opts.limit = limit;
+    self
+  }
+
+  pub fn offset(mut self, offset: u32) -> Self {
+    self.opts.offset = offset;
+    self
+  }
+
+  /// descending order starting from key inclusively, see Select::reverse
+  pub fn reverse(self) -> Self {
+    self.iterator(Iterator::Le)
+  }
+
+  /// descending order starting before key, see Select::before
+  pub fn before(self) -> Self {
+    self.iterator(Iterator::Lt)
+  }
+
+  pub fn key<K: IntoKey>(mut self, key: K) -> Self {
+    self.keys = key.into_key();
+    self
+  }
+
+  pub async fn fetch<T>(self) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned + Send
+  {
+    self.index.select(self.keys, self.opts).await
+  }
+}
+
+/// This is index handle, primary index is used if name is none.
+#[derive(Debug)]
+pub struct Index<'c, C> {
+  client: &'c C,
+  space: String,
+  name: Option<String>,
+}
+
+impl<'c, C> Index<'c, C>
+  where C: TarantoolClient
+{
+  pub fn name(&self) -> Option<&str> {
+    self.name.as_deref()
+  }
+
+  /// ids of space and index
+  pub async fn ids(&self) -> Result<(u64, u64), Error> {
+    let space_id = self.client.space_id(&self.space).await?;
+    let index_id = match &self.name {
+      Some(name) => self.client.index_id(space_id, name).await?,
+      None => 0,
+    };
+
+    Ok((space_id, index_id))
+  }
+
+  pub async fn select<T, K>(&self, key: K, opts: SelectOptions) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned + Send, K: IntoKey
+  {
+    let (space_id, index_id) = self.ids().await?;
+
+    self.client.select(Select {
+      space_id, index_id,
+      limit: opts.limit, offset: opts.offset,
+      iterator: opts.iterator,
+      keys: key.into_key(),
+    }).await
+  }
+
+  /**
+    selects up to limit tuples in descending order starting from key inclusively,
+    empty key starts from the last tuple, so it is "latest N records" query.
+  */
+  pub async fn select_rev<T, K>(&self, key: K, limit: u32) -> Result<Vec<T>, Error>
+    where T: DeserializeOwned + Send, K: IntoKey
+  {
+    let (space_id, index_id) = self.ids().await?;
+    self.client.select(Select::reverse(space_id, index_id, key.into_key(), limit)).await
+  }
+
+  /**
+    streams tuples equal to key, empty key streams whole index.
+    Pages of batch_size tuples are selected lazily after position
+    of the previous page, so it requires tarantool 2.11+.
+  */
+  pub fn select_stream<T, K>(&self, key: K, batch_size: u32) -> SelectStream<'c, T>
+    where T: DeserializeOwned + Send + 'c, K: IntoKey
+  {
+    let client = self.client;
+    let (space, name) = (self.space.clone(), self.name.clone());
+    let keys = key.into_key();
+    let batch_size = batch_size.max(1);
+
+    SelectStream::new(batch_size, Box::new(move |after_position| {
+      let index = Index { client, space: space.clone(), name: name.clone() };
+      let keys = keys.clone();
+
+      Box::pin(async move {
+        let (space_id, index_id) = index.ids().await?;
+        client.select_page(PagedSelect {
+          select: Select { space_id, index_id, limit: batch_size, keys, ..Default::default() },
+          after_position,
+          ..Default::default()
+        }).await
+      })
+    }))
+  }
+
+  pub async fn get<T, K>(&self, key: K) -> Result<Option<T>, Error>
+    where T: DeserializeOwned + Send, K: IntoKey
+  {
+    let (space_id, index_id) = self.ids().await?;
+    self.client.get(space_id, index_id, key.into_key()).await
+  }
+
+  /// tuple with the least key
+  pub async fn min<T>(&self) -> Result<Option<T>, Error>
+    where T: DeserializeOwned + Send
+  {
+    self.first(Iterator::Ge).await
+  }
+
+  /// tuple with the greatest key
+  pub async fn max<T>(&self) -> Result<Option<T>, Error>
+    where T: DeserializeOwned + Send
+  {
+    self.first(Iterator::Le).await
+  }
+
+  /**
+    number of tuples matching key with iterator, it is counted by tarantool
+    with call of `box.space.<space>.index.<index>:count`
+  */
+  pub async fn count<K>(&self, key: K, iterator: Iterator) -> Result<u64, Error>
+    where K: IntoKey
+  {
+    let function = match &self.name {
+      Some(name) => format!("box.space.{}.index.{}:count", self.space, name),
+      None => format!("box.space.{}:count", self.space),
+    };
+
+    let (count,): (u64,) = self.client.call(Call {
+      function,
+      args: vec![
+        Value::Array(key.into_key()),
+        Value::Map(vec![ ("iterator".into(), Value::UInt(iterator as u64)) ]),
+      ],
+    }).await?;
+
+    Ok(count)
+  }
+
+  /// index should be unique
+  pub async fn update<T, K, O>(&self, key: K, ops: O) -> Result<Option<T>, Error>
+    where T: DeserializeOwned + Send, K: IntoKey,
+          O: IntoIterator, O::Item: Into<Vec<Value>>,
+  {
+    let (space_id, index_id) = self.ids().await?;
+
+    let tuples: Vec<T> = self.client.update(Update {
+      space_id, index_id, index_base: 0,
+      key: key.into_key(),
+      tuple: ops.into_iter().map(Into::into).collect(),
+    }).await?;
+
+    Ok(tuples.into_iter().next())
+  }
+
+  /// index should be unique
+  pub async fn delete<T, K>(&self, key: K) -> Result<Option<T>, Error>
+    where T: DeserializeOwned + Send, K: IntoKey
+  {
+    let (space_id, index_id) = self.ids().await?;
+
+    let tuples: Vec<T> = self.client.delete(Delete {
+      space_id, index_id,
+      key: key.into_key(),
+    }).await?;
+
+    Ok(tuples.into_iter().next())
+  }
+
+  async fn first<T>(&self, iterator: Iterator) -> Result<Option<T>, Error>
+    where T: DeserializeOwned + Send
+  {
+    let tuples: Vec<T> = self.select((), SelectOptions::new()
+      .with_iterator(iterator)
+      .with_limit(1)).await?;
+
+    Ok(tuples.into_iter().next())
+  }
+}
+
+type PageFuture<'c, T> = Pin<Box<dyn Future<Output = Result<Page<T>, Error>> + Send + 'c>>;
+type FetchPage<'c, T> = Box<dyn Fn(Option<Vec<u8>>) -> PageFuture<'c, T> + Send + 'c>;
+
+/**
+  This is stream of tuples selected page by page, see Index::select_stream.
+
+  Next page is selected once tuples of previous one are taken,
+  stream ends after short page or the first error.
+*/
+pub struct SelectStream<'c, T> {
+  fetch: FetchPage<'c, T>,
+  next: Option<PageFuture<'c, T>>,
+  buffer: VecDeque<T>,
+  batch_size: u32,
+}
+
+impl<'c, T> SelectStream<'c, T> {
+  fn new(batch_size: u32, fetch: FetchPage<'c, T>) -> SelectStream<'c, T> {
+    let next = Some(fetch(None));
+    SelectStream { fetch, next, buffer: VecDeque::new(), batch_size }
+  }
+}
+
+impl<T> fmt::Debug for SelectStream<'_, T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("SelectStream")
+      .field("buffered", &self.buffer.len())
+      .field("batch_size", &self.batch_size)
+      .field("done", &self.next.is_none())
+      .finish()
+  }
+}
+
+impl<T> Unpin for SelectStream<'_, T> {}
+
+impl<T> Stream for SelectStream<'_, T> {
+  type Item = Result<T, Error>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    loop {
+      if let Some(tuple) = self.buffer.pop_front() {
+        return Poll::Ready(Some(Ok(tuple)));
+      }
+
+      let page = match self.next.as_mut() {
+        Some(next) => match next.as_mut().poll(cx) {
+          Poll::Ready(page) => page,
+          Poll::Pending => return Poll::Pending,
+        },
+        None => return Poll::Ready(None),
+      };
+
+      let page = match page {
+        Ok(page) => page,
+        Err(err) => {
+          self.next = None;
+          return Poll::Ready(Some(Err(err)));
+        },
+      };
+
+      self.next = match page.position {
+        Some(position) if page.tuples.len() >= self.batch_size as usize =>
+          Some((self.fetch)(Some(position))),
+        _ => None,
+      };
+      self.buffer.extend(page.tuples);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::future::poll_fn;
+
+  use crate::{iproto::{request::NoKey, update::UpdateOp}, testing::FakeClient};
+
+  use super::*;
+
+  type User = (u64, String, u32);
+
+  #[tokio::test]
+  async fn test_space() {
+    let client = FakeClient::new()
+      .with_space(512, vec![ 0 ])
+      .with_space_name(512, "users")
+      .with_index(512, 1, vec![ 2 ])
+      .with_index_name(512, 1, "age");
+
+    let users = client.space("users");
+    assert_eq!(users.id().await.unwrap(), 512);
+
+    let ann: User = users.insert(( 1u64, "ann", 30u32 )).await.unwrap();
+    assert_eq!(ann, (1, "ann".into(), 30));
+    users.insert::<User, _>(( 2u64, "bob", 17u32 )).await.unwrap();
+    users.replace::<User, _>(( 3u64, "eve", 45u32 )).await.unwrap();
+
+    let bob: Option<User> = users.get(2u64).await.unwrap();
+    assert_eq!(bob.map(|bob| bob.1), Some("bob".into()));
+
+    let ages = users.index("age");
+    let adults: Vec<User> = ages
+      .select(18u32, SelectOptions::new().with_iterator(Iterator::Ge))
+      .await.unwrap();
+    assert_eq!(adults.iter().map(|user| user.0).collect::<Vec<_>>(), vec![ 1, 3 ]);
+
+    let latest: Vec<User> = users.select_rev(NoKey, 2).await.unwrap();
+    assert_eq!(latest.iter().map(|user| user.0).collect::<Vec<_>>(), vec![ 3, 2 ]);
+    let older: Vec<User> = ages.select_rev(( 30u32, ), 5).await.unwrap();
+    assert_eq!(older.iter().map(|user| user.0).collect::<Vec<_>>(), vec![ 1, 2 ]);
+
+    let youngest: Option<User> = ages.min().await.unwrap();
+    assert_eq!(youngest.unwrap().0, 2);
+    let last: Option<User> = users.primary().max().await.unwrap();
+    assert_eq!(last.unwrap().0, 3);
+
+    let updated: Option<User> = users.update(( 1u64, ), vec![ UpdateOp::add(2, 1u32).unwrap() ]).await.unwrap();
+    assert_eq!(updated.unwrap().2, 31);
+    let missing: Option<User> = users.update(( 9u64, ), vec![ UpdateOp::assign(1, "x") ]).await.unwrap();
+    assert_eq!(missing, None);
+
+    assert_eq!(ages.count(( 18u32, ), Iterator::Ge).await.unwrap(), 2);
+    assert_eq!(users.primary().count(NoKey, Iterator::All).await.unwrap(), 3);
+
+    let deleted: Option<User> = users.delete(2u64).await.unwrap();
+    assert_eq!(deleted.unwrap().0, 2);
+    assert!(users.index("email").get::<User, _>(( "x", )).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn test_select_builder() {
+    let client = FakeClient::new()
+      .with_space(512, vec![ 0 ])
+      .with_space_name(512, "users")
+      .with_index(512, 1, vec![ 2 ])
+      .with_index_name(512, 1, "age");
+
+    let users = client.space("users");
+    for (id, age) in [ (1u64, 30u32), (2, 17), (3, 45), (4, 60) ] {
+      users.insert::<User, _>(( id, "user", age )).await.unwrap();
+    }
+
+    let all: Vec<User> = users.select_builder().fetch().await.unwrap();
+    assert_eq!(all.len(), 4);
+
+    let adults: Vec<User> = users.select_builder()
+      .index("age")
+      .iterator(Iterator::Ge)
+      .key(( 18u32, ))
+      .offset(1)
+      .limit(1)
+      .fetch().await.unwrap();
+    assert_eq!(adults.iter().map(|user| user.0).collect::<Vec<_>>(), vec![ 3 ]);
+
+    let younger: Vec<User> = users.select_builder()
+      .index("age")
+      .key(( 45u32, ))
+      .before()
+      .fetch().await.unwrap();
+    assert_eq!(younger.iter().map(|user| user.0).collect::<Vec<_>>(), vec![ 1, 2 ]);
+  }
+
+  #[tokio::test]
+  async fn test_select_stream() {
+    let client = FakeClient::new()
+      .with_space(512, vec![ 0 ])
+      .with_space_name(512, "users")
+      .with_index(512, 1, vec![ 2 ])
+      .with_index_name(512, 1, "age");
+
+    let users = client.space("users");
+    for id in 1..=5u64 {
+      users.insert::<User, _>(( id, "user", (id % 2) as u32 )).await.unwrap();
+    }
+
+    let mut all = users.select_stream::<User, _>((), 2);
+    let mut ids = Vec::new();
+    while let Some(user) = poll_fn(|cx| Pin::new(&mut all).poll_next(cx)).await {
+      ids.push(user.unwrap().0);
+    }
+    assert_eq!(ids, vec![ 1, 2, 3, 4, 5 ]);
+
+    let mut odd = users.index("age").select_stream::<User, _>(( 1u32, ), 3);
+    let mut ids = Vec::new();
+    while let Some(user) = poll_fn(|cx| Pin::new(&mut odd).poll_next(cx)).await {
+      ids.push(user.unwrap().0);
+    }
+    assert_eq!(ids, vec![ 1, 3, 5 ]);
+
+    // error ends stream
+    let mut missing = client.space("groups").select_stream::<User, _>((), 2);
+    assert!(poll_fn(|cx| Pin::new(&mut missing).poll_next(cx)).await.unwrap().is_err());
+    assert!(poll_fn(|cx| Pin::new(&mut missing).poll_next(cx)).await.is_none());
+  }
+}
@@ -0,0 +1,835 @@
+/*!
+  This module contains in-memory fake of tarantool client for unit tests,
+  it is enabled by `testing` feature.
+*/
+
+use std::{
+  cmp::Ordering,
+  collections::{BTreeMap, HashMap},
+  convert::TryInto,
+  sync::Mutex,
+};
+
+use async_trait::async_trait;
+use num_traits::{FromPrimitive, ToPrimitive};
+use serde::de::DeserializeOwned;
+
+use crate::{
+  client::TarantoolClient,
+  iproto::{
+    constants::{Code, Iterator},
+    request::{
+      Call, Delete, Eval, Execute, Insert,
+      PagedSelect, Replace, Select, Update, Upsert, Value,
+    },
+    response::{Page, SQLBody, TarantoolError},
+    types::Error,
+  },
+};
+
+type Handler = Box<dyn Fn(Vec<Value>) -> Result<Vec<Value>, Error> + Send + Sync>;
+
+/**
+  This is in-memory implementation of TarantoolClient.
+
+  Spaces are BTreeMap-backed and support basic iterator semantics
+  (Eq, Req, All, Lt, Le, Ge, Gt), calls and evals are served by registered handlers.
+  Calls of `box.space.<space>:count` and `box.space.<space>.index.<index>:count`
+  are counted over spaces and indexes resolved by registered names.
+  Sql is not supported.
+
+  Example:
+  ```rust
+    # use alopecosa::{Error, Insert, IntoTuple, TarantoolClient, testing::FakeClient};
+    # #[tokio::main(flavor = "current_thread")]
+    # async fn main() -> Result<(), Error> {
+    let client = FakeClient::new()
+      .with_space(512, vec![ 0 ])
+      .with_index(512, 1, vec![ 1 ])
+      .with_function("echo", |args| Ok(args));
+
+    let _: Vec<(u64, String)> = client.insert(Insert {
+      space_id: 512,
+      tuple: ( 1u64, "alice" ).into_tuple(),
+    }).await?;
+
+    let user: Option<(u64, String)> = client.get(512, 0, vec![ 1u64.into() ]).await?;
+    assert_eq!(user, Some(( 1, "alice".into() )));
+    # Ok(()) }
+  ```
+*/
+#[derive(Default)]
+pub struct FakeClient {
+  spaces: Mutex<HashMap<u64, FakeSpace>>,
+  space_names: HashMap<String, u64>,
+  index_names: HashMap<(u64, String), u64>,
+  functions: HashMap<String, Handler>,
+  evals: HashMap<String, Handler>,
+}
+
+#[derive(Debug, Default)]
+struct FakeSpace {
+  // index id -> key field numbers, 0 is primary
+  indexes: HashMap<u64, Vec<usize>>,
+  tuples: BTreeMap<Vec<KeyPart>, Vec<Value>>,
+}
+
+impl FakeClient {
+  pub fn new() -> FakeClient {
+    FakeClient::default()
+  }
+
+  /// creates space with primary index over key_fields
+  pub fn with_space(self, space_id: u64, key_fields: Vec<usize>) -> Self {
+    let mut space = FakeSpace::default();
+    space.indexes.insert(0, key_fields);
+    self.spaces.lock().unwrap().insert(space_id, space);
+    self
+  }
+
+  /// allows to resolve space by name
+  pub fn with_space_name(mut self, space_id: u64, name: &str) -> Self {
+    self.space_names.insert(name.into(), space_id);
+    self
+  }
+
+  /// creates secondary (non-unique) index over key_fields
+  pub fn with_index(self, space_id: u64, index_id: u64, key_fields: Vec<usize>) -> Self {
+    if let Some(space) = self.spaces.lock().unwrap().get_mut(&space_id) {
+      space.indexes.insert(index_id, key_fields);
+    }
+    self
+  }
+
+  /// allows to resolve index by name
+  pub fn with_index_name(mut self, space_id: u64, index_id: u64, name: &str) -> Self {
+    self.index_names.insert((space_id, name.into()), index_id);
+    self
+  }
+
+  /// registers handler for Call of function
+  pub fn with_function<F>(mut self, name: &str, handler: F) -> Self
+    where F: Fn(Vec<Value>) -> Result<Vec<Value>, Error> + Send + Sync + 'static
+  {
+    self.functions.insert(name.into(), Box::new(handler));
+    self
+  }
+
+  /// registers handler for Eval of expression
+  pub fn with_eval<F>(mut self, expr: &str, handler: F) -> Self
+    where F: Fn(Vec<Value>) -> Result<Vec<Value>, Error> + Send + Sync + 'static
+  {
+    self.evals.insert(expr.into(), Box::new(handler));
+    self
+  }
+
+  /// returns all tuples of space in primary key order
+  pub fn tuples(&self, space_id: u64) -> Vec<Vec<Value>> {
+    self.spaces.lock().unwrap().get(&space_id)
+      .map(|space| space.tuples.values().cloned().collect())
+      .unwrap_or_default()
+  }
+
+  fn with_space_mut<R, F>(&self, space_id: u64, f: F) -> Result<R, Error>
+    where F: FnOnce(&mut FakeSpace) -> Result<R, Error>
+  {
+    let mut spaces = self.spaces.lock().unwrap();
+    let space = spaces.get_mut(&space_id)
+      .ok_or_else(|| error(
+        Code::ErrorNoSuchSpace,
+        format!("Space '{}' does not exist", space_id),
+      ))?;
+    f(space)
+  }
+
+  fn select_tuples(&self, body: &Select) -> Result<Vec<Value>, Error> {
+    self.with_space_mut(body.space_id, |space| {
+      let found = space.select(body.index_id, body.iterator, &body.keys)?;
+
+      Ok(found.into_iter()
+        .skip(body.offset as usize)
+        .take(body.limit as usize)
+        .map(Value::Array)
+        .collect())
+    })
+  }
+
+  /// positions of fake pages are offsets in iterator order
+  fn select_page_tuples(&self, page: &PagedSelect) -> Result<(Vec<Value>, Option<Vec<u8>>), Error> {
+    let body = &page.select;
+    self.with_space_mut(body.space_id, |space| {
+      let found = space.select(body.index_id, body.iterator, &body.keys)?;
+
+      let start = match (&page.after_position, &page.after_tuple) {
+        (Some(position), _) => position.as_slice().try_into()
+          .map(|offset| u64::from_le_bytes(offset) as usize)
+          .map_err(|_| error(Code::ErrorIllegalParams, "Illegal parameters, invalid position"))?,
+        (None, Some(after)) => {
+          let key = space.primary_key(after)?;
+          found.iter()
+            .position(|tuple| matches!(space.primary_key(tuple), Ok(found) if found == key))
+            .map_or(0, |index| index + 1)
+        },
+        (None, None) => 0,
+      } + body.offset as usize;
+
+      let tuples: Vec<Value> = found.into_iter()
+        .skip(start)
+        .take(body.limit as usize)
+        .map(Value::Array)
+        .collect();
+
+      let position = match tuples.is_empty() {
+        true => None,
+        false => Some(((start + tuples.len()) as u64).to_le_bytes().to_vec()),
+      };
+      Ok((tuples, position))
+    })
+  }
+
+  fn store(&self, space_id: u64, tuple: Vec<Value>, replace: bool) -> Result<Vec<Value>, Error> {
+    self.with_space_mut(space_id, |space| {
+      let key = space.primary_key(&tuple)?;
+
+      if !replace && space.tuples.contains_key(&key) {
+        return Err(error(Code::ErrorTupleFound, "Duplicate key exists in unique index 'primary'"));
+      }
+
+      space.tuples.insert(key, tuple.clone());
+      Ok(vec![ Value::Array(tuple) ])
+    })
+  }
+
+  /// serves count of space or index called by path, it is none for other functions
+  fn count(&self, function: &str, args: &[Value]) -> Option<Result<Vec<Value>, Error>> {
+    let path = function.strip_prefix("box.space.")?.strip_suffix(":count")?;
+    let (space, index) = match path.split_once(".index.") {
+      Some((space, index)) => (space, Some(index)),
+      None => (path, None),
+    };
+
+    let count = || {
+      let space_id = *self.space_names.get(space)
+        .ok_or_else(|| error(Code::ErrorNoSuchSpace, format!("Space '{}' does not exist", space)))?;
+      let index_id = match index {
+        Some(index) => *self.index_names.get(&(space_id, index.into()))
+          .ok_or_else(|| error(
+            Code::ErrorNoSuchIndexName,
+            format!("No index '{}' is defined in space {}", index, space),
+          ))?,
+        None => 0,
+      };
+
+      let keys = match args.first() {
+        Some(Value::Array(keys)) => keys.clone(),
+        _ => Vec::new(),
+      };
+      let iterator = match args.get(1) {
+        Some(Value::Map(opts)) => opts.iter()
+          .find(|(key, _)| matches!(key, Value::Str(key) if key == "iterator"))
+          .and_then(|(_, iterator)| match iterator {
+            Value::UInt(iterator) => Iterator::from_u64(*iterator),
+            _ => None,
+          })
+          .ok_or_else(|| error(Code::ErrorIllegalParams, "Illegal parameters, invalid iterator"))?,
+        _ => Iterator::Eq,
+      };
+
+      let found = self.with_space_mut(space_id, |space| space.select(index_id, iterator, &keys))?;
+      Ok(vec![ (found.len() as u64).into() ])
+    };
+
+    Some(count())
+  }
+
+  fn handle(handlers: &HashMap<String, Handler>, name: &str, args: Vec<Value>) -> Result<Vec<Value>, Error> {
+    match handlers.get(name) {
+      Some(handler) => handler(args),
+      None => Err(error(
+        Code::ErrorNoSuchProc,
+        format!("Procedure '{}' is not defined", name),
+      )),
+    }
+  }
+}
+
+#[async_trait]
+impl TarantoolClient for FakeClient {
+  async fn select<T>(&self, body: Select) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    decode(self.select_tuples(&body)?)
+  }
+
+  async fn select_page<T>(&self, body: PagedSelect) -> Result<Page<T>, Error>
+    where T: DeserializeOwned
+  {
+    let (tuples, position) = self.select_page_tuples(&body)?;
+    Ok(Page { tuples: decode(tuples)?, position })
+  }
+
+  async fn insert<T>(&self, body: Insert) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    decode(self.store(body.space_id, body.tuple, false)?)
+  }
+
+  async fn replace<T>(&self, body: Replace) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    decode(self.store(body.space_id, body.tuple, true)?)
+  }
+
+  async fn update<T>(&self, body: Update) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    let updated = self.with_space_mut(body.space_id, |space| {
+      let key = match space.find_unique(body.index_id, &body.key)? {
+        Some(key) => key,
+        None => return Ok(Vec::new()),
+      };
+
+      let mut tuple = space.tuples[&key].clone();
+      apply_ops(&mut tuple, &body.tuple, body.index_base)?;
+
+      if space.primary_key(&tuple)? != key {
+        return Err(error(
+          Code::ErrorCantUpdatePrimaryKey,
+          "Attempt to modify a tuple field which is part of primary index",
+        ));
+      }
+
+      space.tuples.insert(key, tuple.clone());
+      Ok(vec![ Value::Array(tuple) ])
+    })?;
+
+    decode(updated)
+  }
+
+  async fn delete<T>(&self, body: Delete) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    let deleted = self.with_space_mut(body.space_id, |space| {
+      Ok(space.find_unique(body.index_id, &body.key)?
+        .and_then(|key| space.tuples.remove(&key))
+        .map(|tuple| vec![ Value::Array(tuple) ])
+        .unwrap_or_default())
+    })?;
+
+    decode(deleted)
+  }
+
+  async fn upsert(&self, body: Upsert) -> Result<(), Error> {
+    self.with_space_mut(body.space_id, |space| {
+      let key = space.primary_key(&body.tuple)?;
+
+      let tuple = match space.tuples.get(&key) {
+        Some(tuple) => {
+          let mut tuple = tuple.clone();
+          apply_ops(&mut tuple, &body.ops, body.index_base)?;
+          tuple
+        },
+        None => body.tuple,
+      };
+
+      space.tuples.insert(key, tuple);
+      Ok(())
+    })
+  }
+
+  async fn call<T>(&self, body: Call) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    if !self.functions.contains_key(&body.function) {
+      if let Some(result) = self.count(&body.function, &body.args) {
+        return decode(result?);
+      }
+    }
+
+    decode(Self::handle(&self.functions, &body.function, body.args)?)
+  }
+
+  async fn eval<T>(&self, body: Eval) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    decode(Self::handle(&self.evals, &body.expr, body.args)?)
+  }
+
+  async fn execute(&self, _body: Execute) -> Result<SQLBody, Error> {
+    Err(error(Code::ErrorUnsupported, "sql is not supported by FakeClient"))
+  }
+
+  async fn space_id(&self, name: &str) -> Result<u64, Error> {
+    self.space_names.get(name).copied()
+      .ok_or_else(|| error(
+        Code::ErrorNoSuchSpace,
+        format!("Space '{}' does not exist", name),
+      ))
+  }
+
+  async fn index_id(&self, space_id: u64, name: &str) -> Result<u64, Error> {
+    self.index_names.get(&(space_id, name.into())).copied()
+      .ok_or_else(|| error(
+        Code::ErrorNoSuchIndexName,
+        format!("No index '{}' is defined in space {}", name, space_id),
+      ))
+  }
+
+  async fn execute_select<T>(&self, _body: Execute) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    Err(error(Code::ErrorUnsupported, "sql is not supported by FakeClient"))
+  }
+}
+
+impl FakeSpace {
+  fn key_fields(&self, index_id: u64) -> Result<&Vec<usize>, Error> {
+    self.indexes.get(&index_id)
+      .ok_or_else(|| error(
+        Code::ErrorNoSuchIndexID,
+        format!("No index #{} is defined in space", index_id),
+      ))
+  }
+
+  fn tuple_key(fields: &[usize], tuple: &[Value]) -> Result<Vec<KeyPart>, Error> {
+    fields.iter()
+      .map(|&field| match tuple.get(field) {
+        Some(value) => KeyPart::from_value(value),
+        None => Err(error(
+          Code::ErrorFieldMissing,
+          format!("Tuple field {} required by space format is missing", field + 1),
+        )),
+      })
+      .collect()
+  }
+
+  fn primary_key(&self, tuple: &[Value]) -> Result<Vec<KeyPart>, Error> {
+    Self::tuple_key(self.key_fields(0)?, tuple)
+  }
+
+  /// returns tuples of index in iterator order
+  fn select(&self, index_id: u64, iterator: Iterator, key: &[Value]) -> Result<Vec<Vec<Value>>, Error> {
+    let fields = self.key_fields(index_id)?;
+
+    if key.len() > fields.len() {
+      return Err(error(
+        Code::ErrorKeyPartCount,
+        format!("Invalid key part count (expected [0..{}], got {})", fields.len(), key.len()),
+      ));
+    }
+
+    let key: Vec<KeyPart> = key.iter()
+      .map(KeyPart::from_value)
+      .collect::<Result<_, _>>()?;
+
+    let mut tuples: Vec<(Vec<KeyPart>, &Vec<Value>)> = self.tuples.values()
+      .map(|tuple| Ok((Self::tuple_key(fields, tuple)?, tuple)))
+      .collect::<Result<_, Error>>()?;
+
+    // sort is stable, so equal secondary keys stay in primary key order
+    tuples.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let matches = |tuple_key: &[KeyPart]| -> bool {
+      let prefix = &tuple_key[..key.len()];
+      match iterator {
+        Iterator::Eq | Iterator::Req => prefix == key.as_slice(),
+        Iterator::All => true,
+        Iterator::Lt => key.is_empty() || prefix < key.as_slice(),
+        Iterator::Le => prefix <= key.as_slice(),
+        Iterator::Ge => prefix >= key.as_slice(),
+        Iterator::Gt => key.is_empty() || prefix > key.as_slice(),
+        _ => false,
+      }
+    };
+
+    let reverse = match iterator {
+      Iterator::Eq | Iterator::All | Iterator::Ge | Iterator::Gt => false,
+      Iterator::Req | Iterator::Lt | Iterator::Le => true,
+      iterator => return Err(error(
+        Code::ErrorUnsupported,
+        format!("FakeClient does not support iterator {:?}", iterator),
+      )),
+    };
+
+    let mut found: Vec<Vec<Value>> = tuples.into_iter()
+      .filter(|(tuple_key, _)| matches(tuple_key))
+      .map(|(_, tuple)| tuple.clone())
+      .collect();
+
+    if reverse { found.reverse(); }
+
+    Ok(found)
+  }
+
+  /// finds primary key of tuple by full key of index
+  fn find_unique(&self, index_id: u64, key: &[Value]) -> Result<Option<Vec<KeyPart>>, Error> {
+    let fields = self.key_fields(index_id)?;
+
+    if key.len() != fields.len() {
+      return Err(error(
+        Code::ErrorExactMatch,
+        format!("Invalid key part count in an exact match (expected {}, got {})", fields.len(), key.len()),
+      ));
+    }
+
+    match self.select(index_id, Iterator::Eq, key)?.first() {
+      Some(tuple) => Ok(Some(self.primary_key(tuple)?)),
+      None => Ok(None),
+    }
+  }
+}
+
+/// comparable representation of key part
+#[derive(Debug, Clone)]
+enum KeyPart {
+  Null,
+  Bool(bool),
+  Int(i128),
+  Float(f64),
+  Str(String),
+  Bin(Vec<u8>),
+}
+
+impl KeyPart {
+  fn from_value(value: &Value) -> Result<KeyPart, Error> {
+    Ok(match value {
+      Value::Null => KeyPart::Null,
+      &Value::Bool(v) => KeyPart::Bool(v),
+      &Value::Int(v) => KeyPart::Int(v as i128),
+      &Value::UInt(v) => KeyPart::Int(v as i128),
+      &Value::F32(v) => KeyPart::Float(v as f64),
+      &Value::F64(v) => KeyPart::Float(v),
+      Value::Decimal(v) => KeyPart::Float(v.to_f64().unwrap_or(f64::NAN)),
+      Value::DateTime(v) => KeyPart::Int(
+        v.and_utc().timestamp() as i128 * 1_000_000_000 + v.and_utc().timestamp_subsec_nanos() as i128
+      ),
+      Value::DateTimeTz(v) => KeyPart::Int(
+        v.timestamp() as i128 * 1_000_000_000 + v.timestamp_subsec_nanos() as i128
+      ),
+      Value::Str(v) => KeyPart::Str(v.clone()),
+      Value::Bin(v) => KeyPart::Bin(v.clone()),
+      Value::Uuid(v) => KeyPart::Bin(v.as_bytes().to_vec()),
+      value => return Err(error(
+        Code::ErrorKeyPartType,
+        format!("{:?} can't be used as key part", value),
+      )),
+    })
+  }
+
+  fn rank(&self) -> u8 {
+    match self {
+      KeyPart::Null => 0,
+      KeyPart::Bool(_) => 1,
+      KeyPart::Int(_) | KeyPart::Float(_) => 2,
+      KeyPart::Str(_) => 3,
+      KeyPart::Bin(_) => 4,
+    }
+  }
+}
+
+impl Ord for KeyPart {
+  fn cmp(&self, other: &Self) -> Ordering {
+    match (self, other) {
+      (KeyPart::Bool(a), KeyPart::Bool(b)) => a.cmp(b),
+      (KeyPart::Int(a), KeyPart::Int(b)) => a.cmp(b),
+      (KeyPart::Float(a), KeyPart::Float(b)) => a.total_cmp(b),
+      (&KeyPart::Int(a), KeyPart::Float(b)) => (a as f64).total_cmp(b),
+      (KeyPart::Float(a), &KeyPart::Int(b)) => a.total_cmp(&(b as f64)),
+      (KeyPart::Str(a), KeyPart::Str(b)) => a.cmp(b),
+      (KeyPart::Bin(a), KeyPart::Bin(b)) => a.cmp(b),
+      (a, b) => a.rank().cmp(&b.rank()),
+    }
+  }
+}
+
+impl PartialOrd for KeyPart {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl PartialEq for KeyPart {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl Eq for KeyPart {}
+
+fn error<S: Into<String>>(code: Code, message: S) -> Error {
+  Error::TarantoolError(code, TarantoolError::new(message))
+}
+
+fn decode<T: DeserializeOwned>(data: Vec<Value>) -> Result<T, Error> {
+  let mut buf: Vec<u8> = Vec::new();
+  Value::Array(data).pack(&mut buf)?;
+  rmp_serde::from_slice(&buf).map_err(Error::ParseError)
+}
+
+fn apply_ops(tuple: &mut Vec<Value>, ops: &[Vec<Value>], index_base: u64) -> Result<(), Error> {
+  let bad_op = |op: &Vec<Value>| error(
+    Code::ErrorIllegalParams,
+    format!("Illegal parameters, bad update operation {:?}", op),
+  );
+
+  for op in ops.iter() {
+    let (name, field) = match (op.first(), op.get(1)) {
+      (Some(Value::Str(name)), Some(&Value::Int(field))) => (name.as_str(), field),
+      (Some(Value::Str(name)), Some(&Value::UInt(field))) => (name.as_str(), field as i64),
+      _ => return Err(bad_op(op)),
+    };
+
+    let field = match field < 0 {
+      true => tuple.len() as i64 + field,
+      false => field - index_base as i64,
+    };
+
+    if field < 0 || field as usize > tuple.len() {
+      return Err(error(Code::ErrorNoSuchFieldNo, format!("Field {} was not found in the tuple", field)));
+    }
+    let field = field as usize;
+
+    let arg = op.get(2).cloned().ok_or_else(|| bad_op(op))?;
+
+    match name {
+      "=" if field == tuple.len() => tuple.push(arg),
+      "=" => tuple[field] = arg,
+      "!" => tuple.insert(field, arg),
+      "#" => {
+        let count = match arg {
+          Value::UInt(count) => count as usize,
+          Value::Int(count) if count > 0 => count as usize,
+          _ => return Err(bad_op(op)),
+        };
+        let end = (field + count).min(tuple.len());
+        tuple.drain(field..end);
+      },
+      "+" | "-" | "&" | "|" | "^" => {
+        let current = tuple.get(field).ok_or_else(|| bad_op(op))?;
+        tuple[field] = arith(name, current, &arg).ok_or_else(|| error(
+          Code::ErrorUpdateArgType,
+          format!("Argument type in operation '{}' on field {} does not match field type", name, field),
+        ))?;
+      },
+      ":" => {
+        let current = match tuple.get(field) {
+          Some(Value::Str(current)) => current.clone(),
+          _ => return Err(bad_op(op)),
+        };
+        let (offset, length, replacement) = match (op.get(2), op.get(3), op.get(4)) {
+          (Some(offset), Some(length), Some(Value::Str(replacement))) =>
+            (as_i64(offset).ok_or_else(|| bad_op(op))?, as_i64(length).ok_or_else(|| bad_op(op))?, replacement),
+          _ => return Err(bad_op(op)),
+        };
+
+        let chars: Vec<char> = current.chars().collect();
+        let start = match offset < 0 {
+          true => (chars.len() as i64 + offset + 1).max(0) as usize,
+          false => ((offset - index_base as i64).max(0) as usize).min(chars.len()),
+        };
+        let end = match length < 0 {
+          true => ((chars.len() as i64 + length).max(start as i64)) as usize,
+          false => (start + length as usize).min(chars.len()),
+        };
+
+        let mut spliced: String = chars[..start].iter().collect();
+        spliced.push_str(replacement);
+        spliced.extend(chars[end..].iter());
+        tuple[field] = Value::Str(spliced);
+      },
+      _ => return Err(error(Code::ErrorUnknownUpdateOp, format!("Unknown UPDATE operation '{}'", name))),
+    }
+  }
+
+  Ok(())
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+  match *value {
+    Value::Int(v) => Some(v),
+    Value::UInt(v) => Some(v as i64),
+    _ => None,
+  }
+}
+
+fn arith(op: &str, a: &Value, b: &Value) -> Option<Value> {
+  match (a, b) {
+    (&Value::UInt(a), &Value::UInt(b)) => match op {
+      "+" => a.checked_add(b).map(Value::UInt),
+      "-" => match a.checked_sub(b) {
+        Some(v) => Some(Value::UInt(v)),
+        None => (a as i64).checked_sub(b as i64).map(Value::Int),
+      },
+      "&" => Some(Value::UInt(a & b)),
+      "|" => Some(Value::UInt(a | b)),
+      "^" => Some(Value::UInt(a ^ b)),
+      _ => None,
+    },
+    (Value::Int(_), _) | (_, Value::Int(_)) => {
+      let (a, b) = (as_i64(a)?, as_i64(b)?);
+      match op {
+        "+" => a.checked_add(b).map(Value::Int),
+        "-" => a.checked_sub(b).map(Value::Int),
+        _ => None,
+      }
+    },
+    (Value::Decimal(a), Value::Decimal(b)) => match op {
+      "+" => a.checked_add(*b).map(Value::Decimal),
+      "-" => a.checked_sub(*b).map(Value::Decimal),
+      _ => None,
+    },
+    _ => {
+      let to_f64 = |v: &Value| match *v {
+        Value::F32(v) => Some(v as f64),
+        Value::F64(v) => Some(v),
+        Value::UInt(v) => Some(v as f64),
+        _ => None,
+      };
+      let (a, b) = (to_f64(a)?, to_f64(b)?);
+      match op {
+        "+" => Some(Value::F64(a + b)),
+        "-" => Some(Value::F64(a - b)),
+        _ => None,
+      }
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{IntoTuple, NoKey, space::SelectOptions};
+
+  use super::*;
+
+  fn client() -> FakeClient {
+    FakeClient::new()
+      .with_space(512, vec![ 0 ])
+      .with_index(512, 1, vec![ 1 ])
+      .with_function("sum", |args| {
+        let sum: u64 = args.iter().filter_map(as_i64).sum::<i64>() as u64;
+        Ok(vec![ Value::UInt(sum) ])
+      })
+  }
+
+  #[tokio::test]
+  async fn test_fake_crud() {
+    let client = client();
+
+    for (id, name) in [ (3u64, "c"), (1, "a"), (2, "b") ].iter() {
+      let _: Vec<(u64, String)> = client.insert(Insert {
+        space_id: 512, tuple: ( *id, *name ).into_tuple(),
+      }).await.unwrap();
+    }
+
+    let err = client.insert::<Vec<(u64, String)>>(Insert {
+      space_id: 512, tuple: ( 1u64, "x" ).into_tuple(),
+    }).await.unwrap_err();
+    assert!(matches!(err, Error::TarantoolError(Code::ErrorTupleFound, _)));
+
+    let all: Vec<(u64, String)> = client.select(Select {
+      space_id: 512, index_id: 0,
+      limit: 100, offset: 0,
+      iterator: Iterator::All,
+      keys: Vec::new(),
+    }).await.unwrap();
+    assert_eq!(all.iter().map(|t| t.0).collect::<Vec<_>>(), vec![ 1, 2, 3 ]);
+
+    let last: Vec<(u64, String)> = client.select(Select::last(512, 0, 2)).await.unwrap();
+    assert_eq!(last.iter().map(|t| t.0).collect::<Vec<_>>(), vec![ 3, 2 ]);
+
+    let by_name: Option<(u64, String)> = client.get(512, 1, vec![ "b".into() ]).await.unwrap();
+    assert_eq!(by_name, Some((2, "b".into())));
+
+    let updated: Vec<(u64, String)> = client.update(Update {
+      space_id: 512, index_id: 0, index_base: 0,
+      key: vec![ Value::UInt(2) ],
+      tuple: vec![ ( "=", 1u64, "bb" ).into_tuple() ],
+    }).await.unwrap();
+    assert_eq!(updated, vec![ (2, "bb".into()) ]);
+
+    let deleted: Vec<(u64, String)> = client.delete(Delete {
+      space_id: 512, index_id: 0,
+      key: vec![ Value::UInt(3) ],
+    }).await.unwrap();
+    assert_eq!(deleted, vec![ (3, "c".into()) ]);
+    assert_eq!(client.tuples(512).len(), 2);
+
+    client.upsert(Upsert {
+      space_id: 512, index_base: 0,
+      tuple: ( 1u64, "a" ).into_tuple(),
+      ops: vec![ ( ":", 1u64, -1, 0, "!" ).into_tuple() ],
+    }).await.unwrap();
+    let upserted: Option<(u64, String)> = client.get(512, 0, vec![ 1u64.into() ]).await.unwrap();
+    assert_eq!(upserted, Some((1, "a!".into())));
+  }
+
+  #[tokio::test]
+  async fn test_fake_call() {
+    let client = client();
+
+    let (sum,): (u64,) = client.call(Call {
+      function: "sum".into(),
+      args: ( 1u64, 2u64, 3u64 ).into_tuple(),
+    }).await.unwrap();
+    assert_eq!(sum, 6);
+
+    let err = client.call::<()>(Call {
+      function: "missing".into(),
+      args: Vec::new(),
+    }).await.unwrap_err();
+    assert!(matches!(err, Error::TarantoolError(Code::ErrorNoSuchProc, _)));
+  }
+
+  #[tokio::test]
+  async fn test_fake_call_typed() {
+    let client = client()
+      .with_eval("return ...", Ok);
+
+    let (sum,): (u64,) = client.call_typed("sum", ( 1u64, 2u64 )).await.unwrap();
+    assert_eq!(sum, 3);
+
+    let (name, id): (String, u64) = client.eval_typed("return ...", ( "a", 1u64 )).await.unwrap();
+    assert_eq!((name.as_str(), id), ("a", 1));
+
+    let err = client.call_typed::<(), _>("missing", Vec::<Value>::new()).await.unwrap_err();
+    assert!(matches!(err, Error::TarantoolError(Code::ErrorNoSuchProc, _)));
+  }
+
+  #[tokio::test]
+  async fn test_fake_typed_space_operations() {
+    let client = client();
+
+    let inserted: Vec<(u64, String, u64)> = client.insert_typed(512, ( 1u64, "ann", 30u64 )).await.unwrap();
+    assert_eq!(inserted, vec![ (1, "ann".into(), 30) ]);
+    client.insert_typed::<(u64, String, u64), _>(512, ( 2u64, "bob", 20u64 )).await.unwrap();
+
+    let users: Vec<(u64, String, u64)> = client
+      .select_typed(512, 0, NoKey, SelectOptions::new().with_iterator(Iterator::All))
+      .await.unwrap();
+    assert_eq!(users.len(), 2);
+
+    let users: Vec<(u64, String, u64)> = client
+      .select_typed(512, 1, "bob", SelectOptions::new())
+      .await.unwrap();
+    assert_eq!(users, vec![ (2, "bob".into(), 20) ]);
+
+    let updated: Vec<(u64, String, u64)> = client
+      .update_typed(512, 0, 1u64, vec![ vec![ Value::from("+"), Value::UInt(2), Value::UInt(1) ] ])
+      .await.unwrap();
+    assert_eq!(updated, vec![ (1, "ann".into(), 31) ]);
+
+    let missing: Vec<(u64, String, u64)> = client
+      .update_typed(512, 0, 3u64, vec![ vec![ Value::from("+"), Value::UInt(2), Value::UInt(1) ] ])
+      .await.unwrap();
+    assert!(missing.is_empty());
+
+    client.upsert_typed(512, ( 3u64, "cat", 1u64 ), vec![ vec![ Value::from("+"), Value::UInt(2), Value::UInt(1) ] ])
+      .await.unwrap();
+    client.upsert_typed(512, ( 3u64, "cat", 1u64 ), vec![ vec![ Value::from("+"), Value::UInt(2), Value::UInt(1) ] ])
+      .await.unwrap();
+
+    let deleted: Vec<(u64, String, u64)> = client.delete_typed(512, 0, 3u64).await.unwrap();
+    assert_eq!(deleted, vec![ (3, "cat".into(), 2) ]);
+    assert!(client.delete_typed::<(u64, String, u64), _>(512, 0, 3u64).await.unwrap().is_empty());
+  }
+}
@@ -0,0 +1,237 @@
+/*!
+  This module contains management of space triggers.
+
+  Triggers are made from lua chunks which return trigger function,
+  they are installed with eval and registered by names in global lua table,
+  so installing trigger with the same name again replaces it.
+
+  Triggers are not persisted by tarantool,
+  so they should be installed on every start of application.
+
+  Example:
+  ```rust,no_run
+    # use alopecosa::{Connection, Error, TarantoolClient, TriggerKind};
+    # async fn f(conn: &Connection) -> Result<(), Error> {
+    let triggers = conn.triggers("users");
+    triggers.install(TriggerKind::BeforeReplace, "touch", r#"
+      return function(old, new)
+        if new ~= nil then return new:update({{ '=', 'updated_at', os.time() }}) end
+      end
+    "#).await?;
+
+    assert_eq!(triggers.list(TriggerKind::BeforeReplace).await?, vec![ "touch" ]);
+    # Ok(()) }
+  ```
+*/
+
+use crate::{
+  client::TarantoolClient,
+  iproto::{request::Eval, types::Error},
+};
+
+/// finds space and triggers installed by this module, they are registered by "space:kind" keys and names
+macro_rules! trigger_expr {
+  ($body:literal) => {
+    concat!(
+      "local space_name, kind, name, chunk = ... ",
+      "local space = box.space[space_name] ",
+      "if space == nil then box.error(box.error.NO_SUCH_SPACE, space_name) end ",
+      "local registry = rawget(_G, '_alopecosa_triggers') or {} ",
+      "rawset(_G, '_alopecosa_triggers', registry) ",
+      "local key = space_name .. ':' .. kind ",
+      "registry[key] = registry[key] or {} ",
+      "local installed = registry[key] ",
+      // triggers of dropped and created again space are gone
+      "for trigger_name, trigger in pairs(installed) do ",
+      "  local found = false ",
+      "  for _, t in ipairs(space[kind](space)) do found = found or t == trigger end ",
+      "  if not found then installed[trigger_name] = nil end ",
+      "end ",
+      $body,
+    )
+  };
+}
+
+pub(crate) const INSTALL_EXPR: &str = trigger_expr!(
+  "local make, err = load(chunk, '=' .. name) \
+   if make == nil then error(err) end \
+   local trigger = make() \
+   if type(trigger) ~= 'function' then error('trigger chunk must return function') end \
+   local old = installed[name] \
+   space[kind](space, trigger, old) \
+   installed[name] = trigger \
+   return old ~= nil"
+);
+pub(crate) const REMOVE_EXPR: &str = trigger_expr!(
+  "local old = installed[name] \
+   if old == nil then return false end \
+   space[kind](space, nil, old) \
+   installed[name] = nil \
+   return true"
+);
+pub(crate) const LIST_EXPR: &str = trigger_expr!(
+  "local names = {} \
+   for trigger_name in pairs(installed) do table.insert(names, trigger_name) end \
+   table.sort(names) \
+   return names"
+);
+
+/// Kind of space trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerKind {
+  OnReplace,
+  BeforeReplace,
+}
+
+impl TriggerKind {
+  /// name of space method which sets trigger
+  pub fn method(&self) -> &'static str {
+    match self {
+      TriggerKind::OnReplace => "on_replace",
+      TriggerKind::BeforeReplace => "before_replace",
+    }
+  }
+}
+
+/// This is handle of triggers of space, see TarantoolClient::triggers.
+#[derive(Debug)]
+pub struct Triggers<'c, C> {
+  client: &'c C,
+  space: String,
+}
+
+impl<'c, C> Triggers<'c, C>
+  where C: TarantoolClient
+{
+  pub(crate) fn new(client: &'c C, space: &str) -> Triggers<'c, C> {
+    Triggers { client, space: space.into() }
+  }
+
+  /**
+    installs trigger made by lua chunk which returns trigger function,
+    it returns true if trigger with the same name was replaced
+  */
+  pub async fn install(&self, kind: TriggerKind, name: &str, chunk: &str) -> Result<bool, Error> {
+    let (replaced,): (bool,) = self.eval(INSTALL_EXPR, kind, name, chunk).await?;
+    Ok(replaced)
+  }
+
+  /// removes trigger, it returns false if there is no such trigger
+  pub async fn remove(&self, kind: TriggerKind, name: &str) -> Result<bool, Error> {
+    let (removed,): (bool,) = self.eval(REMOVE_EXPR, kind, name, "").await?;
+    Ok(removed)
+  }
+
+  /// sorted names of triggers installed with this module
+  pub async fn list(&self, kind: TriggerKind) -> Result<Vec<String>, Error> {
+    let (names,): (Vec<String>,) = self.eval(LIST_EXPR, kind, "", "").await?;
+    Ok(names)
+  }
+
+  async fn eval<T>(&self, expr: &str, kind: TriggerKind, name: &str, chunk: &str) -> Result<T, Error>
+    where T: serde::de::DeserializeOwned
+  {
+    self.client.eval(Eval {
+      expr: expr.into(),
+      args: vec![
+        self.space.as_str().into(), kind.method().into(),
+        name.into(), chunk.into(),
+      ],
+    }).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::BTreeMap, sync::{Arc, Mutex}};
+
+  use serde::de::IgnoredAny;
+
+  use crate::{
+    connection::{Connection, connector::Connector},
+    iproto::request::{Replace, Value},
+    testing::FakeClient,
+  };
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_triggers() {
+    let installed: Arc<Mutex<BTreeMap<String, String>>> = Default::default();
+    let key = |args: &[Value]| match (&args[0], &args[1], &args[2]) {
+      (Value::Str(space), Value::Str(kind), Value::Str(name)) => (format!("{}:{}", space, kind), name.clone()),
+      _ => unreachable!(),
+    };
+
+    let client = {
+      let (install, remove, list) = (installed.clone(), installed.clone(), installed.clone());
+      FakeClient::new()
+        .with_eval(INSTALL_EXPR, move |args| {
+          let (key, name) = key(&args);
+          let replaced = install.lock().unwrap().insert(format!("{}/{}", key, name), key).is_some();
+          Ok(vec![ replaced.into() ])
+        })
+        .with_eval(REMOVE_EXPR, move |args| {
+          let (key, name) = key(&args);
+          Ok(vec![ remove.lock().unwrap().remove(&format!("{}/{}", key, name)).is_some().into() ])
+        })
+        .with_eval(LIST_EXPR, move |args| {
+          let (key, _) = key(&args);
+          let names: Vec<Value> = list.lock().unwrap().iter()
+            .filter(|(_, k)| **k == key)
+            .map(|(full, _)| full.rsplit('/').next().unwrap().into())
+            .collect();
+          Ok(vec![ Value::Array(names) ])
+        })
+    };
+
+    let triggers = client.triggers("users");
+    let chunk = "return function(old, new) end";
+    assert!(!triggers.install(TriggerKind::OnReplace, "audit", chunk).await.unwrap());
+    assert!(triggers.install(TriggerKind::OnReplace, "audit", chunk).await.unwrap());
+    assert!(!triggers.install(TriggerKind::BeforeReplace, "touch", chunk).await.unwrap());
+
+    assert_eq!(triggers.list(TriggerKind::OnReplace).await.unwrap(), vec![ "audit" ]);
+    assert!(triggers.remove(TriggerKind::OnReplace, "audit").await.unwrap());
+    assert!(!triggers.remove(TriggerKind::OnReplace, "audit").await.unwrap());
+    assert!(triggers.list(TriggerKind::OnReplace).await.unwrap().is_empty());
+    assert_eq!(triggers.list(TriggerKind::BeforeReplace).await.unwrap(), vec![ "touch" ]);
+  }
+
+  #[tokio::test]
+  async fn test_tnt_triggers() {
+    let addr = "127.0.0.1:3301".parse().unwrap();
+    let conn: Arc<Connection> = Connector::new(addr)
+      .connect().await.unwrap();
+
+    let (space_id,): (u64,) = conn.eval(Eval {
+      expr: "local space = box.schema.space.create(..., { if_not_exists = true, temporary = true }) \
+             space:create_index('primary', { if_not_exists = true }) \
+             return space.id".into(),
+      args: vec![ "alopecosa_triggers".into() ],
+    }).await.unwrap();
+
+    let triggers = conn.triggers("alopecosa_triggers");
+    let touch = "return function(old, new) return new:update({{ '=', 2, 'touched' }}) end";
+    assert!(!triggers.install(TriggerKind::BeforeReplace, "touch", touch).await.unwrap());
+    assert!(triggers.install(TriggerKind::BeforeReplace, "touch", touch).await.unwrap());
+    assert_eq!(triggers.list(TriggerKind::BeforeReplace).await.unwrap(), vec![ "touch" ]);
+
+    let replace = |value: &str| Replace { space_id, tuple: vec![ 1u64.into(), value.into() ] };
+    let (tuple,): ((u64, String),) = conn.replace(replace("plain")).await.unwrap();
+    assert_eq!(tuple, (1, "touched".into()));
+
+    assert!(triggers.remove(TriggerKind::BeforeReplace, "touch").await.unwrap());
+    assert!(triggers.list(TriggerKind::BeforeReplace).await.unwrap().is_empty());
+    let (tuple,): ((u64, String),) = conn.replace(replace("plain")).await.unwrap();
+    assert_eq!(tuple, (1, "plain".into()));
+
+    let err = triggers.install(TriggerKind::OnReplace, "broken", "return function(").await.unwrap_err();
+    assert!(err.to_string().contains("broken"));
+
+    let _: Vec<IgnoredAny> = conn.eval(Eval {
+      expr: "box.space[...]:drop()".into(),
+      args: vec![ "alopecosa_triggers".into() ],
+    }).await.unwrap();
+  }
+}
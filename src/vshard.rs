@@ -0,0 +1,482 @@
+/*!
+  This module contains routing of calls across vshard cluster.
+
+  Bucket of key is computed like `vshard.router.bucket_id_strcrc32` does,
+  buckets are mapped to replica sets by discovery from storages or by static config,
+  calls are made with `vshard.storage.call`, so storages check bucket on their side.
+
+  Example:
+  ```rust,no_run
+    # use std::sync::Arc;
+    # use alopecosa::{Connection, Connector, IntoTuple, ReplicaSet, Sharded, vshard};
+    # async fn f(router: &Connection, customer_id: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let uris = vshard::replicaset_uris(router).await?;
+    let mut sharded = Sharded::new(3000);
+    for (uuid, uris) in uris {
+      let mut connectors = uris.iter()
+        .map(|uri| Connector::from_url(&format!("tarantool://{}", uri)))
+        .collect::<Result<Vec<_>, _>>()?;
+      let master = connectors.remove(0).connect().await?;
+      let mut rs = ReplicaSet::new(master);
+      for connector in connectors {
+        rs = rs.with_replica(connector.connect().await?);
+      }
+      sharded = sharded.with_replicaset(&uuid, rs);
+    }
+    sharded.discover().await?;
+
+    let bucket_id = sharded.bucket_id(customer_id)?;
+    let (balance,): (u64,) = sharded.call_ro(bucket_id, "customer_balance", (customer_id,).into_tuple()).await?;
+    # Ok(()) }
+  ```
+*/
+
+use std::{
+  collections::HashMap,
+  fmt,
+  marker::PhantomData,
+  ops::RangeInclusive,
+  sync::RwLock,
+};
+
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Deserializer, de::{self, DeserializeOwned, SeqAccess, Visitor, value::SeqAccessDeserializer}};
+
+use crate::{
+  client::TarantoolClient,
+  iproto::{
+    constants::{Code, ERROR_BITMASK},
+    request::{Call, Eval, IntoKey, Value},
+    response::TarantoolError,
+    types::Error,
+  },
+  replicaset::{ReadPreference, ReplicaSet},
+};
+
+/// uris of instances of every replica set known to router, master goes first
+const REPLICASET_URIS_EXPR: &str = r#"
+  local result = {}
+  for uuid, rs in pairs(vshard.router.static.replicasets) do
+    local uris = {}
+    if rs.master ~= nil then table.insert(uris, rs.master.uri) end
+    for _, replica in pairs(rs.replicas) do
+      if replica ~= rs.master then table.insert(uris, replica.uri) end
+    end
+    result[uuid] = uris
+  end
+  return result
+"#;
+
+const STORAGE_CALL: &str = "vshard.storage.call";
+const BUCKETS_DISCOVERY: &str = "vshard.storage.buckets_discovery";
+
+/// crc32 of tarantool digest module, it is crc32c without final xor
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+  for byte in data {
+    crc ^= *byte as u32;
+    for _ in 0..8 {
+      crc = match crc & 1 {
+        1 => (crc >> 1) ^ 0x82f6_3b78,
+        _ => crc >> 1,
+      };
+    }
+  }
+  crc
+}
+
+/// formats number like lua tostring does, it is %.14g
+fn lua_number(value: f64) -> String {
+  if value.is_nan() {
+    return "nan".into();
+  }
+  if value.is_infinite() {
+    return match value > 0.0 { true => "inf".into(), false => "-inf".into() };
+  }
+  if value == 0.0 {
+    return match value.is_sign_negative() { true => "-0".into(), false => "0".into() };
+  }
+
+  let scientific = format!("{:.13e}", value);
+  let (mantissa, exp) = scientific.split_once('e').expect("scientific format has exponent");
+  let exp: i32 = exp.parse().expect("exponent is number");
+
+  let trim = |number: &str| -> String {
+    match number.contains('.') {
+      true => number.trim_end_matches('0').trim_end_matches('.').into(),
+      false => number.into(),
+    }
+  };
+
+  match !(-4..14).contains(&exp) {
+    true => format!("{}e{}{:02}", trim(mantissa), if exp < 0 { '-' } else { '+' }, exp.abs()),
+    false => trim(&format!("{:.*}", (13 - exp) as usize, value)),
+  }
+}
+
+/// tostring of key part as tarantool decodes it from msgpack
+fn lua_tostring(value: &Value) -> Result<String, Error> {
+  // integers up to 2^53 are decoded as lua numbers and formatted by %.14g, others as cdata
+  const MAX_NUMBER: u64 = 1 << 53;
+  Ok(match value {
+    Value::Str(value) => value.clone(),
+    Value::Bool(value) => value.to_string(),
+    Value::UInt(value) if *value <= MAX_NUMBER => lua_number(*value as f64),
+    Value::UInt(value) => format!("{}ULL", value),
+    Value::Int(value) if *value >= 0 => return lua_tostring(&Value::UInt(*value as u64)),
+    Value::Int(value) if value.unsigned_abs() <= MAX_NUMBER => lua_number(*value as f64),
+    Value::Int(value) => format!("{}LL", value),
+    Value::F32(value) => lua_number(*value as f64),
+    Value::F64(value) => lua_number(*value),
+    Value::Uuid(value) => value.to_string(),
+    value => return Err(Error::InvalidKey(format!("sharding key part {:?} is not supported", value))),
+  })
+}
+
+/**
+  computes bucket of key like vshard.router.bucket_id_strcrc32,
+  parts of key are concatenated as lua tostring makes them
+*/
+pub fn bucket_id_strcrc32(key: &[Value], bucket_count: u64) -> Result<u64, Error> {
+  let mut crc = u32::MAX;
+  for part in key.iter() {
+    crc = crc32_update(crc, lua_tostring(part)?.as_bytes());
+  }
+  Ok(crc as u64 % bucket_count + 1)
+}
+
+/// asks router about uris of instances of every replica set, master goes first
+pub async fn replicaset_uris<C>(router: &C) -> Result<HashMap<String, Vec<String>>, Error>
+  where C: TarantoolClient
+{
+  let (uris,): (HashMap<String, Vec<String>>,) = router.eval(Eval {
+    expr: REPLICASET_URIS_EXPR.into(),
+    args: Vec::new(),
+  }).await?;
+  Ok(uris)
+}
+
+/// page of buckets_discovery, vshard before 0.1.17 returns just list of buckets
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Discovery {
+  Page { buckets: Vec<u64>, next_from: Option<u64> },
+  List(Vec<u64>),
+}
+
+/// error returned by vshard.storage.call
+#[derive(Debug, Deserialize)]
+struct StorageError {
+  #[serde(rename = "type", default)]
+  kind: Option<String>,
+  #[serde(default)]
+  code: Option<u64>,
+  #[serde(default)]
+  name: Option<String>,
+  #[serde(default)]
+  message: String,
+  /// replica set which has bucket, it is sent with WRONG_BUCKET
+  #[serde(default)]
+  destination: Option<String>,
+}
+
+impl StorageError {
+  fn into_error(self) -> Error {
+    let code = match (self.kind.as_deref(), self.code) {
+      (Some("ClientError"), Some(code)) => Code::from_u64(ERROR_BITMASK as u64 | code)
+        .unwrap_or(Code::ErrorUnknown),
+      _ => Code::ErrorUnknown,
+    };
+    let message = match self.name {
+      Some(name) => format!("{}: {}", name, self.message),
+      None => self.message,
+    };
+    Error::TarantoolError(code, TarantoolError::new(message))
+  }
+}
+
+/// result of vshard.storage.call is true with results of function or nil with error
+enum StorageResult<T> {
+  Ok(T),
+  Err(StorageError),
+}
+
+impl<'de, T> Deserialize<'de> for StorageResult<T>
+  where T: Deserialize<'de>
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>
+  {
+    struct ResultVisitor<T>(PhantomData<T>);
+
+    impl<'de, T> Visitor<'de> for ResultVisitor<T>
+      where T: Deserialize<'de>
+    {
+      type Value = StorageResult<T>;
+
+      fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("result of vshard.storage.call")
+      }
+
+      fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where A: SeqAccess<'de>
+      {
+        match seq.next_element::<Option<bool>>()?.flatten() {
+          // results of function follow true like they are returned by call
+          Some(true) => T::deserialize(SeqAccessDeserializer::new(seq)).map(StorageResult::Ok),
+          _ => seq.next_element::<StorageError>()?
+            .map(StorageResult::Err)
+            .ok_or_else(|| de::Error::custom("vshard.storage.call failed without error")),
+        }
+      }
+    }
+
+    deserializer.deserialize_seq(ResultVisitor(PhantomData))
+  }
+}
+
+/**
+  This is client of vshard cluster over its replica sets.
+
+  Replica sets are named as in vshard config, e.g. by uuid,
+  so WRONG_BUCKET errors are followed to the replica set which has bucket.
+*/
+#[derive(Debug)]
+pub struct Sharded<C> {
+  bucket_count: u64,
+  replicasets: HashMap<String, ReplicaSet<C>>,
+  /// bucket id -> name of replica set
+  buckets: RwLock<HashMap<u64, String>>,
+}
+
+impl<C> Sharded<C>
+  where C: TarantoolClient
+{
+  pub fn new(bucket_count: u64) -> Sharded<C> {
+    Sharded {
+      bucket_count,
+      replicasets: HashMap::new(),
+      buckets: RwLock::new(HashMap::new()),
+    }
+  }
+
+  pub fn with_replicaset(mut self, name: &str, replicaset: ReplicaSet<C>) -> Self {
+    self.replicasets.insert(name.into(), replicaset);
+    self
+  }
+
+  /// maps buckets to replica set without discovery
+  pub fn with_buckets(self, name: &str, buckets: RangeInclusive<u64>) -> Self {
+    self.buckets.write().unwrap().extend(buckets.map(|bucket_id| (bucket_id, name.to_string())));
+    self
+  }
+
+  pub fn bucket_count(&self) -> u64 {
+    self.bucket_count
+  }
+
+  pub fn bucket_id<K: IntoKey>(&self, key: K) -> Result<u64, Error> {
+    bucket_id_strcrc32(&key.into_key(), self.bucket_count)
+  }
+
+  /// name of replica set which has bucket as far as it is known
+  pub fn bucket_replicaset(&self, bucket_id: u64) -> Option<String> {
+    self.buckets.read().unwrap().get(&bucket_id).cloned()
+  }
+
+  /// asks masters of replica sets which buckets they have
+  pub async fn discover(&self) -> Result<(), Error> {
+    for (name, replicaset) in self.replicasets.iter() {
+      let mut from = 1;
+      loop {
+        let (discovery,): (Discovery,) = replicaset.master().call(Call {
+          function: BUCKETS_DISCOVERY.into(),
+          args: vec![ Value::Map(vec![ ("from".into(), from.into()) ]) ],
+        }).await?;
+
+        let (buckets, next_from) = match discovery {
+          Discovery::Page { buckets, next_from } => (buckets, next_from),
+          Discovery::List(buckets) => (buckets, None),
+        };
+
+        self.buckets.write().unwrap()
+          .extend(buckets.into_iter().map(|bucket_id| (bucket_id, name.clone())));
+
+        match next_from {
+          Some(next) if next > from => from = next,
+          _ => break,
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// calls function on master of replica set which has bucket
+  pub async fn call_rw<T>(&self, bucket_id: u64, function: &str, args: Vec<Value>) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.storage_call(bucket_id, "write", function, args).await
+  }
+
+  /// calls function on replica of replica set which has bucket, master is used if there are no replicas
+  pub async fn call_ro<T>(&self, bucket_id: u64, function: &str, args: Vec<Value>) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    self.storage_call(bucket_id, "read", function, args).await
+  }
+
+  async fn storage_call<T>(&self, bucket_id: u64, mode: &str, function: &str, args: Vec<Value>) -> Result<T, Error>
+    where T: DeserializeOwned
+  {
+    if self.bucket_replicaset(bucket_id).is_none() {
+      self.discover().await?;
+    }
+
+    // bucket may be moved once while it is called
+    let mut followed = false;
+    loop {
+      let name = self.bucket_replicaset(bucket_id)
+        .ok_or_else(|| no_route(bucket_id))?;
+      let replicaset = self.replicasets.get(&name)
+        .ok_or_else(|| no_route(bucket_id))?;
+
+      let body = Call {
+        function: STORAGE_CALL.into(),
+        args: vec![ bucket_id.into(), mode.into(), function.into(), Value::Array(args.clone()) ],
+      };
+      let result = match mode {
+        "read" => replicaset.call_with::<StorageResult<T>>(body, ReadPreference::PreferReplica).await?,
+        _ => replicaset.master().call::<StorageResult<T>>(body).await?,
+      };
+
+      let err = match result {
+        StorageResult::Ok(value) => return Ok(value),
+        StorageResult::Err(err) => err,
+      };
+
+      let destination = match (err.name.as_deref(), &err.destination) {
+        (Some("WRONG_BUCKET"), Some(destination)) if !followed
+          && self.replicasets.contains_key(destination) => destination.clone(),
+        (Some("WRONG_BUCKET"), _) => {
+          self.buckets.write().unwrap().remove(&bucket_id);
+          return Err(err.into_error());
+        },
+        _ => return Err(err.into_error()),
+      };
+
+      log::debug!("bucket {} is moved to {}", bucket_id, destination);
+      self.buckets.write().unwrap().insert(bucket_id, destination);
+      followed = true;
+    }
+  }
+}
+
+fn no_route(bucket_id: u64) -> Error {
+  Error::TarantoolError(
+    Code::ErrorUnknown,
+    TarantoolError::new(format!("NO_ROUTE_TO_BUCKET: bucket {} is not found", bucket_id)),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::testing::FakeClient;
+
+  use super::*;
+
+  /// storage answers name of replica set, buckets of other replica sets are wrong
+  fn storage(name: &'static str, buckets: Vec<u64>, moved_to: &'static str) -> FakeClient {
+    let owned = buckets.clone();
+    FakeClient::new()
+      .with_function(BUCKETS_DISCOVERY, move |args| {
+        let from = match &args[..] {
+          [ Value::Map(opts) ] => match opts.as_slice() {
+            [ (_, Value::UInt(from)) ] => *from,
+            _ => 1,
+          },
+          _ => 1,
+        };
+        // one bucket per page
+        let page: Vec<Value> = buckets.iter().filter(|&&id| id >= from).take(1).map(|&id| id.into()).collect();
+        let next_from = buckets.iter().find(|&&id| id > from).map(|&id| Value::UInt(id)).unwrap_or(Value::Null);
+        Ok(vec![ Value::Map(vec![
+          ("buckets".into(), Value::Array(page)),
+          ("next_from".into(), next_from),
+        ]) ])
+      })
+      .with_function(STORAGE_CALL, move |args| match &args[0] {
+        Value::UInt(bucket_id) if owned.contains(bucket_id) => Ok(vec![ true.into(), name.into() ]),
+        _ => Ok(vec![ Value::Null, Value::Map(vec![
+          ("type".into(), "ShardingError".into()),
+          ("code".into(), 1u64.into()),
+          ("name".into(), "WRONG_BUCKET".into()),
+          ("message".into(), "bucket is moved".into()),
+          ("destination".into(), moved_to.into()),
+        ]) ]),
+      })
+  }
+
+  #[test]
+  fn test_bucket_id() {
+    assert_eq!(crc32_update(u32::MAX, b"123456789"), !0xe306_9283);
+
+    let key = |parts: Vec<Value>| bucket_id_strcrc32(&parts, u32::MAX as u64 + 1).unwrap() - 1;
+    assert_eq!(key(vec![ "123456789".into() ]), !0xe306_9283u32 as u64);
+    // parts are concatenated as strings
+    assert_eq!(key(vec![ "1234".into(), 56789u64.into() ]), !0xe306_9283u32 as u64);
+    assert_eq!(key(vec![ Value::F64(12345.6789) ]), key(vec![ "12345.6789".into() ]));
+
+    assert_eq!(lua_number(1e15), "1e+15");
+    assert_eq!(lua_number(0.5), "0.5");
+    assert_eq!(lua_number(1.0 / 3.0), "0.33333333333333");
+    assert_eq!(lua_tostring(&Value::UInt(1 << 60)).unwrap(), "1152921504606846976ULL");
+    assert_eq!(lua_tostring(&Value::Int(-5)).unwrap(), "-5");
+    assert_eq!(lua_tostring(&Value::UInt(99_999_999_999_999)).unwrap(), "99999999999999");
+    assert_eq!(lua_tostring(&Value::UInt(1_000_000_000_000_000)).unwrap(), "1e+15");
+    assert_eq!(lua_tostring(&Value::Int(-123_456_789_012_345)).unwrap(), "-1.2345678901234e+14");
+    assert_eq!(lua_tostring(&Value::UInt((1 << 53) + 1)).unwrap(), "9007199254740993ULL");
+    assert!(lua_tostring(&Value::Null).is_err());
+
+    let sharded: Sharded<FakeClient> = Sharded::new(3000);
+    let bucket_id = sharded.bucket_id("key").unwrap();
+    assert!((1..=3000).contains(&bucket_id));
+  }
+
+  #[tokio::test]
+  async fn test_sharded_call() {
+    let sharded = Sharded::new(4)
+      .with_replicaset("rs1", ReplicaSet::new(storage("rs1", vec![ 1, 2 ], "rs2")))
+      .with_replicaset("rs2", ReplicaSet::new(storage("rs2", vec![ 3, 4 ], "rs1")));
+
+    // buckets are discovered on first call
+    let (name,): (String,) = sharded.call_rw(3, "whoami", Vec::new()).await.unwrap();
+    assert_eq!(name, "rs2");
+    assert_eq!(sharded.bucket_replicaset(2).as_deref(), Some("rs1"));
+    assert_eq!(sharded.bucket_replicaset(4).as_deref(), Some("rs2"));
+
+    let (name,): (String,) = sharded.call_ro(1, "whoami", Vec::new()).await.unwrap();
+    assert_eq!(name, "rs1");
+  }
+
+  #[tokio::test]
+  async fn test_wrong_bucket() {
+    let sharded = Sharded::new(4)
+      .with_replicaset("rs1", ReplicaSet::new(storage("rs1", vec![ 1, 2, 3 ], "rs2")))
+      .with_replicaset("rs2", ReplicaSet::new(storage("rs2", vec![ 4 ], "rs1")))
+      .with_buckets("rs1", 1..=2)
+      .with_buckets("rs2", 3..=4);
+
+    // bucket 3 is moved to rs1
+    let (name,): (String,) = sharded.call_rw(3, "whoami", Vec::new()).await.unwrap();
+    assert_eq!(name, "rs1");
+    assert_eq!(sharded.bucket_replicaset(3).as_deref(), Some("rs1"));
+
+    // bucket is moved back and forth, it is followed only once
+    let sharded = Sharded::new(4)
+      .with_replicaset("rs1", ReplicaSet::new(storage("rs1", vec![], "rs2")))
+      .with_replicaset("rs2", ReplicaSet::new(storage("rs2", vec![], "rs1")))
+      .with_buckets("rs1", 1..=1);
+    let err = sharded.call_rw::<(String,)>(1, "whoami", Vec::new()).await.unwrap_err();
+    assert!(matches!(err, Error::TarantoolError(Code::ErrorUnknown, err) if err.message.starts_with("WRONG_BUCKET")));
+    assert_eq!(sharded.bucket_replicaset(1), None);
+  }
+}